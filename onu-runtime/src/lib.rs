@@ -0,0 +1,805 @@
+//! Ọ̀nụ Runtime: The Builtins Every Compiled Discourse Links Against
+//!
+//! Every builtin that `CompilerSession::new` registers is exported here under its
+//! hyphenated symbol name, so native executables link this crate's staticlib and
+//! the JIT maps the very same functions into the execution engine.
+//!
+//! Semantics:
+//! Text is UTF-8 and every position counts characters, not bytes. No builtin ever
+//! reads or writes outside its text: out-of-range positions leave the text
+//! untouched (or yield 0 for `char-at`) and invalid character codes become U+FFFD.
+//! The NUL character is stored as the two bytes C0 80, as in Modified UTF-8, so a
+//! text holding a zero (a cleared tape cell, say) keeps its length across calls.
+//!
+//! Arrays:
+//! An array is one heap block: a header holding its length and the size of one
+//! element, then the elements themselves, packed. The compiled program reads and
+//! writes elements in place; the runtime only allocates, copies and checks bounds.
+//! Like texts, arrays are never freed.
+//!
+//! Matrices:
+//! A matrix is one heap block too: a header holding its rows and columns, then
+//! its entries as f64, row by row. Every matrix verb delivers a new matrix and
+//! checks the shapes it combines.
+//!
+//! Broadcasts are written to stdout unless a `capture` is active on the current
+//! thread, in which case they are collected for the caller.
+//!
+//! Traps:
+//! Checked arithmetic that overflows, any division by zero, any `element-at`
+//! outside its array, and any matrix verb given the wrong shapes ends the program
+//! through a trap. The trap releases every captured broadcast to stdout, writes a
+//! peer review memo naming the verb, behavior and line to stderr, and exits with
+//! status 101. Under `catch_traps`, as in the JIT, the trap unwinds back to the
+//! caller as an `Err` instead, and the process lives on. The builtins that may
+//! trap are therefore `extern "C-unwind"`.
+
+use std::cell::{Cell, RefCell};
+use std::ffi::{CStr, CString, c_char};
+
+thread_local! {
+    static CAPTURE: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f` while collecting every broadcast made on this thread, returning the
+/// result alongside the captured lines in emission order.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    let previous = CAPTURE.with(|c| c.borrow_mut().replace(Vec::new()));
+    let result = f();
+    let lines = CAPTURE.with(|c| std::mem::replace(&mut *c.borrow_mut(), previous)).unwrap_or_default();
+    (result, lines)
+}
+
+/// Every builtin exported for JIT mapping: its symbol and its address.
+pub fn builtins() -> [(&'static str, usize); 33] {
+    type Text = *const c_char;
+    [
+        ("as-text", as_text as extern "C" fn(i64) -> *mut c_char as usize),
+        ("unsigned-as-text", unsigned_as_text as extern "C" fn(u64) -> *mut c_char as usize),
+        ("float-as-text", float_as_text as extern "C" fn(f64) -> *mut c_char as usize),
+        ("boolean-as-text", boolean_as_text as extern "C" fn(bool) -> *mut c_char as usize),
+        ("formatted-to", formatted_to as extern "C" fn(f64, i64) -> *mut c_char as usize),
+        ("joined-with", joined_with as unsafe extern "C" fn(Text, Text) -> *mut c_char as usize),
+        ("len", len as unsafe extern "C" fn(Text) -> i64 as usize),
+        ("char-at", char_at as unsafe extern "C" fn(Text, i64) -> i64 as usize),
+        ("set-char", set_char as unsafe extern "C" fn(Text, i64, i64) -> *mut c_char as usize),
+        ("tail-of", tail_of as unsafe extern "C" fn(Text) -> *mut c_char as usize),
+        ("init-of", init_of as unsafe extern "C" fn(Text) -> *mut c_char as usize),
+        ("char-from-code", char_from_code as extern "C" fn(i64) -> *mut c_char as usize),
+        ("broadcasts", broadcasts as unsafe extern "C" fn(Text) as usize),
+        ("broadcasts-integer", broadcasts_integer as extern "C" fn(i64) as usize),
+        ("broadcasts-unsigned", broadcasts_unsigned as extern "C" fn(u64) as usize),
+        ("broadcasts-float", broadcasts_float as extern "C" fn(f64) as usize),
+        ("broadcasts-boolean", broadcasts_boolean as extern "C" fn(bool) as usize),
+        ("array-new", array_new as extern "C" fn(i64, i64) -> *mut u8 as usize),
+        ("array-grown", array_grown as unsafe extern "C" fn(*const u8) -> *mut u8 as usize),
+        ("element-slot", element_slot as unsafe extern "C-unwind" fn(*mut u8, i64, Text, i64) -> *mut u8 as usize),
+        ("length-of", length_of as unsafe extern "C" fn(*const u8) -> i64 as usize),
+        ("slice-of", slice_of as unsafe extern "C" fn(*const u8, i64, i64) -> *mut u8 as usize),
+        ("matrix-new", matrix_new as extern "C" fn(i64, i64) -> *mut u8 as usize),
+        ("matrix-sum", matrix_sum as unsafe extern "C-unwind" fn(*const u8, *const u8, Text, i64) -> *mut u8 as usize),
+        ("matrix-scaled", matrix_scaled as unsafe extern "C" fn(*const u8, f64) -> *mut u8 as usize),
+        ("matrix-product", matrix_product as unsafe extern "C-unwind" fn(*const u8, *const u8, Text, i64) -> *mut u8 as usize),
+        ("matrix-product-target", matrix_product_target as unsafe extern "C-unwind" fn(*const u8, *const u8, Text, i64) -> *mut u8 as usize),
+        ("matrix-transposed", matrix_transposed as unsafe extern "C" fn(*const u8) -> *mut u8 as usize),
+        ("matrix-entry", matrix_entry as unsafe extern "C-unwind" fn(*const u8, i64, i64, Text, i64) -> f64 as usize),
+        ("matrix-determinant", matrix_determinant as unsafe extern "C-unwind" fn(*const u8, Text, i64) -> f64 as usize),
+        ("matrix-as-text", matrix_as_text as unsafe extern "C" fn(*const u8) -> *mut c_char as usize),
+        ("overflow-trap", overflow_trap as unsafe extern "C-unwind" fn(Text, Text, i64) as usize),
+        ("zero-divisor-trap", zero_divisor_trap as unsafe extern "C-unwind" fn(Text, i64) as usize),
+    ]
+}
+
+/// Returns the address of the builtin exported under `symbol`, for JIT mapping.
+pub fn builtin_address(symbol: &str) -> Option<usize> {
+    builtins().into_iter().find(|(name, _)| *name == symbol).map(|(_, address)| address)
+}
+
+fn broadcast_line(line: String) {
+    CAPTURE.with(|c| match c.borrow_mut().as_mut() {
+        Some(lines) => lines.push(line),
+        None => println!("{}", line),
+    });
+}
+
+/// Borrows a NUL-terminated text, replacing invalid UTF-8 and treating null as empty.
+///
+/// # Safety
+/// `ptr` must be null or point to a NUL-terminated string that outlives the borrow.
+unsafe fn text_of<'a>(ptr: *const c_char) -> std::borrow::Cow<'a, str> {
+    if ptr.is_null() {
+        return std::borrow::Cow::Borrowed("");
+    }
+    // SAFETY: guaranteed by the caller.
+    let bytes = unsafe { CStr::from_ptr(ptr) }.to_bytes();
+    if !bytes.windows(2).any(|pair| pair == ENCODED_NUL) {
+        return String::from_utf8_lossy(bytes);
+    }
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i..].starts_with(&ENCODED_NUL) {
+            decoded.push(0);
+            i += 2;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    std::borrow::Cow::Owned(String::from_utf8_lossy(&decoded).into_owned())
+}
+
+const ENCODED_NUL: [u8; 2] = [0xC0, 0x80];
+
+/// Hands a new text to the compiled program. Texts are never freed, matching the
+/// program's lack of ownership tracking.
+fn into_text(text: String) -> *mut c_char {
+    let mut bytes = Vec::with_capacity(text.len());
+    for byte in text.into_bytes() {
+        if byte == 0 { bytes.extend_from_slice(&ENCODED_NUL) } else { bytes.push(byte) }
+    }
+    // SAFETY: every NUL byte was replaced by its two-byte encoding above.
+    unsafe { CString::from_vec_unchecked(bytes) }.into_raw()
+}
+
+/// The character a code names, or U+FFFD for an invalid code; shared with the MIR interpreter.
+pub fn code_to_char(code: i64) -> char {
+    u32::try_from(code).ok().and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)
+}
+
+/// The code `char-at` delivers, shared with the MIR interpreter.
+pub fn code_at(text: &str, idx: i64) -> i64 {
+    usize::try_from(idx).ok().and_then(|i| text.chars().nth(i)).map_or(0, |c| c as i64)
+}
+
+/// The text `set-char` delivers, shared with the MIR interpreter.
+pub fn with_char_set(text: &str, idx: i64, code: i64) -> String {
+    let mut chars: Vec<char> = text.chars().collect();
+    if let Some(slot) = usize::try_from(idx).ok().and_then(|i| chars.get_mut(i)) {
+        *slot = code_to_char(code);
+    }
+    chars.into_iter().collect()
+}
+
+#[unsafe(export_name = "as-text")]
+pub extern "C" fn as_text(n: i64) -> *mut c_char {
+    into_text(n.to_string())
+}
+
+#[unsafe(export_name = "unsigned-as-text")]
+pub extern "C" fn unsigned_as_text(n: u64) -> *mut c_char {
+    into_text(n.to_string())
+}
+
+#[unsafe(export_name = "float-as-text")]
+pub extern "C" fn float_as_text(x: f64) -> *mut c_char {
+    into_text(x.to_string())
+}
+
+/// Renders a float rounded to a fixed number of fractional digits, at most 17.
+/// A negative digit count means none.
+#[unsafe(export_name = "formatted-to")]
+pub extern "C" fn formatted_to(x: f64, digits: i64) -> *mut c_char {
+    into_text(format_float(x, digits))
+}
+
+/// The text `formatted-to` produces, shared with the MIR interpreter.
+pub fn format_float(x: f64, digits: i64) -> String {
+    format!("{:.*}", digits.clamp(0, 17) as usize, x)
+}
+
+#[unsafe(export_name = "boolean-as-text")]
+pub extern "C" fn boolean_as_text(b: bool) -> *mut c_char {
+    into_text(b.to_string())
+}
+
+/// # Safety
+/// Both arguments must be null or valid NUL-terminated strings.
+#[unsafe(export_name = "joined-with")]
+pub unsafe extern "C" fn joined_with(a: *const c_char, b: *const c_char) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    let (a, b) = unsafe { (text_of(a), text_of(b)) };
+    into_text(format!("{}{}", a, b))
+}
+
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "len")]
+pub unsafe extern "C" fn len(s: *const c_char) -> i64 {
+    // SAFETY: guaranteed by the caller.
+    unsafe { text_of(s) }.chars().count() as i64
+}
+
+/// Returns the code of the character at `idx`, or 0 when `idx` lies outside the text.
+///
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "char-at")]
+pub unsafe extern "C" fn char_at(s: *const c_char, idx: i64) -> i64 {
+    // SAFETY: guaranteed by the caller.
+    code_at(&unsafe { text_of(s) }, idx)
+}
+
+/// Returns a copy of the text with the character at `idx` replaced; an `idx`
+/// outside the text leaves the copy unchanged.
+///
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "set-char")]
+pub unsafe extern "C" fn set_char(s: *const c_char, idx: i64, code: i64) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    into_text(with_char_set(&unsafe { text_of(s) }, idx, code))
+}
+
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "tail-of")]
+pub unsafe extern "C" fn tail_of(s: *const c_char) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    into_text(unsafe { text_of(s) }.chars().skip(1).collect())
+}
+
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "init-of")]
+pub unsafe extern "C" fn init_of(s: *const c_char) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    let mut chars: Vec<char> = unsafe { text_of(s) }.chars().collect();
+    chars.pop();
+    into_text(chars.into_iter().collect())
+}
+
+#[unsafe(export_name = "char-from-code")]
+pub extern "C" fn char_from_code(code: i64) -> *mut c_char {
+    into_text(code_to_char(code).to_string())
+}
+
+/// The header in front of every array's elements.
+#[repr(C)]
+struct ArrayHeader {
+    length: i64,
+    element_size: i64,
+}
+
+/// The size of the header, which is also the alignment of every element.
+pub const ARRAY_HEADER_SIZE: usize = std::mem::size_of::<ArrayHeader>();
+
+/// Allocates a zeroed array of `length` elements of `element_size` bytes each.
+#[unsafe(export_name = "array-new")]
+pub extern "C" fn array_new(length: i64, element_size: i64) -> *mut u8 {
+    let (count, size) = (length.max(0) as usize, element_size.max(0) as usize);
+    let layout = std::alloc::Layout::from_size_align(ARRAY_HEADER_SIZE + count * size, ARRAY_HEADER_SIZE).expect("array too large");
+    // SAFETY: the layout is never zero-sized, as it always holds the header.
+    let block = unsafe { std::alloc::alloc_zeroed(layout) };
+    if block.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    // SAFETY: the block is large enough and suitably aligned for the header.
+    unsafe { block.cast::<ArrayHeader>().write(ArrayHeader { length: count as i64, element_size: size as i64 }) };
+    block
+}
+
+/// Reads the header and the packed elements of an array.
+///
+/// # Safety
+/// `array` must have been returned by `array-new` or another array builtin.
+unsafe fn array_parts<'a>(array: *const u8) -> (&'a ArrayHeader, &'a [u8]) {
+    // SAFETY: guaranteed by the caller.
+    unsafe {
+        let header = &*array.cast::<ArrayHeader>();
+        let bytes = std::slice::from_raw_parts(array.add(ARRAY_HEADER_SIZE), (header.length * header.element_size) as usize);
+        (header, bytes)
+    }
+}
+
+/// Copies the elements `start..end` of an array into a new array of `length` elements.
+///
+/// # Safety
+/// `array` must be a valid array and `start..end` must lie within it.
+unsafe fn array_copy(array: *const u8, start: usize, end: usize, length: usize) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let (header, bytes) = unsafe { array_parts(array) };
+    let size = header.element_size as usize;
+    let copy = array_new(length as i64, header.element_size);
+    // SAFETY: the new array holds at least `end - start` elements of the same size.
+    unsafe { std::ptr::copy_nonoverlapping(bytes[start * size..end * size].as_ptr(), copy.add(ARRAY_HEADER_SIZE), (end - start) * size) };
+    copy
+}
+
+/// Returns a copy of the array with one more, zeroed, element at its end, which
+/// the compiled program then fills.
+///
+/// # Safety
+/// `array` must be a valid array.
+#[unsafe(export_name = "array-grown")]
+pub unsafe extern "C" fn array_grown(array: *const u8) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let length = unsafe { array_parts(array) }.0.length as usize;
+    // SAFETY: the whole array is copied.
+    unsafe { array_copy(array, 0, length, length + 1) }
+}
+
+/// Returns the address of the element at `index`, trapping when it lies outside the array.
+///
+/// # Safety
+/// `array` must be a valid array and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "element-slot")]
+pub unsafe extern "C-unwind" fn element_slot(array: *mut u8, index: i64, behavior: *const c_char, line: i64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let header = unsafe { array_parts(array) }.0;
+    if !(0..header.length).contains(&index) {
+        // SAFETY: guaranteed by the caller.
+        let behavior = unsafe { text_of(behavior) };
+        trap(
+            format!(
+                "'element-at' position {} lies outside an array of length {} in the behavior '{}' (line {}).",
+                index, header.length, behavior, line
+            ),
+            "An array holds no element before its first or after its last.",
+            line,
+        );
+    }
+    // SAFETY: the index was checked against the length above.
+    unsafe { array.add(ARRAY_HEADER_SIZE + (index * header.element_size) as usize) }
+}
+
+/// # Safety
+/// `array` must be a valid array.
+#[unsafe(export_name = "length-of")]
+pub unsafe extern "C" fn length_of(array: *const u8) -> i64 {
+    // SAFETY: guaranteed by the caller.
+    unsafe { array_parts(array) }.0.length
+}
+
+/// Returns the elements from `start` up to, but excluding, `end`. Positions are
+/// clamped to the array, so an empty or reversed range yields an empty array.
+///
+/// # Safety
+/// `array` must be a valid array.
+#[unsafe(export_name = "slice-of")]
+pub unsafe extern "C" fn slice_of(array: *const u8, start: i64, end: i64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let length = unsafe { array_parts(array) }.0.length;
+    let end = end.clamp(0, length) as usize;
+    let start = (start.clamp(0, length) as usize).min(end);
+    // SAFETY: the range was clamped to the array above.
+    unsafe { array_copy(array, start, end, end - start) }
+}
+
+/// The header in front of every matrix's entries.
+#[repr(C)]
+struct MatrixHeader {
+    rows: i64,
+    cols: i64,
+}
+
+/// The size of the header; the entries follow it, row by row.
+pub const MATRIX_HEADER_SIZE: usize = std::mem::size_of::<MatrixHeader>();
+
+/// Allocates a zeroed matrix of `rows` by `cols` entries.
+#[unsafe(export_name = "matrix-new")]
+pub extern "C" fn matrix_new(rows: i64, cols: i64) -> *mut u8 {
+    let (rows, cols) = (rows.max(0), cols.max(0));
+    let size = MATRIX_HEADER_SIZE + (rows * cols) as usize * std::mem::size_of::<f64>();
+    let layout = std::alloc::Layout::from_size_align(size, MATRIX_HEADER_SIZE).expect("matrix too large");
+    // SAFETY: the layout is never zero-sized, as it always holds the header.
+    let block = unsafe { std::alloc::alloc_zeroed(layout) };
+    if block.is_null() {
+        std::alloc::handle_alloc_error(layout);
+    }
+    // SAFETY: the block is large enough and suitably aligned for the header.
+    unsafe { block.cast::<MatrixHeader>().write(MatrixHeader { rows, cols }) };
+    block
+}
+
+/// Reads the dimensions and the entries of a matrix.
+///
+/// # Safety
+/// `matrix` must have been returned by `matrix-new` or another matrix builtin.
+unsafe fn matrix_parts<'a>(matrix: *const u8) -> (usize, usize, &'a [f64]) {
+    // SAFETY: guaranteed by the caller.
+    unsafe {
+        let header = &*matrix.cast::<MatrixHeader>();
+        let (rows, cols) = (header.rows as usize, header.cols as usize);
+        (rows, cols, std::slice::from_raw_parts(matrix.add(MATRIX_HEADER_SIZE).cast::<f64>(), rows * cols))
+    }
+}
+
+/// Allocates a matrix holding the given entries.
+fn into_matrix(rows: usize, cols: usize, entries: &[f64]) -> *mut u8 {
+    let matrix = matrix_new(rows as i64, cols as i64);
+    // SAFETY: the new matrix holds exactly `rows * cols` entries.
+    unsafe { std::ptr::copy_nonoverlapping(entries.as_ptr(), matrix.add(MATRIX_HEADER_SIZE).cast::<f64>(), rows * cols) };
+    matrix
+}
+
+/// Multiplies a `rows` by `inner` matrix with an `inner` by `cols` one.
+pub fn product_of(rows: usize, inner: usize, cols: usize, a: &[f64], b: &[f64]) -> Vec<f64> {
+    let mut product = vec![0.0; rows * cols];
+    for i in 0..rows {
+        for k in 0..inner {
+            for j in 0..cols {
+                product[i * cols + j] += a[i * inner + k] * b[k * cols + j];
+            }
+        }
+    }
+    product
+}
+
+/// Transposes a `rows` by `cols` matrix.
+pub fn transpose_of(rows: usize, cols: usize, entries: &[f64]) -> Vec<f64> {
+    (0..cols).flat_map(|j| (0..rows).map(move |i| entries[i * cols + j])).collect()
+}
+
+/// The determinant of an `n` by `n` matrix, by Gaussian elimination with partial pivoting.
+pub fn determinant_of(n: usize, entries: &[f64]) -> f64 {
+    let mut m = entries.to_vec();
+    let mut determinant = 1.0;
+    for column in 0..n {
+        let pivot = (column..n).max_by(|&a, &b| m[a * n + column].abs().total_cmp(&m[b * n + column].abs())).unwrap();
+        if m[pivot * n + column] == 0.0 {
+            return 0.0;
+        }
+        if pivot != column {
+            for j in 0..n {
+                m.swap(pivot * n + j, column * n + j);
+            }
+            determinant = -determinant;
+        }
+        determinant *= m[column * n + column];
+        for row in column + 1..n {
+            let factor = m[row * n + column] / m[column * n + column];
+            for j in column..n {
+                m[row * n + j] -= factor * m[column * n + j];
+            }
+        }
+    }
+    determinant
+}
+
+/// Renders a matrix the way it is written: `[1 2 : 3 4]`.
+pub fn render_matrix(rows: usize, cols: usize, entries: &[f64]) -> String {
+    let rows: Vec<String> = (0..rows)
+        .map(|i| entries[i * cols..(i + 1) * cols].iter().map(f64::to_string).collect::<Vec<_>>().join(" "))
+        .collect();
+    format!("[{}]", rows.join(" : "))
+}
+
+/// Traps with a memo naming the matrix verb, the behavior and its line.
+///
+/// # Safety
+/// `behavior` must be null or a valid NUL-terminated string.
+unsafe fn matrix_trap(observation: String, behavior: *const c_char, line: i64, assessment: &str) -> ! {
+    // SAFETY: guaranteed by the caller.
+    let behavior = unsafe { text_of(behavior) };
+    trap(format!("{} in the behavior '{}' (line {}).", observation, behavior, line), assessment, line)
+}
+
+/// # Safety
+/// Both matrices must be valid and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "matrix-sum")]
+pub unsafe extern "C-unwind" fn matrix_sum(a: *const u8, b: *const u8, behavior: *const c_char, line: i64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let ((ra, ca, a), (rb, cb, b)) = unsafe { (matrix_parts(a), matrix_parts(b)) };
+    if (ra, ca) != (rb, cb) {
+        let observation = format!("'added-to' cannot combine a {}x{} matrix with a {}x{} matrix", ra, ca, rb, cb);
+        // SAFETY: guaranteed by the caller.
+        unsafe { matrix_trap(observation, behavior, line, "Matrices are added entry by entry, so their shapes must agree.") }
+    }
+    let sum: Vec<f64> = a.iter().zip(b).map(|(x, y)| x + y).collect();
+    into_matrix(ra, ca, &sum)
+}
+
+/// # Safety
+/// `matrix` must be a valid matrix.
+#[unsafe(export_name = "matrix-scaled")]
+pub unsafe extern "C" fn matrix_scaled(matrix: *const u8, factor: f64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let (rows, cols, entries) = unsafe { matrix_parts(matrix) };
+    let scaled: Vec<f64> = entries.iter().map(|x| x * factor).collect();
+    into_matrix(rows, cols, &scaled)
+}
+
+/// Checks that `a` can be multiplied by `b` and allocates their zeroed product,
+/// for compiled loops that accumulate the product in place.
+///
+/// # Safety
+/// Both matrices must be valid and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "matrix-product-target")]
+pub unsafe extern "C-unwind" fn matrix_product_target(a: *const u8, b: *const u8, behavior: *const c_char, line: i64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let ((ra, ca, _), (rb, cb, _)) = unsafe { (matrix_parts(a), matrix_parts(b)) };
+    if ca != rb {
+        let observation = format!("'multiplied-with' cannot multiply a {}x{} matrix by a {}x{} matrix", ra, ca, rb, cb);
+        // SAFETY: guaranteed by the caller.
+        unsafe { matrix_trap(observation, behavior, line, "A product needs as many rows in its object as columns in its subject.") }
+    }
+    matrix_new(ra as i64, cb as i64)
+}
+
+/// # Safety
+/// Both matrices must be valid and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "matrix-product")]
+pub unsafe extern "C-unwind" fn matrix_product(a: *const u8, b: *const u8, behavior: *const c_char, line: i64) -> *mut u8 {
+    // SAFETY: guaranteed by the caller; the target's shape is checked there.
+    unsafe {
+        let target = matrix_product_target(a, b, behavior, line);
+        let ((ra, ca, a), (_, cb, b)) = (matrix_parts(a), matrix_parts(b));
+        std::ptr::copy_nonoverlapping(product_of(ra, ca, cb, a, b).as_ptr(), target.add(MATRIX_HEADER_SIZE).cast::<f64>(), ra * cb);
+        target
+    }
+}
+
+/// # Safety
+/// `matrix` must be a valid matrix.
+#[unsafe(export_name = "matrix-transposed")]
+pub unsafe extern "C" fn matrix_transposed(matrix: *const u8) -> *mut u8 {
+    // SAFETY: guaranteed by the caller.
+    let (rows, cols, entries) = unsafe { matrix_parts(matrix) };
+    into_matrix(cols, rows, &transpose_of(rows, cols, entries))
+}
+
+/// Returns the entry at `row` and `col`, trapping when it lies outside the matrix.
+///
+/// # Safety
+/// `matrix` must be a valid matrix and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "matrix-entry")]
+pub unsafe extern "C-unwind" fn matrix_entry(matrix: *const u8, row: i64, col: i64, behavior: *const c_char, line: i64) -> f64 {
+    // SAFETY: guaranteed by the caller.
+    let (rows, cols, entries) = unsafe { matrix_parts(matrix) };
+    if !(0..rows as i64).contains(&row) || !(0..cols as i64).contains(&col) {
+        let observation = format!("'entry-at' position ({}, {}) lies outside a {}x{} matrix", row, col, rows, cols);
+        // SAFETY: guaranteed by the caller.
+        unsafe { matrix_trap(observation, behavior, line, "A matrix holds no entry outside its rows and columns.") }
+    }
+    entries[row as usize * cols + col as usize]
+}
+
+/// # Safety
+/// `matrix` must be a valid matrix and `behavior` null or a valid NUL-terminated string.
+#[unsafe(export_name = "matrix-determinant")]
+pub unsafe extern "C-unwind" fn matrix_determinant(matrix: *const u8, behavior: *const c_char, line: i64) -> f64 {
+    // SAFETY: guaranteed by the caller.
+    let (rows, cols, entries) = unsafe { matrix_parts(matrix) };
+    if rows != cols {
+        let observation = format!("'determinant' needs a square matrix, but was given a {}x{} matrix", rows, cols);
+        // SAFETY: guaranteed by the caller.
+        unsafe { matrix_trap(observation, behavior, line, "Only a square matrix has a determinant.") }
+    }
+    determinant_of(rows, entries)
+}
+
+/// # Safety
+/// `matrix` must be a valid matrix.
+#[unsafe(export_name = "matrix-as-text")]
+pub unsafe extern "C" fn matrix_as_text(matrix: *const u8) -> *mut c_char {
+    // SAFETY: guaranteed by the caller.
+    let (rows, cols, entries) = unsafe { matrix_parts(matrix) };
+    into_text(render_matrix(rows, cols, entries))
+}
+
+/// # Safety
+/// `s` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "broadcasts")]
+pub unsafe extern "C" fn broadcasts(s: *const c_char) {
+    // SAFETY: guaranteed by the caller.
+    broadcast_line(unsafe { text_of(s) }.into_owned());
+}
+
+#[unsafe(export_name = "broadcasts-integer")]
+pub extern "C" fn broadcasts_integer(n: i64) {
+    broadcast_line(n.to_string());
+}
+
+#[unsafe(export_name = "broadcasts-unsigned")]
+pub extern "C" fn broadcasts_unsigned(n: u64) {
+    broadcast_line(n.to_string());
+}
+
+#[unsafe(export_name = "broadcasts-float")]
+pub extern "C" fn broadcasts_float(x: f64) {
+    broadcast_line(x.to_string());
+}
+
+#[unsafe(export_name = "broadcasts-boolean")]
+pub extern "C" fn broadcasts_boolean(b: bool) {
+    broadcast_line(b.to_string());
+}
+
+/// The exit status of a program ended by a trap.
+pub const TRAP_STATUS: i32 = 101;
+
+/// Renders the memo a trap writes, in the compiler's peer review format.
+pub fn trap_memo(observation: &str, assessment: &str) -> String {
+    format!(
+        "\n═══════════════════════════════════════════\n           PEER REVIEW MEMO\n═══════════════════════════════════════════\n\n\
+         Observation: {}\nAssessment:  {}\nConclusion:  The derivation refuses to evaluate.\n",
+        observation, assessment
+    )
+}
+
+/// A trap caught by `catch_traps`: what went wrong, why it is refused, and the
+/// line of the behavior it happened on.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Trap {
+    pub observation: String,
+    pub assessment: String,
+    pub line: i64,
+}
+
+/// Runs `f`, turning a trap raised on this thread into an `Err` rather than the
+/// end of the process. Broadcasts captured before the trap stay captured.
+pub fn catch_traps<R>(f: impl FnOnce() -> R) -> Result<R, Trap> {
+    let previous = CATCHING.with(|c| c.replace(true));
+    let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(f));
+    CATCHING.with(|c| c.set(previous));
+    result.map_err(|payload| match payload.downcast::<Trap>() {
+        Ok(trap) => *trap,
+        Err(payload) => std::panic::resume_unwind(payload),
+    })
+}
+
+fn trap(observation: String, assessment: &str, line: i64) -> ! {
+    let trap = Trap { observation, assessment: assessment.to_string(), line };
+    if CATCHING.with(Cell::get) {
+        // Resuming skips the panic hook, so nothing is printed on the way out.
+        std::panic::resume_unwind(Box::new(trap));
+    }
+    let captured = CAPTURE.with(|c| c.borrow_mut().take()).unwrap_or_default();
+    for line in captured {
+        println!("{}", line);
+    }
+    eprint!("{}", trap_memo(&trap.observation, &trap.assessment));
+    std::process::exit(TRAP_STATUS)
+}
+
+/// # Safety
+/// Both texts must be null or valid NUL-terminated strings.
+#[unsafe(export_name = "overflow-trap")]
+pub unsafe extern "C-unwind" fn overflow_trap(verb: *const c_char, behavior: *const c_char, line: i64) {
+    // SAFETY: guaranteed by the caller.
+    let (verb, behavior) = unsafe { (text_of(verb), text_of(behavior)) };
+    trap(
+        format!("'{}' overflowed its width in the behavior '{}' (line {}).", verb, behavior, line),
+        "Checked arithmetic refuses a result the operands' type cannot hold.",
+        line,
+    )
+}
+
+/// # Safety
+/// `behavior` must be null or a valid NUL-terminated string.
+#[unsafe(export_name = "zero-divisor-trap")]
+pub unsafe extern "C-unwind" fn zero_divisor_trap(behavior: *const c_char, line: i64) {
+    // SAFETY: guaranteed by the caller.
+    let behavior = unsafe { text_of(behavior) };
+    trap(
+        format!("'partitions-by' received a zero divisor in the behavior '{}' (line {}).", behavior, line),
+        "No number can be partitioned into zero parts.",
+        line,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(ptr: *mut c_char) -> String {
+        unsafe { text_of(ptr) }.into_owned()
+    }
+
+    #[test]
+    fn test_capture_collects_broadcasts() {
+        let ((), lines) = capture(|| {
+            broadcasts_integer(42);
+            broadcasts_unsigned(u64::MAX);
+            broadcasts_boolean(true);
+            unsafe { broadcasts(c"done".as_ptr()) };
+        });
+        assert_eq!(lines, vec!["42", "18446744073709551615", "true", "done"]);
+    }
+
+    #[test]
+    fn test_text_builtins_count_characters() {
+        let text = c"Ọ̀nụ".as_ptr();
+        unsafe {
+            assert_eq!(len(text), 4);
+            assert_eq!(char_at(text, 0), 'Ọ' as i64);
+            assert_eq!(read(tail_of(text)), "̀nụ");
+            assert_eq!(read(init_of(text)), "Ọ̀n");
+            assert_eq!(read(joined_with(text, c"!".as_ptr())), "Ọ̀nụ!");
+        }
+    }
+
+    #[test]
+    fn test_positions_are_bounds_checked() {
+        let text = c"abc".as_ptr();
+        unsafe {
+            assert_eq!(char_at(text, 3), 0);
+            assert_eq!(char_at(text, -1), 0);
+            assert_eq!(read(set_char(text, 1, 'x' as i64)), "axc");
+            assert_eq!(read(set_char(text, 7, 'x' as i64)), "abc");
+            assert_eq!(read(tail_of(c"".as_ptr())), "");
+            assert_eq!(read(init_of(std::ptr::null())), "");
+        }
+    }
+
+    #[test]
+    fn test_nul_characters_keep_their_place() {
+        unsafe {
+            let cleared = set_char(c"abc".as_ptr(), 1, 0);
+            assert_eq!(len(cleared), 3);
+            assert_eq!(char_at(cleared, 1), 0);
+            assert_eq!(char_at(cleared, 2), 'c' as i64);
+            assert_eq!(len(char_from_code(0)), 1);
+        }
+    }
+
+    #[test]
+    fn test_character_codes() {
+        assert_eq!(read(char_from_code(65)), "A");
+        assert_eq!(read(char_from_code(0x1EE5)), "ụ");
+        assert_eq!(read(char_from_code(-1)), "\u{FFFD}");
+        assert_eq!(read(as_text(-12)), "-12");
+    }
+
+    #[test]
+    fn test_floats_format_to_fixed_digits() {
+        assert_eq!(read(formatted_to(std::f64::consts::PI, 2)), "3.14");
+        assert_eq!(read(formatted_to(2.0, 3)), "2.000");
+        assert_eq!(read(formatted_to(-7.6, -1)), "-8");
+        assert_eq!(read(float_as_text(2.5)), "2.5");
+    }
+
+    #[test]
+    fn test_arrays_grow_and_slice_by_copy() {
+        let read_all = |array: *const u8| unsafe {
+            let (_, bytes) = array_parts(array);
+            bytes.chunks(8).map(|chunk| i64::from_ne_bytes(chunk.try_into().unwrap())).collect::<Vec<_>>()
+        };
+        unsafe {
+            let array = array_new(2, 8);
+            element_slot(array, 0, std::ptr::null(), 0).cast::<i64>().write(7);
+            element_slot(array, 1, std::ptr::null(), 0).cast::<i64>().write(9);
+            let grown = array_grown(array);
+            element_slot(grown, 2, std::ptr::null(), 0).cast::<i64>().write(11);
+            assert_eq!(read_all(array), vec![7, 9]);
+            assert_eq!(length_of(grown), 3);
+            assert_eq!(read_all(slice_of(grown, 1, 3)), vec![9, 11]);
+            assert_eq!(read_all(slice_of(grown, 2, 1)), Vec::<i64>::new());
+            assert_eq!(read_all(slice_of(grown, -5, 50)), vec![7, 9, 11]);
+        }
+    }
+
+    #[test]
+    fn test_matrices_combine_by_shape() {
+        unsafe {
+            let a = into_matrix(2, 3, &[1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+            let b = matrix_transposed(a);
+            assert_eq!(read(matrix_as_text(b)), "[1 4 : 2 5 : 3 6]");
+            assert_eq!(read(matrix_as_text(matrix_product(a, b, std::ptr::null(), 0))), "[14 32 : 32 77]");
+            assert_eq!(read(matrix_as_text(matrix_sum(a, matrix_scaled(a, 2.0), std::ptr::null(), 0))), "[3 6 9 : 12 15 18]");
+            assert_eq!(matrix_entry(a, 1, 2, std::ptr::null(), 0), 6.0);
+        }
+        assert_eq!(determinant_of(2, &[1.0, 0.0, 0.0, 1.0]), 1.0);
+        assert_eq!(determinant_of(3, &[0.0, 2.0, 1.0, 1.0, 1.0, 0.0, 3.0, 0.0, 1.0]), -5.0);
+        assert_eq!(determinant_of(2, &[1.0, 2.0, 2.0, 4.0]), 0.0);
+    }
+
+    #[test]
+    fn test_every_symbol_resolves() {
+        for (symbol, address) in builtins() {
+            // A symbol listed twice would resolve to its first entry only.
+            assert_eq!(builtin_address(symbol), Some(address), "builtin {} does not resolve to its own entry", symbol);
+        }
+        assert!(builtin_address("no-such-builtin").is_none());
+    }
+
+    #[test]
+    fn test_trap_memo_follows_the_peer_review_format() {
+        let memo = trap_memo("'added-to' overflowed", "Too large.");
+        assert!(memo.contains("PEER REVIEW MEMO"));
+        assert!(memo.contains("Observation: 'added-to' overflowed\nAssessment:  Too large.\n"));
+    }
+}
+
@@ -0,0 +1,309 @@
+//! Ọ̀nụ Command Line: The Invocation Layer
+//!
+//! This module reads the arguments of the `onu` command into a `Cli`: one
+//! subcommand, the files it works on, and the options that configure the
+//! session and the target machine. Parsing only describes the invocation;
+//! the binary carries it out. Options may be written `--name value` or
+//! `--name=value`, and an unknown option is refused rather than ignored.
+
+use crate::codegen::TargetOptions;
+use crate::concern::{ConcernPolicy, Strictness};
+use crate::mir::ArithmeticMode;
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage: onu <command> [<file.onu>] [options]
+
+Commands:
+  check <file>          Analyse a discourse unit and the modules it draws on
+  build [<file>]        Compile a file into a native executable, or the package of Onu.toml
+  run [<file>]          Run a file's entry behavior in-process, or build and run the package
+  emit <form> <file>    Write one stage of compilation: ast, hir, mir, llvm-ir, asm, bc or obj
+  fmt <file>...         Lay out discourse units in the canonical indentation (--check to only verify)
+  doc <file>            Write the reference documentation of a module as Markdown
+  test <file>           Run every `test-` behavior and report which deliver true
+  <file>                Check a file, then run its entry behavior if it has one
+
+Options:
+  -o <path>             Where to write the output
+  -O0 | -O1 | -O2 | -O3 Optimization level of generated machine code (default -O3)
+  --search-path <dirs>  Directories to find drawn-upon modules in, before the file's own
+  --arithmetic <mode>   wrapping, checked (default) or saturating
+  --fast-math           Let LLVM reassociate and approximate float arithmetic
+  --polly-loops         Emit matrix products as loop nests for Polly
+  --target <triple>     --cpu <name>  --features <list>  --reloc <model>
+  --concern <level>     off, warn (default) or deny, for intents that stray from the module's concern
+  --concern-threshold <percent>  The alignment an intent must reach (default 25)
+  --synonyms <words>    Comma-separated words to treat as interchangeable; may be repeated
+  --stop-words <words>  Comma-separated words to ignore when scoring alignment
+  --manifest-path <path>  --profile <name>  (packages)
+  --check               (fmt) Report unformatted files instead of rewriting them";
+
+/// The work a single invocation asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Command {
+    /// A bare file: check it, then run its entry behavior if it has one.
+    CheckThenRun,
+    Check,
+    Build,
+    Run,
+    Emit(EmitForm),
+    Fmt,
+    Doc,
+    Test,
+}
+
+/// The stage of compilation `onu emit` writes out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitForm {
+    Ast,
+    Hir,
+    Mir,
+    LlvmIr,
+    Asm,
+    Bc,
+    Obj,
+}
+
+impl EmitForm {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "ast" => Some(EmitForm::Ast),
+            "hir" => Some(EmitForm::Hir),
+            "mir" => Some(EmitForm::Mir),
+            "llvm-ir" => Some(EmitForm::LlvmIr),
+            "asm" => Some(EmitForm::Asm),
+            "bc" => Some(EmitForm::Bc),
+            "obj" => Some(EmitForm::Obj),
+            _ => None,
+        }
+    }
+
+    /// Whether the form is text, written to standard output unless `-o` is given.
+    pub fn is_text(&self) -> bool {
+        matches!(self, EmitForm::Ast | EmitForm::Hir | EmitForm::Mir | EmitForm::LlvmIr)
+    }
+}
+
+/// Cli is one parsed invocation of `onu`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub command: Command,
+    pub files: Vec<PathBuf>,
+    pub output: Option<PathBuf>,
+    /// 0 to 3, from `-O0` to `-O3`.
+    pub opt_level: Option<u8>,
+    pub search_path: Vec<PathBuf>,
+    pub arithmetic: Option<ArithmeticMode>,
+    pub fast_math: bool,
+    pub polly_loops: bool,
+    pub triple: Option<String>,
+    pub cpu: Option<String>,
+    pub features: Option<String>,
+    pub reloc: Option<inkwell::targets::RelocMode>,
+    pub concern: Option<Strictness>,
+    /// 0 to 100, from `--concern-threshold`.
+    pub concern_threshold: Option<u8>,
+    /// Each `--synonyms` group, split at its commas.
+    pub synonyms: Vec<Vec<String>>,
+    pub stop_words: Vec<String>,
+    pub manifest_path: Option<PathBuf>,
+    pub profile: Option<String>,
+    /// `fmt --check`: report instead of rewrite.
+    pub check_only: bool,
+}
+
+impl Cli {
+    fn new(command: Command) -> Self {
+        Self {
+            command,
+            files: Vec::new(),
+            output: None,
+            opt_level: None,
+            search_path: Vec::new(),
+            arithmetic: None,
+            fast_math: false,
+            polly_loops: false,
+            triple: None,
+            cpu: None,
+            features: None,
+            reloc: None,
+            concern: None,
+            concern_threshold: None,
+            synonyms: Vec::new(),
+            stop_words: Vec::new(),
+            manifest_path: None,
+            profile: None,
+            check_only: false,
+        }
+    }
+
+    /// Parses the arguments that follow the program name. The error explains
+    /// what was wrong with them, to be shown above the usage.
+    pub fn parse(args: &[String]) -> Result<Self, String> {
+        let Some(first) = args.first() else {
+            return Err("No command or file was given.".to_string());
+        };
+        let (mut cli, rest) = match first.as_str() {
+            "check" => (Self::new(Command::Check), &args[1..]),
+            "build" => (Self::new(Command::Build), &args[1..]),
+            "run" => (Self::new(Command::Run), &args[1..]),
+            "fmt" => (Self::new(Command::Fmt), &args[1..]),
+            "doc" => (Self::new(Command::Doc), &args[1..]),
+            "test" => (Self::new(Command::Test), &args[1..]),
+            "emit" => {
+                let form = args.get(1).ok_or("'emit' needs a form: ast, hir, mir, llvm-ir, asm, bc or obj.")?;
+                let form = EmitForm::from_name(form)
+                    .ok_or_else(|| format!("'{}' is not a form 'emit' can write; expected ast, hir, mir, llvm-ir, asm, bc or obj.", form))?;
+                (Self::new(Command::Emit(form)), &args[2..])
+            }
+            other if other.starts_with('-') => (Self::new(Command::CheckThenRun), args),
+            other if other.ends_with(".onu") || std::path::Path::new(other).is_file() => (Self::new(Command::CheckThenRun), args),
+            other => return Err(format!("'{}' is neither a command nor a .onu file.", other)),
+        };
+
+        let mut rest = rest.iter();
+        while let Some(arg) = rest.next() {
+            let (name, inline) = match arg.split_once('=') {
+                Some((name, value)) if name.starts_with("--") => (name, Some(value.to_string())),
+                _ => (arg.as_str(), None),
+            };
+            let mut value = |option: &str| -> Result<String, String> {
+                inline.clone().or_else(|| rest.next().cloned()).ok_or_else(|| format!("'{}' needs a value.", option))
+            };
+            match name {
+                "-o" | "--output" => cli.output = Some(PathBuf::from(value(name)?)),
+                "-O0" | "-O1" | "-O2" | "-O3" => cli.opt_level = Some(name.as_bytes()[2] - b'0'),
+                "--search-path" => cli.search_path.extend(std::env::split_paths(&value(name)?)),
+                "--arithmetic" => {
+                    let mode = value(name)?;
+                    cli.arithmetic = Some(ArithmeticMode::from_flag(&mode).ok_or_else(|| {
+                        format!("Unknown --arithmetic value '{}'. Expected wrapping, checked or saturating.", mode)
+                    })?);
+                }
+                "--fast-math" => cli.fast_math = true,
+                "--polly-loops" => cli.polly_loops = true,
+                "--target" => cli.triple = Some(value(name)?),
+                "--cpu" => cli.cpu = Some(value(name)?),
+                "--features" => cli.features = Some(value(name)?),
+                "--reloc" => {
+                    let model = value(name)?;
+                    cli.reloc = Some(TargetOptions::reloc_from_flag(&model).ok_or_else(|| {
+                        format!("Unknown --reloc value '{}'. Expected default, static, pic or dynamic-no-pic.", model)
+                    })?);
+                }
+                "--concern" => {
+                    let level = value(name)?;
+                    cli.concern = Some(Strictness::from_flag(&level).ok_or_else(|| {
+                        format!("Unknown --concern value '{}'. Expected off, warn or deny.", level)
+                    })?);
+                }
+                "--concern-threshold" => {
+                    let percent = value(name)?;
+                    cli.concern_threshold = Some(percent.parse().ok().filter(|p| *p <= 100).ok_or_else(|| {
+                        format!("Unknown --concern-threshold value '{}'. Expected a percentage from 0 to 100.", percent)
+                    })?);
+                }
+                "--synonyms" => cli.synonyms.push(Self::word_list(&value(name)?)),
+                "--stop-words" => cli.stop_words.extend(Self::word_list(&value(name)?)),
+                "--manifest-path" => cli.manifest_path = Some(PathBuf::from(value(name)?)),
+                "--profile" => cli.profile = Some(value(name)?),
+                "--check" if cli.command == Command::Fmt => cli.check_only = true,
+                option if option.starts_with('-') => return Err(format!("Unknown option '{}'.", option)),
+                file => cli.files.push(PathBuf::from(file)),
+            }
+        }
+
+        match (cli.command, cli.files.len()) {
+            (Command::Fmt, 0) => Err("'fmt' needs at least one file.".to_string()),
+            (Command::Fmt, _) | (Command::Build | Command::Run, 0) | (_, 1) => Ok(cli),
+            (_, 0) => Err("No .onu file was given.".to_string()),
+            (_, _) => Err("Only 'fmt' accepts more than one file.".to_string()),
+        }
+    }
+
+    /// The single file of a command that takes one, if given.
+    pub fn file(&self) -> Option<&PathBuf> {
+        self.files.first()
+    }
+
+    /// The target machine the options describe.
+    pub fn target_options(&self) -> TargetOptions {
+        let mut options = TargetOptions { triple: self.triple.clone(), ..TargetOptions::default() };
+        if let Some(cpu) = &self.cpu { options.cpu = cpu.clone(); }
+        if let Some(features) = &self.features { options.features = features.clone(); }
+        if let Some(reloc) = self.reloc { options.reloc = reloc; }
+        if let Some(level) = self.opt_level { options.opt_level = TargetOptions::opt_level_from(level); }
+        options
+    }
+
+    /// The concern policy the options make of `policy`, the default or a manifest's.
+    pub fn concern_policy(&self, mut policy: ConcernPolicy) -> ConcernPolicy {
+        if let Some(strictness) = self.concern { policy.strictness = strictness; }
+        if let Some(percent) = self.concern_threshold { policy.threshold = percent as f64 / 100.0; }
+        for group in &self.synonyms { policy.vocabulary.add_synonyms(group); }
+        policy.vocabulary.add_stop_words(&self.stop_words);
+        policy
+    }
+
+    fn word_list(words: &str) -> Vec<String> {
+        words.split(',').map(str::trim).filter(|word| !word.is_empty()).map(str::to_string).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use inkwell::OptimizationLevel;
+
+    fn parse(args: &str) -> Result<Cli, String> {
+        Cli::parse(&args.split_whitespace().map(str::to_string).collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn test_subcommands_and_their_files() {
+        let cli = parse("emit llvm-ir bf.onu -o bf.ll -O2").unwrap();
+        assert_eq!(cli.command, Command::Emit(EmitForm::LlvmIr));
+        assert_eq!(cli.file(), Some(&PathBuf::from("bf.onu")));
+        assert_eq!(cli.output, Some(PathBuf::from("bf.ll")));
+        assert_eq!(cli.target_options().opt_level, OptimizationLevel::Default);
+        assert!(EmitForm::LlvmIr.is_text() && !EmitForm::Bc.is_text());
+
+        assert_eq!(parse("hello.onu").unwrap().command, Command::CheckThenRun);
+        assert_eq!(parse("build --profile release").unwrap().profile.as_deref(), Some("release"));
+        assert_eq!(parse("fmt a.onu b.onu --check").unwrap().files.len(), 2);
+    }
+
+    #[test]
+    fn test_options_take_their_values_either_way() {
+        let cli = parse("run bf.onu --search-path=lib --arithmetic wrapping --fast-math --reloc=static").unwrap();
+        assert_eq!(cli.search_path, vec![PathBuf::from("lib")]);
+        assert_eq!(cli.arithmetic, Some(ArithmeticMode::Wrapping));
+        assert!(cli.fast_math && !cli.polly_loops);
+        assert_eq!(cli.target_options().reloc, inkwell::targets::RelocMode::Static);
+    }
+
+    #[test]
+    fn test_concern_options_adjust_the_policy() {
+        let cli = parse("check bf.onu --concern=deny --concern-threshold 40 --synonyms tape,machine --synonyms=bracket,loop --stop-words find").unwrap();
+        let policy = cli.concern_policy(ConcernPolicy::default());
+        assert_eq!(policy.strictness, Strictness::Deny);
+        assert_eq!(policy.threshold, 0.4);
+        assert_eq!(policy.vocabulary.synonyms.len(), ConcernPolicy::default().vocabulary.synonyms.len() + 2);
+        assert!(policy.vocabulary.stop_words.contains("find"));
+        assert!(parse("check bf.onu --concern loud").unwrap_err().contains("Expected off, warn or deny"));
+        assert!(parse("check bf.onu --concern-threshold 101").unwrap_err().contains("percentage"));
+    }
+
+    #[test]
+    fn test_malformed_invocations_are_refused() {
+        assert!(parse("").unwrap_err().contains("No command or file"));
+        assert!(parse("emit wasm bf.onu").unwrap_err().contains("'wasm' is not a form"));
+        assert!(parse("check bf.onu --run").unwrap_err().contains("Unknown option '--run'"));
+        assert!(parse("check bf.onu -o").unwrap_err().contains("'-o' needs a value"));
+        assert!(parse("check").unwrap_err().contains("No .onu file"));
+        assert!(parse("doc a.onu b.onu").unwrap_err().contains("Only 'fmt'"));
+        assert!(parse("frobnicate").unwrap_err().contains("neither a command nor a .onu file"));
+        assert!(parse("check bf.onu --check").is_err(), "--check belongs to fmt");
+    }
+}
@@ -1,22 +1,110 @@
-use crate::mir::{MirProgram, MirFunction, MirInstruction, MirOperand, MirLiteral, MirBinOp, MirTerminator};
+use crate::mir::{ArithmeticMode, MirProgram, MirFunction, MirInstruction, MirOperand, MirLiteral, MirBinOp, MirTerminator};
 use crate::types::OnuType;
+use crate::error::Span;
 use inkwell::context::Context;
 use inkwell::builder::Builder;
 use inkwell::module::Module;
-use inkwell::values::{FunctionValue, BasicValueEnum, BasicValue, PointerValue};
-use inkwell::types::{BasicTypeEnum, BasicType, BasicMetadataTypeEnum};
+use inkwell::values::{FunctionValue, BasicValueEnum, BasicValue, IntValue, PointerValue};
+use inkwell::types::{AnyType, BasicTypeEnum, BasicType, BasicMetadataTypeEnum, StructType};
 use inkwell::passes::PassManager;
+use inkwell::targets::{CodeModel, FileType, InitializationConfig, RelocMode, Target, TargetMachine, TargetTriple};
+use inkwell::OptimizationLevel;
 use std::collections::HashMap;
+use std::path::Path;
 
 pub trait CodeGenerator {
     fn generate(&self, program: &MirProgram) -> Result<Vec<u8>, String>;
 }
 
+/// The artifact a compilation writes from its module. Executables are linked from
+/// an object file by the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EmitKind {
+    Object,
+    Assembly,
+    Bitcode,
+    LlvmIr,
+}
+
+impl EmitKind {
+    /// The conventional file extension for this artifact.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            EmitKind::Object => "o",
+            EmitKind::Assembly => "s",
+            EmitKind::Bitcode => "bc",
+            EmitKind::LlvmIr => "ll",
+        }
+    }
+}
+
+/// Describes the machine that native artifacts are generated for.
+/// The default targets the host with position-independent code.
+#[derive(Debug, Clone)]
+pub struct TargetOptions {
+    /// The target triple; `None` selects the host triple.
+    pub triple: Option<String>,
+    pub cpu: String,
+    pub features: String,
+    pub reloc: RelocMode,
+    pub opt_level: OptimizationLevel,
+}
+
+impl Default for TargetOptions {
+    fn default() -> Self {
+        Self {
+            triple: None,
+            cpu: "generic".to_string(),
+            features: String::new(),
+            reloc: RelocMode::PIC,
+            opt_level: OptimizationLevel::Aggressive,
+        }
+    }
+}
+
+impl TargetOptions {
+    /// Parses the value of a `--reloc=` flag.
+    pub fn reloc_from_flag(flag: &str) -> Option<RelocMode> {
+        match flag {
+            "default" => Some(RelocMode::Default),
+            "static" => Some(RelocMode::Static),
+            "pic" => Some(RelocMode::PIC),
+            "dynamic-no-pic" => Some(RelocMode::DynamicNoPic),
+            _ => None,
+        }
+    }
+
+    /// The optimization level of `-O0` to `-O3`; any higher level is `-O3`.
+    pub fn opt_level_from(level: u8) -> OptimizationLevel {
+        match level {
+            0 => OptimizationLevel::None,
+            1 => OptimizationLevel::Less,
+            2 => OptimizationLevel::Default,
+            _ => OptimizationLevel::Aggressive,
+        }
+    }
+
+    fn create_target_machine(&self) -> Result<TargetMachine, String> {
+        Target::initialize_all(&InitializationConfig::default());
+        let triple = match &self.triple {
+            Some(t) => TargetTriple::create(t),
+            None => TargetMachine::get_default_triple(),
+        };
+        let target = Target::from_triple(&triple).map_err(|e| e.to_string())?;
+        target
+            .create_target_machine(&triple, &self.cpu, &self.features, self.opt_level, self.reloc, CodeModel::Default)
+            .ok_or_else(|| format!("LLVM cannot create a target machine for '{}' (cpu '{}').", triple, self.cpu))
+    }
+}
+
 pub struct LlvmGenerator<'ctx> {
     context: &'ctx Context,
     module: Module<'ctx>,
     builder: Builder<'ctx>,
     registry: Option<crate::registry::Registry>,
+    arithmetic: ArithmeticMode,
+    fast_math: bool,
+    polly_loops: bool,
 }
 
 impl<'ctx> LlvmGenerator<'ctx> {
@@ -27,7 +115,27 @@ impl<'ctx> LlvmGenerator<'ctx> {
     pub fn new(context: &'ctx Context, module_name: &str, registry: Option<crate::registry::Registry>) -> Self {
         let module = context.create_module(module_name);
         let builder = context.create_builder();
-        Self { context, module, builder, registry }
+        Self { context, module, builder, registry, arithmetic: ArithmeticMode::default(), fast_math: false, polly_loops: false }
+    }
+
+    /// Selects how integer arithmetic treats results outside their width.
+    pub fn with_arithmetic(mut self, mode: ArithmeticMode) -> Self {
+        self.arithmetic = mode;
+        self
+    }
+
+    /// Lets LLVM reassociate and approximate float arithmetic, assuming no NaNs,
+    /// infinities or signed zeros. LLVM 14 takes these as function attributes.
+    pub fn with_fast_math(mut self, enabled: bool) -> Self {
+        self.fast_math = enabled;
+        self
+    }
+
+    /// Emits matrix products as affine loop nests in the module rather than runtime
+    /// calls, so a polyhedral optimizer such as Polly can tile and vectorize them.
+    pub fn with_polly_loops(mut self, enabled: bool) -> Self {
+        self.polly_loops = enabled;
+        self
     }
 
     fn run_optimizations(&self) {
@@ -44,22 +152,74 @@ impl<'ctx> LlvmGenerator<'ctx> {
         for function in self.module.get_functions() {
             fpm.run_on(&function);
         }
+
+        // A behavior kept internal that nothing calls any longer is dropped, until none is left.
+        while let Some(unused) = self.module.get_functions().find(|f| {
+            f.get_linkage() == inkwell::module::Linkage::Internal && f.as_global_value().as_pointer_value().get_first_use().is_none()
+        }) {
+            unsafe { unused.delete() };
+        }
     }
 
     fn onu_type_to_llvm(&self, typ: &OnuType) -> BasicTypeEnum<'ctx> {
         match typ {
-            OnuType::I64 => self.context.i64_type().as_basic_type_enum(),
+            OnuType::I8 | OnuType::U8 => self.context.i8_type().as_basic_type_enum(),
+            OnuType::I16 | OnuType::U16 => self.context.i16_type().as_basic_type_enum(),
+            OnuType::I32 | OnuType::U32 => self.context.i32_type().as_basic_type_enum(),
+            OnuType::I64 | OnuType::U64 => self.context.i64_type().as_basic_type_enum(),
+            OnuType::I128 | OnuType::U128 => self.context.i128_type().as_basic_type_enum(),
+            OnuType::F32 => self.context.f32_type().as_basic_type_enum(),
             OnuType::F64 => self.context.f64_type().as_basic_type_enum(),
             OnuType::Boolean => self.context.bool_type().as_basic_type_enum(),
-            OnuType::Strings => self.context.i8_type().ptr_type(inkwell::AddressSpace::default()).as_basic_type_enum(),
+            OnuType::Strings | OnuType::Array(_) | OnuType::Matrix => self.context.i8_type().ptr_type(inkwell::AddressSpace::default()).as_basic_type_enum(),
             OnuType::Tuple(types) => {
                 let llvm_types: Vec<BasicTypeEnum> = types.iter().map(|t| self.onu_type_to_llvm(t)).collect();
                 self.context.struct_type(&llvm_types, false).as_basic_type_enum()
             }
+            OnuType::Record(name) => self.record_type(name).as_basic_type_enum(),
+            OnuType::Variety(name) => self.variety_type(name).as_basic_type_enum(),
             _ => self.context.i64_type().as_basic_type_enum(),
         }
     }
 
+    /// The named LLVM struct of a structure, `%Point = type { double, double }`,
+    /// defined from the registry on first use.
+    fn record_type(&self, name: &str) -> StructType<'ctx> {
+        if let Some(existing) = self.module.get_struct_type(name) {
+            return existing;
+        }
+        let record = self.context.opaque_struct_type(name);
+        let fields: Vec<BasicTypeEnum> = self
+            .registry
+            .as_ref()
+            .and_then(|r| r.get_structure(name))
+            .map_or_else(Vec::new, |fields| fields.iter().map(|field| self.onu_type_to_llvm(&field.typ)).collect());
+        record.set_body(&fields, false);
+        record
+    }
+
+    /// The named LLVM struct of a variety: its tag, then the payload of every
+    /// variant in declaration order, `%Instruction = type { i64, i64, i64, ... }`.
+    fn variety_type(&self, name: &str) -> StructType<'ctx> {
+        if let Some(existing) = self.module.get_struct_type(name) {
+            return existing;
+        }
+        let variety = self.context.opaque_struct_type(name);
+        let mut slots = vec![self.context.i64_type().as_basic_type_enum()];
+        if let Some(variants) = self.registry.as_ref().and_then(|r| r.get_variety(name)) {
+            slots.extend(variants.iter().flat_map(|v| &v.payload).map(|(_, typ)| self.onu_type_to_llvm(typ)));
+        }
+        variety.set_body(&slots, false);
+        variety
+    }
+
+    /// Whether a behavior delivering `typ` returns it through a hidden `sret` pointer.
+    /// Tuples, structures and varieties do, whatever the platform's rules for small structs: to C,
+    /// a behavior delivering `(a : b)` is `void name(struct { A a; B b; } *out, ...)`.
+    fn returns_through_pointer(typ: &OnuType) -> bool {
+        matches!(typ, OnuType::Tuple(_) | OnuType::Record(_) | OnuType::Variety(_))
+    }
+
     fn generate_function(&self, mir_func: &MirFunction) -> Result<Option<FunctionValue<'ctx>>, String> {
         if mir_func.args.iter().any(|arg| matches!(arg.typ, OnuType::Shape(_) | OnuType::Nothing)) {
             return Ok(None);
@@ -73,89 +233,17 @@ impl<'ctx> LlvmGenerator<'ctx> {
         let entry_bb = self.context.append_basic_block(function, "entry");
         self.builder.position_at_end(entry_bb);
 
-        let mut var_types: HashMap<usize, BasicTypeEnum<'ctx>> = HashMap::new();
-        for arg in &mir_func.args {
-            var_types.insert(arg.ssa_var, self.onu_type_to_llvm(&arg.typ));
-        }
-        
-        // Pass to determine types
-        for block in &mir_func.blocks {
-            for inst in &block.instructions {
-                match inst {
-                    MirInstruction::Assign { dest, src } => {
-                        let typ = match src {
-                            MirOperand::Constant(lit) => match lit {
-                                MirLiteral::I64(_) => self.context.i64_type().as_basic_type_enum(),
-                                MirLiteral::F64(_) => self.context.f64_type().as_basic_type_enum(),
-                                MirLiteral::Boolean(_) => self.context.bool_type().as_basic_type_enum(),
-                                MirLiteral::Text(_) => self.context.i8_type().ptr_type(inkwell::AddressSpace::default()).as_basic_type_enum(),
-                                MirLiteral::Nothing => self.context.i64_type().as_basic_type_enum(),
-                            },
-                            MirOperand::Variable(id) => *var_types.get(id).unwrap_or(&self.context.i64_type().as_basic_type_enum()),
-                        };
-                        var_types.insert(*dest, typ);
-                    }
-                    MirInstruction::BinaryOperation { dest, op, lhs, .. } => {
-                        let typ = match op {
-                            MirBinOp::Eq | MirBinOp::Gt | MirBinOp::Lt => self.context.i64_type().as_basic_type_enum(),
-                            _ => match lhs {
-                                MirOperand::Variable(id) => *var_types.get(id).unwrap_or(&self.context.i64_type().as_basic_type_enum()),
-                                _ => self.context.i64_type().as_basic_type_enum(),
-                            }
-                        };
-                        var_types.insert(*dest, typ);
-                    }
-                    MirInstruction::Call { dest, name, .. } => {
-                        let ret_type = if let Some(f) = self.module.get_function(name) {
-                            f.get_type().get_return_type().unwrap_or(self.context.i64_type().as_basic_type_enum())
-                        } else if name == "broadcasts" || name == "emit" {
-                            self.context.i32_type().as_basic_type_enum()
-                        } else {
-                            let actual_name = if let Some(idx) = name.find('_') { &name[..idx] } else { name };
-                            if let Some(sig) = self.registry.as_ref().and_then(|r| r.get_signature(actual_name)) {
-                                self.onu_type_to_llvm(&sig.return_type)
-                            } else {
-                                self.context.i64_type().as_basic_type_enum()
-                            }
-                        };
-                        var_types.insert(*dest, ret_type);
-                    }
-                    MirInstruction::Tuple { dest, elements } => {
-                        let mut elem_types = Vec::new();
-                        for e in elements {
-                            match e {
-                                MirOperand::Constant(lit) => match lit {
-                                    MirLiteral::I64(_) => elem_types.push(self.context.i64_type().as_basic_type_enum()),
-                                    MirLiteral::F64(_) => elem_types.push(self.context.f64_type().as_basic_type_enum()),
-                                    MirLiteral::Boolean(_) => elem_types.push(self.context.bool_type().as_basic_type_enum()),
-                                    MirLiteral::Text(_) => elem_types.push(self.context.i8_type().ptr_type(inkwell::AddressSpace::default()).as_basic_type_enum()),
-                                    MirLiteral::Nothing => elem_types.push(self.context.i64_type().as_basic_type_enum()),
-                                },
-                                MirOperand::Variable(id) => elem_types.push(*var_types.get(id).unwrap_or(&self.context.i64_type().as_basic_type_enum())),
-                            }
-                        }
-                        let struct_type = self.context.struct_type(&elem_types, false);
-                        var_types.insert(*dest, struct_type.as_basic_type_enum());
-                    }
-                    MirInstruction::Index { dest, subject, index } => {
-                        if let MirOperand::Variable(id) = subject {
-                            if let Some(BasicTypeEnum::StructType(st)) = var_types.get(id) {
-                                let field_type = st.get_field_type_at_index(*index as u32).unwrap();
-                                var_types.insert(*dest, field_type);
-                            }
-                        }
-                    }
-                    _ => {}
-                }
-            }
-        }
-
-        for (id, typ) in &var_types {
-            let ptr = self.builder.build_alloca(*typ, &format!("v{}", id)).unwrap();
-            ssa_storage.insert(*id, (ptr, *typ));
+        // Every SSA variable was typed by the type checker, so storage is allocated
+        // straight from the MIR's annotations.
+        for (id, typ) in &mir_func.ssa_types {
+            let typ = self.onu_type_to_llvm(typ);
+            let ptr = self.builder.build_alloca(typ, &format!("v{}", id)).unwrap();
+            ssa_storage.insert(*id, (ptr, typ));
         }
 
-        for (i, arg) in function.get_param_iter().enumerate() {
+        // A tuple result is written through the hidden first parameter.
+        let result_slot = Self::returns_through_pointer(&mir_func.return_type).then(|| function.get_nth_param(0).unwrap().into_pointer_value());
+        for (i, arg) in function.get_param_iter().skip(result_slot.is_some() as usize).enumerate() {
             let mir_arg = &mir_func.args[i];
             let (ptr, _) = ssa_storage.get(&mir_arg.ssa_var).unwrap();
             self.builder.build_store(*ptr, arg).unwrap();
@@ -183,19 +271,19 @@ impl<'ctx> LlvmGenerator<'ctx> {
                         let (ptr, _) = ssa_storage.get(dest).unwrap();
                         self.builder.build_store(*ptr, val).unwrap();
                     }
-                    MirInstruction::BinaryOperation { dest, op, lhs, rhs } => {
+                    MirInstruction::BinaryOperation { dest, op, lhs, rhs, span } => {
                         let l_val = self.operand_to_llvm(lhs, &ssa_storage)?;
                         let r_val = self.operand_to_llvm(rhs, &ssa_storage)?;
+                        let unsigned = mir_func.operand_type(lhs).is_unsigned();
+                        let line = mir_func.locate(*span).line;
                         let res = match op {
                             MirBinOp::Add | MirBinOp::Sub | MirBinOp::Mul | MirBinOp::Div => {
                                 if l_val.is_int_value() {
+                                    let (l, r) = (l_val.into_int_value(), r_val.into_int_value());
                                     match op {
-                                        MirBinOp::Add => self.builder.build_int_add(l_val.into_int_value(), r_val.into_int_value(), "addtmp"),
-                                        MirBinOp::Sub => self.builder.build_int_sub(l_val.into_int_value(), r_val.into_int_value(), "subtmp"),
-                                        MirBinOp::Mul => self.builder.build_int_mul(l_val.into_int_value(), r_val.into_int_value(), "multmp"),
-                                        MirBinOp::Div => self.builder.build_int_signed_div(l_val.into_int_value(), r_val.into_int_value(), "divtmp"),
-                                        _ => unreachable!(),
-                                    }.unwrap().as_basic_value_enum()
+                                        MirBinOp::Div => self.build_int_division(l, r, unsigned, mir_func, line),
+                                        _ => self.build_int_step(op, l, r, unsigned, mir_func, line),
+                                    }.as_basic_value_enum()
                                 } else {
                                     match op {
                                         MirBinOp::Add => self.builder.build_float_add(l_val.into_float_value(), r_val.into_float_value(), "addtmp"),
@@ -207,26 +295,49 @@ impl<'ctx> LlvmGenerator<'ctx> {
                                 }
                             }
                             MirBinOp::Eq | MirBinOp::Gt | MirBinOp::Lt => {
+                                let (gt, lt) = if unsigned {
+                                    (inkwell::IntPredicate::UGT, inkwell::IntPredicate::ULT)
+                                } else {
+                                    (inkwell::IntPredicate::SGT, inkwell::IntPredicate::SLT)
+                                };
                                 let cond = match op {
                                     MirBinOp::Eq => if l_val.is_int_value() { self.builder.build_int_compare(inkwell::IntPredicate::EQ, l_val.into_int_value(), r_val.into_int_value(), "eqtmp") } else { self.builder.build_float_compare(inkwell::FloatPredicate::OEQ, l_val.into_float_value(), r_val.into_float_value(), "eqtmp") },
-                                    MirBinOp::Gt => if l_val.is_int_value() { self.builder.build_int_compare(inkwell::IntPredicate::SGT, l_val.into_int_value(), r_val.into_int_value(), "gttmp") } else { self.builder.build_float_compare(inkwell::FloatPredicate::OGT, l_val.into_float_value(), r_val.into_float_value(), "gttmp") },
-                                    MirBinOp::Lt => if l_val.is_int_value() { self.builder.build_int_compare(inkwell::IntPredicate::SLT, l_val.into_int_value(), r_val.into_int_value(), "lttmp") } else { self.builder.build_float_compare(inkwell::FloatPredicate::OLT, l_val.into_float_value(), r_val.into_float_value(), "lttmp") },
+                                    MirBinOp::Gt => if l_val.is_int_value() { self.builder.build_int_compare(gt, l_val.into_int_value(), r_val.into_int_value(), "gttmp") } else { self.builder.build_float_compare(inkwell::FloatPredicate::OGT, l_val.into_float_value(), r_val.into_float_value(), "gttmp") },
+                                    MirBinOp::Lt => if l_val.is_int_value() { self.builder.build_int_compare(lt, l_val.into_int_value(), r_val.into_int_value(), "lttmp") } else { self.builder.build_float_compare(inkwell::FloatPredicate::OLT, l_val.into_float_value(), r_val.into_float_value(), "lttmp") },
                                     _ => unreachable!(),
                                 }.unwrap();
-                                self.builder.build_int_z_extend(cond, self.context.i64_type(), "booltmp").unwrap().as_basic_value_enum()
+                                cond.as_basic_value_enum()
                             }
                         };
                         let (ptr, _) = ssa_storage.get(dest).unwrap();
                         self.builder.build_store(*ptr, res).unwrap();
                     }
-                    MirInstruction::Call { dest, name, args } => {
+                    MirInstruction::Call { dest, name, args, .. } if Self::is_numeric_builtin(name) => {
+                        let mut values = Vec::new();
+                        for arg in args { values.push(self.operand_to_llvm(arg, &ssa_storage)?); }
+                        let types: Vec<OnuType> = args.iter().map(|arg| mir_func.operand_type(arg)).collect();
+                        let res = self.build_numeric_builtin(name, &values, &types)?;
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, res).unwrap();
+                    }
+                    MirInstruction::Call { dest, name, args, span } if Self::is_matrix_builtin(name, args, mir_func) => {
+                        let mut values = Vec::new();
+                        for arg in args { values.push(self.operand_to_llvm(arg, &ssa_storage)?); }
+                        let res = self.build_matrix_builtin(name, &values, mir_func, *span);
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, res).unwrap();
+                    }
+                    MirInstruction::Call { dest, name, args, span } if Self::is_array_builtin(name) => {
+                        let mut values = Vec::new();
+                        for arg in args { values.push(self.operand_to_llvm(arg, &ssa_storage)?); }
+                        let subject = mir_func.operand_type(&args[0]);
+                        let res = self.build_array_builtin(name, &values, &subject, mir_func, *span);
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, res).unwrap();
+                    }
+                    MirInstruction::Call { dest, name, args, .. } => {
                         let (llvm_func, _ret_type) = if let Some(f) = self.module.get_function(name) {
                             (f, f.get_type().get_return_type().unwrap_or(self.context.i64_type().as_basic_type_enum()))
-                        } else if name == "broadcasts" || name == "emit" {
-                            let i32_type = self.context.i32_type();
-                            let str_ptr_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
-                            let fn_type = i32_type.fn_type(&[str_ptr_type.into()], false);
-                            (self.module.add_function("puts", fn_type, Some(inkwell::module::Linkage::External)), i32_type.as_basic_type_enum())
                         } else {
                             let actual_name = if let Some(idx) = name.find('_') { &name[..idx] } else { name };
                             if let Some(sig) = self.registry.as_ref().and_then(|r| r.get_signature(actual_name)) {
@@ -243,9 +354,16 @@ impl<'ctx> LlvmGenerator<'ctx> {
                             }
                         };
                         let mut llvm_args = Vec::new();
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        let through_pointer = mir_func.ssa_types.get(dest).is_some_and(Self::returns_through_pointer);
+                        if through_pointer {
+                            llvm_args.push((*ptr).into());
+                        }
                         for arg in args { llvm_args.push(self.operand_to_llvm(arg, &ssa_storage)?.into()); }
-                        let call_target = if name == "broadcasts" || name == "emit" { self.module.get_function("puts").unwrap() } else { llvm_func };
-                        let call_res = self.builder.build_call(call_target, &llvm_args, "calltmp").unwrap();
+                        let call_res = self.builder.build_call(llvm_func, &llvm_args, "calltmp").unwrap();
+                        if through_pointer {
+                            continue;
+                        }
                         let res = match call_res.try_as_basic_value() {
                             inkwell::values::ValueKind::Basic(val) => val,
                             inkwell::values::ValueKind::Instruction(_) => self.context.i64_type().const_int(0, false).as_basic_value_enum()
@@ -254,14 +372,55 @@ impl<'ctx> LlvmGenerator<'ctx> {
                         self.builder.build_store(*ptr, res).unwrap();
                     }
                     MirInstruction::Tuple { dest, elements } => {
-                        let (ptr, typ) = ssa_storage.get(dest).unwrap();
-                        let _struct_type = typ.into_struct_type();
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
                         for (i, e) in elements.iter().enumerate() {
                             let val = self.operand_to_llvm(e, &ssa_storage)?;
                             let field_ptr = self.builder.build_struct_gep(*ptr, i as u32, &format!("f{}", i)).unwrap();
                             self.builder.build_store(field_ptr, val).unwrap();
                         }
                     }
+                    MirInstruction::Variant { dest, tag, payload } => {
+                        // The slots of the other variants are zeroed, so the value is fully defined.
+                        let (ptr, typ) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, typ.const_zero()).unwrap();
+                        let tag_ptr = self.builder.build_struct_gep(*ptr, 0, "tag").unwrap();
+                        self.builder.build_store(tag_ptr, self.context.i64_type().const_int(*tag as u64, false)).unwrap();
+                        for (slot, e) in payload {
+                            let val = self.operand_to_llvm(e, &ssa_storage)?;
+                            let slot_ptr = self.builder.build_struct_gep(*ptr, *slot as u32, &format!("p{}", slot)).unwrap();
+                            self.builder.build_store(slot_ptr, val).unwrap();
+                        }
+                    }
+                    MirInstruction::Array { dest, elements } => {
+                        let element_type = match mir_func.ssa_types.get(dest) {
+                            Some(OnuType::Array(element)) => self.onu_type_to_llvm(element),
+                            _ => self.context.i64_type().as_basic_type_enum(),
+                        };
+                        let length = self.context.i64_type().const_int(elements.len() as u64, false);
+                        let array = self.build_array_new(length, element_type);
+                        for (i, e) in elements.iter().enumerate() {
+                            let val = self.operand_to_llvm(e, &ssa_storage)?;
+                            let slot = self.build_element_pointer(array, element_type, self.context.i64_type().const_int(i as u64, false));
+                            self.builder.build_store(slot, val).unwrap();
+                        }
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, array).unwrap();
+                    }
+                    MirInstruction::Matrix { dest, rows, cols, entries } => {
+                        let i64_type = self.context.i64_type();
+                        let function = self.runtime_function("matrix-new", &[i64_type.into(), i64_type.into()], Some(self.onu_type_to_llvm(&OnuType::Matrix)));
+                        let call = self.builder.build_call(function, &[i64_type.const_int(*rows as u64, false).into(), i64_type.const_int(*cols as u64, false).into()], "matrixtmp").unwrap();
+                        let matrix = call.try_as_basic_value().unwrap_basic().into_pointer_value();
+                        let data = self.build_matrix_entries(matrix);
+                        for (i, e) in entries.iter().enumerate() {
+                            let val = self.operand_to_llvm(e, &ssa_storage)?;
+                            // SAFETY: the matrix was allocated with exactly `rows * cols` entries.
+                            let slot = unsafe { self.builder.build_gep(data, &[i64_type.const_int(i as u64, false)], "entryptr") }.unwrap();
+                            self.builder.build_store(slot, val).unwrap();
+                        }
+                        let (ptr, _) = ssa_storage.get(dest).unwrap();
+                        self.builder.build_store(*ptr, matrix).unwrap();
+                    }
                     MirInstruction::Index { dest, subject, index } => {
                         let (subj_ptr, _subj_type) = match subject {
                             MirOperand::Variable(id) => ssa_storage.get(id).unwrap(),
@@ -272,7 +431,18 @@ impl<'ctx> LlvmGenerator<'ctx> {
                         let (ptr, _) = ssa_storage.get(dest).unwrap();
                         self.builder.build_store(*ptr, val).unwrap();
                     }
-                    MirInstruction::Emit(_op) => {}
+                    MirInstruction::Convert { dest, src } => {
+                        let val = self.operand_to_llvm(src, &ssa_storage)?;
+                        let source = mir_func.operand_type(src);
+                        let (ptr, target_type) = ssa_storage.get(dest).unwrap();
+                        let target = mir_func.ssa_types.get(dest).cloned().unwrap_or(OnuType::I64);
+                        let res = self.build_conversion(val, &source, *target_type, &target);
+                        self.builder.build_store(*ptr, res).unwrap();
+                    }
+                    MirInstruction::Emit(op) => {
+                        let val = self.operand_to_llvm(op, &ssa_storage)?;
+                        self.build_emit(val, &mir_func.operand_type(op))?;
+                    }
                 }
             }
 
@@ -282,6 +452,10 @@ impl<'ctx> LlvmGenerator<'ctx> {
                         self.builder.build_return(Some(&self.context.i32_type().const_int(0, false))).unwrap();
                     } else if mir_func.return_type == OnuType::Nothing {
                         self.builder.build_return(None).unwrap();
+                    } else if let Some(slot) = result_slot {
+                        let val = self.operand_to_llvm(op, &ssa_storage)?;
+                        self.builder.build_store(slot, val).unwrap();
+                        self.builder.build_return(None).unwrap();
                     } else {
                         let val = self.operand_to_llvm(op, &ssa_storage)?;
                         self.builder.build_return(Some(&val)).unwrap();
@@ -298,6 +472,11 @@ impl<'ctx> LlvmGenerator<'ctx> {
                     let else_bb = llvm_blocks.get(else_block).unwrap();
                     self.builder.build_conditional_branch(cond_val, *then_bb, *else_bb).unwrap();
                 }
+                MirTerminator::Switch { discriminant, arms, otherwise } => {
+                    let tag = self.operand_to_llvm(discriminant, &ssa_storage)?.into_int_value();
+                    let cases: Vec<_> = arms.iter().map(|(value, block)| (self.context.i64_type().const_int(*value as u64, false), *llvm_blocks.get(block).unwrap())).collect();
+                    self.builder.build_switch(tag, *llvm_blocks.get(otherwise).unwrap(), &cases).unwrap();
+                }
                 MirTerminator::Unreachable => {
                     self.builder.build_unreachable().unwrap();
                 }
@@ -306,11 +485,515 @@ impl<'ctx> LlvmGenerator<'ctx> {
         if function.verify(true) { Ok(Some(function)) } else { Err(format!("LLVM Function verification failed for {}", mir_func.name)) }
     }
 
+    /// Adds, subtracts or multiplies two integers under the generator's arithmetic mode.
+    /// `line` is where the verb is written, for the overflow trap.
+    fn build_int_step(&self, op: &MirBinOp, l: IntValue<'ctx>, r: IntValue<'ctx>, unsigned: bool, mir_func: &MirFunction, line: usize) -> IntValue<'ctx> {
+        let (verb, operation) = match op {
+            MirBinOp::Add => ("added-to", "add"),
+            MirBinOp::Sub => ("decreased-by", "sub"),
+            _ => ("scales-by", "mul"),
+        };
+        let sign = if unsigned { "u" } else { "s" };
+        match self.arithmetic {
+            ArithmeticMode::Wrapping => match op {
+                MirBinOp::Add => self.builder.build_int_add(l, r, "addtmp"),
+                MirBinOp::Sub => self.builder.build_int_sub(l, r, "subtmp"),
+                _ => self.builder.build_int_mul(l, r, "multmp"),
+            }.unwrap(),
+            ArithmeticMode::Saturating => {
+                // Saturating multiplication is the fixed-point intrinsic with no fractional bits.
+                let (name, scale) = match op {
+                    MirBinOp::Mul => (format!("llvm.{}mul.fix.sat", sign), Some(self.context.i32_type().const_zero())),
+                    _ => (format!("llvm.{}{}.sat", sign, operation), None),
+                };
+                let mut args: Vec<inkwell::values::BasicMetadataValueEnum> = vec![l.into(), r.into()];
+                if let Some(scale) = scale {
+                    args.push(scale.into());
+                }
+                let call = self.builder.build_call(self.intrinsic(&name, l.get_type().as_basic_type_enum()), &args, "sattmp").unwrap();
+                call.try_as_basic_value().unwrap_basic().into_int_value()
+            }
+            ArithmeticMode::Checked => {
+                let name = format!("llvm.{}{}.with.overflow", sign, operation);
+                let call = self.builder.build_call(self.intrinsic(&name, l.get_type().as_basic_type_enum()), &[l.into(), r.into()], "chktmp").unwrap();
+                let pair = call.try_as_basic_value().unwrap_basic().into_struct_value();
+                let overflowed = self.builder.build_extract_value(pair, 1, "overflowed").unwrap().into_int_value();
+                self.build_trap_if(overflowed, "overflow-trap", Some(verb), mir_func, line);
+                self.builder.build_extract_value(pair, 0, "result").unwrap().into_int_value()
+            }
+        }
+    }
+
+    /// Divides two integers, refusing a zero divisor in every mode. A signed division
+    /// by -1 is a negation, so it wraps, traps or saturates like `decreased-by`.
+    fn build_int_division(&self, l: IntValue<'ctx>, r: IntValue<'ctx>, unsigned: bool, mir_func: &MirFunction, line: usize) -> IntValue<'ctx> {
+        let int_type = l.get_type();
+        let is_zero = self.builder.build_int_compare(inkwell::IntPredicate::EQ, r, int_type.const_zero(), "iszero").unwrap();
+        self.build_trap_if(is_zero, "zero-divisor-trap", None, mir_func, line);
+        if unsigned {
+            return self.builder.build_int_unsigned_div(l, r, "divtmp").unwrap();
+        }
+
+        let minus_one = int_type.const_all_ones();
+        let is_minus_one = self.builder.build_int_compare(inkwell::IntPredicate::EQ, r, minus_one, "isminusone").unwrap();
+        if self.arithmetic == ArithmeticMode::Checked {
+            let min = int_type.const_int(1, false).const_shl(int_type.const_int(int_type.get_bit_width() as u64 - 1, false));
+            let is_min = self.builder.build_int_compare(inkwell::IntPredicate::EQ, l, min, "ismin").unwrap();
+            let overflowed = self.builder.build_and(is_minus_one, is_min, "overflowed").unwrap();
+            self.build_trap_if(overflowed, "overflow-trap", Some("partitions-by"), mir_func, line);
+        }
+        let negated = match self.arithmetic {
+            ArithmeticMode::Saturating => {
+                let call = self.builder.build_call(self.intrinsic("llvm.ssub.sat", int_type.as_basic_type_enum()), &[int_type.const_zero().into(), l.into()], "negtmp").unwrap();
+                call.try_as_basic_value().unwrap_basic().into_int_value()
+            }
+            _ => self.builder.build_int_sub(int_type.const_zero(), l, "negtmp").unwrap(),
+        };
+        // Dividing the minimum by -1 is undefined in LLVM, so that divisor never reaches `sdiv`.
+        let divisor = self.builder.build_select(is_minus_one, int_type.const_int(1, false), r, "divisor").unwrap().into_int_value();
+        let quotient = self.builder.build_int_signed_div(l, divisor, "divtmp").unwrap();
+        self.builder.build_select(is_minus_one, negated, quotient, "quotient").unwrap().into_int_value()
+    }
+
+    /// Branches to a call of the runtime trap when `condition` holds, continuing in a
+    /// fresh block otherwise. The trap names the verb, the behavior and the line it failed on.
+    fn build_trap_if(&self, condition: IntValue<'ctx>, trap: &str, verb: Option<&str>, mir_func: &MirFunction, line: usize) {
+        let function = self.builder.get_insert_block().and_then(|b| b.get_parent()).unwrap();
+        let trap_block = self.context.append_basic_block(function, "trap");
+        let continue_block = self.context.append_basic_block(function, "cont");
+        self.builder.build_conditional_branch(condition, trap_block, continue_block).unwrap();
+
+        self.builder.position_at_end(trap_block);
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let mut params: Vec<BasicMetadataTypeEnum> = Vec::new();
+        let mut args: Vec<inkwell::values::BasicMetadataValueEnum> = Vec::new();
+        if let Some(verb) = verb {
+            params.push(text_type.into());
+            args.push(self.builder.build_global_string_ptr(verb, "trapverb").unwrap().as_pointer_value().into());
+        }
+        params.push(text_type.into());
+        params.push(self.context.i64_type().into());
+        args.push(self.builder.build_global_string_ptr(&mir_func.name, "trapbehavior").unwrap().as_pointer_value().into());
+        args.push(self.context.i64_type().const_int(line as u64, false).into());
+        let function = self.runtime_function(trap, &params, None);
+        self.builder.build_call(function, &args, "").unwrap();
+        self.builder.build_unreachable().unwrap();
+
+        self.builder.position_at_end(continue_block);
+    }
+
+    /// Declares an overloaded LLVM intrinsic for the given operand type.
+    fn intrinsic(&self, name: &str, operand: BasicTypeEnum<'ctx>) -> FunctionValue<'ctx> {
+        self.intrinsic_over(name, &[operand])
+    }
+
+    /// Declares an LLVM intrinsic overloaded on several types, in the order LLVM names them.
+    fn intrinsic_over(&self, name: &str, types: &[BasicTypeEnum<'ctx>]) -> FunctionValue<'ctx> {
+        inkwell::intrinsics::Intrinsic::find(name)
+            .and_then(|intrinsic| intrinsic.get_declaration(&self.module, types))
+            .unwrap_or_else(|| panic!("LLVM provides no intrinsic named {}", name))
+    }
+
+    fn is_numeric_builtin(name: &str) -> bool {
+        matches!(name, "as-text" | "square-root-of" | "floor-of" | "rounded" | "raised-to" | "formatted-to")
+    }
+
+    /// Lowers the builtins that dispatch on the width of their subject: rendering
+    /// goes through the runtime printers, and the float builtins become intrinsics.
+    fn build_numeric_builtin(&self, name: &str, args: &[BasicValueEnum<'ctx>], types: &[OnuType]) -> Result<BasicValueEnum<'ctx>, String> {
+        let intrinsic = match name {
+            "as-text" => return Ok(self.build_render_text(args[0], &types[0])?.as_basic_value_enum()),
+            "formatted-to" => {
+                let value = match types[0] {
+                    OnuType::F32 => self.builder.build_float_ext(args[0].into_float_value(), self.context.f64_type(), "fmtext").unwrap().as_basic_value_enum(),
+                    _ => args[0],
+                };
+                let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+                let function = self.runtime_function("formatted-to", &[value.get_type().into(), args[1].get_type().into()], Some(text_type.as_basic_type_enum()));
+                let call = self.builder.build_call(function, &[value.into(), args[1].into()], "fmttmp").unwrap();
+                return Ok(call.try_as_basic_value().unwrap_basic());
+            }
+            "square-root-of" => "llvm.sqrt",
+            "floor-of" => "llvm.floor",
+            "rounded" => "llvm.round",
+            _ => "llvm.pow",
+        };
+        let operands: Vec<inkwell::values::BasicMetadataValueEnum> = args.iter().map(|arg| (*arg).into()).collect();
+        let call = self.builder.build_call(self.intrinsic(intrinsic, args[0].get_type()), &operands, "fltmp").unwrap();
+        Ok(call.try_as_basic_value().unwrap_basic())
+    }
+
+    fn is_array_builtin(name: &str) -> bool {
+        matches!(name, "element-at" | "length-of" | "appended-with" | "slice-of")
+    }
+
+    /// Lowers the array builtins onto the runtime's array blocks. Elements are read
+    /// and written in place; only growing and slicing copy. A position outside the
+    /// array traps at `span`, where the verb is written.
+    fn build_array_builtin(&self, name: &str, args: &[BasicValueEnum<'ctx>], subject: &OnuType, mir_func: &MirFunction, span: Span) -> BasicValueEnum<'ctx> {
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let i64_type = self.context.i64_type();
+        let element_type = match subject {
+            OnuType::Array(element) => self.onu_type_to_llvm(element),
+            _ => i64_type.as_basic_type_enum(),
+        };
+        let array = args[0].into_pointer_value();
+        match name {
+            "element-at" => {
+                let params = [text_type.into(), i64_type.into(), text_type.into(), i64_type.into()];
+                let function = self.runtime_function("element-slot", &params, Some(text_type.as_basic_type_enum()));
+                let behavior = self.builder.build_global_string_ptr(&mir_func.name, "slotbehavior").unwrap().as_pointer_value();
+                let line = i64_type.const_int(mir_func.locate(span).line as u64, false);
+                let call = self.builder.build_call(function, &[array.into(), args[1].into(), behavior.into(), line.into()], "slottmp").unwrap();
+                let slot = call.try_as_basic_value().unwrap_basic().into_pointer_value();
+                let typed = self.builder.build_pointer_cast(slot, element_type.ptr_type(inkwell::AddressSpace::default()), "slotcast").unwrap();
+                self.builder.build_load(typed, "elemtmp").unwrap()
+            }
+            "appended-with" => {
+                let length = self.build_array_length(array);
+                let function = self.runtime_function("array-grown", &[text_type.into()], Some(text_type.as_basic_type_enum()));
+                let call = self.builder.build_call(function, &[array.into()], "growntmp").unwrap();
+                let grown = call.try_as_basic_value().unwrap_basic().into_pointer_value();
+                let slot = self.build_element_pointer(grown, element_type, length);
+                self.builder.build_store(slot, args[1]).unwrap();
+                grown.as_basic_value_enum()
+            }
+            "length-of" => self.build_array_length(array).as_basic_value_enum(),
+            _ => {
+                let function = self.runtime_function("slice-of", &[text_type.into(), i64_type.into(), i64_type.into()], Some(text_type.as_basic_type_enum()));
+                let call = self.builder.build_call(function, &[array.into(), args[1].into(), args[2].into()], "slicetmp").unwrap();
+                call.try_as_basic_value().unwrap_basic()
+            }
+        }
+    }
+
+    fn is_matrix_builtin(name: &str, args: &[MirOperand], mir_func: &MirFunction) -> bool {
+        matches!(name, "added-to" | "scales-by" | "multiplied-with" | "transposed" | "entry-at" | "determinant")
+            && args.first().is_some_and(|subject| mir_func.operand_type(subject) == OnuType::Matrix)
+    }
+
+    /// Lowers the matrix verbs onto the runtime, which checks the shapes they combine.
+    /// With Polly loops enabled, a product is computed by loops in the module instead.
+    /// A refusal traps at `span`, where the verb is written.
+    fn build_matrix_builtin(&self, name: &str, args: &[BasicValueEnum<'ctx>], mir_func: &MirFunction, span: Span) -> BasicValueEnum<'ctx> {
+        let matrix_type = self.onu_type_to_llvm(&OnuType::Matrix);
+        let (i64_type, f64_type) = (self.context.i64_type(), self.context.f64_type());
+        let behavior = self.builder.build_global_string_ptr(&mir_func.name, "matrixbehavior").unwrap().as_pointer_value();
+        let line = i64_type.const_int(mir_func.locate(span).line as u64, false);
+        let mut operands: Vec<inkwell::values::BasicMetadataValueEnum> = args.iter().map(|arg| (*arg).into()).collect();
+        let (symbol, ret, traps) = match name {
+            "added-to" => ("matrix-sum", matrix_type, true),
+            "scales-by" => ("matrix-scaled", matrix_type, false),
+            "multiplied-with" if self.polly_loops => return self.build_matrix_product_loops(args, behavior, line).as_basic_value_enum(),
+            "multiplied-with" => ("matrix-product", matrix_type, true),
+            "transposed" => ("matrix-transposed", matrix_type, false),
+            "entry-at" => ("matrix-entry", f64_type.as_basic_type_enum(), true),
+            _ => ("matrix-determinant", f64_type.as_basic_type_enum(), true),
+        };
+        // The verbs that can refuse their operands name the behavior and line in the trap.
+        if traps {
+            operands.push(behavior.into());
+            operands.push(line.into());
+        }
+        let params: Vec<BasicMetadataTypeEnum> = operands
+            .iter()
+            .map(|operand| match operand {
+                inkwell::values::BasicMetadataValueEnum::PointerValue(p) => p.get_type().into(),
+                inkwell::values::BasicMetadataValueEnum::FloatValue(f) => f.get_type().into(),
+                other => other.into_int_value().get_type().into(),
+            })
+            .collect();
+        let function = self.runtime_function(symbol, &params, Some(ret));
+        let call = self.builder.build_call(function, &operands, "matrixtmp").unwrap();
+        call.try_as_basic_value().unwrap_basic()
+    }
+
+    /// Addresses the first entry of a matrix, past its header.
+    fn build_matrix_entries(&self, matrix: PointerValue<'ctx>) -> PointerValue<'ctx> {
+        let header = self.context.i64_type().const_int(onu_runtime::MATRIX_HEADER_SIZE as u64, false);
+        // SAFETY: the runtime allocates the header and the entries contiguously.
+        let data = unsafe { self.builder.build_gep(matrix, &[header], "matrixdata") }.unwrap();
+        self.builder.build_pointer_cast(data, self.context.f64_type().ptr_type(inkwell::AddressSpace::default()), "entriescast").unwrap()
+    }
+
+    /// Reads the rows (field 0) or columns (field 1) from a matrix's header.
+    fn build_matrix_dimension(&self, matrix: PointerValue<'ctx>, field: u64) -> IntValue<'ctx> {
+        let i64_type = self.context.i64_type();
+        let header = self.builder.build_pointer_cast(matrix, i64_type.ptr_type(inkwell::AddressSpace::default()), "headercast").unwrap();
+        // SAFETY: the header holds the rows, then the columns.
+        let slot = unsafe { self.builder.build_gep(header, &[i64_type.const_int(field, false)], "dimptr") }.unwrap();
+        self.builder.build_load(slot, "dim").unwrap().into_int_value()
+    }
+
+    /// Multiplies two matrices with a loop nest whose bounds and subscripts are affine
+    /// in the induction variables. The runtime checks the shapes and allocates the
+    /// zeroed product, which the innermost loop accumulates into.
+    fn build_matrix_product_loops(&self, args: &[BasicValueEnum<'ctx>], behavior: PointerValue<'ctx>, line: IntValue<'ctx>) -> PointerValue<'ctx> {
+        let matrix_type = self.onu_type_to_llvm(&OnuType::Matrix);
+        let i64_type = self.context.i64_type();
+        let (a, b) = (args[0].into_pointer_value(), args[1].into_pointer_value());
+        let params = [matrix_type.into(), matrix_type.into(), matrix_type.into(), i64_type.into()];
+        let function = self.runtime_function("matrix-product-target", &params, Some(matrix_type));
+        let call = self.builder.build_call(function, &[a.into(), b.into(), behavior.into(), line.into()], "producttmp").unwrap();
+        let product = call.try_as_basic_value().unwrap_basic().into_pointer_value();
+
+        let (rows, inner, cols) = (self.build_matrix_dimension(a, 0), self.build_matrix_dimension(a, 1), self.build_matrix_dimension(b, 1));
+        let (a_data, b_data, c_data) = (self.build_matrix_entries(a), self.build_matrix_entries(b), self.build_matrix_entries(product));
+        let entry = |data: PointerValue<'ctx>, row: IntValue<'ctx>, width: IntValue<'ctx>, col: IntValue<'ctx>| {
+            let offset = self.builder.build_int_add(self.builder.build_int_mul(row, width, "rowoff").unwrap(), col, "off").unwrap();
+            // SAFETY: every subscript stays below the bounds of the loop nest, which the runtime checked.
+            unsafe { self.builder.build_gep(data, &[offset], "entryptr") }.unwrap()
+        };
+        self.build_counted_loop(rows, "i", &mut |i| {
+            self.build_counted_loop(inner, "k", &mut |k| {
+                let a_ik = self.builder.build_load(entry(a_data, i, inner, k), "aik").unwrap().into_float_value();
+                self.build_counted_loop(cols, "j", &mut |j| {
+                    let b_kj = self.builder.build_load(entry(b_data, k, cols, j), "bkj").unwrap().into_float_value();
+                    let c_slot = entry(c_data, i, cols, j);
+                    let c_ij = self.builder.build_load(c_slot, "cij").unwrap().into_float_value();
+                    let term = self.builder.build_float_mul(a_ik, b_kj, "term").unwrap();
+                    let sum = self.builder.build_float_add(c_ij, term, "acc").unwrap();
+                    self.builder.build_store(c_slot, sum).unwrap();
+                });
+            });
+        });
+        product
+    }
+
+    /// Builds `for index in 0..bound { body(index) }` with the index in a phi node,
+    /// leaving the builder after the loop.
+    fn build_counted_loop(&self, bound: IntValue<'ctx>, name: &str, body: &mut dyn FnMut(IntValue<'ctx>)) {
+        let i64_type = self.context.i64_type();
+        let before = self.builder.get_insert_block().unwrap();
+        let function = before.get_parent().unwrap();
+        let header = self.context.append_basic_block(function, &format!("{}.head", name));
+        let body_block = self.context.append_basic_block(function, &format!("{}.body", name));
+        let exit = self.context.append_basic_block(function, &format!("{}.exit", name));
+        self.builder.build_unconditional_branch(header).unwrap();
+
+        self.builder.position_at_end(header);
+        let index = self.builder.build_phi(i64_type, name).unwrap();
+        let i = index.as_basic_value().into_int_value();
+        let more = self.builder.build_int_compare(inkwell::IntPredicate::SLT, i, bound, &format!("{}.more", name)).unwrap();
+        self.builder.build_conditional_branch(more, body_block, exit).unwrap();
+
+        self.builder.position_at_end(body_block);
+        body(i);
+        let next = self.builder.build_int_add(i, i64_type.const_int(1, false), &format!("{}.next", name)).unwrap();
+        let body_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(header).unwrap();
+
+        index.add_incoming(&[(&i64_type.const_zero(), before), (&next, body_end)]);
+        self.builder.position_at_end(exit);
+    }
+
+    /// Allocates a zeroed runtime array of `length` elements of the given type.
+    fn build_array_new(&self, length: IntValue<'ctx>, element_type: BasicTypeEnum<'ctx>) -> PointerValue<'ctx> {
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let i64_type = self.context.i64_type();
+        let function = self.runtime_function("array-new", &[i64_type.into(), i64_type.into()], Some(text_type.as_basic_type_enum()));
+        let size = element_type.size_of().unwrap();
+        let call = self.builder.build_call(function, &[length.into(), size.into()], "arraytmp").unwrap();
+        call.try_as_basic_value().unwrap_basic().into_pointer_value()
+    }
+
+    fn build_array_length(&self, array: PointerValue<'ctx>) -> IntValue<'ctx> {
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let function = self.runtime_function("length-of", &[text_type.into()], Some(self.context.i64_type().as_basic_type_enum()));
+        let call = self.builder.build_call(function, &[array.into()], "lentmp").unwrap();
+        call.try_as_basic_value().unwrap_basic().into_int_value()
+    }
+
+    /// Addresses the element at `index` directly, past the array's header, without a bounds check.
+    fn build_element_pointer(&self, array: PointerValue<'ctx>, element_type: BasicTypeEnum<'ctx>, index: IntValue<'ctx>) -> PointerValue<'ctx> {
+        let header = self.context.i64_type().const_int(onu_runtime::ARRAY_HEADER_SIZE as u64, false);
+        // SAFETY: the runtime allocates the header and every element contiguously.
+        let data = unsafe { self.builder.build_gep(array, &[header], "arraydata") }.unwrap();
+        let typed = self.builder.build_pointer_cast(data, element_type.ptr_type(inkwell::AddressSpace::default()), "datacast").unwrap();
+        // SAFETY: callers only address positions below the array's length.
+        unsafe { self.builder.build_gep(typed, &[index], "elemptr") }.unwrap()
+    }
+
+    /// Renders an array as `[a, b, c]` with a loop over its elements.
+    fn build_render_array(&self, array: PointerValue<'ctx>, element: &OnuType) -> Result<PointerValue<'ctx>, String> {
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let i64_type = self.context.i64_type();
+        let join = self.runtime_function("joined-with", &[text_type.into(), text_type.into()], Some(text_type.as_basic_type_enum()));
+        let append = |acc: BasicValueEnum<'ctx>, piece: PointerValue<'ctx>| {
+            let call = self.builder.build_call(join, &[acc.into(), piece.into()], "jointmp").unwrap();
+            call.try_as_basic_value().unwrap_basic().into_pointer_value()
+        };
+        let element_type = self.onu_type_to_llvm(element);
+        let open = self.builder.build_global_string_ptr("[", "arrayopen").unwrap().as_pointer_value();
+        let length = self.build_array_length(array);
+        let before = self.builder.get_insert_block().unwrap();
+        let function = before.get_parent().unwrap();
+        let (header, body, exit) = (
+            self.context.append_basic_block(function, "renderhead"),
+            self.context.append_basic_block(function, "renderbody"),
+            self.context.append_basic_block(function, "renderexit"),
+        );
+        self.builder.build_unconditional_branch(header).unwrap();
+
+        self.builder.position_at_end(header);
+        let index = self.builder.build_phi(i64_type, "renderidx").unwrap();
+        let text = self.builder.build_phi(text_type, "rendertext").unwrap();
+        let more = self.builder.build_int_compare(inkwell::IntPredicate::SLT, index.as_basic_value().into_int_value(), length, "more").unwrap();
+        self.builder.build_conditional_branch(more, body, exit).unwrap();
+
+        self.builder.position_at_end(body);
+        let i = index.as_basic_value().into_int_value();
+        let first = self.builder.build_int_compare(inkwell::IntPredicate::EQ, i, i64_type.const_zero(), "first").unwrap();
+        let none = self.builder.build_global_string_ptr("", "arraynone").unwrap().as_pointer_value();
+        let comma = self.builder.build_global_string_ptr(", ", "arraysep").unwrap().as_pointer_value();
+        let separator = self.builder.build_select(first, none, comma, "sep").unwrap().into_pointer_value();
+        let prefixed = append(text.as_basic_value(), separator);
+        let slot = self.build_element_pointer(array, element_type, i);
+        let value = self.builder.build_load(slot, "renderelem").unwrap();
+        let piece = self.build_render_text(value, element)?;
+        let extended = append(prefixed.as_basic_value_enum(), piece);
+        let next = self.builder.build_int_add(i, i64_type.const_int(1, false), "nextidx").unwrap();
+        let body_end = self.builder.get_insert_block().unwrap();
+        self.builder.build_unconditional_branch(header).unwrap();
+
+        index.add_incoming(&[(&i64_type.const_zero(), before), (&next, body_end)]);
+        text.add_incoming(&[(&open, before), (&extended, body_end)]);
+        self.builder.position_at_end(exit);
+        let close = self.builder.build_global_string_ptr("]", "arrayclose").unwrap().as_pointer_value();
+        Ok(append(text.as_basic_value(), close))
+    }
+
+    /// Converts a number between widths: integers are extended by their source's
+    /// signedness or truncated, and cross the float boundary by their signedness too.
+    fn build_conversion(&self, val: BasicValueEnum<'ctx>, source: &OnuType, target_type: BasicTypeEnum<'ctx>, target: &OnuType) -> BasicValueEnum<'ctx> {
+        match (val, target_type) {
+            (BasicValueEnum::IntValue(i), BasicTypeEnum::IntType(t)) => {
+                self.builder.build_int_cast_sign_flag(i, t, !source.is_unsigned(), "convtmp").unwrap().as_basic_value_enum()
+            }
+            (BasicValueEnum::IntValue(i), BasicTypeEnum::FloatType(t)) if source.is_unsigned() => {
+                self.builder.build_unsigned_int_to_float(i, t, "convtmp").unwrap().as_basic_value_enum()
+            }
+            (BasicValueEnum::IntValue(i), BasicTypeEnum::FloatType(t)) => {
+                self.builder.build_signed_int_to_float(i, t, "convtmp").unwrap().as_basic_value_enum()
+            }
+            // A float outside the width saturates at its nearest end, and NaN becomes 0,
+            // as in the interpreter; plain fptosi and fptoui would yield poison.
+            (BasicValueEnum::FloatValue(f), BasicTypeEnum::IntType(t)) => {
+                let name = if target.is_unsigned() { "llvm.fptoui.sat" } else { "llvm.fptosi.sat" };
+                let function = self.intrinsic_over(name, &[t.as_basic_type_enum(), f.get_type().as_basic_type_enum()]);
+                let call = self.builder.build_call(function, &[f.into()], "convtmp").unwrap();
+                call.try_as_basic_value().unwrap_basic()
+            }
+            (BasicValueEnum::FloatValue(f), BasicTypeEnum::FloatType(t)) => {
+                self.builder.build_float_cast(f, t, "convtmp").unwrap().as_basic_value_enum()
+            }
+            _ => val,
+        }
+    }
+
+    /// Brings an integer to the 64 bits the runtime printers take, by its signedness.
+    /// A 128-bit integer is printed from its low 64 bits.
+    fn build_widen_for_runtime(&self, i: inkwell::values::IntValue<'ctx>, typ: &OnuType) -> BasicValueEnum<'ctx> {
+        self.builder.build_int_cast_sign_flag(i, self.context.i64_type(), !typ.is_unsigned(), "rtext").unwrap().as_basic_value_enum()
+    }
+
+    /// Lowers a broadcast by dispatching on the operand's type to the matching runtime printer.
+    /// Tuples, arrays and matrices have no printer of their own; they are rendered to text first.
+    fn build_emit(&self, val: BasicValueEnum<'ctx>, typ: &OnuType) -> Result<(), String> {
+        let (printer, arg) = match val {
+            _ if matches!(typ, OnuType::Array(_) | OnuType::Matrix) => ("broadcasts", self.build_render_text(val, typ)?.as_basic_value_enum()),
+            BasicValueEnum::PointerValue(_) => ("broadcasts", val),
+            BasicValueEnum::IntValue(i) if i.get_type().get_bit_width() == 1 => ("broadcasts-boolean", val),
+            BasicValueEnum::IntValue(i) if typ.is_unsigned() => ("broadcasts-unsigned", self.build_widen_for_runtime(i, typ)),
+            BasicValueEnum::IntValue(i) => ("broadcasts-integer", self.build_widen_for_runtime(i, typ)),
+            BasicValueEnum::FloatValue(f) => {
+                let widened = self.builder.build_float_ext(f, self.context.f64_type(), "emitext").unwrap();
+                ("broadcasts-float", if typ == &OnuType::F32 { widened.as_basic_value_enum() } else { val })
+            }
+            BasicValueEnum::StructValue(_) => ("broadcasts", self.build_render_text(val, typ)?.as_basic_value_enum()),
+            other => return Err(format!("Cannot broadcast a value of type {}", other.get_type().print_to_string())),
+        };
+        let function = self.runtime_function(printer, &[arg.get_type().into()], None);
+        self.builder.build_call(function, &[arg.into()], "").unwrap();
+        Ok(())
+    }
+
+    /// Renders any printable value as runtime text, recursing through tuple fields and array elements.
+    /// Matrices are rendered by the runtime.
+    fn build_render_text(&self, val: BasicValueEnum<'ctx>, typ: &OnuType) -> Result<PointerValue<'ctx>, String> {
+        let text_type = self.context.i8_type().ptr_type(inkwell::AddressSpace::default());
+        let convert = |name: &str, arg: BasicValueEnum<'ctx>| {
+            let function = self.runtime_function(name, &[arg.get_type().into()], Some(text_type.as_basic_type_enum()));
+            let call = self.builder.build_call(function, &[arg.into()], "rendertmp").unwrap();
+            call.try_as_basic_value().unwrap_basic().into_pointer_value()
+        };
+        match val {
+            BasicValueEnum::PointerValue(p) => match typ {
+                OnuType::Array(element) => self.build_render_array(p, element),
+                OnuType::Matrix => {
+                    let function = self.runtime_function("matrix-as-text", &[p.get_type().into()], Some(text_type.as_basic_type_enum()));
+                    let call = self.builder.build_call(function, &[p.into()], "rendertmp").unwrap();
+                    Ok(call.try_as_basic_value().unwrap_basic().into_pointer_value())
+                }
+                _ => Ok(p),
+            },
+            BasicValueEnum::IntValue(i) if i.get_type().get_bit_width() == 1 => Ok(convert("boolean-as-text", val)),
+            BasicValueEnum::IntValue(i) if typ.is_unsigned() => Ok(convert("unsigned-as-text", self.build_widen_for_runtime(i, typ))),
+            BasicValueEnum::IntValue(i) => Ok(convert("as-text", self.build_widen_for_runtime(i, typ))),
+            BasicValueEnum::FloatValue(f) if typ == &OnuType::F32 => {
+                let widened = self.builder.build_float_ext(f, self.context.f64_type(), "renderext").unwrap();
+                Ok(convert("float-as-text", widened.as_basic_value_enum()))
+            }
+            BasicValueEnum::FloatValue(_) => Ok(convert("float-as-text", val)),
+            BasicValueEnum::StructValue(st) => {
+                let join = self.runtime_function("joined-with", &[text_type.into(), text_type.into()], Some(text_type.as_basic_type_enum()));
+                let append = |acc: PointerValue<'ctx>, piece: PointerValue<'ctx>| {
+                    let call = self.builder.build_call(join, &[acc.into(), piece.into()], "jointmp").unwrap();
+                    call.try_as_basic_value().unwrap_basic().into_pointer_value()
+                };
+                let mut text = self.builder.build_global_string_ptr("(", "tupleopen").unwrap().as_pointer_value();
+                for i in 0..st.get_type().count_fields() {
+                    if i > 0 {
+                        let separator = self.builder.build_global_string_ptr(", ", "tuplesep").unwrap().as_pointer_value();
+                        text = append(text, separator);
+                    }
+                    let field = self.builder.build_extract_value(st, i, &format!("field{}", i)).unwrap();
+                    let field_type = match typ {
+                        OnuType::Tuple(types) => types.get(i as usize).cloned().unwrap_or(OnuType::Nothing),
+                        _ => OnuType::Nothing,
+                    };
+                    text = append(text, self.build_render_text(field, &field_type)?);
+                }
+                let close = self.builder.build_global_string_ptr(")", "tupleclose").unwrap().as_pointer_value();
+                Ok(append(text, close))
+            }
+            other => Err(format!("Cannot render a value of type {} as text", other.get_type().print_to_string())),
+        }
+    }
+
+    /// Returns the declaration of a runtime builtin, declaring it on first use.
+    fn runtime_function(&self, name: &str, params: &[BasicMetadataTypeEnum<'ctx>], ret: Option<BasicTypeEnum<'ctx>>) -> FunctionValue<'ctx> {
+        if let Some(f) = self.module.get_function(name) {
+            return f;
+        }
+        let fn_type = match ret {
+            Some(t) => t.fn_type(params, false),
+            None => self.context.void_type().fn_type(params, false),
+        };
+        self.module.add_function(name, fn_type, Some(inkwell::module::Linkage::External))
+    }
+
     fn operand_to_llvm(&self, op: &MirOperand, ssa_storage: &HashMap<usize, (PointerValue<'ctx>, BasicTypeEnum<'ctx>)>) -> Result<BasicValueEnum<'ctx>, String> {
         match op {
             MirOperand::Constant(lit) => match lit {
                 MirLiteral::I64(n) => Ok(self.context.i64_type().const_int(*n as u64, true).as_basic_value_enum()),
                 MirLiteral::F64(n) => Ok(self.context.f64_type().const_float(*n).as_basic_value_enum()),
+                MirLiteral::Integer(n, typ) => {
+                    let int_type = self.onu_type_to_llvm(typ).into_int_type();
+                    let words = [*n as u64, (*n >> 64) as u64];
+                    Ok(int_type.const_int_arbitrary_precision(&words[..(int_type.get_bit_width() as usize).div_ceil(64)]).as_basic_value_enum())
+                }
+                MirLiteral::Float(n, typ) => Ok(self.onu_type_to_llvm(typ).into_float_type().const_float(*n).as_basic_value_enum()),
                 MirLiteral::Boolean(b) => Ok(self.context.bool_type().const_int(if *b { 1 } else { 0 }, false).as_basic_value_enum()),
                 MirLiteral::Text(s) => {
                     let global_str = self.builder.build_global_string_ptr(s, "strtmp").unwrap();
@@ -326,20 +1009,96 @@ impl<'ctx> LlvmGenerator<'ctx> {
     }
 }
 
-impl<'ctx> CodeGenerator for LlvmGenerator<'ctx> {
-    fn generate(&self, program: &MirProgram) -> Result<Vec<u8>, String> {
+impl<'ctx> LlvmGenerator<'ctx> {
+    /// Lowers the whole program into this generator's module and optimizes it.
+    pub fn build_module(&self, program: &MirProgram) -> Result<(), String> {
+        self.build_module_with(program, &|_| true)
+    }
+
+    /// Lowers the functions `defines` picks into this generator's module and
+    /// optimizes it. The program's other exposed functions are only declared, so
+    /// that the object can be linked with those that define them.
+    pub fn build_module_with(&self, program: &MirProgram, defines: &dyn Fn(&MirFunction) -> bool) -> Result<(), String> {
         for mir_func in &program.functions {
             if mir_func.args.iter().any(|arg| matches!(arg.typ, OnuType::Shape(_) | OnuType::Nothing)) { continue; }
-            let arg_types: Vec<BasicMetadataTypeEnum> = mir_func.args.iter().map(|arg| self.onu_type_to_llvm(&arg.typ).into()).collect();
+            if mir_func.internal && !defines(mir_func) { continue; }
+            let mut arg_types: Vec<BasicMetadataTypeEnum> = mir_func.args.iter().map(|arg| self.onu_type_to_llvm(&arg.typ).into()).collect();
             let fn_name = if mir_func.name == "main" || mir_func.name == "run" { "main" } else { &mir_func.name };
+            let returns_tuple = fn_name != "main" && Self::returns_through_pointer(&mir_func.return_type);
+            if returns_tuple {
+                let result_type = self.onu_type_to_llvm(&mir_func.return_type);
+                arg_types.insert(0, result_type.ptr_type(inkwell::AddressSpace::default()).into());
+            }
             let fn_type = if fn_name == "main" { self.context.i32_type().fn_type(&arg_types, false) } 
-                          else if mir_func.return_type == OnuType::Nothing { self.context.void_type().fn_type(&arg_types, false) } 
+                          else if mir_func.return_type == OnuType::Nothing || returns_tuple { self.context.void_type().fn_type(&arg_types, false) } 
                           else { self.onu_type_to_llvm(&mir_func.return_type).fn_type(&arg_types, false) };
             let function = self.module.add_function(fn_name, fn_type, None);
             if fn_name == "main" { function.set_linkage(inkwell::module::Linkage::External); }
+            else if mir_func.internal { function.set_linkage(inkwell::module::Linkage::Internal); }
+            if returns_tuple {
+                let sret = inkwell::attributes::Attribute::get_named_enum_kind_id("sret");
+                let result_type = self.onu_type_to_llvm(&mir_func.return_type).as_any_type_enum();
+                function.add_attribute(inkwell::attributes::AttributeLoc::Param(0), self.context.create_type_attribute(sret, result_type));
+            }
+            if self.fast_math {
+                for attribute in ["unsafe-fp-math", "no-nans-fp-math", "no-infs-fp-math", "no-signed-zeros-fp-math", "approx-func-fp-math"] {
+                    function.add_attribute(inkwell::attributes::AttributeLoc::Function, self.context.create_string_attribute(attribute, "true"));
+                }
+            }
         }
-        for func in &program.functions { self.generate_function(func)?; }
+        for func in program.functions.iter().filter(|f| defines(f)) { self.generate_function(func)?; }
         self.run_optimizations();
+        Ok(())
+    }
+
+    /// Executes the built module in-process, resolving every external declaration
+    /// against the Rust runtime. Returns the exit status of the entry behavior.
+    pub fn run_jit(&self) -> Result<i32, String> {
+        Target::initialize_native(&InitializationConfig::default())?;
+        let engine = self.module.create_jit_execution_engine(OptimizationLevel::None).map_err(|e| e.to_string())?;
+        for function in self.module.get_functions() {
+            if function.count_basic_blocks() > 0 || function.get_intrinsic_id() != 0 {
+                continue;
+            }
+            let name = function.get_name().to_string_lossy();
+            match onu_runtime::builtin_address(&name) {
+                Some(address) => engine.add_global_mapping(&function, address),
+                None => return Err(format!("The runtime provides no implementation for '{}'.", name)),
+            }
+        }
+        let address = engine.get_function_address("main")
+            .map_err(|_| "The program has no entry behavior ('run' or 'main') to execute.".to_string())?;
+        // A trap unwinds out of `main`, so it is called directly rather than through
+        // LLVM, whose frames a trap caught by `onu_runtime::catch_traps` must not cross.
+        // SAFETY: every external symbol is mapped above, and `main` takes nothing and delivers an i32.
+        let main: unsafe extern "C-unwind" fn() -> i32 = unsafe { std::mem::transmute(address) };
+        Ok(unsafe { main() })
+    }
+}
+
+impl<'ctx> LlvmGenerator<'ctx> {
+    /// Writes the built module to `path` as the requested artifact. Object files and
+    /// assembly go through a TargetMachine; executables are linked by the caller.
+    pub fn write_artifact(&self, kind: EmitKind, options: &TargetOptions, path: &Path) -> Result<(), String> {
+        match kind {
+            EmitKind::Bitcode => {
+                if self.module.write_bitcode_to_path(path) { Ok(()) } else { Err(format!("Could not write bitcode to {}", path.display())) }
+            }
+            EmitKind::LlvmIr => self.module.print_to_file(path).map_err(|e| e.to_string()),
+            EmitKind::Object | EmitKind::Assembly => {
+                let machine = options.create_target_machine()?;
+                self.module.set_triple(&machine.get_triple());
+                self.module.set_data_layout(&machine.get_target_data().get_data_layout());
+                let file_type = if kind == EmitKind::Object { FileType::Object } else { FileType::Assembly };
+                machine.write_to_file(&self.module, file_type, path).map_err(|e| e.to_string())
+            }
+        }
+    }
+}
+
+impl<'ctx> CodeGenerator for LlvmGenerator<'ctx> {
+    fn generate(&self, program: &MirProgram) -> Result<Vec<u8>, String> {
+        self.build_module(program)?;
         Ok(self.module.write_bitcode_to_memory().as_slice().to_vec())
     }
 }
@@ -0,0 +1,256 @@
+//! Ọ̀nụ Concern Checker: The Single-Responsibility Layer
+//!
+//! This module enforces that a discourse unit addresses exactly one concern.
+//! A unit may declare at most one `the module called`, and every behavior's
+//! `with intent:` should speak to that module's `with concern:`.
+//!
+//! Alignment Scoring:
+//! Both texts are reduced to their content words (stop words removed); the
+//! concern also counts the words of the module's own name. Each
+//! intent word is aligned if the concern uses the same word, a synonym from the
+//! vocabulary, or a word sharing its stem (a common prefix of five letters).
+//! The score is the aligned fraction of the intent's content words; a behavior
+//! scoring below the policy's threshold is reported as a warning or refused,
+//! depending on the policy's strictness. A package sets the policy in the
+//! `[concern]` table of `Onu.toml`, and the command line with `--concern`,
+//! `--concern-threshold`, `--synonyms` and `--stop-words`.
+
+use crate::error::{OnuError, Span};
+use crate::hir::HirDiscourse;
+use std::collections::HashSet;
+
+/// How misaligned intents are treated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Strictness {
+    /// Alignment is not scored at all.
+    Off,
+    /// Misaligned intents are reported as warnings.
+    Warn,
+    /// Misaligned intents are refused with a CONCERN VIOLATION.
+    Deny,
+}
+
+/// The words the scorer ignores and the words it treats as interchangeable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcernVocabulary {
+    pub stop_words: HashSet<String>,
+    pub synonyms: Vec<HashSet<String>>,
+}
+
+impl Strictness {
+    /// Reads `off`, `warn` or `deny`, as written in `Onu.toml` or after `--concern`.
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "off" => Some(Strictness::Off),
+            "warn" => Some(Strictness::Warn),
+            "deny" => Some(Strictness::Deny),
+            _ => None,
+        }
+    }
+}
+
+/// The default knows only English function words and a few groups of general
+/// programming terms; a program's own domain is added with `add_synonyms`.
+impl Default for ConcernVocabulary {
+    fn default() -> Self {
+        let words = |list: &[&str]| list.iter().map(|w| w.to_string()).collect::<HashSet<_>>();
+        Self {
+            stop_words: words(&[
+                "a", "an", "the", "and", "or", "of", "to", "for", "with", "in", "on", "by", "from", "into", "as", "at",
+                "its", "their", "it", "is", "be", "this", "that", "every", "each", "all", "some", "if", "then", "whether",
+            ]),
+            synonyms: vec![
+                words(&["arithmetic", "math", "mathematical", "calculation", "computation", "addition", "subtraction", "number", "numeric"]),
+                words(&["multiplication", "product", "scaling", "scale"]),
+                words(&["text", "string", "character", "characters", "word", "words"]),
+                words(&["output", "broadcast", "display", "print", "report"]),
+                words(&["sequence", "series", "progression"]),
+                words(&["recursion", "recursive", "repetition", "loop", "iteration"]),
+            ],
+        }
+    }
+}
+
+impl ConcernVocabulary {
+    /// Treats the given words as interchangeable, in any case.
+    pub fn add_synonyms<S: AsRef<str>>(&mut self, group: &[S]) {
+        self.synonyms.push(group.iter().map(|word| word.as_ref().to_lowercase()).collect());
+    }
+
+    /// Ignores the given words, in any case, when scoring.
+    pub fn add_stop_words<S: AsRef<str>>(&mut self, words: &[S]) {
+        self.stop_words.extend(words.iter().map(|word| word.as_ref().to_lowercase()));
+    }
+}
+
+/// The configuration of the concern pass.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConcernPolicy {
+    pub strictness: Strictness,
+    /// The minimum alignment score, between 0 and 1, an intent must reach.
+    pub threshold: f64,
+    pub vocabulary: ConcernVocabulary,
+}
+
+impl Default for ConcernPolicy {
+    fn default() -> Self {
+        Self { strictness: Strictness::Warn, threshold: 0.25, vocabulary: ConcernVocabulary::default() }
+    }
+}
+
+pub struct ConcernChecker<'a> {
+    policy: &'a ConcernPolicy,
+}
+
+impl<'a> ConcernChecker<'a> {
+    pub fn new(policy: &'a ConcernPolicy) -> Self {
+        Self { policy }
+    }
+
+    /// Checks the discourse unit, returning the alignment warnings it produced.
+    pub fn check_program(&self, hir: &[HirDiscourse]) -> Result<Vec<String>, OnuError> {
+        let modules: Vec<(&String, &String, Span)> = hir
+            .iter()
+            .filter_map(|discourse| match discourse {
+                HirDiscourse::Module { name, concern, span } => Some((name, concern, *span)),
+                _ => None,
+            })
+            .collect();
+
+        if let [(first, _, _), (second, _, span), ..] = modules.as_slice() {
+            return Err(OnuError::ConcernViolation {
+                message: format!(
+                    "The module '{}' is declared in the same discourse unit as '{}'; each unit may declare only one module.",
+                    second, first
+                ),
+                span: *span,
+            });
+        }
+
+        let mut warnings = Vec::new();
+        let Some((module, concern, _)) = modules.first() else { return Ok(warnings) };
+        if self.policy.strictness == Strictness::Off {
+            return Ok(warnings);
+        }
+
+        for discourse in hir {
+            let HirDiscourse::Behavior { header, .. } = discourse else { continue };
+            // The entry point orchestrates the whole unit, so its intent is the unit's own.
+            if header.intent.is_empty() || (header.is_effect && (header.name == "run" || header.name == "main")) {
+                continue;
+            }
+            let Some(score) = self.score(&header.intent, &format!("{} {}", Self::split_name(module), concern)) else { continue };
+            if score >= self.policy.threshold {
+                continue;
+            }
+            let message = format!(
+                "The intent of '{}' (\"{}\") aligns {:.0}% with the concern of '{}' (\"{}\").",
+                header.name,
+                header.intent,
+                score * 100.0,
+                module,
+                concern
+            );
+            match self.policy.strictness {
+                Strictness::Deny => return Err(OnuError::ConcernViolation { message, span: header.span }),
+                _ => warnings.push(format!("{}: {}", header.span, message)),
+            }
+        }
+        Ok(warnings)
+    }
+
+    /// Returns the aligned fraction of the intent's content words, or `None` if it has none.
+    pub fn score(&self, intent: &str, concern: &str) -> Option<f64> {
+        let intent_words = self.content_words(intent);
+        if intent_words.is_empty() {
+            return None;
+        }
+        let concern_words = self.content_words(concern);
+        let aligned = intent_words
+            .iter()
+            .filter(|word| concern_words.iter().any(|other| self.aligned(word, other)))
+            .count();
+        Some(aligned as f64 / intent_words.len() as f64)
+    }
+
+    /// Splits a module name such as `TapeMachine` into the words `Tape Machine`.
+    fn split_name(name: &str) -> String {
+        let mut words = String::new();
+        for c in name.chars() {
+            if c.is_uppercase() && !words.is_empty() {
+                words.push(' ');
+            }
+            words.push(c);
+        }
+        words
+    }
+
+    fn content_words(&self, text: &str) -> Vec<String> {
+        text.split(|c: char| !c.is_alphanumeric())
+            .map(str::to_lowercase)
+            .filter(|word| !word.is_empty() && !self.policy.vocabulary.stop_words.contains(word))
+            .collect()
+    }
+
+    fn aligned(&self, a: &str, b: &str) -> bool {
+        const STEM: usize = 5;
+        a == b
+            || self.policy.vocabulary.synonyms.iter().any(|group| group.contains(a) && group.contains(b))
+            || (a.chars().count() >= STEM && b.chars().count() >= STEM && a.chars().take(STEM).eq(b.chars().take(STEM)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompilerSession;
+
+    const MISALIGNED: &str = "
+the module called Math
+    with concern: arithmetic
+
+the behavior called greet
+    with intent: say hello to a visitor
+    takes: nothing
+    delivers: a string
+    as:
+        \"hello\"
+";
+
+    #[test]
+    fn test_synonyms_and_stems_align() {
+        let policy = ConcernPolicy::default();
+        let checker = ConcernChecker::new(&policy);
+        assert_eq!(checker.score("arithmetic addition", "arithmetic and calculation"), Some(1.0));
+        assert_eq!(checker.score("a sequential product", "sequences of multiplication"), Some(1.0));
+        assert_eq!(checker.score("the", "anything"), None);
+    }
+
+    #[test]
+    fn test_domain_words_are_added_to_the_vocabulary() {
+        let mut policy = ConcernPolicy::default();
+        let intent = "greet the visitor";
+        assert_eq!(ConcernChecker::new(&policy).score(intent, "introductions"), Some(0.0));
+        policy.vocabulary.add_synonyms(&["Greet", "introductions"]);
+        policy.vocabulary.add_stop_words(&["visitor"]);
+        assert_eq!(ConcernChecker::new(&policy).score(intent, "introductions"), Some(1.0));
+        assert_eq!(Strictness::from_flag("deny"), Some(Strictness::Deny));
+        assert_eq!(Strictness::from_flag("loud"), None);
+    }
+
+    #[test]
+    fn test_misaligned_intent_warns_by_default() {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(MISALIGNED).unwrap();
+        assert_eq!(session.concern_warnings.len(), 1);
+        assert!(session.concern_warnings[0].contains("'greet'"));
+    }
+
+    #[test]
+    fn test_misaligned_intent_is_refused_when_strict() {
+        let mut session = CompilerSession::new().unwrap();
+        session.concern_policy.strictness = Strictness::Deny;
+        let err = session.build_mir(MISALIGNED).unwrap_err();
+        assert!(matches!(err, OnuError::ConcernViolation { .. }));
+    }
+}
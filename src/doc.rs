@@ -0,0 +1,195 @@
+//! Ọ̀nụ Documentation: The Reference Layer
+//!
+//! This module writes the reference of a discourse unit as Markdown: its
+//! module and concern, the modules it draws from, and the structures,
+//! varieties, shapes and exposed behaviors it offers to the modules that draw
+//! on it. A behavior's intent and signature come from its header; the comment
+//! lines written directly above a declaration become its description.
+//! Behaviors kept internal are not part of what a module offers, so they are
+//! only counted.
+
+use crate::parser::{Argument, BehaviorHeader, Discourse, Visibility};
+use crate::types::OnuType;
+use std::fmt::Write;
+
+/// Returns the Markdown reference of a parsed discourse unit and its source.
+pub fn document(ast: &[Discourse], source: &str) -> String {
+    let lines: Vec<&str> = source.lines().collect();
+    let mut doc = String::new();
+
+    match ast.iter().find(|d| matches!(d, Discourse::Module { .. })) {
+        Some(Discourse::Module { name, concern, draws_from, span }) => {
+            let _ = writeln!(doc, "# Module `{}`\n", name);
+            describe(&mut doc, &lines, span.line);
+            let _ = writeln!(doc, "*Concern:* {}\n", concern);
+            if !draws_from.is_empty() {
+                let drawn: Vec<String> = draws_from.iter().map(|m| format!("`{}`", m)).collect();
+                let _ = writeln!(doc, "*Draws from:* {}\n", drawn.join(", "));
+            }
+        }
+        _ => doc.push_str("# Discourse Unit\n\n"),
+    }
+
+    let structures: Vec<&Discourse> = ast.iter().filter(|d| matches!(d, Discourse::Structure { .. })).collect();
+    if !structures.is_empty() {
+        doc.push_str("## Structures\n\n");
+        for structure in structures {
+            let Discourse::Structure { name, fields, span } = structure else { continue };
+            let _ = writeln!(doc, "### `{}`\n", name);
+            describe(&mut doc, &lines, span.line);
+            doc.push_str("| Field | Type | Readable |\n| --- | --- | --- |\n");
+            for field in fields {
+                let readable = match field.visibility {
+                    Visibility::Exposed => "wherever the structure is".to_string(),
+                    Visibility::Kept => "within this module".to_string(),
+                    Visibility::Internal => format!("by behaviors whose subject is a {}", name),
+                };
+                let _ = writeln!(doc, "| `{}` | {} | {} |", field.name, field.type_info.onu_type, readable);
+            }
+            doc.push('\n');
+        }
+    }
+
+    let varieties: Vec<&Discourse> = ast.iter().filter(|d| matches!(d, Discourse::Variety { .. })).collect();
+    if !varieties.is_empty() {
+        doc.push_str("## Varieties\n\n");
+        for variety in varieties {
+            let Discourse::Variety { name, variants, span } = variety else { continue };
+            let _ = writeln!(doc, "### `{}`\n", name);
+            describe(&mut doc, &lines, span.line);
+            for variant in variants {
+                match variant.payload.as_slice() {
+                    [] => { let _ = writeln!(doc, "- `{}`", variant.name); }
+                    payload => { let _ = writeln!(doc, "- `{}` carrying {}", variant.name, arguments(payload)); }
+                }
+            }
+            doc.push('\n');
+        }
+    }
+
+    let shapes: Vec<&Discourse> = ast.iter().filter(|d| matches!(d, Discourse::Shape { .. })).collect();
+    if !shapes.is_empty() {
+        doc.push_str("## Shapes\n\n");
+        for shape in shapes {
+            let Discourse::Shape { name, behaviors } = shape else { continue };
+            let _ = writeln!(doc, "### `{}`\n\nPromises:\n", name);
+            for behavior in behaviors {
+                let _ = writeln!(doc, "- `{}`: {}", behavior.name, signature(behavior));
+            }
+            doc.push('\n');
+        }
+    }
+
+    let behaviors: Vec<&BehaviorHeader> = ast.iter().filter_map(|d| match d {
+        Discourse::Behavior { header, .. } => Some(header),
+        _ => None,
+    }).collect();
+    let (exposed, internal): (Vec<&BehaviorHeader>, Vec<&BehaviorHeader>) =
+        behaviors.into_iter().partition(|header| header.visibility == Visibility::Exposed);
+    if !exposed.is_empty() {
+        doc.push_str("## Behaviors\n\n");
+        for header in exposed {
+            let kind = if header.is_effect { "effect behavior" } else { "behavior" };
+            let _ = writeln!(doc, "### `{}`\n\n*The {}* {}\n", header.name, kind, signature(header));
+            if !header.intent.is_empty() {
+                let _ = writeln!(doc, "*Intent:* {}\n", header.intent);
+            }
+            describe(&mut doc, &lines, header.span.line);
+        }
+    }
+    match internal.len() {
+        0 => {}
+        1 => doc.push_str("One behavior is kept internal.\n"),
+        n => { let _ = writeln!(doc, "{} behaviors are kept internal.", n); }
+    }
+
+    while doc.ends_with("\n\n") {
+        doc.pop();
+    }
+    doc
+}
+
+/// Writes the comment lines directly above the declaration on `line` (1-based).
+fn describe(doc: &mut String, lines: &[&str], line: usize) {
+    let above = &lines[..line.saturating_sub(1).min(lines.len())];
+    let comments: Vec<&str> = above
+        .iter()
+        .rev()
+        .map(|l| l.trim())
+        .take_while(|l| l.starts_with("--"))
+        .map(|l| l.trim_start_matches('-').trim())
+        .filter(|l| !l.is_empty() && !l.chars().all(|c| c == '═'))
+        .collect();
+    if !comments.is_empty() {
+        let text: Vec<&str> = comments.into_iter().rev().collect();
+        let _ = writeln!(doc, "{}\n", text.join(" "));
+    }
+}
+
+/// A behavior's signature in prose: `takes an i64 called n; delivers an i64`.
+fn signature(header: &BehaviorHeader) -> String {
+    let takes = if header.takes.is_empty() { "nothing".to_string() } else { arguments(&header.takes) };
+    format!("takes {}; delivers {}", takes, typed(&header.delivers.0))
+}
+
+fn arguments(args: &[Argument]) -> String {
+    let described: Vec<String> = args.iter().map(|arg| match &arg.type_info.via_role {
+        Some(role) => format!("{} called `{}` via the role {}", typed(&arg.type_info.onu_type), arg.name, role),
+        None => format!("{} called `{}`", typed(&arg.type_info.onu_type), arg.name),
+    }).collect();
+    described.join(", ")
+}
+
+/// A type with its article: `an i64`, `a string`, `nothing`.
+fn typed(typ: &OnuType) -> String {
+    let name = typ.to_string();
+    match typ {
+        OnuType::Nothing => name,
+        _ if name.starts_with(['a', 'e', 'i', 'o', 'u', 'A', 'E', 'I', 'O', 'U']) => format!("an {}", name),
+        _ => format!("a {}", name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompilerSession;
+
+    #[test]
+    fn test_reference_lists_what_a_module_offers() {
+        let source = "
+the module called Counting
+    with concern: counting steps
+
+the variety called Step is one of:
+    a Halt
+    a Stride carrying an integer called length
+
+-- Takes one step forward.
+the behavior called stepped
+    exposes
+    with intent: take one counting step
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n utilizes helped
+
+the behavior called helped
+    keeps internal
+    with intent: help counting steps
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 1
+";
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(source).unwrap();
+        let doc = document(&session.ast, source);
+        assert!(doc.starts_with("# Module `Counting`\n\n*Concern:* counting steps\n"), "{}", doc);
+        assert!(doc.contains("- `Stride` carrying an i64 called `length`"), "{}", doc);
+        assert!(doc.contains("### `stepped`\n\n*The behavior* takes an i64 called `n`; delivers an i64\n\n*Intent:* take one counting step\n\nTakes one step forward.\n"), "{}", doc);
+        assert!(!doc.contains("`helped`") && doc.ends_with("One behavior is kept internal.\n"), "{}", doc);
+    }
+}
@@ -29,13 +29,13 @@ impl Environment for StdoutEnvironment {
 }
 
 /// A simple Mock environment for testing purposes.
-#[cfg(test)]
+/// It records every emitted message and serves reads from a queue.
+#[derive(Debug, Default)]
 pub struct MockEnvironment {
     pub emitted: Vec<String>,
     pub input_queue: Vec<String>,
 }
 
-#[cfg(test)]
 impl MockEnvironment {
     pub fn new() -> Self {
         Self {
@@ -45,7 +45,6 @@ impl MockEnvironment {
     }
 }
 
-#[cfg(test)]
 impl Environment for MockEnvironment {
     fn emit(&mut self, text: &str) {
         self.emitted.push(text.to_string());
@@ -23,56 +23,195 @@ pub enum OnuError {
     LexicalError { message: String, span: Span },
     ParseError { message: String, span: Span },
     RuntimeError { message: String, span: Span },
-    BehaviorConflict { name: String, other_name: String },
+    BehaviorConflict { name: String, other_name: String, span: Span, other_span: Span },
     MonomorphizationError { message: String },
     BorrowError { message: String, span: Span },
+    TypeError { message: String, span: Span },
+    TerminationViolation { message: String, span: Span },
+    ConcernViolation { message: String, span: Span },
+    ShapeViolation { message: String, span: Span },
+    VisibilityViolation { message: String, span: Span },
+    ExhaustivenessViolation { message: String, span: Span },
+    ImportViolation { message: String, span: Span },
+    ManifestError { message: String, span: Span },
     CodeGenError { message: String },
+    /// Several memos from one pass, reported together. Built by `OnuError::batch`.
+    ReviewBatch { memos: Vec<OnuError> },
 }
 
 impl fmt::Display for OnuError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let OnuError::ReviewBatch { memos } = self {
+            for memo in memos {
+                write!(f, "{}", memo)?;
+            }
+            write!(f, "\n═══════════════════════════════════════════\n")?;
+            return writeln!(f, "The review found {} violations in the discourse.", memos.len());
+        }
         write!(f, "\n═══════════════════════════════════════════\n")?;
-        write!(f, "           PEER REVIEW MEMO\n")?;
+        writeln!(f, "           PEER REVIEW MEMO")?;
         write!(f, "═══════════════════════════════════════════\n\n")?;
         
         match self {
             OnuError::LexicalError { message, span } => {
-                write!(f, "Observation: An illegal character attempts to enter the discourse at {}.\n", span)?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  The discourse refuses to be tokenized.\n")
+                writeln!(f, "Observation: An illegal character attempts to enter the discourse at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  The discourse refuses to be tokenized.")
             }
             OnuError::ParseError { message, span } => {
-                write!(f, "Observation: The proposition at {} violates the grammatical covenant.\n", span)?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  The proposition refuses to comply with the grammar.\n")
+                writeln!(f, "Observation: The proposition at {} violates the grammatical covenant.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  The proposition refuses to comply with the grammar.")
             }
             OnuError::RuntimeError { message, span } => {
-                write!(f, "Observation: An evaluation event failed at {}.\n", span)?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  The derivation refuses to evaluate.\n")
+                writeln!(f, "Observation: An evaluation event failed at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  The derivation refuses to evaluate.")
             }
-            OnuError::BehaviorConflict { name, other_name } => {
-                write!(f, "Observation: Duplicate semantic implementation detected.\n")?;
-                write!(f, "Assessment:  The behavior '{}' is semantically identical to '{}'.\n", name, other_name)?;
-                write!(f, "Conclusion:  This violates the Principle of Non-Repetition (DRY).\n")
+            OnuError::BehaviorConflict { name, other_name, span, other_span } => {
+                writeln!(f, "Observation: Duplicate semantic implementation detected at {} (first written at {}).", span, other_span)?;
+                writeln!(f, "Assessment:  The behavior '{}' is semantically identical to '{}'.", name, other_name)?;
+                writeln!(f, "Conclusion:  This violates the Principle of Non-Repetition (DRY).")
             }
             OnuError::MonomorphizationError { message } => {
-                write!(f, "Observation: The polymorphic expansion failed.\n")?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  Interface dispatch must resolve to concrete types at compile-time.\n")
+                writeln!(f, "Observation: The polymorphic expansion failed.")?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  Interface dispatch must resolve to concrete types at compile-time.")
             }
             OnuError::BorrowError { message, span } => {
-                write!(f, "Observation: A memory safety violation detected at {}.\n", span)?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  The resource ownership rules are absolute and must be obeyed.\n")
+                writeln!(f, "Observation: A memory safety violation detected at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  The resource ownership rules are absolute and must be obeyed.")
+            }
+            OnuError::TypeError { message, span } => {
+                writeln!(f, "Observation: The behavior at {} combines values of incompatible types.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  Every proposition must agree with the types it promises.")
+            }
+            OnuError::TerminationViolation { message, span } => {
+                writeln!(f, "Observation: TERMINATION VIOLATION in the behavior at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  Every recursive path must diminish toward its base case.")
+            }
+            OnuError::ConcernViolation { message, span } => {
+                writeln!(f, "Observation: CONCERN VIOLATION in the discourse unit at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  A discourse unit must address a single concern (SRP).")
+            }
+            OnuError::ShapeViolation { message, span } => {
+                writeln!(f, "Observation: SHAPE VIOLATION at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  A subject may only act-as a role whose every promise it keeps.")
+            }
+            OnuError::VisibilityViolation { message, span } => {
+                writeln!(f, "Observation: VISIBILITY VIOLATION at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  What a discourse keeps internal is not for others to read or call.")
+            }
+            OnuError::ExhaustivenessViolation { message, span } => {
+                writeln!(f, "Observation: EXHAUSTIVENESS VIOLATION in the case analysis at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  Every variant of a variety must be answered, and answered once.")
+            }
+            OnuError::ImportViolation { message, span } => {
+                writeln!(f, "Observation: IMPORT VIOLATION in the module header at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  A module may draw only on discourse that can be found, and never on itself.")
+            }
+            OnuError::ManifestError { message, span } => {
+                writeln!(f, "Observation: MANIFEST ERROR in Onu.toml at {}.", span)?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  A package must state plainly what it is, where its discourse lives and how it is built.")
             }
             OnuError::CodeGenError { message } => {
-                write!(f, "Observation: Lowering to machine discourse failed.\n")?;
-                write!(f, "Assessment:  {}\n", message)?;
-                write!(f, "Conclusion:  The architectural design cannot be realized in the target hardware.\n")
+                writeln!(f, "Observation: Lowering to machine discourse failed.")?;
+                writeln!(f, "Assessment:  {}", message)?;
+                writeln!(f, "Conclusion:  The architectural design cannot be realized in the target hardware.")
             }
+            OnuError::ReviewBatch { .. } => unreachable!("a batch is written memo by memo"),
+        }
+    }
+}
+
+impl OnuError {
+    /// Reports the memos of one pass together; a single memo stands alone.
+    pub fn batch(mut memos: Vec<OnuError>) -> OnuError {
+        if memos.len() == 1 {
+            memos.remove(0)
+        } else {
+            OnuError::ReviewBatch { memos }
+        }
+    }
+
+    /// The status the `onu` command exits with when this error stops it: one per
+    /// stage of the pipeline, so that a script can tell what refused the program.
+    /// A runtime error shares 101 with the traps of compiled programs, and a
+    /// batch exits as its first memo does.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            OnuError::ReviewBatch { memos } => memos.first().map_or(1, OnuError::exit_code),
+            OnuError::LexicalError { .. } => 10,
+            OnuError::ParseError { .. } => 11,
+            OnuError::ImportViolation { .. } => 12,
+            OnuError::ManifestError { .. } => 13,
+            OnuError::BehaviorConflict { .. } => 20,
+            OnuError::TypeError { .. } => 21,
+            OnuError::BorrowError { .. } => 22,
+            OnuError::TerminationViolation { .. } => 23,
+            OnuError::ConcernViolation { .. } => 24,
+            OnuError::ShapeViolation { .. } => 25,
+            OnuError::VisibilityViolation { .. } => 26,
+            OnuError::ExhaustivenessViolation { .. } => 27,
+            OnuError::MonomorphizationError { .. } => 30,
+            OnuError::CodeGenError { .. } => 31,
+            OnuError::RuntimeError { .. } => 101,
         }
     }
 }
 
 impl std::error::Error for OnuError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_each_stage_exits_with_its_own_status() {
+        let span = Span::default();
+        let errors = [
+            OnuError::LexicalError { message: String::new(), span },
+            OnuError::ParseError { message: String::new(), span },
+            OnuError::ImportViolation { message: String::new(), span },
+            OnuError::ManifestError { message: String::new(), span },
+            OnuError::BehaviorConflict { name: String::new(), other_name: String::new(), span, other_span: span },
+            OnuError::TypeError { message: String::new(), span },
+            OnuError::BorrowError { message: String::new(), span },
+            OnuError::TerminationViolation { message: String::new(), span },
+            OnuError::ConcernViolation { message: String::new(), span },
+            OnuError::ShapeViolation { message: String::new(), span },
+            OnuError::VisibilityViolation { message: String::new(), span },
+            OnuError::ExhaustivenessViolation { message: String::new(), span },
+            OnuError::MonomorphizationError { message: String::new() },
+            OnuError::CodeGenError { message: String::new() },
+            OnuError::RuntimeError { message: String::new(), span },
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(OnuError::exit_code).collect();
+        assert!(codes.iter().all(|&code| code > 2), "0 is success, 1 a failed command and 2 a usage error");
+        codes.sort();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+
+    #[test]
+    fn test_a_batch_reports_every_memo() {
+        let memo = |line| OnuError::ParseError { message: format!("Unexpected token on line {}", line), span: Span { line, column: 1 } };
+        assert_eq!(OnuError::batch(vec![memo(3)]), memo(3));
+
+        let batch = OnuError::batch(vec![memo(3), memo(9)]);
+        let report = batch.to_string();
+        assert_eq!(report.matches("PEER REVIEW MEMO").count(), 2, "{}", report);
+        assert!(report.find("line 3").unwrap() < report.find("line 9").unwrap(), "{}", report);
+        assert!(report.ends_with("The review found 2 violations in the discourse.\n"), "{}", report);
+        assert_eq!(batch.exit_code(), 11);
+    }
+}
@@ -0,0 +1,202 @@
+//! Ọ̀nụ Exhaustiveness Checker: The Case Analysis Layer
+//!
+//! This module verifies that every `when` answers each variant of the variety it
+//! analyses exactly once. It runs over the type-checked HIR, so every subject is
+//! known to be of the variety its arms name. A missing variant is refused unless
+//! the analysis ends with `otherwise`; an `otherwise` that no variant can reach,
+//! and an arm answering a variant already answered, are refused too, since they
+//! are logic no value can ever run.
+
+use crate::error::{OnuError, Span};
+use crate::hir::{HirBehaviorHeader, HirDiscourse, HirExpression};
+use crate::registry::Registry;
+
+pub struct ExhaustivenessChecker<'a> {
+    registry: &'a Registry,
+}
+
+impl<'a> ExhaustivenessChecker<'a> {
+    pub fn new(registry: &'a Registry) -> Self {
+        Self { registry }
+    }
+
+    /// Checks every case analysis in the program, stopping at the first violation.
+    pub fn check_program(&self, hir: &[HirDiscourse]) -> Result<(), OnuError> {
+        for discourse in hir {
+            if let HirDiscourse::Behavior { header, body } = discourse {
+                self.check_expression(header, body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_expression(&self, header: &HirBehaviorHeader, expr: &HirExpression) -> Result<(), OnuError> {
+        match expr {
+            HirExpression::Case { subject, variety, arms, otherwise } => {
+                self.check_expression(header, subject)?;
+                let answered: Vec<&str> = arms.iter().map(|arm| arm.variant.as_str()).collect();
+                self.check_case(header, variety, &answered, otherwise.is_some())?;
+                for arm in arms {
+                    self.check_expression(header, &arm.body)?;
+                }
+                otherwise.iter().try_for_each(|e| self.check_expression(header, e))
+            }
+            HirExpression::Derivation { value, body, .. } => {
+                self.check_expression(header, value)?;
+                self.check_expression(header, body)
+            }
+            HirExpression::If { condition, then_branch, else_branch } => {
+                self.check_expression(header, condition)?;
+                self.check_expression(header, then_branch)?;
+                self.check_expression(header, else_branch)
+            }
+            HirExpression::Call { args, .. } | HirExpression::Tuple(args) | HirExpression::Block(args) => {
+                args.iter().try_for_each(|e| self.check_expression(header, e))
+            }
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                fields.iter().try_for_each(|(_, e)| self.check_expression(header, e))
+            }
+            HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => self.check_expression(header, subject),
+            HirExpression::Emit(e) => self.check_expression(header, e),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => Ok(()),
+        }
+    }
+
+    fn check_case(&self, header: &HirBehaviorHeader, variety: &str, answered: &[&str], has_otherwise: bool) -> Result<(), OnuError> {
+        if let Some((_, repeated)) = answered.iter().enumerate().find(|(i, variant)| answered[..*i].contains(variant)) {
+            return Err(Self::violation(header.span, format!(
+                "A case analysis of {} in '{}' answers the variant '{}' twice; the second arm can never run.",
+                variety, header.name, repeated
+            )));
+        }
+        let variants = self.registry.get_variety(variety).map_or_else(Vec::new, |variants| variants.iter().map(|v| v.name.as_str()).collect());
+        let missing: Vec<&str> = variants.into_iter().filter(|variant| !answered.contains(variant)).collect();
+        match (missing.as_slice(), has_otherwise) {
+            ([], true) => Err(Self::violation(header.span, format!(
+                "The 'otherwise' of a case analysis of {} in '{}' is never reached; every variant is already answered.",
+                variety, header.name
+            ))),
+            ([], false) | (_, true) => Ok(()),
+            (missing, false) => Err(Self::violation(header.span, format!(
+                "A case analysis of {} in '{}' leaves {} unanswered; answer {} or add 'otherwise'.",
+                variety,
+                header.name,
+                Self::list(missing),
+                if missing.len() == 1 { "it" } else { "them" }
+            ))),
+        }
+    }
+
+    /// Names variants in prose: `the variant 'Exit'`, `the variants 'Enter' and 'Exit'`.
+    fn list(variants: &[&str]) -> String {
+        let quoted: Vec<String> = variants.iter().map(|v| format!("'{}'", v)).collect();
+        match quoted.as_slice() {
+            [only] => format!("the variant {}", only),
+            [rest @ .., last] => format!("the variants {} and {}", rest.join(", "), last),
+            [] => String::new(),
+        }
+    }
+
+    fn violation(span: Span, message: String) -> OnuError {
+        OnuError::ExhaustivenessViolation { message, span }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CompilerSession;
+    use crate::error::OnuError;
+
+    const VARIETY: &str = "
+the variety called Signal is one of:
+    a Stop
+    a Go carrying an integer called speed
+    a Turn carrying an integer called degrees and a boolean called left
+
+";
+
+    fn check(body: &str) -> Result<(), OnuError> {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(&format!("{}{}", VARIETY, body)).map(|_| ())
+    }
+
+    fn violation(body: &str) -> String {
+        match check(body) {
+            Err(OnuError::ExhaustivenessViolation { message, .. }) => message,
+            other => panic!("Expected an exhaustiveness violation, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_every_variant_answered_passes() {
+        check("
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Stop then 0
+            is a Go carrying speed then speed
+            is a Turn carrying degrees left then degrees
+").unwrap();
+    }
+
+    #[test]
+    fn test_missing_variants_are_named() {
+        let message = violation("
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Go carrying speed then speed
+");
+        assert!(message.contains("leaves the variants 'Stop' and 'Turn' unanswered"), "{}", message);
+    }
+
+    #[test]
+    fn test_otherwise_answers_the_rest_but_must_be_reachable() {
+        check("
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Go carrying speed then speed
+            otherwise 0
+").unwrap();
+
+        let message = violation("
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Stop then 0
+            is a Go then 1
+            is a Turn then 2
+            otherwise 3
+");
+        assert!(message.contains("'otherwise'") && message.contains("never reached"), "{}", message);
+    }
+
+    #[test]
+    fn test_repeated_arms_are_refused() {
+        let message = violation("
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Go then 1
+            is a Go carrying speed then speed
+            otherwise 0
+");
+        assert!(message.contains("answers the variant 'Go' twice"), "{}", message);
+    }
+}
@@ -0,0 +1,99 @@
+//! Ọ̀nụ Formatter: The Layout Layer
+//!
+//! This module lays a discourse unit out in its canonical form. Indentation
+//! carries no meaning for the lexer, so the formatter works line by line:
+//! each nesting depth the author wrote is re-indented to four spaces per
+//! level, trailing whitespace is trimmed, runs of blank lines collapse to one,
+//! and the unit ends with a single newline. Comments keep their place and
+//! take the depth of the discourse around them. A layout that would change
+//! the unit's tokens is refused, so formatting never alters a program.
+
+use crate::error::{OnuError, Span};
+use crate::lexer::{Lexer, Token};
+
+/// The indentation of one nesting level.
+pub const INDENT: &str = "    ";
+
+/// Returns the canonical layout of a discourse unit.
+pub fn format_source(source: &str) -> Result<String, OnuError> {
+    let mut depths: Vec<usize> = vec![0]; // the indentation widths of the enclosing levels
+    let mut lines: Vec<String> = Vec::new();
+
+    for raw in source.lines() {
+        let expanded = expand_leading_tabs(raw);
+        let line = expanded.trim_end();
+        let content = line.trim_start();
+        if content.is_empty() {
+            if lines.last().is_some_and(|last| !last.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        let width = line.len() - content.len();
+        let level = if content.starts_with("--") {
+            // A comment takes the level it reaches without opening or closing one;
+            // indented past the current level, it belongs to the level the next line opens.
+            match depths.last() {
+                Some(&deepest) if width > deepest => depths.len(),
+                _ => depths.iter().rposition(|&depth| depth <= width).unwrap_or(0),
+            }
+        } else {
+            while depths.last().is_some_and(|&depth| depth > width) {
+                depths.pop();
+            }
+            if depths.last().is_none_or(|&depth| depth < width) {
+                depths.push(width);
+            }
+            depths.len() - 1
+        };
+        lines.push(format!("{}{}", INDENT.repeat(level), content));
+    }
+    while lines.last().is_some_and(String::is_empty) {
+        lines.pop();
+    }
+
+    let mut formatted = lines.join("\n");
+    formatted.push('\n');
+    if tokens(source) != tokens(&formatted) {
+        return Err(OnuError::LexicalError {
+            message: "Laying the discourse out anew would change its tokens; it is left as written.".to_string(),
+            span: Span::default(),
+        });
+    }
+    Ok(formatted)
+}
+
+fn expand_leading_tabs(line: &str) -> String {
+    let content = line.trim_start_matches([' ', '\t']);
+    let leading = &line[..line.len() - content.len()];
+    format!("{}{}", leading.replace('\t', INDENT), content)
+}
+
+fn tokens(source: &str) -> Vec<Token> {
+    Lexer::new(source).tokenize().into_iter().map(|t| t.token).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nesting_is_reindented_to_four_spaces() {
+        let source = "\n\nthe behavior called twice   \n  takes:\n\tan integer called n\n  delivers: an integer\n  as:\n      -- doubled\n      n added-to n\n\n\n\nthe behavior called thrice\n  takes: nothing\n";
+        let expected = "the behavior called twice\n    takes:\n        an integer called n\n    delivers: an integer\n    as:\n        -- doubled\n        n added-to n\n\nthe behavior called thrice\n    takes: nothing\n";
+        assert_eq!(format_source(source).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let once = format_source(include_str!("../bf.onu")).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+    }
+
+    #[test]
+    fn test_comments_do_not_open_or_close_levels() {
+        let source = "the behavior called f\n  as:\n-- a note at the margin\n        1\n";
+        let formatted = format_source(source).unwrap();
+        assert_eq!(formatted, "the behavior called f\n    as:\n-- a note at the margin\n        1\n");
+    }
+}
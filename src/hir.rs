@@ -1,10 +1,16 @@
 use crate::types::OnuType;
-use crate::parser::{Discourse, Expression, BehaviorHeader, Argument};
+use crate::error::Span;
+use crate::parser::{Discourse, Expression, BehaviorHeader, Argument, Visibility};
+use std::collections::HashMap;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum HirDiscourse {
-    Module { name: String, concern: String },
+    Module { name: String, concern: String, span: Span },
     Shape { name: String, behaviors: Vec<HirBehaviorHeader> },
+    /// The fields themselves live in the `Registry`; the unit only marks where they were declared.
+    Structure { name: String, span: Span },
+    /// The variants themselves live in the `Registry`, like a structure's fields.
+    Variety { name: String, span: Span },
     Behavior { header: HirBehaviorHeader, body: HirExpression },
 }
 
@@ -12,26 +18,37 @@ pub enum HirDiscourse {
 pub struct HirBehaviorHeader {
     pub name: String,
     pub is_effect: bool,
+    pub intent: String,
     pub args: Vec<HirArgument>,
     pub return_type: OnuType,
+    pub diminishing: Option<String>, // the argument every recursive call must shrink
+    pub skip_termination_check: bool,
+    pub visibility: Visibility,
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct HirArgument {
     pub name: String,
     pub typ: OnuType,
+    pub role: Option<String>, // the shape promised by `via the role`
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum HirExpression {
     Literal(HirLiteral),
     Variable(String),
-    Call { name: String, args: Vec<HirExpression> },
+    /// `typ` is the delivered type, left `None` by lowering and filled in by the type checker.
+    /// `span` is where the verb is written, for type errors and the traps of arithmetic.
+    Call { name: String, args: Vec<HirExpression>, typ: Option<OnuType>, span: Span },
+    /// `typ` is the declared type if one was written, and the inferred type after type checking.
+    /// `span` is where the derivation is written.
     Derivation { 
         name: String, 
-        typ: OnuType, 
+        typ: Option<OnuType>, 
         value: Box<HirExpression>, 
-        body: Box<HirExpression> 
+        body: Box<HirExpression>,
+        span: Span,
     },
     If { 
         condition: Box<HirExpression>, 
@@ -43,6 +60,17 @@ pub enum HirExpression {
         shape: String 
     },
     Tuple(Vec<HirExpression>),
+    /// Lowering keeps the fields as written; the type checker reorders them as declared.
+    Construct { structure: String, fields: Vec<(String, HirExpression)> },
+    /// One variant of a variety; the type checker reorders its payload as declared.
+    Variant { variety: String, variant: String, fields: Vec<(String, HirExpression)> },
+    /// A case analysis: the arm answering the subject's variant runs, else `otherwise`.
+    Case {
+        subject: Box<HirExpression>,
+        variety: String,
+        arms: Vec<HirCaseArm>,
+        otherwise: Option<Box<HirExpression>>,
+    },
     Index { 
         subject: Box<HirExpression>, 
         index: usize 
@@ -51,28 +79,119 @@ pub enum HirExpression {
     Emit(Box<HirExpression>),
 }
 
+/// HirCaseArm answers one variant, binding the values of its payload by position.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HirCaseArm {
+    pub variant: String,
+    pub bindings: Vec<String>,
+    /// The types of the bound values, left empty by lowering and filled in by the type checker.
+    pub payload: Vec<OnuType>,
+    pub body: HirExpression,
+}
+
+impl HirCaseArm {
+    /// The scope within the arm's body: the outer scope and the arm's bindings.
+    pub fn scope(&self, outer: &HashMap<String, OnuType>) -> HashMap<String, OnuType> {
+        let mut scope = outer.clone();
+        scope.extend(self.bindings.iter().cloned().zip(self.payload.iter().cloned()));
+        scope
+    }
+}
+
+/// The verbs that apply a behavior to each element of an array. The behavior is
+/// named, as a bare variable, by the second argument.
+pub const TRAVERSALS: [&str; 3] = ["transforms-each-by", "filters-by", "folds-with"];
+
+/// The verbs that project one element out of a tuple, by position: `pair first-of`.
+pub const PROJECTIONS: [&str; 5] = ["first-of", "second-of", "third-of", "fourth-of", "fifth-of"];
+
+/// The tuple position a projection verb reads, counting from zero.
+pub fn projection_index(name: &str) -> Option<usize> {
+    PROJECTIONS.iter().position(|verb| *verb == name)
+}
+
+/// Reads the dimensions of a matrix literal, which lowers to a call named
+/// `matrix-RxC` whose arguments are the entries, row by row.
+pub fn matrix_dimensions(name: &str) -> Option<(usize, usize)> {
+    let (rows, cols) = name.strip_prefix("matrix-")?.split_once('x')?;
+    Some((rows.parse().ok()?, cols.parse().ok()?))
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum HirLiteral {
+    /// An integer literal of no declared width; it settles on i64 unless its context asks for another.
     I64(i64),
+    /// A float literal of no declared width; it settles on f64 unless its context asks for f32.
     F64(f64),
+    /// An integer literal settled at a specific width.
+    Integer(i128, OnuType),
+    /// A float literal settled at a specific width.
+    Float(f64, OnuType),
     Boolean(bool),
     Text(String),
     Nothing,
 }
 
+impl HirExpression {
+    /// The behavior named by a traversal such as `xs transforms-each-by double`.
+    pub fn traversed_behavior(&self) -> Option<&str> {
+        match self {
+            HirExpression::Call { name, args, .. } if TRAVERSALS.contains(&name.as_str()) => match args.get(1) {
+                Some(HirExpression::Variable(behavior)) => Some(behavior),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Reads the expression's type back from the type checker's annotations.
+    ///
+    /// `scope` maps the variables visible at this point to their types.
+    pub fn annotated_type(&self, scope: &HashMap<String, OnuType>) -> OnuType {
+        match self {
+            HirExpression::Literal(lit) => match lit {
+                HirLiteral::I64(_) => OnuType::I64,
+                HirLiteral::F64(_) => OnuType::F64,
+                HirLiteral::Boolean(_) => OnuType::Boolean,
+                HirLiteral::Text(_) => OnuType::Strings,
+                HirLiteral::Integer(_, typ) | HirLiteral::Float(_, typ) => typ.clone(),
+                HirLiteral::Nothing => OnuType::Nothing,
+            },
+            HirExpression::Variable(name) => scope.get(name).cloned().unwrap_or(OnuType::Nothing),
+            HirExpression::Call { typ, .. } => typ.clone().unwrap_or(OnuType::Nothing),
+            HirExpression::ActsAs { subject, .. } => subject.annotated_type(scope),
+            HirExpression::Tuple(exprs) => OnuType::Tuple(exprs.iter().map(|e| e.annotated_type(scope)).collect()),
+            HirExpression::Construct { structure, .. } => OnuType::Record(structure.clone()),
+            HirExpression::Variant { variety, .. } => OnuType::Variety(variety.clone()),
+            HirExpression::Case { arms, .. } => arms.first().map_or(OnuType::Nothing, |arm| arm.body.annotated_type(&arm.scope(scope))),
+            HirExpression::Index { subject, index } => match subject.annotated_type(scope) {
+                OnuType::Tuple(types) => types.get(*index).cloned().unwrap_or(OnuType::Nothing),
+                _ => OnuType::Nothing,
+            },
+            HirExpression::If { then_branch, .. } => then_branch.annotated_type(scope),
+            HirExpression::Block(exprs) => exprs.last().map_or(OnuType::Nothing, |e| e.annotated_type(scope)),
+            // A derivation in subject position is parenthesized; its body is not in scope here.
+            HirExpression::Derivation { .. } | HirExpression::Emit(_) => OnuType::Nothing,
+        }
+    }
+}
+
 pub struct LoweringVisitor;
 
 impl LoweringVisitor {
     pub fn lower_discourse(discourse: &Discourse) -> HirDiscourse {
         match discourse {
-            Discourse::Module { name, concern } => HirDiscourse::Module {
+            Discourse::Module { name, concern, span, .. } => HirDiscourse::Module {
                 name: name.clone(),
                 concern: concern.clone(),
+                span: *span,
             },
             Discourse::Shape { name, behaviors } => HirDiscourse::Shape {
                 name: name.clone(),
                 behaviors: behaviors.iter().map(Self::lower_header).collect(),
             },
+            Discourse::Structure { name, span, .. } => HirDiscourse::Structure { name: name.clone(), span: *span },
+            Discourse::Variety { name, span, .. } => HirDiscourse::Variety { name: name.clone(), span: *span },
             Discourse::Behavior { header, body } => HirDiscourse::Behavior {
                 header: Self::lower_header(header),
                 body: Self::lower_expression(body),
@@ -84,8 +203,13 @@ impl LoweringVisitor {
         HirBehaviorHeader {
             name: header.name.clone(),
             is_effect: header.is_effect,
+            intent: header.intent.clone(),
             args: header.takes.iter().map(Self::lower_argument).collect(),
             return_type: header.delivers.0.clone(),
+            diminishing: header.diminishing.clone(),
+            skip_termination_check: header.skip_termination_check,
+            visibility: header.visibility,
+            span: header.span,
         }
     }
 
@@ -93,6 +217,7 @@ impl LoweringVisitor {
         HirArgument {
             name: arg.name.clone(),
             typ: arg.type_info.onu_type.clone(),
+            role: arg.type_info.via_role.clone(),
         }
     }
 
@@ -104,26 +229,27 @@ impl LoweringVisitor {
             Expression::Text(s) => HirExpression::Literal(HirLiteral::Text(s.clone())),
             Expression::Nothing => HirExpression::Literal(HirLiteral::Nothing),
             Expression::Identifier(s) => HirExpression::Variable(s.clone()),
-            Expression::BehaviorCall { name, args } => {
-                // Heuristic: identify linguistic indexing (char-at)
-                if name == "char-at" && args.len() == 2 {
-                    if let Expression::I64(idx) = args[1] {
-                        return HirExpression::Index {
-                            subject: Box::new(Self::lower_expression(&args[0])),
-                            index: idx as usize,
-                        };
-                    }
-                }
+            Expression::BehaviorCall { name, args, .. } if (name == "broadcasts" || name == "emit") && args.len() == 1 => {
+                HirExpression::Emit(Box::new(Self::lower_expression(&args[0])))
+            }
+            Expression::BehaviorCall { name, args, .. } if args.len() == 1 && projection_index(name).is_some() => HirExpression::Index {
+                subject: Box::new(Self::lower_expression(&args[0])),
+                index: projection_index(name).unwrap(),
+            },
+            Expression::BehaviorCall { name, args, span } => {
                 HirExpression::Call {
                     name: name.clone(),
                     args: args.iter().map(Self::lower_expression).collect(),
+                    typ: None,
+                    span: *span,
                 }
             }
-            Expression::Derivation { name, type_info, value, body } => HirExpression::Derivation {
+            Expression::Derivation { name, type_info, value, body, span } => HirExpression::Derivation {
                 name: name.clone(),
-                typ: type_info.as_ref().map(|ti| ti.onu_type.clone()).unwrap_or(OnuType::Nothing), // Default to nothing if unknown, though type checker should handle it
+                typ: type_info.as_ref().map(|ti| ti.onu_type.clone()),
                 value: Box::new(Self::lower_expression(value)),
                 body: Box::new(Self::lower_expression(body)),
+                span: *span,
             },
             Expression::If { condition, then_branch, else_branch } => HirExpression::If {
                 condition: Box::new(Self::lower_expression(condition)),
@@ -140,28 +266,55 @@ impl LoweringVisitor {
             Expression::Emit(e) | Expression::Broadcasts(e) => HirExpression::Emit(
                 Box::new(Self::lower_expression(e))
             ),
-            // Handle other literal types by mapping them to I64/F64 for now
-            Expression::I8(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::I16(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::I32(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::I128(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::U8(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::U16(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::U32(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::U64(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::U128(n) => HirExpression::Literal(HirLiteral::I64(*n as i64)),
-            Expression::F32(n) => HirExpression::Literal(HirLiteral::F64(*n as f64)),
+            Expression::I8(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::I8)),
+            Expression::I16(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::I16)),
+            Expression::I32(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::I32)),
+            Expression::I128(n) => HirExpression::Literal(HirLiteral::Integer(*n, OnuType::I128)),
+            Expression::U8(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::U8)),
+            Expression::U16(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::U16)),
+            Expression::U32(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::U32)),
+            Expression::U64(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::U64)),
+            // A u128 beyond the i128 range cannot be carried; it wraps, as a u128 bit pattern.
+            Expression::U128(n) => HirExpression::Literal(HirLiteral::Integer(*n as i128, OnuType::U128)),
+            Expression::F32(n) => HirExpression::Literal(HirLiteral::Float(*n as f64, OnuType::F32)),
             
             Expression::Tuple(v) => HirExpression::Tuple(
                  v.iter().map(Self::lower_expression).collect()
             ),
+            Expression::Construct { structure, fields } => HirExpression::Construct {
+                 structure: structure.clone(),
+                 fields: fields.iter().map(|(name, e)| (name.clone(), Self::lower_expression(e))).collect(),
+            },
+            Expression::Variant { variety, variant, fields } => HirExpression::Variant {
+                 variety: variety.clone(),
+                 variant: variant.clone(),
+                 fields: fields.iter().map(|(name, e)| (name.clone(), Self::lower_expression(e))).collect(),
+            },
+            Expression::Case { subject, variety, arms, otherwise } => HirExpression::Case {
+                 subject: Box::new(Self::lower_expression(subject)),
+                 variety: variety.clone(),
+                 arms: arms
+                     .iter()
+                     .map(|arm| HirCaseArm {
+                         variant: arm.variant.clone(),
+                         bindings: arm.bindings.clone(),
+                         payload: Vec::new(),
+                         body: Self::lower_expression(&arm.body),
+                     })
+                     .collect(),
+                 otherwise: otherwise.as_ref().map(|e| Box::new(Self::lower_expression(e))),
+            },
             Expression::Array(v) => HirExpression::Call {
                  name: "array".to_string(),
-                 args: v.iter().map(Self::lower_expression).collect()
+                 args: v.iter().map(Self::lower_expression).collect(),
+                 typ: None,
+                 span: Span::default(),
             },
             Expression::Matrix { rows, cols, data } => HirExpression::Call {
                  name: format!("matrix-{}x{}", rows, cols),
-                 args: data.iter().map(Self::lower_expression).collect()
+                 args: data.iter().map(Self::lower_expression).collect(),
+                 typ: None,
+                 span: Span::default(),
             },
         }
     }
@@ -0,0 +1,1035 @@
+//! Ọ̀nụ MIR Interpreter: The Evaluation Use Case
+//!
+//! This module executes a `MirProgram` directly, without lowering it to LLVM.
+//! It walks the basic blocks of each `MirFunction`, keeping every SSA variable
+//! in a register file, and routes all communication with the outside world
+//! through the injected `Environment`.
+//!
+//! Clean Architecture:
+//! The interpreter is a Use Case. It depends only on the MIR (the domain model)
+//! and the `Environment` port, so programs can be evaluated in tests and on
+//! machines without clang, llvm-link or lli installed.
+
+use crate::env::Environment;
+use crate::error::{OnuError, Span};
+use crate::mir::{ArithmeticMode, BasicBlock, MirBinOp, MirFunction, MirInstruction, MirLiteral, MirOperand, MirProgram, MirTerminator};
+use std::collections::HashMap;
+use std::fmt;
+
+/// The maximum number of nested behavior invocations before evaluation is refused.
+const MAX_CALL_DEPTH: usize = 10_000;
+
+/// Value is the runtime representation of every Ọ̀nụ datum.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    /// A signed integer of the given width in bits, held exactly.
+    Int(i128, u32),
+    /// An unsigned integer of the given width in bits, held exactly.
+    UInt(u128, u32),
+    F64(f64),
+    Boolean(bool),
+    Text(String),
+    Tuple(Vec<Value>),
+    Array(Vec<Value>),
+    Matrix { rows: usize, cols: usize, entries: Vec<f64> },
+    Nothing,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n, _) => write!(f, "{}", n),
+            Value::UInt(n, _) => write!(f, "{}", n),
+            Value::F64(n) => write!(f, "{}", n),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Tuple(values) => {
+                write!(f, "(")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, ")")
+            }
+            Value::Array(values) => {
+                write!(f, "[")?;
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 { write!(f, ", ")?; }
+                    write!(f, "{}", v)?;
+                }
+                write!(f, "]")
+            }
+            Value::Matrix { rows, cols, entries } => write!(f, "{}", onu_runtime::render_matrix(*rows, *cols, entries)),
+            Value::Nothing => write!(f, "nothing"),
+        }
+    }
+}
+
+impl Value {
+    /// Returns true if the value affirms a proposition (a non-zero integer or `true`).
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Int(n, _) => *n != 0,
+            Value::UInt(n, _) => *n != 0,
+            Value::Boolean(b) => *b,
+            _ => false,
+        }
+    }
+}
+
+/// The exact integers arithmetic is computed in: i128 for the signed widths and
+/// u128 for the unsigned ones, so that every width up to 128 bits is held whole.
+trait Exact: Copy + PartialOrd {
+    const ZERO: Self;
+    fn checked_add(self, other: Self) -> Option<Self>;
+    fn checked_sub(self, other: Self) -> Option<Self>;
+    fn checked_mul(self, other: Self) -> Option<Self>;
+    fn checked_div(self, other: Self) -> Option<Self>;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn wrapping_mul(self, other: Self) -> Self;
+    fn wrapping_div(self, other: Self) -> Self;
+    /// The smallest and largest values of a width in bits.
+    fn lowest(width: u32) -> Self;
+    fn highest(width: u32) -> Self;
+    /// The value as an integer of the type, keeping the low bits its width holds.
+    fn truncated(self, typ: &crate::types::OnuType) -> Value;
+
+    fn fits(self, width: u32) -> bool {
+        (Self::lowest(width)..=Self::highest(width)).contains(&self)
+    }
+}
+
+impl Exact for i128 {
+    const ZERO: Self = 0;
+    fn checked_add(self, other: Self) -> Option<Self> { i128::checked_add(self, other) }
+    fn checked_sub(self, other: Self) -> Option<Self> { i128::checked_sub(self, other) }
+    fn checked_mul(self, other: Self) -> Option<Self> { i128::checked_mul(self, other) }
+    fn checked_div(self, other: Self) -> Option<Self> { i128::checked_div(self, other) }
+    fn wrapping_add(self, other: Self) -> Self { i128::wrapping_add(self, other) }
+    fn wrapping_sub(self, other: Self) -> Self { i128::wrapping_sub(self, other) }
+    fn wrapping_mul(self, other: Self) -> Self { i128::wrapping_mul(self, other) }
+    fn wrapping_div(self, other: Self) -> Self { i128::wrapping_div(self, other) }
+    fn lowest(width: u32) -> Self { if width >= 128 { i128::MIN } else { -(1 << (width - 1)) } }
+    fn highest(width: u32) -> Self { if width >= 128 { i128::MAX } else { (1 << (width - 1)) - 1 } }
+    fn truncated(self, typ: &crate::types::OnuType) -> Value { MirInterpreter::truncate(self as u128, typ) }
+}
+
+impl Exact for u128 {
+    const ZERO: Self = 0;
+    fn checked_add(self, other: Self) -> Option<Self> { u128::checked_add(self, other) }
+    fn checked_sub(self, other: Self) -> Option<Self> { u128::checked_sub(self, other) }
+    fn checked_mul(self, other: Self) -> Option<Self> { u128::checked_mul(self, other) }
+    fn checked_div(self, other: Self) -> Option<Self> { u128::checked_div(self, other) }
+    fn wrapping_add(self, other: Self) -> Self { u128::wrapping_add(self, other) }
+    fn wrapping_sub(self, other: Self) -> Self { u128::wrapping_sub(self, other) }
+    fn wrapping_mul(self, other: Self) -> Self { u128::wrapping_mul(self, other) }
+    fn wrapping_div(self, other: Self) -> Self { u128::wrapping_div(self, other) }
+    fn lowest(_: u32) -> Self { 0 }
+    fn highest(width: u32) -> Self { if width >= 128 { u128::MAX } else { (1 << width) - 1 } }
+    fn truncated(self, typ: &crate::types::OnuType) -> Value { MirInterpreter::truncate(self, typ) }
+}
+
+/// MirInterpreter evaluates the behaviors of a `MirProgram`.
+pub struct MirInterpreter<'a> {
+    functions: HashMap<&'a str, &'a MirFunction>,
+    env: &'a mut dyn Environment,
+    arithmetic: ArithmeticMode,
+}
+
+/// One behavior invocation in progress: its registers, the block it is in, and
+/// the next instruction of that block to evaluate.
+struct Frame<'a> {
+    function: &'a MirFunction,
+    blocks: HashMap<usize, &'a BasicBlock>,
+    registers: HashMap<usize, Value>,
+    block: &'a BasicBlock,
+    next: usize,
+}
+
+/// Why a frame stopped advancing.
+enum Step<'a> {
+    /// The instruction at `next` invokes a behavior of the program with these arguments.
+    Invoke(&'a MirFunction, Vec<Value>),
+    /// The behavior delivered its result.
+    Deliver(Value),
+}
+
+impl<'a> Frame<'a> {
+    fn enter(function: &'a MirFunction, args: Vec<Value>) -> Result<Self, OnuError> {
+        if args.len() != function.args.len() {
+            return Err(MirInterpreter::error(format!(
+                "The behavior '{}' expects {} argument(s) but received {}.",
+                function.name, function.args.len(), args.len()
+            )));
+        }
+        // A behavior without blocks delivers nothing at once.
+        static EMPTY: BasicBlock = BasicBlock { id: 0, instructions: Vec::new(), terminator: MirTerminator::Return(MirOperand::Constant(MirLiteral::Nothing)) };
+        let registers = function.args.iter().map(|arg| arg.ssa_var).zip(args).collect();
+        Ok(Self {
+            function,
+            blocks: function.blocks.iter().map(|b| (b.id, b)).collect(),
+            registers,
+            block: function.blocks.first().unwrap_or(&EMPTY),
+            next: 0,
+        })
+    }
+
+    /// Stores the result of the invocation at `next` and moves past it.
+    fn resume(&mut self, value: Value) {
+        if let Some(MirInstruction::Call { dest, .. }) = self.block.instructions.get(self.next) {
+            let value = MirInterpreter::delivered(value, self.function, *dest);
+            self.registers.insert(*dest, value);
+        }
+        self.next += 1;
+    }
+}
+
+impl<'a> MirInterpreter<'a> {
+    /// Creates an interpreter over a program, emitting into the given Environment.
+    pub fn new(program: &'a MirProgram, env: &'a mut dyn Environment) -> Self {
+        let functions = program.functions.iter().map(|f| (f.name.as_str(), f)).collect();
+        Self { functions, env, arithmetic: ArithmeticMode::default() }
+    }
+
+    /// Selects how integer arithmetic treats results outside their width.
+    pub fn with_arithmetic(mut self, mode: ArithmeticMode) -> Self {
+        self.arithmetic = mode;
+        self
+    }
+
+    /// Returns the name of the program's entry behavior (`run` or `main`), if any.
+    pub fn entry_point(&self) -> Option<&'a str> {
+        ["run", "main"].into_iter()
+            .find_map(|name| self.functions.get(name).map(|f| f.name.as_str()))
+    }
+
+    /// Evaluates the entry behavior. Programs without one evaluate to `nothing`.
+    pub fn run_entry(&mut self) -> Result<Value, OnuError> {
+        match self.entry_point() {
+            Some(name) => self.call(name, Vec::new()),
+            None => Ok(Value::Nothing),
+        }
+    }
+
+    /// Invokes a behavior (user-defined or built-in) by name.
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, OnuError> {
+        if let Some(function) = self.functions.get(name).copied() {
+            return self.execute(function, args);
+        }
+        self.call_builtin(name, args, Span::default())
+    }
+
+    /// Evaluates a behavior of the program and every behavior it invokes. The
+    /// invocations are kept on a stack of frames on the heap rather than on the
+    /// Rust stack, so a recursion is bounded only by `MAX_CALL_DEPTH`.
+    fn execute(&mut self, function: &'a MirFunction, args: Vec<Value>) -> Result<Value, OnuError> {
+        let mut frames = vec![Frame::enter(function, args)?];
+        loop {
+            let frame = frames.last_mut().unwrap();
+            match self.advance(frame)? {
+                Step::Invoke(callee, args) => {
+                    if frames.len() >= MAX_CALL_DEPTH {
+                        return Err(Self::error(format!(
+                            "The behavior '{}' exceeds the recursion limit of {} nested invocations.",
+                            callee.name, MAX_CALL_DEPTH
+                        )));
+                    }
+                    frames.push(Frame::enter(callee, args)?);
+                }
+                Step::Deliver(value) => {
+                    let finished = frames.pop().unwrap();
+                    let value = if finished.function.return_type == crate::types::OnuType::Nothing { Value::Nothing } else { value };
+                    match frames.last_mut() {
+                        Some(caller) => caller.resume(value),
+                        None => return Ok(value),
+                    }
+                }
+            }
+        }
+    }
+
+    /// Evaluates a frame until it invokes a behavior of the program or delivers its result.
+    fn advance(&mut self, frame: &mut Frame<'a>) -> Result<Step<'a>, OnuError> {
+        loop {
+            while let Some(inst) = frame.block.instructions.get(frame.next) {
+                if let MirInstruction::Call { name, args, .. } = inst
+                    && let Some(callee) = self.functions.get(name.as_str()).copied() {
+                    let values = args.iter().map(|a| Self::read(a, &frame.registers)).collect::<Result<Vec<_>, _>>()?;
+                    return Ok(Step::Invoke(callee, values));
+                }
+                self.execute_instruction(frame.function, inst, &mut frame.registers)?;
+                frame.next += 1;
+            }
+            let target = match &frame.block.terminator {
+                MirTerminator::Return(op) => return Ok(Step::Deliver(Self::read(op, &frame.registers)?)),
+                MirTerminator::Branch(target) => target,
+                MirTerminator::CondBranch { condition, then_block, else_block } => {
+                    if Self::read(condition, &frame.registers)?.is_truthy() { then_block } else { else_block }
+                }
+                MirTerminator::Switch { discriminant, arms, otherwise } => match Self::read(discriminant, &frame.registers)? {
+                    Value::Int(tag, _) => arms.iter().find(|(value, _)| *value as i128 == tag).map_or(otherwise, |(_, block)| block),
+                    other => return Err(Self::error(format!("A case analysis expects a variant's tag, but received {}.", other))),
+                },
+                MirTerminator::Unreachable => {
+                    return Err(Self::error(format!("The behavior '{}' reached an unreachable block.", frame.function.name)));
+                }
+            };
+            frame.block = frame.blocks[target];
+            frame.next = 0;
+        }
+    }
+
+    fn execute_instruction(&mut self, function: &MirFunction, inst: &MirInstruction, registers: &mut HashMap<usize, Value>) -> Result<(), OnuError> {
+        match inst {
+            MirInstruction::Assign { dest, src } => {
+                let value = Self::read(src, registers)?;
+                registers.insert(*dest, value);
+            }
+            MirInstruction::BinaryOperation { dest, op, lhs, rhs, span } => {
+                let l = Self::read(lhs, registers)?;
+                let r = Self::read(rhs, registers)?;
+                let typ = function.operand_type(lhs);
+                registers.insert(*dest, self.binary_operation(op, l, r, &typ, function, function.locate(*span))?);
+            }
+            MirInstruction::Call { dest, name, args, span } => {
+                let values = args.iter().map(|a| Self::read(a, registers)).collect::<Result<Vec<_>, _>>()?;
+                let value = Self::delivered(self.call_builtin(name, values, function.locate(*span))?, function, *dest);
+                registers.insert(*dest, value);
+            }
+            MirInstruction::Tuple { dest, elements } => {
+                let values = elements.iter().map(|e| Self::read(e, registers)).collect::<Result<Vec<_>, _>>()?;
+                registers.insert(*dest, Value::Tuple(values));
+            }
+            MirInstruction::Variant { dest, tag, payload } => {
+                // A variant is held as its tag followed by every payload slot of its variety.
+                let width = payload.iter().map(|(slot, _)| slot + 1).max().unwrap_or(1);
+                let mut values = vec![Value::Nothing; width];
+                values[0] = Value::Int(*tag as i128, 64);
+                for (slot, e) in payload {
+                    values[*slot] = Self::read(e, registers)?;
+                }
+                registers.insert(*dest, Value::Tuple(values));
+            }
+            MirInstruction::Array { dest, elements } => {
+                let values = elements.iter().map(|e| Self::read(e, registers)).collect::<Result<Vec<_>, _>>()?;
+                registers.insert(*dest, Value::Array(values));
+            }
+            MirInstruction::Matrix { dest, rows, cols, entries } => {
+                let mut values = Vec::new();
+                for entry in entries {
+                    match Self::read(entry, registers)? {
+                        Value::F64(x) => values.push(x),
+                        other => return Err(Self::error(format!("A matrix holds floats, but an entry is {}.", other))),
+                    }
+                }
+                registers.insert(*dest, Value::Matrix { rows: *rows, cols: *cols, entries: values });
+            }
+            MirInstruction::Index { dest, subject, index } => {
+                let value = match Self::read(subject, registers)? {
+                    Value::Tuple(mut values) if *index < values.len() => values.swap_remove(*index),
+                    other => return Err(Self::error(format!("Cannot take element {} of {}.", index, other))),
+                };
+                registers.insert(*dest, value);
+            }
+            MirInstruction::Convert { dest, src } => {
+                let value = Self::read(src, registers)?;
+                let target = function.ssa_types.get(dest).cloned().unwrap_or(crate::types::OnuType::I64);
+                registers.insert(*dest, Self::convert(value, &target)?);
+            }
+            MirInstruction::Emit(op) => {
+                let value = Self::read(op, registers)?;
+                self.env.emit(&value.to_string());
+            }
+        }
+        Ok(())
+    }
+
+    /// A call's result as its destination holds it: a float narrowed to an f32 where that is its width.
+    fn delivered(value: Value, function: &MirFunction, dest: usize) -> Value {
+        match (value, function.ssa_types.get(&dest)) {
+            (Value::F64(x), Some(typ)) => Self::narrow(x, typ),
+            (value, _) => value,
+        }
+    }
+
+    fn read(op: &MirOperand, registers: &HashMap<usize, Value>) -> Result<Value, OnuError> {
+        match op {
+            MirOperand::Constant(lit) => Ok(match lit {
+                MirLiteral::I64(n) => Value::Int(*n as i128, 64),
+                MirLiteral::F64(n) => Value::F64(*n),
+                MirLiteral::Integer(n, typ) => Self::convert(Value::Int(*n, 128), typ)?,
+                MirLiteral::Float(n, typ) => Self::convert(Value::F64(*n), typ)?,
+                MirLiteral::Boolean(b) => Value::Boolean(*b),
+                MirLiteral::Text(s) => Value::Text(s.clone()),
+                MirLiteral::Nothing => Value::Nothing,
+            }),
+            MirOperand::Variable(id) => registers.get(id).cloned()
+                .ok_or_else(|| Self::error(format!("SSA variable {} was read before it was derived.", id))),
+        }
+    }
+
+    /// Converts a number to the given width. An integer keeps the low bits of its
+    /// two's complement form, as an LLVM truncation or extension does. A float is cut
+    /// to a whole number that saturates at the ends of the width, NaN becoming 0, as
+    /// LLVM's saturating conversions do.
+    fn convert(value: Value, target: &crate::types::OnuType) -> Result<Value, OnuError> {
+        use crate::types::OnuType;
+        let float = |x: f64| Value::F64(if *target == OnuType::F32 { x as f32 as f64 } else { x });
+        let bits = match value {
+            Value::Int(n, _) if target.is_float() => return Ok(float(n as f64)),
+            Value::UInt(n, _) if target.is_float() => return Ok(float(n as f64)),
+            Value::F64(x) if target.is_float() => return Ok(float(x)),
+            Value::Int(n, _) => n as u128,
+            Value::UInt(n, _) => n,
+            Value::F64(x) if target.is_unsigned() => {
+                return Ok((x as u128).min(u128::highest(target.bit_width().unwrap_or(64))).truncated(target));
+            }
+            Value::F64(x) => {
+                let width = target.bit_width().unwrap_or(64);
+                return Ok((x as i128).clamp(i128::lowest(width), i128::highest(width)).truncated(target));
+            }
+            other => return Err(Self::error(format!("Only numbers can be converted to {}, but received {}.", target, other))),
+        };
+        Ok(Self::truncate(bits, target))
+    }
+
+    /// Keeps the low bits of an integer that fill the width of `typ` (i64 for any
+    /// other type), read as signed or unsigned as the width is.
+    fn truncate(bits: u128, typ: &crate::types::OnuType) -> Value {
+        let width = if typ.is_integer() { typ.bit_width().unwrap_or(64) } else { 64 };
+        let shift = 128 - width;
+        if typ.is_unsigned() {
+            Value::UInt((bits << shift) >> shift, width)
+        } else {
+            Value::Int(((bits << shift) as i128) >> shift, width)
+        }
+    }
+
+    /// The verb an operation is written with, for its errors.
+    fn verb(op: &MirBinOp) -> &'static str {
+        match op {
+            MirBinOp::Add => "added-to",
+            MirBinOp::Sub => "decreased-by",
+            MirBinOp::Mul => "scales-by",
+            MirBinOp::Div => "partitions-by",
+            MirBinOp::Eq => "matches",
+            MirBinOp::Gt => "exceeds",
+            MirBinOp::Lt => "falls-short-of",
+        }
+    }
+
+    fn binary_operation(&self, op: &MirBinOp, lhs: Value, rhs: Value, typ: &crate::types::OnuType, function: &MirFunction, span: Span) -> Result<Value, OnuError> {
+        let verb = Self::verb(op);
+        let flag = Value::Boolean;
+        match (lhs, rhs) {
+            // Both operands are brought to the operation's width, so a literal meets its partner's kind.
+            (lhs @ (Value::Int(..) | Value::UInt(..)), rhs @ (Value::Int(..) | Value::UInt(..))) if typ.is_integer() => {
+                match (Self::convert(lhs, typ)?, Self::convert(rhs, typ)?) {
+                    (Value::UInt(a, _), Value::UInt(b, _)) => self.integer_operation(op, a, b, typ, function, span),
+                    (Value::Int(a, _), Value::Int(b, _)) => self.integer_operation(op, a, b, typ, function, span),
+                    _ => unreachable!("a conversion delivers the signedness of its width"),
+                }
+            }
+            (Value::Int(a, _), Value::Int(b, _)) => self.integer_operation(op, a, b, &crate::types::OnuType::I64, function, span),
+            (Value::F64(a), Value::F64(b)) => Ok(match op {
+                MirBinOp::Add => Self::narrow(a + b, typ),
+                MirBinOp::Sub => Self::narrow(a - b, typ),
+                MirBinOp::Mul => Self::narrow(a * b, typ),
+                MirBinOp::Div => Self::narrow(a / b, typ),
+                MirBinOp::Eq => flag(a == b),
+                MirBinOp::Gt => flag(a > b),
+                MirBinOp::Lt => flag(a < b),
+            }),
+            (Value::Text(a), Value::Text(b)) if *op == MirBinOp::Eq => Ok(flag(a == b)),
+            (Value::Boolean(a), Value::Boolean(b)) if *op == MirBinOp::Eq => Ok(flag(a == b)),
+            (a, b) => Err(Self::error(format!(
+                "'{}' requires two numbers of the same kind, but received {} and {}.", verb, a, b
+            ))),
+        }
+    }
+
+    /// Rounds a float result to an f32 when that is its width; floats are held as f64.
+    fn narrow(x: f64, typ: &crate::types::OnuType) -> Value {
+        Value::F64(if *typ == crate::types::OnuType::F32 { x as f32 as f64 } else { x })
+    }
+
+    /// Applies an integer verb to two operands of the width `typ`. A result that
+    /// leaves the width, or the 128 bits the operands are held in, is treated as
+    /// the arithmetic mode says; comparisons and division respect signedness.
+    /// Its errors carry `span`, where the verb is written.
+    fn integer_operation<N: Exact>(&self, op: &MirBinOp, a: N, b: N, typ: &crate::types::OnuType, function: &MirFunction, span: Span) -> Result<Value, OnuError> {
+        let verb = Self::verb(op);
+        let exact = match op {
+            MirBinOp::Eq => return Ok(Value::Boolean(a == b)),
+            MirBinOp::Gt => return Ok(Value::Boolean(a > b)),
+            MirBinOp::Lt => return Ok(Value::Boolean(a < b)),
+            MirBinOp::Div if b == N::ZERO => {
+                return Err(OnuError::RuntimeError {
+                    message: format!("'{}' refuses a zero divisor in the behavior '{}' (line {}).", verb, function.name, span.line),
+                    span,
+                });
+            }
+            MirBinOp::Add => a.checked_add(b),
+            MirBinOp::Sub => a.checked_sub(b),
+            MirBinOp::Mul => a.checked_mul(b),
+            MirBinOp::Div => a.checked_div(b),
+        };
+        let width = typ.bit_width().unwrap_or(64);
+        match exact {
+            Some(exact) if exact.fits(width) => Ok(exact.truncated(typ)),
+            _ if self.arithmetic == ArithmeticMode::Checked => Err(OnuError::RuntimeError {
+                message: format!("'{}' overflowed {} in the behavior '{}' (line {}).", verb, typ, function.name, span.line),
+                span,
+            }),
+            // Truncating the exact result to the width is exactly wrapping arithmetic.
+            _ if self.arithmetic == ArithmeticMode::Wrapping => Ok(match op {
+                MirBinOp::Add => a.wrapping_add(b),
+                MirBinOp::Sub => a.wrapping_sub(b),
+                MirBinOp::Mul => a.wrapping_mul(b),
+                _ => a.wrapping_div(b),
+            }.truncated(typ)),
+            // Saturation clamps toward the side the exact result lies on.
+            _ => {
+                let below = match op {
+                    MirBinOp::Add => b < N::ZERO,
+                    MirBinOp::Sub => b > N::ZERO,
+                    MirBinOp::Mul => (a < N::ZERO) != (b < N::ZERO),
+                    _ => false,
+                };
+                Ok(if below { N::lowest(width) } else { N::highest(width) }.truncated(typ))
+            }
+        }
+    }
+
+    /// Evaluates the core built-in behaviors registered by `CompilerSession::new`.
+    /// The errors of a builtin that refuses its operands carry `span`, where it is written.
+    fn call_builtin(&mut self, name: &str, args: Vec<Value>, span: Span) -> Result<Value, OnuError> {
+        let mismatch = |args: &[Value]| {
+            let received: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+            Self::error(format!("The behavior '{}' refuses the argument(s) [{}].", name, received.join(", ")))
+        };
+        match (name, args.as_slice()) {
+            ("broadcasts" | "emit", [value]) => {
+                self.env.emit(&value.to_string());
+                Ok(Value::Nothing)
+            }
+            ("joined-with", [Value::Text(a), Value::Text(b)]) => Ok(Value::Text(format!("{}{}", a, b))),
+            ("len", [Value::Text(s)]) => Ok(Value::Int(s.chars().count() as i128, 64)),
+            ("char-at", [Value::Text(s), Value::Int(idx, _)]) => Ok(Value::Int(onu_runtime::code_at(s, *idx as i64) as i128, 64)),
+            ("as-text", [value]) => Ok(Value::Text(value.to_string())),
+            ("square-root-of", [Value::F64(x)]) => Ok(Value::F64(x.sqrt())),
+            ("floor-of", [Value::F64(x)]) => Ok(Value::F64(x.floor())),
+            ("rounded", [Value::F64(x)]) => Ok(Value::F64(x.round())),
+            ("raised-to", [Value::F64(x), Value::F64(y)]) => Ok(Value::F64(x.powf(*y))),
+            ("formatted-to", [Value::F64(x), Value::Int(digits, _)]) => Ok(Value::Text(onu_runtime::format_float(*x, *digits as i64))),
+            ("set-char", [Value::Text(s), Value::Int(idx, _), Value::Int(code, _)]) => Ok(Value::Text(onu_runtime::with_char_set(s, *idx as i64, *code as i64))),
+            ("tail-of", [Value::Text(s)]) => Ok(Value::Text(s.chars().skip(1).collect())),
+            ("init-of", [Value::Text(s)]) => {
+                let mut chars: Vec<char> = s.chars().collect();
+                chars.pop();
+                Ok(Value::Text(chars.into_iter().collect()))
+            }
+            ("char-from-code", [Value::Int(code, _)]) => Ok(Value::Text(onu_runtime::code_to_char(*code as i64).to_string())),
+            ("added-to", [Value::Matrix { rows, cols, entries: a }, Value::Matrix { rows: r, cols: c, entries: b }]) => {
+                if (rows, cols) != (r, c) {
+                    return Err(Self::error_at(format!("'added-to' cannot combine a {}x{} matrix with a {}x{} matrix.", rows, cols, r, c), span));
+                }
+                Ok(Value::Matrix { rows: *rows, cols: *cols, entries: a.iter().zip(b).map(|(x, y)| x + y).collect() })
+            }
+            ("scales-by", [Value::Matrix { rows, cols, entries }, Value::F64(factor)]) => {
+                Ok(Value::Matrix { rows: *rows, cols: *cols, entries: entries.iter().map(|x| x * factor).collect() })
+            }
+            ("multiplied-with", [Value::Matrix { rows, cols, entries: a }, Value::Matrix { rows: r, cols: c, entries: b }]) => {
+                if cols != r {
+                    return Err(Self::error_at(format!("'multiplied-with' cannot multiply a {}x{} matrix by a {}x{} matrix.", rows, cols, r, c), span));
+                }
+                Ok(Value::Matrix { rows: *rows, cols: *c, entries: onu_runtime::product_of(*rows, *cols, *c, a, b) })
+            }
+            ("transposed", [Value::Matrix { rows, cols, entries }]) => {
+                Ok(Value::Matrix { rows: *cols, cols: *rows, entries: onu_runtime::transpose_of(*rows, *cols, entries) })
+            }
+            ("entry-at", [Value::Matrix { rows, cols, entries }, Value::Int(row, _), Value::Int(col, _)]) => {
+                if !(0..*rows as i128).contains(row) || !(0..*cols as i128).contains(col) {
+                    return Err(Self::error_at(format!("'entry-at' position ({}, {}) lies outside a {}x{} matrix.", row, col, rows, cols), span));
+                }
+                Ok(Value::F64(entries[*row as usize * cols + *col as usize]))
+            }
+            ("determinant", [Value::Matrix { rows, cols, entries }]) => {
+                if rows != cols {
+                    return Err(Self::error_at(format!("'determinant' needs a square matrix, but was given a {}x{} matrix.", rows, cols), span));
+                }
+                Ok(Value::F64(onu_runtime::determinant_of(*rows, entries)))
+            }
+            ("element-at", [Value::Array(values), Value::Int(idx, _)]) => {
+                usize::try_from(*idx).ok().and_then(|i| values.get(i)).cloned().ok_or_else(|| {
+                    Self::error_at(format!("'element-at' position {} lies outside an array of length {}.", idx, values.len()), span)
+                })
+            }
+            ("length-of", [Value::Array(values)]) => Ok(Value::Int(values.len() as i128, 64)),
+            ("appended-with", [Value::Array(values), element]) => {
+                let mut grown = values.clone();
+                grown.push(element.clone());
+                Ok(Value::Array(grown))
+            }
+            ("slice-of", [Value::Array(values), Value::Int(start, _), Value::Int(end, _)]) => {
+                let end = (*end).clamp(0, values.len() as i128) as usize;
+                let start = (*start).clamp(0, end as i128) as usize;
+                Ok(Value::Array(values[start..end].to_vec()))
+            }
+            ("joined-with" | "len" | "char-at" | "as-text" | "set-char" | "tail-of" | "init-of" | "char-from-code" | "broadcasts" | "emit"
+            | "square-root-of" | "floor-of" | "rounded" | "raised-to" | "formatted-to"
+            | "element-at" | "length-of" | "appended-with" | "slice-of"
+            | "multiplied-with" | "transposed" | "entry-at" | "determinant", _) => {
+                Err(mismatch(&args))
+            }
+            _ => Err(Self::error(format!("The behavior '{}' has no implementation to evaluate.", name))),
+        }
+    }
+
+    fn error(message: String) -> OnuError {
+        Self::error_at(message, Span::default())
+    }
+
+    fn error_at(message: String, span: Span) -> OnuError {
+        OnuError::RuntimeError { message, span }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::MockEnvironment;
+    use crate::CompilerSession;
+
+    fn evaluate(source: &str) -> (Result<Value, OnuError>, Vec<String>) {
+        let mut session = CompilerSession::new().unwrap();
+        let program = session.build_mir(source).unwrap();
+        let mut env = MockEnvironment::new();
+        let result = MirInterpreter::new(&program, &mut env).run_entry();
+        (result, env.emitted)
+    }
+
+    #[test]
+    fn test_interpreter_broadcasts_text() {
+        let (result, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: greet
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts "Hello, World!"
+"#);
+        assert_eq!(result, Ok(Value::Nothing));
+        assert_eq!(emitted, vec!["Hello, World!".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_recursion_and_branches() {
+        let (result, emitted) = evaluate(r#"
+the behavior called factorial
+    with intent: calculate factorial
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then 1
+            else
+                derivation: next derives-from an integer n decreased-by 1
+                derivation: rest derives-from an integer next utilizes factorial
+                n scales-by rest
+
+the effect behavior called run
+    with intent: show factorial
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: result derives-from an integer 5 utilizes factorial
+        broadcasts (result utilizes as-text)
+"#);
+        assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+        assert_eq!(emitted, vec!["120".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_recursion_reaches_the_call_depth_limit() {
+        let program = |depth: usize| format!(r#"
+the behavior called countdown
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then 0
+            else
+                derivation: next derives-from an integer n decreased-by 1
+                next utilizes countdown
+
+the effect behavior called run
+    with intent: recurse deeply
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts (({} utilizes countdown) as-text)
+"#, depth);
+        // `run` and the invocations of `countdown` from n down to 0 fill every frame.
+        let (result, emitted) = evaluate(&program(MAX_CALL_DEPTH - 2));
+        assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+        assert_eq!(emitted, vec!["0".to_string()]);
+
+        let (result, _) = evaluate(&program(MAX_CALL_DEPTH - 1));
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("exceeds the recursion limit of 10000 nested invocations"), "{}", err);
+    }
+
+    #[test]
+    fn test_interpreter_text_builtins() {
+        let (_, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: exercise text
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: word derives-from a string "tape" joined-with "s"
+        derivation: d1 derives-from nothing broadcasts (word utilizes tail-of)
+        derivation: d2 derives-from nothing broadcasts (word utilizes init-of)
+        derivation: d3 derives-from nothing broadcasts (word utilizes set-char 0 80)
+        derivation: d4 derives-from nothing broadcasts (word utilizes set-char 9 80)
+        broadcasts (1114112 utilizes char-from-code)
+"#);
+        // Out-of-range positions and invalid codes behave as in onu-runtime.
+        assert_eq!(emitted, vec!["apes".to_string(), "tape".to_string(), "Papes".to_string(), "tapes".to_string(), "\u{FFFD}".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_zero_divisor_is_runtime_error() {
+        let (result, _) = evaluate(r#"
+the effect behavior called run
+    with intent: divide badly
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: x derives-from an integer 10 partitions-by 0
+        broadcasts (x utilizes as-text)
+"#);
+        let err = result.unwrap_err();
+        // The error points at the division, not at the behavior's header on line 2.
+        assert!(matches!(err, OnuError::RuntimeError { span: Span { line: 7, .. }, .. }), "{:?}", err);
+        let err = err.to_string();
+        assert!(err.contains("zero divisor") && err.contains("(line 7)"), "{}", err);
+    }
+
+    #[test]
+    fn test_interpreter_logic_short_circuits() {
+        let (result, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: decide lazily
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: never derives-from a boolean (1 matches 2) unites-with ((10 partitions-by 0) matches 1)
+        derivation: always derives-from a boolean (1 matches 1) joins-with ((10 partitions-by 0) matches 1)
+        if (never opposes) unites-with always
+            then broadcasts "lazy"
+            else broadcasts "eager"
+"#);
+        assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+        assert_eq!(emitted, vec!["lazy".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_float_builtins() {
+        let (result, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: exercise floats
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: side derives-from a float 2.0 scales-by 3
+        derivation: small derives-from an f32 ((side raised-to 2.0) square-root-of) utilizes as-f32
+        derivation: d1 derives-from nothing broadcasts ((side raised-to 2.0) formatted-to 2)
+        derivation: d2 derives-from nothing broadcasts ((7.5 floor-of) as-text)
+        broadcasts ((small rounded) formatted-to 1)
+"#);
+        assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+        assert_eq!(emitted, vec!["36.00".to_string(), "7".to_string(), "6.0".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_conversions_wrap_into_the_target_width() {
+        let (_, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: convert widths
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: big derives-from a u8 250
+        derivation: d1 derives-from nothing broadcasts (big utilizes as-i8)
+        broadcasts ((big utilizes widens-to-i64) added-to 1000)
+"#);
+        assert_eq!(emitted, vec!["-6".to_string(), "1250".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_float_conversions_saturate_at_the_target_width() {
+        let (_, emitted) = evaluate(r#"
+the effect behavior called run
+    with intent: convert floats
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: big derives-from a float 300.7
+        derivation: below derives-from a float 0.0 decreased-by 5.5
+        derivation: d1 derives-from nothing broadcasts (big utilizes as-i8)
+        derivation: d2 derives-from nothing broadcasts (below utilizes as-u8)
+        derivation: d3 derives-from nothing broadcasts (below utilizes as-i16)
+        broadcasts ((big scales-by big) utilizes as-u16)
+"#);
+        assert_eq!(emitted, vec!["127".to_string(), "0".to_string(), "-5".to_string(), "65535".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_checked_arithmetic_refuses_overflow() {
+        let (result, _) = evaluate(r#"
+the behavior called double
+    takes:
+        an i8 called n
+    delivers: an i8
+    as:
+        n scales-by 2
+
+the effect behavior called run
+    with intent: overflow a byte
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts (100 utilizes double)
+"#);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'scales-by' overflowed i8 in the behavior 'double'"), "{}", err);
+    }
+
+    #[test]
+    fn test_interpreter_holds_unsigned_values_above_i64_max() {
+        let program = |mode: ArithmeticMode| {
+            let source = r#"
+the effect behavior called run
+    with intent: exercise wide unsigned integers
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: big derives-from a u64 18000000000000000000
+        derivation: small derives-from a u64 5
+        derivation: d1 derives-from nothing broadcasts (big added-to small)
+        derivation: d2 derives-from nothing broadcasts ((big exceeds small) as-text)
+        derivation: d3 derives-from nothing broadcasts (big partitions-by 7)
+        derivation: d4 derives-from nothing broadcasts ((big utilizes as-i64) as-text)
+        broadcasts (big added-to big)
+"#;
+            let mut session = CompilerSession::new().unwrap();
+            let program = session.build_mir(source).unwrap();
+            let mut env = MockEnvironment::new();
+            let result = MirInterpreter::new(&program, &mut env).with_arithmetic(mode).run_entry();
+            (result, env.emitted)
+        };
+
+        let (result, emitted) = program(ArithmeticMode::Checked);
+        assert_eq!(emitted, vec!["18000000000000000005", "true", "2571428571428571428", "-446744073709551616"]);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'added-to' overflowed u64"), "{}", err);
+
+        let (result, emitted) = program(ArithmeticMode::Wrapping);
+        assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+        assert_eq!(emitted[4], "17553255926290448384");
+
+        let (_, emitted) = program(ArithmeticMode::Saturating);
+        assert_eq!(emitted[4], u64::MAX.to_string());
+    }
+
+    #[test]
+    fn test_interpreter_wide_signed_arithmetic_saturates_at_its_width() {
+        let (_, emitted) = {
+            let source = r#"
+the effect behavior called run
+    with intent: exercise the widest integers
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: huge derives-from an i128 170141183460469231731687303715884105727
+        derivation: d1 derives-from nothing broadcasts (huge scales-by 2)
+        derivation: d2 derives-from nothing broadcasts ((0 utilizes as-i128) decreased-by huge)
+        broadcasts ((3 utilizes as-u8) decreased-by (5 utilizes as-u8))
+"#;
+            let mut session = CompilerSession::new().unwrap();
+            let program = session.build_mir(source).unwrap();
+            let mut env = MockEnvironment::new();
+            let result = MirInterpreter::new(&program, &mut env).with_arithmetic(ArithmeticMode::Saturating).run_entry();
+            (result, env.emitted)
+        };
+        assert_eq!(emitted, vec![i128::MAX.to_string(), (-i128::MAX).to_string(), "0".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_array_traversals() {
+        let (result, emitted) = evaluate(r#"
+the behavior called doubled
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n scales-by 2
+
+the behavior called summed
+    takes:
+        an integer called total
+        an integer called n
+    delivers: an integer
+    as:
+        total added-to n
+
+the effect behavior called run
+    with intent: traverse an array
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: xs derives-from an array of integer [1 2 3 4]
+        derivation: d1 derives-from nothing broadcasts (xs transforms-each-by doubled)
+        derivation: d2 derives-from nothing broadcasts ((xs appended-with 5) slice-of 2 9)
+        derivation: d3 derives-from nothing broadcasts (xs folds-with summed 0)
+        broadcasts (xs element-at 4)
+"#);
+        assert_eq!(emitted, vec!["[2, 4, 6, 8]".to_string(), "[3, 4, 5]".to_string(), "10".to_string()]);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'element-at' position 4 lies outside an array of length 4"), "{}", err);
+    }
+
+    #[test]
+    fn test_interpreter_matrices() {
+        let (result, emitted) = evaluate(r#"
+the behavior called entry
+    takes:
+        a matrix called m
+    delivers: a float
+    as:
+        m entry-at 2 0
+
+the effect behavior called run
+    with intent: exercise matrices
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: a derives-from a matrix [1 2 : 3 4]
+        derivation: d1 derives-from nothing broadcasts ((a multiplied-with a) added-to (a transposed))
+        derivation: d2 derives-from nothing broadcasts (a determinant)
+        broadcasts (a utilizes entry)
+"#);
+        assert_eq!(emitted, vec!["[8 13 : 17 26]".to_string(), "-2".to_string()]);
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("'entry-at' position (2, 0) lies outside a 2x2 matrix"), "{}", err);
+    }
+
+    #[test]
+    fn test_interpreter_tuples() {
+        let (result, emitted) = evaluate(r#"
+the behavior called divided-by
+    takes:
+        an integer called n
+        an integer called d
+    delivers: a tuple of (integer : integer)
+    as:
+        derivation: q derives-from n partitions-by d
+        (q : n decreased-by (q scales-by d))
+
+the effect behavior called run
+    with intent: return and destructure tuples
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: (q : r) derives-from 17 divided-by 5
+        derivation: d1 derives-from nothing broadcasts (q as-text)
+        derivation: d2 derives-from nothing broadcasts (r as-text)
+        broadcasts ("abc" char-at 2) as-text
+"#);
+        result.unwrap();
+        assert_eq!(emitted, vec!["3".to_string(), "2".to_string(), "99".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_structures() {
+        let (result, emitted) = evaluate(r#"
+the shape Positioned promises:
+    a behavior called x-of
+        takes: nothing
+        delivers: a float
+
+the structure called Point
+    exposes:
+        a float called x
+        a float called y
+    keeps internal:
+        an integer called id
+
+the behavior called shifted
+    takes:
+        a Point called p
+        a float called dx
+    delivers: a Point
+    as:
+        a Point with x (p x-of added-to dx) y (p y-of) id (p id-of)
+
+the behavior called horizontal
+    takes:
+        a Positioned called thing
+    delivers: a float
+    as:
+        thing x-of
+
+the effect behavior called run
+    with intent: build and read structures
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: p derives-from a Point with y 2.0 x 1.5 id 7
+        derivation: q derives-from p shifted 1.0
+        derivation: d1 derives-from nothing broadcasts (q x-of as-text)
+        derivation: d2 derives-from nothing broadcasts ((q utilizes horizontal) as-text)
+        broadcasts (q y-of as-text)
+"#);
+        result.unwrap();
+        assert_eq!(emitted, vec!["2.5".to_string(), "2.5".to_string(), "2".to_string()]);
+    }
+
+    #[test]
+    fn test_interpreter_varieties() {
+        let (result, emitted) = evaluate(r#"
+the variety called Signal is one of:
+    a Stop
+    a Go carrying an integer called speed
+    a Turn carrying an integer called degrees and a boolean called left
+
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Go carrying speed then speed
+            is a Turn carrying degrees left then if left then degrees else 0 decreased-by degrees
+            otherwise 0
+
+the effect behavior called run
+    with intent: build and analyse varieties
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: d1 derives-from nothing broadcasts ((a Stop utilizes pace) as-text)
+        derivation: d2 derives-from nothing broadcasts ((a Go with speed 7 utilizes pace) as-text)
+        broadcasts ((a Turn with left false degrees 90 utilizes pace) as-text)
+"#);
+        result.unwrap();
+        assert_eq!(emitted, vec!["0".to_string(), "7".to_string(), "-90".to_string()]);
+    }
+}
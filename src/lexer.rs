@@ -1,13 +1,13 @@
-/// Ọ̀nụ Lexer: The First Discourse Layer
-///
-/// This module implements a handwritten lexer for the Ọ̀nụ language.
-/// Following the project's aesthetic of a "legal discourse" or "academic whitepaper,"
-/// the lexer must handle multi-word keywords (e.g., "the module called") as single
-/// semantic tokens while maintaining a simple, predictive SVO topology.
-///
-/// Design Patterns:
-/// - Iterator/Peekable: Uses Rust's standard Peekable interface to look ahead
-///   one character without consuming it, allowing for LL(1)-like lexing.
+//! Ọ̀nụ Lexer: The First Discourse Layer
+//!
+//! This module implements a handwritten lexer for the Ọ̀nụ language.
+//! Following the project's aesthetic of a "legal discourse" or "academic whitepaper,"
+//! the lexer must handle multi-word keywords (e.g., "the module called") as single
+//! semantic tokens while maintaining a simple, predictive SVO topology.
+//!
+//! Design Patterns:
+//! - Iterator/Peekable: Uses Rust's standard Peekable interface to look ahead
+//!   one character without consuming it, allowing for LL(1)-like lexing.
 
 use std::iter::Peekable;
 use std::str::Chars;
@@ -37,6 +37,8 @@ pub enum Token {
     // --- Discourse Structures ---
     TheModuleCalled,
     TheShape,
+    TheStructureCalled,
+    TheVarietyCalled,
     TheBehaviorCalled,
     TheEffectBehaviorCalled,
     Called,
@@ -45,8 +47,10 @@ pub enum Token {
     With,
     WithIntent,
     WithConcern,
+    DrawsFrom,  // names the modules a module's discourse builds on
     WithDiminishing,
     NoGuaranteedTermination, // Composite keyword
+    IntentionalRepetition,   // Composite keyword: opts a behavior out of DRY enforcement
     Receiving,
     Returning,
     Takes,      // New
@@ -85,6 +89,8 @@ pub enum Token {
     If,
     Then,
     Else,
+    When,
+    Otherwise,
     LParen,
     RParen,
     LBracket,
@@ -147,6 +153,11 @@ impl<'a> Lexer<'a> {
         }
     }
 
+    /// Lexes the whole input into the token stream the parser reads.
+    pub fn tokenize(mut self) -> Vec<TokenWithSpan> {
+        std::iter::from_fn(|| self.next_token()).collect()
+    }
+
     /// Peeks at the next character without consuming it.
     fn peek_char(&mut self) -> Option<char> {
         self.input.peek().copied()
@@ -242,6 +253,7 @@ impl<'a> Lexer<'a> {
         match first.as_str() {
             "derivation" => Some(Token::Derivation),
             "derives-from" => Some(Token::DerivesFrom),
+            "draws-from" => Some(Token::DrawsFrom),
             "takes" => Some(Token::Takes),
             "delivers" => Some(Token::Delivers),
             "utilizes" => Some(Token::Utilizes),
@@ -270,6 +282,8 @@ impl<'a> Lexer<'a> {
             "if" => Some(Token::If),
             "then" => Some(Token::Then),
             "else" => Some(Token::Else),
+            "when" => Some(Token::When),
+            "otherwise" => Some(Token::Otherwise),
             "of" => Some(Token::Of),
             "via" => Some(Token::Via),
             "role" => Some(Token::Role),
@@ -297,6 +311,20 @@ impl<'a> Lexer<'a> {
                         }
                     }
                     "shape" => return Some(Token::TheShape),
+                    "structure" => {
+                        self.skip_whitespace();
+                        let third = self.lex_single_identifier_or_keyword();
+                        if third == "called" {
+                            return Some(Token::TheStructureCalled);
+                        }
+                    }
+                    "variety" => {
+                        self.skip_whitespace();
+                        let third = self.lex_single_identifier_or_keyword();
+                        if third == "called" {
+                            return Some(Token::TheVarietyCalled);
+                        }
+                    }
                     "behavior" => {
                         self.skip_whitespace();
                         let third = self.lex_single_identifier_or_keyword();
@@ -337,6 +365,16 @@ impl<'a> Lexer<'a> {
                     Some(Token::WithConcern)
                 } else if second == "diminishing" {
                     Some(Token::WithDiminishing)
+                } else if second == "intentional" {
+                    self.skip_whitespace();
+                    let third = self.lex_single_identifier_or_keyword();
+                    if third == "repetition" {
+                        return Some(Token::IntentionalRepetition);
+                    }
+                    self.line = saved_line;
+                    self.column = saved_column;
+                    self.input = saved_input;
+                    Some(Token::With)
                 } else if second == "no" {
                     self.skip_whitespace();
                     let third = self.lex_single_identifier_or_keyword();
@@ -479,6 +517,7 @@ mod tests {
     use super::*;
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn test_lex_let_is_number() {
         let input = "let pi is the number 3.14159";
         let mut lexer = Lexer::new(input);
@@ -531,6 +570,37 @@ mod tests {
         assert!(lexer.next_token().is_none());
     }
 
+    #[test]
+    fn test_lex_module_draws_from() {
+        let input = "the module called Brainfuck with concern: tapes draws-from: Tape";
+        let mut lexer = Lexer::new(input);
+        let tokens: Vec<Token> = std::iter::from_fn(|| lexer.next_token().map(|t| t.token)).collect();
+        assert_eq!(&tokens[5..], &[Token::DrawsFrom, Token::Colon, Token::Identifier("Tape".to_string())]);
+    }
+
+    #[test]
+    fn test_lex_structure_header() {
+        let input = "the structure called Point keeps internal: a float called x";
+        let mut lexer = Lexer::new(input);
+        assert_eq!(lexer.next_token().unwrap().token, Token::TheStructureCalled);
+        assert_eq!(lexer.next_token().unwrap().token, Token::Identifier("Point".to_string()));
+        assert_eq!(lexer.next_token().unwrap().token, Token::KeepsInternal);
+        assert_eq!(lexer.next_token().unwrap().token, Token::Colon);
+    }
+
+    #[test]
+    fn test_lex_variety_and_case_analysis() {
+        let input = "the variety called Instruction is one of: when op is a Shift then 1 otherwise 0";
+        let tokens: Vec<Token> = std::iter::from_fn({
+            let mut lexer = Lexer::new(input);
+            move || lexer.next_token().map(|t| t.token)
+        })
+        .collect();
+        assert_eq!(&tokens[..2], &[Token::TheVarietyCalled, Token::Identifier("Instruction".to_string())]);
+        assert_eq!(tokens[6], Token::When);
+        assert_eq!(tokens[13], Token::Otherwise);
+    }
+
     #[test]
     fn test_lex_behavior_declaration() {
         let input = "the behavior called scale-value with intent: transform receiving: a number returning: an integer as: result";
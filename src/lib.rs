@@ -1,8 +1,12 @@
-use crate::lexer::Lexer;
-use crate::registry::{Registry, BehaviorSignature};
-use crate::parser::{Parser, Discourse};
+use crate::lexer::{Lexer, Token};
+use crate::registry::{Registry, BehaviorSignature, StructureField, VarietyVariant};
+use crate::parser::{Parser, Discourse, Visibility};
 use crate::types::OnuType;
 use crate::error::OnuError;
+use crate::env::Environment;
+use crate::interpreter::MirInterpreter;
+use std::collections::HashMap;
+use std::path::PathBuf;
 
 pub mod env;
 pub mod error;
@@ -12,15 +16,41 @@ pub mod registry;
 pub mod types;
 pub mod linguistics;
 pub mod hir;
+pub mod typeck;
+pub mod termination;
+pub mod concern;
+pub mod shape;
+pub mod exhaustiveness;
+pub mod modules;
+pub mod manifest;
+pub mod formatter;
+pub mod doc;
+pub mod cli;
 pub mod monomorphize;
 pub mod mir;
 pub mod codegen;
+pub mod interpreter;
 
 pub struct CompilerSession {
     pub registry: Registry,
     pub ast: Vec<Discourse>,
     pub hir: Vec<crate::hir::HirDiscourse>,
     pub mir: Option<crate::mir::MirProgram>,
+    pub termination: crate::termination::TerminationReport,
+    pub concern_policy: crate::concern::ConcernPolicy,
+    pub concern_warnings: Vec<String>,
+    pub arithmetic: crate::mir::ArithmeticMode,
+    /// Whether LLVM may relax IEEE semantics to optimize float arithmetic.
+    pub fast_math: bool,
+    /// Whether matrix products are emitted as affine loop nests for Polly.
+    pub polly_loops: bool,
+    /// The directories searched, in order, for the modules a program draws from.
+    pub search_path: Vec<PathBuf>,
+    /// The registry each module of the last program was checked against, by module name.
+    pub modules: HashMap<String, Registry>,
+    /// The behaviors each module defines, by the names they are linked under, in
+    /// load order with the root last.
+    pub units: Vec<(String, Vec<String>)>,
 }
 
 impl CompilerSession {
@@ -41,16 +71,37 @@ impl CompilerSession {
             registry.add_signature(name, sig);
             registry.mark_implemented(name);
         }
+        // Every number, truth value and matrix renders as text.
+        for subject in OnuType::NUMERIC.into_iter().chain([OnuType::Boolean, OnuType::Matrix]).filter(|t| *t != OnuType::I64) {
+            registry.add_overload("as-text", BehaviorSignature { input_types: vec![subject], return_type: OnuType::Strings });
+        }
 
-        let math_signatures = vec![
-            ("added-to", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("decreased-by", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("scales-by", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("partitions-by", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("matches", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("exceeds", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-            ("falls-short-of", BehaviorSignature { input_types: vec![OnuType::I64, OnuType::I64], return_type: OnuType::I64 }),
-        ];
+        // The arithmetic verbs and comparisons serve every numeric width, I64 first.
+        let mut math_signatures = Vec::new();
+        for width in std::iter::once(OnuType::I64).chain(OnuType::NUMERIC.into_iter().filter(|t| *t != OnuType::I64)) {
+            for name in ["added-to", "decreased-by", "scales-by", "partitions-by"] {
+                math_signatures.push((name, BehaviorSignature { input_types: vec![width.clone(), width.clone()], return_type: width.clone() }));
+            }
+            for name in ["matches", "exceeds", "falls-short-of"] {
+                math_signatures.push((name, BehaviorSignature { input_types: vec![width.clone(), width.clone()], return_type: OnuType::Boolean }));
+            }
+        }
+        // The float builtins, F64 first.
+        for width in [OnuType::F64, OnuType::F32] {
+            for name in ["square-root-of", "floor-of", "rounded"] {
+                math_signatures.push((name, BehaviorSignature { input_types: vec![width.clone()], return_type: width.clone() }));
+            }
+            math_signatures.push(("raised-to", BehaviorSignature { input_types: vec![width.clone(), width.clone()], return_type: width.clone() }));
+            math_signatures.push(("formatted-to", BehaviorSignature { input_types: vec![width.clone(), OnuType::I64], return_type: OnuType::Strings }));
+        }
+        // Matrices hold f64 entries; their shapes are checked by the type checker when known.
+        let matrix = |inputs: &[OnuType], return_type: OnuType| BehaviorSignature { input_types: inputs.to_vec(), return_type };
+        math_signatures.push(("added-to", matrix(&[OnuType::Matrix, OnuType::Matrix], OnuType::Matrix)));
+        math_signatures.push(("scales-by", matrix(&[OnuType::Matrix, OnuType::F64], OnuType::Matrix)));
+        math_signatures.push(("multiplied-with", matrix(&[OnuType::Matrix, OnuType::Matrix], OnuType::Matrix)));
+        math_signatures.push(("transposed", matrix(&[OnuType::Matrix], OnuType::Matrix)));
+        math_signatures.push(("entry-at", matrix(&[OnuType::Matrix, OnuType::I64, OnuType::I64], OnuType::F64)));
+        math_signatures.push(("determinant", matrix(&[OnuType::Matrix], OnuType::F64)));
         
         let math_shapes = vec![
             ("Addable", vec![
@@ -65,11 +116,46 @@ impl CompilerSession {
 
         registry.add_suite("StandardMath", math_signatures, math_shapes);
 
+        let logic_signatures = vec![
+            ("unites-with", BehaviorSignature { input_types: vec![OnuType::Boolean, OnuType::Boolean], return_type: OnuType::Boolean }),
+            ("joins-with", BehaviorSignature { input_types: vec![OnuType::Boolean, OnuType::Boolean], return_type: OnuType::Boolean }),
+            ("opposes", BehaviorSignature { input_types: vec![OnuType::Boolean], return_type: OnuType::Boolean }),
+        ];
+        registry.add_suite("StandardLogic", logic_signatures, Vec::new());
+        // The array verbs and traversals work on any element type; the type checker types them.
+        for (name, arity) in [("element-at", 2), ("length-of", 1), ("appended-with", 2), ("slice-of", 3), ("transforms-each-by", 2), ("filters-by", 2), ("folds-with", 3)] {
+            registry.add_name(name, arity);
+            registry.mark_implemented(name);
+        }
+        // The tuple projections lower to indexing; the type checker types them by position.
+        for name in crate::hir::PROJECTIONS {
+            registry.add_name(name, 1);
+            registry.mark_implemented(name);
+        }
+        // Widths change only through these conversions; the type checker types them.
+        for target in OnuType::NUMERIC {
+            for name in [format!("as-{}", target), format!("widens-to-{}", target)] {
+                registry.add_name(&name, 1);
+                for width in OnuType::NUMERIC {
+                    registry.add_implementation(&name, width);
+                }
+            }
+        }
+
         Ok(Self {
             registry,
             ast: Vec::new(),
             hir: Vec::new(),
             mir: None,
+            termination: Default::default(),
+            concern_policy: Default::default(),
+            concern_warnings: Vec::new(),
+            arithmetic: Default::default(),
+            fast_math: false,
+            polly_loops: false,
+            search_path: vec![PathBuf::from(".")],
+            modules: HashMap::new(),
+            units: Vec::new(),
         })
     }
 
@@ -77,20 +163,188 @@ impl CompilerSession {
         if _source.is_empty() {
              return Ok(Vec::new());
         }
-        let tokens = self.lex(_source).map_err(|e| OnuError::LexicalError { message: e, span: Default::default() })?;
+        let mir = self.build_mir(_source)?;
+        let binary = self.emit(&mir).map_err(|e| OnuError::CodeGenError { message: e })?;
         
+        Ok(binary)
+    }
+
+    /// Runs every analysis pass up to and including MIR construction, without
+    /// touching LLVM. Both the code generator and the interpreter start from here.
+    ///
+    /// The modules the source draws on are loaded from the search path and each is
+    /// checked against a registry of its own, holding only its own declarations and
+    /// those of the modules it draws from. The checked modules are then linked into
+    /// one program for the passes that follow.
+    ///
+    /// A violation does not stop the checking: every module is parsed, the units
+    /// that did parse go through the later passes, and every memo found along the
+    /// way is reported in one batch.
+    pub fn build_mir(&mut self, source: &str) -> Result<crate::mir::MirProgram, OnuError> {
+        let tokens = self.lex(source).map_err(|e| OnuError::LexicalError { message: e, span: Default::default() })?;
+        let root = crate::modules::ModuleSource::new(tokens, None);
+        let modules = crate::modules::ModuleLoader::new(&self.search_path).load(root)?;
+
+        let standard = self.registry.clone();
+        let mut behaviors: HashMap<String, Vec<(String, Visibility)>> = HashMap::new(); // module -> the behaviors it defines
+        let mut checked = Vec::new();
+        let mut concern_warnings = Vec::new();
+        let mut memos = Vec::new();
+        for module in &modules {
+            let mut registry = standard.clone();
+            for drawn in modules.iter().filter(|m| module.draws_from.iter().any(|name| m.name.as_ref() == Some(name))) {
+                Self::register_declarations(&mut registry, &drawn.tokens);
+                for (name, _) in behaviors[drawn.key()].iter().filter(|(_, visibility)| *visibility == Visibility::Internal) {
+                    registry.keep_internal(name, drawn.key());
+                }
+            }
+            Self::register_declarations(&mut registry, &module.tokens);
+
+            // The units that did parse go on to the later passes.
+            let (ast, diagnostics, resumed) = Self::parse(&module.tokens, &registry);
+            memos.extend(diagnostics);
+            let own: Vec<(String, Visibility)> = ast.iter().filter_map(|d| match d {
+                Discourse::Behavior { header, .. } => Some((header.name.clone(), header.visibility)),
+                _ => None,
+            }).collect();
+            Self::gather(&mut memos, crate::modules::check_drawn_names(module, &own, &behaviors));
+            behaviors.insert(module.key().to_string(), own);
+
+            Self::gather(&mut memos, Self::enforce_non_repetition(&ast, &mut registry));
+            let mut hir = self.lower(&ast).map_err(|e| OnuError::MonomorphizationError { message: e })?;
+            if let Some(warnings) = Self::gather(&mut memos, crate::concern::ConcernChecker::new(&self.concern_policy).check_program(&hir)) {
+                concern_warnings.extend(warnings);
+            }
+            // Each behavior is checked alone; the later passes read the types, so they
+            // see only the behaviors that type-checked. A body that parsed only in part
+            // would report what its missing lines left out, so it is not checked at all.
+            let mut types = crate::typeck::TypeChecker::new(&registry);
+            let mut shapes = crate::shape::ShapeChecker::new(&registry);
+            let cases = crate::exhaustiveness::ExhaustivenessChecker::new(&registry);
+            for discourse in hir.iter_mut() {
+                if matches!(discourse, crate::hir::HirDiscourse::Behavior { header, .. } if resumed.contains(&header.name)) {
+                    continue;
+                }
+                let discourse = std::slice::from_mut(discourse);
+                if Self::gather(&mut memos, types.check_program(discourse)).is_some() {
+                    Self::gather(&mut memos, cases.check_program(discourse));
+                    Self::gather(&mut memos, shapes.check_program(discourse));
+                }
+            }
+
+            self.ast = ast;
+            self.registry = registry.clone();
+            self.modules.insert(module.key().to_string(), registry);
+            checked.push(hir);
+        }
+        self.concern_warnings = concern_warnings;
+        if !memos.is_empty() {
+            return Err(OnuError::batch(memos));
+        }
+
+        // The root keeps its names. A drawn-upon module's behavior is qualified by its
+        // module's name where another module defines the same name, and always when it
+        // is an entry behavior, which only the root may provide.
+        let (drawn, _) = modules.split_at(modules.len() - 1);
+        for module in drawn {
+            let defines = |names: &[(String, Visibility)], name: &str| names.iter().any(|(other, _)| other == name);
+            let renamed: HashMap<String, String> = behaviors[module.key()]
+                .iter()
+                .map(|(name, _)| name)
+                .filter(|name| crate::modules::ENTRY_BEHAVIORS.contains(&name.as_str()) || behaviors.iter().any(|(other, names)| other != module.key() && defines(names, name)))
+                .map(|name| (name.clone(), format!("{}.{}", module.key(), name)))
+                .collect();
+            // The modules drawing on this one call its exposed behaviors by the same names, its entry aside.
+            let mut drawn_names = renamed.clone();
+            drawn_names.retain(|name, _| {
+                !crate::modules::ENTRY_BEHAVIORS.contains(&name.as_str())
+                    && behaviors[module.key()].contains(&(name.clone(), Visibility::Exposed))
+            });
+            for (hir, user) in checked.iter_mut().zip(&modules) {
+                if user.key() == module.key() {
+                    crate::modules::qualify(hir, &renamed);
+                } else if user.draws_from.iter().any(|name| name == module.key()) {
+                    crate::modules::qualify(hir, &drawn_names);
+                }
+            }
+        }
+        for module in drawn {
+            let types = &self.modules[module.key()];
+            self.registry.adopt_types(types);
+        }
+
+        self.units = checked
+            .iter()
+            .zip(&modules)
+            .map(|(hir, module)| {
+                let names = hir.iter().filter_map(|discourse| match discourse {
+                    crate::hir::HirDiscourse::Behavior { header, .. } => Some(header.name.clone()),
+                    _ => None,
+                });
+                (module.key().to_string(), names.collect())
+            })
+            .collect();
+        self.hir = checked.into_iter().flatten().collect();
+        self.termination = crate::termination::TerminationChecker::new(&self.hir).check_program()?;
+        let mir = Self::analyze(&mut self.hir, &self.registry).map_err(|e| OnuError::MonomorphizationError { message: e })?;
+        self.mir = Some(mir.clone());
+        Ok(mir)
+    }
+
+    /// Keeps the value of a pass that succeeded, or adds the memos of one that did not.
+    fn gather<T>(memos: &mut Vec<OnuError>, result: Result<T, OnuError>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(OnuError::ReviewBatch { memos: batch }) => {
+                memos.extend(batch);
+                None
+            }
+            Err(memo) => {
+                memos.push(memo);
+                None
+            }
+        }
+    }
+
+    /// Registers what a discourse unit declares, ahead of parsing its bodies: its
+    /// structures and varieties, then its shapes and behavior signatures. Field
+    /// accessors are registered last, as overloads of any behavior bearing their name.
+    fn register_declarations(registry: &mut Registry, tokens: &[crate::lexer::TokenWithSpan]) {
+        // Structures and varieties are named before anything is parsed, so that any header may use them as types.
+        let mut structure_names = registry.type_names();
+        for pair in tokens.windows(2) {
+            match (&pair[0].token, &pair[1].token) {
+                (Token::TheStructureCalled, Token::Identifier(name)) => {
+                    structure_names.declare_structure(name);
+                    registry.declare_structure(name);
+                }
+                (Token::TheVarietyCalled, Token::Identifier(name)) => {
+                    structure_names.declare_variety(name);
+                    registry.declare_variety(name);
+                }
+                _ => {}
+            }
+        }
+
+        let mut structures = Vec::new();
+        let mut varieties = Vec::new();
         let mut current_pos = 0;
         while current_pos < tokens.len() {
-             let mut parser = Parser::new(&tokens[current_pos..]);
+             let mut parser = Parser::with_registry(&tokens[current_pos..], &structure_names);
              if let Ok(discourse) = parser.parse_structural_discourse() {
                  match discourse {
+                     Discourse::Structure { name, fields, .. } => structures.push((name, fields)),
+                     Discourse::Variety { name, variants, .. } => varieties.push((name, variants)),
                      Discourse::Behavior { ref header, .. } => {
-                         let inputs = header.takes.iter().map(|a| a.type_info.onu_type.clone()).collect();
+                         let inputs: Vec<OnuType> = header.takes.iter().map(|a| a.type_info.onu_type.clone()).collect();
                          let ret = header.delivers.0.clone();
-                         self.registry.add_signature(&header.name, BehaviorSignature {
+                         let subject = inputs.first().cloned().unwrap_or(OnuType::Nothing);
+                         registry.add_signature(&header.name, BehaviorSignature {
                              input_types: inputs,
                              return_type: ret,
                          });
+                         registry.add_implementation(&header.name, subject);
+                         registry.reclaim(&header.name);
                      }
                      Discourse::Shape { ref name, ref behaviors } => {
                          let mut behavior_sigs = Vec::new();
@@ -101,63 +355,171 @@ impl CompilerSession {
                                  input_types: inputs,
                                  return_type: ret,
                              };
-                             self.registry.add_signature(&bh.name, sig.clone());
+                             registry.add_signature(&bh.name, sig.clone());
                              behavior_sigs.push((bh.name.clone(), sig));
                          }
-                         self.registry.add_shape(name, behavior_sigs);
+                         registry.add_shape(name, behavior_sigs);
                      }
                      _ => {}
                  }
                  current_pos += parser.pos;
              } else {
-                 break;
+                 // The full parse reports the malformed unit; the units after it are still declared.
+                 parser.synchronize(0);
+                 current_pos += parser.pos;
              }
         }
+        for (name, fields) in structures {
+            let fields = fields
+                .into_iter()
+                .map(|field| StructureField { name: field.name, typ: field.type_info.onu_type, visibility: field.visibility })
+                .collect();
+            registry.add_structure(&name, fields);
+        }
+        for (name, variants) in varieties {
+            let variants = variants
+                .into_iter()
+                .map(|variant| VarietyVariant {
+                    name: variant.name,
+                    payload: variant.payload.into_iter().map(|value| (value.name, value.type_info.onu_type)).collect(),
+                })
+                .collect();
+            registry.add_variety(&name, variants);
+        }
+    }
 
-        self.ast = self.parse(&tokens)?;
-        self.hir = self.lower(&self.ast).map_err(|e| OnuError::MonomorphizationError { message: e })?;
-        let mir = Self::analyze(&mut self.hir, &self.registry).map_err(|e| OnuError::MonomorphizationError { message: e })?;
-        self.mir = Some(mir.clone());
-        let binary = self.emit(&mir).map_err(|e| OnuError::CodeGenError { message: e })?;
-        
-        Ok(binary)
+    /// Compiles the source and executes its entry behavior in-process through LLVM's
+    /// JIT. Broadcasts are captured and forwarded to the given Environment. A trap
+    /// is returned as a `RuntimeError`, after the broadcasts made before it.
+    pub fn run_jit(&mut self, source: &str, env: &mut dyn Environment) -> Result<i32, OnuError> {
+        self.build_mir(source)?;
+        self.execute_jit(env)
     }
 
-    pub fn get_llvm_ir(&self, _source: &str) -> Result<String, OnuError> {
-        let mut session = Self::new().unwrap();
-        session.compile(_source)?;
+    /// Executes the entry behavior of the program last built by `build_mir` through
+    /// LLVM's JIT, without analysing the source again.
+    pub fn execute_jit(&self, env: &mut dyn Environment) -> Result<i32, OnuError> {
+        let mir = self.built()?;
         let context = inkwell::context::Context::create();
-        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(session.registry.clone()));
-        use crate::codegen::CodeGenerator;
-        generator.generate(session.mir.as_ref().unwrap()).map_err(|e| OnuError::CodeGenError { message: e })?;
+        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(self.registry.clone())).with_arithmetic(self.arithmetic).with_fast_math(self.fast_math).with_polly_loops(self.polly_loops);
+        generator.build_module(mir).map_err(|e| OnuError::CodeGenError { message: e })?;
+        let (outcome, lines) = onu_runtime::capture(|| onu_runtime::catch_traps(|| generator.run_jit()));
+        for line in &lines {
+            env.emit(line);
+        }
+        match outcome {
+            Ok(status) => status.map_err(|e| OnuError::CodeGenError { message: e }),
+            // A trap ends the program, not the process hosting the JIT.
+            Err(trap) => Err(OnuError::RuntimeError {
+                message: format!("{} {}", trap.observation, trap.assessment),
+                span: crate::error::Span { line: trap.line as usize, column: 0 },
+            }),
+        }
+    }
+
+    /// Compiles the source and writes it to `path` as a bitcode, IR, assembly or
+    /// object artifact for the machine described by `options`.
+    pub fn emit_artifact(&mut self, source: &str, kind: crate::codegen::EmitKind, options: &crate::codegen::TargetOptions, path: &std::path::Path) -> Result<(), OnuError> {
+        self.build_mir(source)?;
+        self.write_artifact(kind, options, path)
+    }
+
+    /// Writes the program last built by `build_mir` to `path` as the given artifact.
+    pub fn write_artifact(&self, kind: crate::codegen::EmitKind, options: &crate::codegen::TargetOptions, path: &std::path::Path) -> Result<(), OnuError> {
+        let mir = self.built()?;
+        let context = inkwell::context::Context::create();
+        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(self.registry.clone())).with_arithmetic(self.arithmetic).with_fast_math(self.fast_math).with_polly_loops(self.polly_loops);
+        generator.build_module(mir).map_err(|e| OnuError::CodeGenError { message: e })?;
+        generator.write_artifact(kind, options, path).map_err(|e| OnuError::CodeGenError { message: e })
+    }
+
+    /// Compiles the source into one object file per module in `dir`, named after the
+    /// module (`main.o` for a root that declares none), and returns their paths. A
+    /// specialization of a generic behavior is placed with the generic.
+    pub fn emit_objects(&mut self, source: &str, options: &crate::codegen::TargetOptions, dir: &std::path::Path) -> Result<Vec<PathBuf>, OnuError> {
+        let mir = self.build_mir(source)?;
+        let root = self.units.len() - 1;
+        let owner = |function: &crate::mir::MirFunction| {
+            let generic = function.name.split('_').next().unwrap_or_default();
+            self.units.iter().position(|(_, names)| names.iter().any(|name| *name == function.name || name == generic)).unwrap_or(root)
+        };
+        let mut objects = Vec::new();
+        for (index, (module, _)) in self.units.iter().enumerate() {
+            let name = if module.is_empty() { "main" } else { module.as_str() };
+            let context = inkwell::context::Context::create();
+            let generator = crate::codegen::LlvmGenerator::new(&context, name, Some(self.registry.clone())).with_arithmetic(self.arithmetic).with_fast_math(self.fast_math).with_polly_loops(self.polly_loops);
+            generator.build_module_with(&mir, &|function| owner(function) == index).map_err(|e| OnuError::CodeGenError { message: e })?;
+            let path = dir.join(format!("{}.o", name));
+            generator.write_artifact(crate::codegen::EmitKind::Object, options, &path).map_err(|e| OnuError::CodeGenError { message: e })?;
+            objects.push(path);
+        }
+        Ok(objects)
+    }
+
+    /// A new session with this one's settings that has built nothing yet.
+    pub fn with_same_settings(&self) -> Result<Self, String> {
+        let mut session = Self::new()?;
+        session.arithmetic = self.arithmetic;
+        session.fast_math = self.fast_math;
+        session.polly_loops = self.polly_loops;
+        session.search_path = self.search_path.clone();
+        session.concern_policy = self.concern_policy.clone();
+        Ok(session)
+    }
+
+    /// Compiles the source in a fresh session with this one's settings and returns its LLVM IR.
+    pub fn get_llvm_ir(&self, source: &str) -> Result<String, OnuError> {
+        let mut session = self.with_same_settings().map_err(|e| OnuError::CodeGenError { message: e })?;
+        session.build_mir(source)?;
+        session.llvm_ir()
+    }
+
+    /// The LLVM IR of the program last built by `build_mir`.
+    pub fn llvm_ir(&self) -> Result<String, OnuError> {
+        let mir = self.built()?;
+        let context = inkwell::context::Context::create();
+        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(self.registry.clone())).with_arithmetic(self.arithmetic).with_fast_math(self.fast_math).with_polly_loops(self.polly_loops);
+        generator.build_module(mir).map_err(|e| OnuError::CodeGenError { message: e })?;
         Ok(generator.get_ir_string())
     }
 
+    /// The program last built by `build_mir`.
+    fn built(&self) -> Result<&crate::mir::MirProgram, OnuError> {
+        self.mir.as_ref().ok_or_else(|| OnuError::CodeGenError { message: "No program has been built yet; build_mir comes first.".to_string() })
+    }
+
     fn lex(&self, _source: &str) -> Result<Vec<crate::lexer::TokenWithSpan>, String> {
-        let mut lexer = Lexer::new(_source);
-        let mut tokens = Vec::new();
-        while let Some(t) = lexer.next_token() {
-            tokens.push(t);
-        }
-        Ok(tokens)
+        Ok(Lexer::new(_source).tokenize())
     }
 
-    fn parse(&self, _tokens: &[crate::lexer::TokenWithSpan]) -> Result<Vec<Discourse>, OnuError> {
-        let mut current_pos = 0;
-        let mut ast = Vec::new();
-        while current_pos < _tokens.len() {
-             let mut parser = Parser::with_registry(&_tokens[current_pos..], &self.registry);
-             match parser.parse_discourse() {
-                 Ok(discourse) => {
-                     current_pos += parser.pos;
-                     ast.push(discourse);
-                 }
-                 Err(e) => {
-                     return Err(e);
-                 }
-             }
+    /// Parses every discourse unit of a module, recovering past malformed ones so
+    /// that all of its parse errors are found in one pass.
+    /// Also names the behaviors whose bodies had to be resumed past a malformed line.
+    fn parse(tokens: &[crate::lexer::TokenWithSpan], registry: &Registry) -> (Vec<Discourse>, Vec<OnuError>, Vec<String>) {
+        let mut parser = Parser::with_registry(tokens, registry);
+        let (ast, diagnostics) = parser.parse_program();
+        (ast, diagnostics, parser.resumed().to_vec())
+    }
+
+    /// Registers the alpha-normalized hash of every behavior body, refusing any
+    /// behavior that repeats another unless it declares `with intentional repetition`.
+    fn enforce_non_repetition(ast: &[Discourse], registry: &mut Registry) -> Result<(), OnuError> {
+        for discourse in ast {
+            if let Discourse::Behavior { header, body } = discourse {
+                if header.allow_repetition {
+                    continue;
+                }
+                let signature = BehaviorSignature {
+                    input_types: header.takes.iter().map(|a| a.type_info.onu_type.clone()).collect(),
+                    return_type: header.delivers.0.clone(),
+                };
+                let args: Vec<String> = header.takes.iter().map(|a| a.name.clone()).collect();
+                let normalized = crate::registry::alpha_normalize(body, &args);
+                let hash = crate::registry::compute_behavior_hash(&normalized, &signature);
+                registry.register(header.name.clone(), hash, header.span)?;
+            }
         }
-        Ok(ast)
+        Ok(())
     }
 
     fn lower(&self, _ast: &[Discourse]) -> Result<Vec<crate::hir::HirDiscourse>, String> {
@@ -165,7 +527,7 @@ impl CompilerSession {
     }
 
     fn analyze(hir: &mut Vec<crate::hir::HirDiscourse>, registry: &Registry) -> Result<crate::mir::MirProgram, String> {
-        crate::monomorphize::Monomorphizer::run(hir);
+        crate::monomorphize::Monomorphizer::run(hir)?;
         let mut builder = crate::mir::MirBuilder::new();
         // Pass registry info if needed for builder
         Ok(builder.build_program_with_registry(hir, registry))
@@ -174,7 +536,30 @@ impl CompilerSession {
     fn emit(&self, _mir: &crate::mir::MirProgram) -> Result<Vec<u8>, String> {
         use crate::codegen::CodeGenerator;
         let context = inkwell::context::Context::create();
-        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(self.registry.clone()));
+        let generator = crate::codegen::LlvmGenerator::new(&context, "onu_module", Some(self.registry.clone())).with_arithmetic(self.arithmetic).with_fast_math(self.fast_math).with_polly_loops(self.polly_loops);
         generator.generate(_mir)
     }
 }
+
+/// Session evaluates Ọ̀nụ scripts in-process with the MIR interpreter.
+///
+/// It runs the same front-end as `CompilerSession` but never touches LLVM,
+/// routing every broadcast through the injected `Environment`.
+pub struct Session {
+    env: Box<dyn Environment>,
+}
+
+impl Session {
+    /// Creates a session that emits into the given Environment.
+    pub fn new(env: Box<dyn Environment>) -> Self {
+        Self { env }
+    }
+
+    /// Compiles the script to MIR and evaluates its entry behavior (`run` or `main`), if any.
+    pub fn run_script(&mut self, source: &str) -> Result<(), String> {
+        let mut compiler = CompilerSession::new()?;
+        let program = compiler.build_mir(source).map_err(|e| e.to_string())?;
+        let mut interpreter = MirInterpreter::new(&program, self.env.as_mut()).with_arithmetic(compiler.arithmetic);
+        interpreter.run_entry().map(|_| ()).map_err(|e| e.to_string())
+    }
+}
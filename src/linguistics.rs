@@ -1,9 +1,9 @@
-/// Ọ̀nụ Linguistic Validator: The Interface Adapter Layer
-///
-/// This module implements the grammatical rules of the Ọ̀nụ language
-/// that are purely linguistic rather than structural or semantic.
-/// Following Clean Architecture, it decouples article validation (a/an)
-/// from the core Parser.
+//! Ọ̀nụ Linguistic Validator: The Interface Adapter Layer
+//!
+//! This module implements the grammatical rules of the Ọ̀nụ language
+//! that are purely linguistic rather than structural or semantic.
+//! Following Clean Architecture, it decouples article validation (a/an)
+//! from the core Parser.
 
 use crate::parser::Discourse;
 use crate::error::OnuError;
@@ -14,14 +14,11 @@ pub struct LinguisticValidator;
 impl LinguisticValidator {
     /// Validates all linguistic rules for a discourse unit.
     pub fn validate(discourse: &Discourse) -> Result<(), OnuError> {
-        match discourse {
-            Discourse::Behavior { header, .. } => {
-                for arg in &header.takes {
-                    Self::validate_article(&arg.type_info.article, &arg.type_info.display_name)?;
-                }
-                // ReturnType is always safe because 'nothing' is handled or it uses OnuType
+        if let Discourse::Behavior { header, .. } = discourse {
+            for arg in &header.takes {
+                Self::validate_article(&arg.type_info.article, &arg.type_info.display_name)?;
             }
-            _ => {}
+            // ReturnType is always safe because 'nothing' is handled or it uses OnuType
         }
         Ok(())
     }
@@ -1,103 +1,356 @@
 use onu::CompilerSession;
+use onu::cli::{Cli, Command, EmitForm, USAGE};
+use onu::codegen::{EmitKind, TargetOptions};
+use onu::env::StdoutEnvironment;
+use onu::error::OnuError;
+use onu::interpreter::{MirInterpreter, Value};
+use onu::manifest::Manifest;
 use std::env;
 use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+
+/// Why a command stopped: a peer review memo, which exits with its variant's own
+/// status, or a failure outside the language, such as a file that cannot be read.
+enum Failure {
+    Onu(OnuError),
+    Other(String),
+}
+
+impl From<OnuError> for Failure {
+    fn from(error: OnuError) -> Self {
+        Failure::Onu(error)
+    }
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 2 {
-        println!("Usage: onu <file_path> [--run] [--ir]");
+    let args: Vec<String> = env::args().skip(1).collect();
+    if matches!(args.first().map(String::as_str), Some("-h" | "--help" | "help")) {
+        println!("{}", USAGE);
         return;
     }
-
-    let file_path = &args[1];
-    let do_run = args.iter().any(|arg| arg == "--run");
-    let show_ir = args.iter().any(|arg| arg == "--ir");
-    let do_native = args.iter().any(|arg| arg == "--native");
-
-    let input = match fs::read_to_string(file_path) {
-        Ok(content) => content,
+    let cli = match Cli::parse(&args) {
+        Ok(cli) => cli,
         Err(e) => {
-            println!("Error: Could not read file '{}': {}", file_path, e);
-            return;
+            eprintln!("Error: {}\n\n{}", e, USAGE);
+            exit(2);
         }
     };
 
-    let mut session = match CompilerSession::new() {
-        Ok(s) => s,
-        Err(e) => {
-            println!("Compiler Initialization Error: {}", e);
-            return;
-        }
+    let outcome = match cli.command {
+        // The checked session is run as it is, so the file is compiled only once.
+        Command::CheckThenRun => check(&cli).and_then(|session| {
+            let runs = session.mir.as_ref().is_some_and(has_entry);
+            report_check(&session, runs);
+            match runs {
+                true => Ok(session.execute_jit(&mut StdoutEnvironment)?),
+                false => Ok(0),
+            }
+        }),
+        Command::Check => check(&cli).map(|session| {
+            report_check(&session, false);
+            0
+        }),
+        Command::Build if cli.file().is_some() => build_file(&cli),
+        Command::Run if cli.file().is_some() => run_file(&cli),
+        Command::Build | Command::Run => run_package(&cli),
+        Command::Emit(form) => emit(&cli, form),
+        Command::Fmt => format_files(&cli),
+        Command::Doc => document(&cli),
+        Command::Test => test(&cli),
     };
+    match outcome {
+        Ok(status) => exit(status),
+        Err(Failure::Onu(e)) => {
+            eprintln!("{}", e);
+            exit(e.exit_code());
+        }
+        Err(Failure::Other(message)) => {
+            eprintln!("Error: {}", message);
+            exit(1);
+        }
+    }
+}
 
-    if show_ir {
-        match session.get_llvm_ir(&input) {
-            Ok(ir) => {
-                println!("--- LLVM IR ---");
-                println!("{}", ir);
-                println!("---------------");
-            }
-            Err(e) => {
-                println!("{}", e);
-                return;
-            }
+/// The single file of the command and its source.
+fn source_of(cli: &Cli) -> Result<(&Path, String), Failure> {
+    let file = cli.file().expect("the command was parsed with its file");
+    let source = fs::read_to_string(file).map_err(|e| Failure::Other(format!("Could not read file '{}': {}", file.display(), e)))?;
+    Ok((file, source))
+}
+
+/// A session configured by the options. Drawn-upon modules are sought on the
+/// given search path first, then beside the source file.
+fn session_for(cli: &Cli, file: Option<&Path>) -> Result<CompilerSession, Failure> {
+    let mut session = CompilerSession::new().map_err(|e| Failure::Other(format!("Compiler initialization failed: {}", e)))?;
+    if let Some(mode) = cli.arithmetic {
+        session.arithmetic = mode;
+    }
+    session.fast_math = cli.fast_math;
+    session.polly_loops = cli.polly_loops;
+    session.concern_policy = cli.concern_policy(Default::default());
+    session.search_path = cli.search_path.clone();
+    session.search_path.extend(file.map(onu::modules::directory_of));
+    Ok(session)
+}
+
+fn has_entry(program: &onu::mir::MirProgram) -> bool {
+    program.functions.iter().any(|f| onu::modules::ENTRY_BEHAVIORS.contains(&f.name.as_str()))
+}
+
+/// Analyses the file and the modules it draws on.
+fn check(cli: &Cli) -> Result<CompilerSession, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    session.build_mir(&source)?;
+    Ok(session)
+}
+
+/// Lists the behaviors the check registered, then the summaries. When the program
+/// is about to run, the list goes to stderr, so that stdout holds only its output.
+fn report_check(session: &CompilerSession, running: bool) {
+    for discourse in &session.ast {
+        if let onu::parser::Discourse::Behavior { header, .. } = discourse {
+            let line = format!("Behavior '{}' parsed and registered.", header.name);
+            if running { eprintln!("{}", line) } else { println!("{}", line) }
         }
     }
+    report_summaries(session);
+}
 
-    match session.compile(&input) {
-        Ok(binary) => {
-            if let Err(e) = fs::write("output.bc", binary) {
-                println!("Error writing output.bc: {}", e);
-                return;
+/// Runs the file's entry behavior through the JIT, exiting with its status.
+fn run_file(cli: &Cli) -> Result<i32, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    let status = session.run_jit(&source, &mut StdoutEnvironment)?;
+    report_summaries(&session);
+    Ok(status)
+}
+
+/// Compiles the file into a native executable, beside the source unless `-o` is given.
+fn build_file(cli: &Cli) -> Result<i32, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    let output = cli.output.clone().unwrap_or_else(|| file.with_extension(""));
+    let object = output.with_extension("o");
+    session.emit_artifact(&source, EmitKind::Object, &cli.target_options(), &object)?;
+    let linked = link_executable(std::slice::from_ref(&object), &output);
+    let _ = fs::remove_file(&object);
+    linked?;
+    eprintln!("Compiled {} to {}.", file.display(), output.display());
+    report_summaries(&session);
+    Ok(0)
+}
+
+/// Writes one stage of compilation: text forms to standard output, binary forms
+/// beside the source, unless `-o` says where.
+fn emit(cli: &Cli, form: EmitForm) -> Result<i32, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    let mir = session.build_mir(&source)?;
+    if form.is_text() {
+        let text = match form {
+            EmitForm::Ast => format!("{:#?}\n", session.ast),
+            EmitForm::Hir => format!("{:#?}\n", session.hir),
+            EmitForm::Mir => format!("{:#?}\n", mir.functions),
+            _ => session.llvm_ir()?,
+        };
+        write_text(cli, &text)?;
+    } else {
+        let kind = match form {
+            EmitForm::Asm => EmitKind::Assembly,
+            EmitForm::Bc => EmitKind::Bitcode,
+            _ => EmitKind::Object,
+        };
+        let output = cli.output.clone().unwrap_or_else(|| file.with_extension(kind.extension()));
+        session.write_artifact(kind, &cli.target_options(), &output)?;
+        eprintln!("Wrote {}.", output.display());
+    }
+    report_summaries(&session);
+    Ok(0)
+}
+
+/// Lays out each file in the canonical form, or with `--check` only reports the
+/// files that are not, failing if there are any.
+fn format_files(cli: &Cli) -> Result<i32, Failure> {
+    let mut unformatted = 0;
+    for file in &cli.files {
+        let source = fs::read_to_string(file).map_err(|e| Failure::Other(format!("Could not read file '{}': {}", file.display(), e)))?;
+        let formatted = onu::formatter::format_source(&source)?;
+        if formatted == source {
+            continue;
+        }
+        if cli.check_only {
+            eprintln!("{} is not formatted.", file.display());
+            unformatted += 1;
+        } else {
+            fs::write(file, formatted).map_err(|e| Failure::Other(format!("Could not write '{}': {}", file.display(), e)))?;
+            eprintln!("Formatted {}.", file.display());
+        }
+    }
+    Ok(if unformatted > 0 { 1 } else { 0 })
+}
+
+/// Writes the module's Markdown reference to standard output, unless `-o` is given.
+fn document(cli: &Cli) -> Result<i32, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    session.build_mir(&source)?;
+    write_text(cli, &onu::doc::document(&session.ast, &source))?;
+    Ok(0)
+}
+
+/// Runs every behavior named `test-...` in the interpreter. A test takes nothing
+/// and passes when it delivers true; the command fails if any test does not.
+fn test(cli: &Cli) -> Result<i32, Failure> {
+    let (file, source) = source_of(cli)?;
+    let mut session = session_for(cli, Some(file))?;
+    let program = session.build_mir(&source)?;
+    report_summaries(&session);
+    let tests: Vec<&onu::mir::MirFunction> = program.functions.iter().filter(|f| f.name.starts_with("test-")).collect();
+
+    println!("running {} test{}", tests.len(), if tests.len() == 1 { "" } else { "s" });
+    let mut failed = Vec::new();
+    for function in &tests {
+        let verdict = if !function.args.is_empty() || function.return_type != onu::types::OnuType::Boolean {
+            Err("a test must take nothing and deliver a boolean".to_string())
+        } else {
+            let mut env = StdoutEnvironment;
+            let mut interpreter = MirInterpreter::new(&program, &mut env).with_arithmetic(session.arithmetic);
+            match interpreter.call(&function.name, Vec::new()) {
+                Ok(Value::Boolean(true)) => Ok(()),
+                Ok(value) => Err(format!("delivered {}", value)),
+                Err(e) => Err(e.to_string()),
             }
-            
-            if do_run {
-                // Automate: clang runtime.c -> llvm-link -> lli
-                let status = std::process::Command::new("clang-14")
-                    .args(&["-emit-llvm", "-c", "runtime.c", "-o", "runtime.bc"])
-                    .status();
-                
-                if status.is_err() || !status.unwrap().success() {
-                    println!("Error: Failed to compile runtime.c. Ensure clang-14 is installed.");
-                    return;
-                }
-
-                let link_status = std::process::Command::new("llvm-link-14")
-                    .args(&["output.bc", "runtime.bc", "-o", "final.bc"])
-                    .status();
-
-                if link_status.is_err() || !link_status.unwrap().success() {
-                    println!("Error: Failed to link bitcode. Ensure llvm-link-14 is installed.");
-                    return;
-                }
-
-                let run_status = std::process::Command::new("lli-14")
-                    .arg("final.bc")
-                    .status();
-
-                if run_status.is_err() || !run_status.unwrap().success() {
-                    println!("Error: Execution failed via lli-14.");
-                }
-            } else if do_native {
-                // Automate: clang runtime.c output.bc -O3 -o onu_prog
-                println!("Compiling to native binary...");
-                let status = std::process::Command::new("clang-14")
-                    .args(&["runtime.c", "output.bc", "-O3", "-o", "onu_prog"])
-                    .status();
-                
-                if status.is_err() || !status.unwrap().success() {
-                    println!("Error: Failed to link native binary.");
-                } else {
-                    println!("Native binary generated: ./onu_prog");
-                }
-            } else {
-                println!("Successfully compiled {} to output.bc.", file_path);
-                println!("To run (JIT): onu {} --run", file_path);
-                println!("To compile (Native): onu {} --native", file_path);
+        };
+        match verdict {
+            Ok(()) => println!("test {} ... ok", function.name),
+            Err(reason) => {
+                println!("test {} ... FAILED", function.name);
+                failed.push((function.name.as_str(), reason));
             }
         }
-        Err(e) => {
-            println!("{}", e);
+    }
+    for (name, reason) in &failed {
+        eprintln!("---- {} ----\n{}", name, reason);
+    }
+    println!(
+        "\ntest result: {}. {} passed; {} failed",
+        if failed.is_empty() { "ok" } else { "FAILED" },
+        tests.len() - failed.len(),
+        failed.len()
+    );
+    Ok(if failed.is_empty() { 0 } else { 1 })
+}
+
+fn write_text(cli: &Cli, text: &str) -> Result<(), Failure> {
+    match &cli.output {
+        Some(path) => fs::write(path, text).map_err(|e| Failure::Other(format!("Could not write '{}': {}", path.display(), e))),
+        None => {
+            print!("{}", text);
+            Ok(())
         }
     }
 }
+
+/// Runs `onu build` or `onu run` for the package whose `Onu.toml` is found from the
+/// current directory upwards, or given with `--manifest-path`.
+fn run_package(cli: &Cli) -> Result<i32, Failure> {
+    let manifest_path = match &cli.manifest_path {
+        Some(path) => path.clone(),
+        None => env::current_dir()
+            .ok()
+            .and_then(|dir| Manifest::find(&dir))
+            .ok_or_else(|| Failure::Other(format!("Could not find {} in this directory or any above it.", onu::manifest::MANIFEST_FILE)))?,
+    };
+    let mut manifest = Manifest::load(&manifest_path)?;
+    if let Some(profile) = &cli.profile {
+        manifest.select_profile(profile)?;
+    }
+    let executable = build_package(cli, &manifest)?;
+    if cli.command == Command::Build {
+        return Ok(0);
+    }
+    let status = std::process::Command::new(&executable)
+        .status()
+        .map_err(|e| Failure::Other(format!("Could not run {}: {}", executable.display(), e)))?;
+    Ok(status.code().unwrap_or(1))
+}
+
+/// Compiles the package's entry module, and every module it draws on, into one
+/// executable under `<output>/<profile>/`, keeping an object file per module in `deps/`.
+/// The options given on the command line override the profile's and the manifest's concern policy.
+fn build_package(cli: &Cli, manifest: &Manifest) -> Result<PathBuf, Failure> {
+    let entry = manifest.entry_file()?;
+    let source = fs::read_to_string(&entry).map_err(|e| Failure::Other(format!("Could not read file '{}': {}", entry.display(), e)))?;
+
+    let mut session = session_for(cli, None)?;
+    session.search_path.extend(manifest.sources.iter().cloned());
+    session.arithmetic = cli.arithmetic.unwrap_or(manifest.profile.arithmetic);
+    session.fast_math |= manifest.profile.fast_math;
+    session.polly_loops |= manifest.profile.polly_loops;
+    session.concern_policy = cli.concern_policy(manifest.concern.clone());
+    let options = TargetOptions {
+        opt_level: TargetOptions::opt_level_from(cli.opt_level.unwrap_or(manifest.profile.opt_level)),
+        ..cli.target_options()
+    };
+
+    let deps = manifest.artifact_dir().join("deps");
+    fs::create_dir_all(&deps).map_err(|e| Failure::Other(format!("Could not create {}: {}", deps.display(), e)))?;
+    let objects = session.emit_objects(&source, &options, &deps)?;
+    let executable = cli.output.clone().unwrap_or_else(|| manifest.executable());
+    link_executable(&objects, &executable)?;
+
+    eprintln!("Built {} ({}) at {}.", manifest.name, manifest.profile.name, executable.display());
+    report_summaries(&session);
+    Ok(executable)
+}
+
+/// Links object files against the runtime into a native executable. The system
+/// link step is the only part that stays external.
+fn link_executable(objects: &[PathBuf], output: &Path) -> Result<(), Failure> {
+    let Some(runtime) = locate_runtime_library() else {
+        return Err(Failure::Other("Could not find libonu_runtime.a. Build the workspace or set ONU_RUNTIME_LIB to its path.".to_string()));
+    };
+    let linker = env::var("CC").unwrap_or_else(|_| "cc".to_string());
+    let status = std::process::Command::new(&linker)
+        .args(objects)
+        .arg(&runtime)
+        .args(["-lpthread", "-ldl", "-lm"])
+        .arg("-o")
+        .arg(output)
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(Failure::Other(format!("Failed to link native binary with '{}'.", linker))),
+    }
+}
+
+/// Lists concern-alignment warnings and the behaviors that opted out of the
+/// termination proof, on stderr so that a running program's own output stays clean.
+fn report_summaries(session: &CompilerSession) {
+    for warning in &session.concern_warnings {
+        eprintln!("Concern warning at {}", warning);
+    }
+    let unchecked = &session.termination.unchecked;
+    if !unchecked.is_empty() {
+        eprintln!("Termination summary: {} behavior(s) declare no guaranteed termination: {}.", unchecked.len(), unchecked.join(", "));
+    }
+}
+
+/// Finds the runtime staticlib: `ONU_RUNTIME_LIB` first, then next to this executable
+/// (where cargo places workspace artifacts), then among its hashed `deps` builds.
+fn locate_runtime_library() -> Option<PathBuf> {
+    if let Ok(path) = env::var("ONU_RUNTIME_LIB") {
+        return Some(PathBuf::from(path));
+    }
+    let exe_dir = env::current_exe().ok()?.parent()?.to_path_buf();
+    let beside = exe_dir.join("libonu_runtime.a");
+    if beside.exists() {
+        return Some(beside);
+    }
+    fs::read_dir(exe_dir.join("deps")).ok()?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| path.file_name().and_then(|n| n.to_str()).is_some_and(|n| n.starts_with("libonu_runtime-") && n.ends_with(".a")))
+        .max_by_key(|path| fs::metadata(path).and_then(|m| m.modified()).ok())
+}
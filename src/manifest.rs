@@ -0,0 +1,375 @@
+//! Ọ̀nụ Manifest: The Package Layer
+//!
+//! This module reads `Onu.toml`, which makes a directory of modules into a
+//! package. The manifest names the package, its entry module and the source
+//! roots its modules are sought on, and chooses the optimization profile and
+//! the output directory a build writes into:
+//!
+//! ```toml
+//! [package]
+//! name = "tape-machines"
+//! entry = "TapeMachine"
+//! sources = ["src"]
+//!
+//! [build]
+//! profile = "release"
+//! output = "target"
+//!
+//! [profile.release]
+//! fast-math = true
+//!
+//! [concern]
+//! strictness = "deny"
+//! threshold = 40
+//! synonyms = ["tape machine emulation", "bracket loop"]
+//! ```
+//!
+//! Only the part of TOML a manifest needs is understood: tables, and keys holding
+//! a string, an integer, a boolean or a one-line list of strings. Unknown tables
+//! and keys are refused, so that a misspelt setting is never silently ignored.
+
+use crate::concern::{ConcernPolicy, Strictness};
+use crate::error::{OnuError, Span};
+use crate::mir::ArithmeticMode;
+use crate::modules::{ModuleLoader, ModuleSource};
+use inkwell::OptimizationLevel;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// The file that marks a directory as the root of a package.
+pub const MANIFEST_FILE: &str = "Onu.toml";
+
+/// Profile is a named set of code generation choices.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Profile {
+    pub name: String,
+    /// 0 to 3, as in `-O0` to `-O3`.
+    pub opt_level: u8,
+    pub arithmetic: ArithmeticMode,
+    pub fast_math: bool,
+    pub polly_loops: bool,
+}
+
+impl Profile {
+    /// `debug` builds without optimization; `release`, and any other profile, at `-O3`.
+    /// Both keep arithmetic checked.
+    pub fn named(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            opt_level: if name == "debug" { 0 } else { 3 },
+            arithmetic: ArithmeticMode::Checked,
+            fast_math: false,
+            polly_loops: false,
+        }
+    }
+
+    pub fn optimization_level(&self) -> OptimizationLevel {
+        crate::codegen::TargetOptions::opt_level_from(self.opt_level)
+    }
+}
+
+/// Manifest is a parsed `Onu.toml`, with every path resolved against its directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Manifest {
+    /// The directory holding `Onu.toml`.
+    pub root: PathBuf,
+    pub name: String,
+    /// The module whose `run` behavior starts the program.
+    pub entry: String,
+    /// The directories modules are sought in, in order.
+    pub sources: Vec<PathBuf>,
+    pub output: PathBuf,
+    pub profile: Profile,
+    /// How the concern pass scores the package's intents.
+    pub concern: ConcernPolicy,
+    /// Every profile the manifest configures, by name.
+    profiles: HashMap<String, Profile>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Text(String),
+    Integer(i64),
+    Boolean(bool),
+    List(Vec<String>),
+}
+
+impl Manifest {
+    /// Finds `Onu.toml` in `dir` or the nearest directory above it.
+    pub fn find(dir: &Path) -> Option<PathBuf> {
+        dir.ancestors().map(|d| d.join(MANIFEST_FILE)).find(|path| path.is_file())
+    }
+
+    /// Reads and parses the manifest at `path`.
+    pub fn load(path: &Path) -> Result<Self, OnuError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Self::error(Span::default(), format!("{} could not be read: {}.", path.display(), e)))?;
+        Self::parse(&text, &crate::modules::directory_of(path))
+    }
+
+    /// Parses the text of a manifest kept in the directory `root`.
+    pub fn parse(text: &str, root: &Path) -> Result<Self, OnuError> {
+        let mut name = None;
+        let mut entry = None;
+        let mut sources = None;
+        let mut output = None;
+        let mut profile_name = None;
+        let mut profiles: HashMap<String, Profile> = HashMap::new();
+        let mut concern = ConcernPolicy::default();
+        let mut table = String::new();
+
+        for (index, raw) in text.lines().enumerate() {
+            let span = Span { line: index + 1, column: 1 };
+            let line = Self::strip_comment(raw).trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(header) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                table = header.trim().to_string();
+                match table.as_str() {
+                    "package" | "build" | "concern" => {}
+                    _ => match table.strip_prefix("profile.") {
+                        Some(profile) if !profile.is_empty() => {
+                            profiles.entry(profile.to_string()).or_insert_with(|| Profile::named(profile));
+                        }
+                        _ => return Err(Self::error(span, format!("The table [{}] is not part of a manifest; expected [package], [build], [concern] or [profile.<name>].", table))),
+                    },
+                }
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                return Err(Self::error(span, format!("'{}' is neither a [table] nor a 'key = value' setting.", line)));
+            };
+            let key = key.trim();
+            let value = Self::parse_value(value.trim()).ok_or_else(|| Self::error(span, format!("The value of '{}' is not a string, an integer, a boolean or a list of strings.", key)))?;
+            let mismatch = |expected: &str| Self::error(span, format!("'{}' in [{}] must be {}.", key, table, expected));
+
+            match (table.as_str(), key, value) {
+                ("package", "name", Value::Text(v)) => name = Some(v),
+                ("package", "entry", Value::Text(v)) => entry = Some(v),
+                ("package", "sources", Value::List(v)) => sources = Some(v),
+                ("build", "profile", Value::Text(v)) => profile_name = Some(v),
+                ("build", "output", Value::Text(v)) => output = Some(v),
+                ("package", "name" | "entry", _) | ("build", "profile" | "output", _) => return Err(mismatch("a string")),
+                ("package", "sources", _) => return Err(mismatch("a list of strings")),
+                ("concern", "strictness", Value::Text(level)) => {
+                    concern.strictness = Strictness::from_flag(&level).ok_or_else(|| mismatch("off, warn or deny"))?;
+                }
+                ("concern", "threshold", Value::Integer(percent)) if (0..=100).contains(&percent) => concern.threshold = percent as f64 / 100.0,
+                // Each string of `synonyms` is one group of interchangeable words.
+                ("concern", "synonyms", Value::List(groups)) => {
+                    for group in groups {
+                        concern.vocabulary.add_synonyms(&group.split_whitespace().collect::<Vec<_>>());
+                    }
+                }
+                ("concern", "stop-words", Value::List(words)) => concern.vocabulary.add_stop_words(&words),
+                ("concern", "strictness", _) => return Err(mismatch("a string")),
+                ("concern", "threshold", _) => return Err(mismatch("a percentage from 0 to 100")),
+                ("concern", "synonyms" | "stop-words", _) => return Err(mismatch("a list of strings")),
+                (profile, key, value) if profile.starts_with("profile.") => {
+                    let profile = profiles.get_mut(&profile["profile.".len()..]).expect("the table header registered the profile");
+                    match (key, value) {
+                        ("opt-level", Value::Integer(level)) if (0..=3).contains(&level) => profile.opt_level = level as u8,
+                        ("arithmetic", Value::Text(mode)) => {
+                            profile.arithmetic = ArithmeticMode::from_flag(&mode).ok_or_else(|| mismatch("wrapping, checked or saturating"))?;
+                        }
+                        ("fast-math", Value::Boolean(on)) => profile.fast_math = on,
+                        ("polly-loops", Value::Boolean(on)) => profile.polly_loops = on,
+                        ("opt-level", _) => return Err(mismatch("an integer from 0 to 3")),
+                        ("arithmetic", _) => return Err(mismatch("a string")),
+                        ("fast-math" | "polly-loops", _) => return Err(mismatch("true or false")),
+                        _ => return Err(Self::error(span, format!("'{}' is not a profile setting; expected opt-level, arithmetic, fast-math or polly-loops.", key))),
+                    }
+                }
+                ("", ..) => return Err(Self::error(span, format!("'{}' must belong to a table such as [package].", key))),
+                (table, key, _) => return Err(Self::error(span, format!("'{}' is not a setting of [{}].", key, table))),
+            }
+        }
+
+        let missing = |key: &str| Self::error(Span::default(), format!("The manifest names no {}; add '{} = \"...\"' under [package].", key, key));
+        let name = name.ok_or_else(|| missing("name"))?;
+        let entry = entry.ok_or_else(|| missing("entry"))?;
+        let profile_name = profile_name.unwrap_or_else(|| "debug".to_string());
+        let mut manifest = Self {
+            root: root.to_path_buf(),
+            name,
+            entry,
+            sources: sources.unwrap_or_else(|| vec![".".to_string()]).iter().map(|dir| root.join(dir)).collect(),
+            output: root.join(output.unwrap_or_else(|| "target".to_string())),
+            profile: Profile::named(&profile_name),
+            profiles,
+            concern,
+        };
+        manifest.select_profile(&profile_name)?;
+        Ok(manifest)
+    }
+
+    /// Builds with the named profile: one the manifest configures, or `debug` or `release`.
+    pub fn select_profile(&mut self, name: &str) -> Result<(), OnuError> {
+        self.profile = match self.profiles.get(name) {
+            Some(profile) => profile.clone(),
+            None if name == "debug" || name == "release" => Profile::named(name),
+            None => return Err(Self::error(Span::default(), format!("The profile '{}' is neither debug, release, nor configured with [profile.{}].", name, name))),
+        };
+        Ok(())
+    }
+
+    /// The directory a build with the current profile writes into: `target/release`.
+    pub fn artifact_dir(&self) -> PathBuf {
+        self.output.join(&self.profile.name)
+    }
+
+    /// The executable a build produces.
+    pub fn executable(&self) -> PathBuf {
+        self.artifact_dir().join(&self.name)
+    }
+
+    /// The file of the entry module, which must declare the module it is found for.
+    pub fn entry_file(&self) -> Result<PathBuf, OnuError> {
+        let Some(path) = ModuleLoader::new(&self.sources).resolve(&self.entry) else {
+            let [exact, snake] = crate::modules::file_names(&self.entry);
+            return Err(Self::error(Span::default(), format!("The entry module '{}' is in neither {} nor {} on the source roots.", self.entry, exact, snake)));
+        };
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| Self::error(Span::default(), format!("{} could not be read: {}.", path.display(), e)))?;
+        let module = ModuleSource::new(crate::lexer::Lexer::new(&source).tokenize(), Some(path.clone()));
+        if module.name.as_deref() != Some(self.entry.as_str()) {
+            return Err(Self::error(Span::default(), format!(
+                "{} was found for the entry module '{}', but declares {}.",
+                path.display(),
+                self.entry,
+                module.name.map_or("no module".to_string(), |other| format!("the module '{}'", other))
+            )));
+        }
+        Ok(path)
+    }
+
+    /// Drops a `#` comment, unless it is inside a string.
+    fn strip_comment(line: &str) -> &str {
+        let mut quoted = false;
+        for (i, c) in line.char_indices() {
+            match c {
+                '"' => quoted = !quoted,
+                '#' if !quoted => return &line[..i],
+                _ => {}
+            }
+        }
+        line
+    }
+
+    fn parse_value(text: &str) -> Option<Value> {
+        let string = |s: &str| s.strip_prefix('"').and_then(|s| s.strip_suffix('"')).filter(|s| !s.contains('"')).map(str::to_string);
+        if let Some(items) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            return items
+                .split(',')
+                .map(str::trim)
+                .filter(|item| !item.is_empty())
+                .map(string)
+                .collect::<Option<Vec<_>>>()
+                .map(Value::List);
+        }
+        match text {
+            "true" => Some(Value::Boolean(true)),
+            "false" => Some(Value::Boolean(false)),
+            _ => string(text).map(Value::Text).or_else(|| text.parse().ok().map(Value::Integer)),
+        }
+    }
+
+    fn error(span: Span, message: String) -> OnuError {
+        OnuError::ManifestError { message, span }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn manifest(text: &str) -> Result<Manifest, OnuError> {
+        Manifest::parse(text, Path::new("/projects/tapes"))
+    }
+
+    fn error(text: &str) -> String {
+        match manifest(text) {
+            Err(OnuError::ManifestError { message, .. }) => message,
+            other => panic!("Expected a manifest error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_manifest_settings_resolve_against_its_directory() {
+        let manifest = manifest(r#"
+# The tape machines.
+[package]
+name = "tape-machines"
+entry = "TapeMachine"
+sources = ["src", "vendor"]  # searched in order
+
+[build]
+profile = "release"
+output = "out"
+"#).unwrap();
+        assert_eq!(manifest.name, "tape-machines");
+        assert_eq!(manifest.entry, "TapeMachine");
+        assert_eq!(manifest.sources, vec![PathBuf::from("/projects/tapes/src"), PathBuf::from("/projects/tapes/vendor")]);
+        assert_eq!(manifest.profile, Profile::named("release"));
+        assert_eq!(manifest.executable(), PathBuf::from("/projects/tapes/out/release/tape-machines"));
+    }
+
+    #[test]
+    fn test_defaults_build_debug_into_target() {
+        let manifest = manifest("[package]\nname = \"hello\"\nentry = \"Hello\"\n").unwrap();
+        assert_eq!(manifest.sources, vec![PathBuf::from("/projects/tapes/.")]);
+        assert_eq!(manifest.profile.optimization_level(), OptimizationLevel::None);
+        assert_eq!(manifest.artifact_dir(), PathBuf::from("/projects/tapes/target/debug"));
+    }
+
+    #[test]
+    fn test_profiles_may_be_configured_and_selected() {
+        let mut manifest = manifest(r#"
+[package]
+name = "hello"
+entry = "Hello"
+
+[profile.bench]
+opt-level = 2
+arithmetic = "wrapping"
+fast-math = true
+"#).unwrap();
+        manifest.select_profile("bench").unwrap();
+        assert_eq!(manifest.profile.optimization_level(), OptimizationLevel::Default);
+        assert_eq!(manifest.profile.arithmetic, ArithmeticMode::Wrapping);
+        assert!(manifest.profile.fast_math && !manifest.profile.polly_loops);
+        assert!(manifest.select_profile("nightly").is_err());
+    }
+
+    #[test]
+    fn test_concern_table_configures_the_policy() {
+        let manifest = manifest(r#"
+[package]
+name = "tape-machines"
+entry = "TapeMachine"
+
+[concern]
+strictness = "deny"
+threshold = 40
+synonyms = ["tape machine emulation", "bracket loop"]
+stop-words = ["find"]
+"#).unwrap();
+        assert_eq!(manifest.concern.strictness, Strictness::Deny);
+        assert_eq!(manifest.concern.threshold, 0.4);
+        let checker = crate::concern::ConcernChecker::new(&manifest.concern);
+        assert_eq!(checker.score("find the closing bracket", "loop emulation"), Some(0.5));
+    }
+
+    #[test]
+    fn test_malformed_manifests_are_refused() {
+        assert!(error("[package]\nname = \"hello\"\n").contains("The manifest names no entry"));
+        assert!(error("[package]\nname = \"hello\"\nentry = \"Hello\"\nversion = \"1\"\n").contains("'version' is not a setting of [package]."));
+        assert!(error("[package]\nname = hello\n").contains("The value of 'name' is not a string"));
+        assert!(error("[package]\nsources = \"src\"\n").contains("'sources' in [package] must be a list of strings."));
+        assert!(error("[dependencies]\n").contains("The table [dependencies] is not part of a manifest"));
+        assert!(error("[profile.release]\nopt-level = 4\n").contains("must be an integer from 0 to 3"));
+        assert!(error("[concern]\nstrictness = \"loud\"\n").contains("must be off, warn or deny"));
+        assert!(error("[concern]\nthreshold = 140\n").contains("must be a percentage from 0 to 100"));
+    }
+}
@@ -1,3 +1,4 @@
+use crate::error::Span;
 use crate::types::OnuType;
 use std::collections::HashMap;
 
@@ -12,6 +13,28 @@ pub struct MirFunction {
     pub args: Vec<MirArgument>,
     pub return_type: OnuType,
     pub blocks: Vec<BasicBlock>,
+    /// The type of every SSA variable, arguments included, as settled by the type checker.
+    pub ssa_types: HashMap<usize, OnuType>,
+    /// Where the behavior is declared, for runtime diagnostics.
+    pub span: Span,
+    /// Kept internal by its module, so no other module may call it.
+    pub internal: bool,
+}
+
+impl MirFunction {
+    /// The type of an operand: a constant's own type, or its variable's annotation.
+    pub fn operand_type(&self, op: &MirOperand) -> OnuType {
+        match op {
+            MirOperand::Constant(lit) => lit.onu_type(),
+            MirOperand::Variable(id) => self.ssa_types.get(id).cloned().unwrap_or(OnuType::Nothing),
+        }
+    }
+
+    /// Where an instruction of this behavior is written: its own span, or the
+    /// behavior's when the compiler made the instruction up.
+    pub fn locate(&self, span: Span) -> Span {
+        if span.line == 0 { self.span } else { span }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -31,10 +54,22 @@ pub struct BasicBlock {
 #[derive(Debug, Clone, PartialEq)]
 pub enum MirInstruction {
     Assign { dest: usize, src: MirOperand },
-    BinaryOperation { dest: usize, op: MirBinOp, lhs: MirOperand, rhs: MirOperand },
-    Call { dest: usize, name: String, args: Vec<MirOperand> },
+    /// `span` is where the verb is written, so a trap can name its line.
+    BinaryOperation { dest: usize, op: MirBinOp, lhs: MirOperand, rhs: MirOperand, span: Span },
+    /// `span` is where the verb is written, so a builtin's trap can name its line.
+    Call { dest: usize, name: String, args: Vec<MirOperand>, span: Span },
+    /// Builds the tuple or structure `dest` from its elements, in order.
     Tuple { dest: usize, elements: Vec<MirOperand> },
+    /// Builds the variety value `dest` of the variant numbered `tag`, placing each
+    /// value of its payload in its slot. The slots of other variants hold nothing.
+    Variant { dest: usize, tag: usize, payload: Vec<(usize, MirOperand)> },
+    /// Allocates an array holding `elements`, of the element type of `dest`.
+    Array { dest: usize, elements: Vec<MirOperand> },
+    /// Allocates a `rows` by `cols` matrix holding `entries`, row by row.
+    Matrix { dest: usize, rows: usize, cols: usize, entries: Vec<MirOperand> },
     Index { dest: usize, subject: MirOperand, index: usize },
+    /// Converts `src` to the numeric type of `dest`.
+    Convert { dest: usize, src: MirOperand },
     Emit(MirOperand),
 }
 
@@ -43,6 +78,32 @@ pub enum MirBinOp {
     Add, Sub, Mul, Div, Eq, Gt, Lt,
 }
 
+/// How integer `added-to`, `decreased-by`, `scales-by` and `partitions-by` behave
+/// when the result leaves the range of the operands' width. A zero divisor is
+/// refused in every mode.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArithmeticMode {
+    /// The result wraps around modulo the width.
+    Wrapping,
+    /// The result is refused at runtime with an overflow memo.
+    #[default]
+    Checked,
+    /// The result clamps to the smallest or largest value of the width.
+    Saturating,
+}
+
+impl ArithmeticMode {
+    /// Parses the value of an `--arithmetic=` flag.
+    pub fn from_flag(flag: &str) -> Option<Self> {
+        match flag {
+            "wrapping" => Some(ArithmeticMode::Wrapping),
+            "checked" => Some(ArithmeticMode::Checked),
+            "saturating" => Some(ArithmeticMode::Saturating),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MirOperand {
     Constant(MirLiteral),
@@ -53,16 +114,33 @@ pub enum MirOperand {
 pub enum MirLiteral {
     I64(i64),
     F64(f64),
+    Integer(i128, OnuType),
+    Float(f64, OnuType),
     Boolean(bool),
     Text(String),
     Nothing,
 }
 
+impl MirLiteral {
+    pub fn onu_type(&self) -> OnuType {
+        match self {
+            MirLiteral::I64(_) => OnuType::I64,
+            MirLiteral::F64(_) => OnuType::F64,
+            MirLiteral::Integer(_, typ) | MirLiteral::Float(_, typ) => typ.clone(),
+            MirLiteral::Boolean(_) => OnuType::Boolean,
+            MirLiteral::Text(_) => OnuType::Strings,
+            MirLiteral::Nothing => OnuType::Nothing,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum MirTerminator {
     Return(MirOperand),
     Branch(usize), // block id
     CondBranch { condition: MirOperand, then_block: usize, else_block: usize },
+    /// Jumps to the block of the arm whose tag matches `discriminant`, else to `otherwise`.
+    Switch { discriminant: MirOperand, arms: Vec<(usize, usize)>, otherwise: usize },
     Unreachable,
 }
 
@@ -70,6 +148,15 @@ pub struct MirBuilder {
     next_ssa_var: usize,
     next_block_id: usize,
     var_map: HashMap<String, usize>, // variable name -> ssa var
+    ssa_types: HashMap<usize, OnuType>,
+    structures: HashMap<String, Vec<String>>, // structure name -> field names, in order
+    varieties: HashMap<String, Vec<crate::registry::VarietyVariant>>, // variety name -> variants, by tag
+}
+
+impl Default for MirBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MirBuilder {
@@ -78,21 +165,208 @@ impl MirBuilder {
             next_ssa_var: 0,
             next_block_id: 0,
             var_map: HashMap::new(),
+            ssa_types: HashMap::new(),
+            structures: HashMap::new(),
+            varieties: HashMap::new(),
         }
     }
 
-    fn new_ssa_var(&mut self) -> usize {
+    fn new_ssa_var(&mut self, typ: OnuType) -> usize {
         let var = self.next_ssa_var;
         self.next_ssa_var += 1;
+        self.ssa_types.insert(var, typ);
         var
     }
 
+    fn operand_type(&self, op: &MirOperand) -> OnuType {
+        match op {
+            MirOperand::Constant(lit) => lit.onu_type(),
+            MirOperand::Variable(id) => self.ssa_types.get(id).cloned().unwrap_or(OnuType::Nothing),
+        }
+    }
+
     fn new_block_id(&mut self) -> usize {
         let id = self.next_block_id;
         self.next_block_id += 1;
         id
     }
 
+    fn is_logic(name: &str, args: &[crate::hir::HirExpression]) -> bool {
+        matches!((name, args.len()), ("unites-with" | "joins-with", 2) | ("opposes", 1))
+    }
+
+    /// Lowers a traversal to a loop over the array's positions. The behavior is
+    /// applied through an ordinary call expression, so builtin verbs work too.
+    fn build_traversal(&mut self, name: &str, args: &[crate::hir::HirExpression], result_type: OnuType, current_block: &mut BasicBlock, blocks: &mut Vec<BasicBlock>) -> MirOperand {
+        use crate::hir::HirExpression;
+        let subject = self.build_expression(&args[0], current_block, blocks);
+        let element_type = match self.operand_type(&subject) {
+            OnuType::Array(element) => *element,
+            _ => OnuType::Nothing,
+        };
+        let Some(HirExpression::Variable(behavior)) = args.get(1) else { unreachable!("a traversal names its behavior") };
+
+        // The accumulator starts as the fold's initial value, or an empty array.
+        let accumulator = self.new_ssa_var(result_type.clone());
+        let initial = match args.get(2) {
+            Some(initial) => self.build_expression(initial, current_block, blocks),
+            None => {
+                let empty = self.new_ssa_var(result_type.clone());
+                current_block.instructions.push(MirInstruction::Array { dest: empty, elements: Vec::new() });
+                MirOperand::Variable(empty)
+            }
+        };
+        current_block.instructions.push(MirInstruction::Assign { dest: accumulator, src: initial });
+        let length = self.new_ssa_var(OnuType::I64);
+        current_block.instructions.push(MirInstruction::Call { dest: length, name: "length-of".to_string(), args: vec![subject.clone()], span: Span::default() });
+        let position = self.new_ssa_var(OnuType::I64);
+        current_block.instructions.push(MirInstruction::Assign { dest: position, src: MirOperand::Constant(MirLiteral::I64(0)) });
+
+        let (header_id, body_id, exit_id) = (self.new_block_id(), self.new_block_id(), self.new_block_id());
+        current_block.terminator = MirTerminator::Branch(header_id);
+        blocks.push(std::mem::replace(current_block, BasicBlock { id: header_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+
+        let more = self.new_ssa_var(OnuType::Boolean);
+        current_block.instructions.push(MirInstruction::BinaryOperation {
+            dest: more,
+            op: MirBinOp::Lt,
+            lhs: MirOperand::Variable(position),
+            rhs: MirOperand::Variable(length),
+            span: Span::default(),
+        });
+        current_block.terminator = MirTerminator::CondBranch { condition: MirOperand::Variable(more), then_block: body_id, else_block: exit_id };
+        blocks.push(std::mem::replace(current_block, BasicBlock { id: body_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+
+        let element = self.new_ssa_var(element_type);
+        current_block.instructions.push(MirInstruction::Call {
+            dest: element,
+            name: "element-at".to_string(),
+            args: vec![subject, MirOperand::Variable(position)],
+            span: Span::default(),
+        });
+        // The element and accumulator are bound under names no discourse can spell.
+        self.var_map.insert(" element".to_string(), element);
+        self.var_map.insert(" accumulator".to_string(), accumulator);
+        let mut call_args = vec![HirExpression::Variable(" element".to_string())];
+        if name == "folds-with" {
+            call_args.insert(0, HirExpression::Variable(" accumulator".to_string()));
+        }
+        let delivered = match (name, &result_type) {
+            ("transforms-each-by", OnuType::Array(delivered)) => delivered.as_ref().clone(),
+            ("filters-by", _) => OnuType::Boolean,
+            _ => result_type.clone(),
+        };
+        let call = HirExpression::Call { name: behavior.clone(), args: call_args, typ: Some(delivered), span: Span::default() };
+        let applied = self.build_expression(&call, current_block, blocks);
+
+        match name {
+            "transforms-each-by" => {
+                let grown = self.new_ssa_var(result_type.clone());
+                current_block.instructions.push(MirInstruction::Call {
+                    dest: grown,
+                    name: "appended-with".to_string(),
+                    args: vec![MirOperand::Variable(accumulator), applied],
+                    span: Span::default(),
+                });
+                current_block.instructions.push(MirInstruction::Assign { dest: accumulator, src: MirOperand::Variable(grown) });
+            }
+            "filters-by" => {
+                let (keep_id, step_id) = (self.new_block_id(), self.new_block_id());
+                current_block.terminator = MirTerminator::CondBranch { condition: applied, then_block: keep_id, else_block: step_id };
+                blocks.push(std::mem::replace(current_block, BasicBlock { id: keep_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+                let grown = self.new_ssa_var(result_type.clone());
+                current_block.instructions.push(MirInstruction::Call {
+                    dest: grown,
+                    name: "appended-with".to_string(),
+                    args: vec![MirOperand::Variable(accumulator), MirOperand::Variable(element)],
+                    span: Span::default(),
+                });
+                current_block.instructions.push(MirInstruction::Assign { dest: accumulator, src: MirOperand::Variable(grown) });
+                current_block.terminator = MirTerminator::Branch(step_id);
+                blocks.push(std::mem::replace(current_block, BasicBlock { id: step_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+            }
+            _ => current_block.instructions.push(MirInstruction::Assign { dest: accumulator, src: applied }),
+        }
+        self.var_map.remove(" element");
+        self.var_map.remove(" accumulator");
+
+        let next = self.new_ssa_var(OnuType::I64);
+        current_block.instructions.push(MirInstruction::BinaryOperation {
+            dest: next,
+            op: MirBinOp::Add,
+            lhs: MirOperand::Variable(position),
+            rhs: MirOperand::Constant(MirLiteral::I64(1)),
+            span: Span::default(),
+        });
+        current_block.instructions.push(MirInstruction::Assign { dest: position, src: MirOperand::Variable(next) });
+        current_block.terminator = MirTerminator::Branch(header_id);
+        blocks.push(std::mem::replace(current_block, BasicBlock { id: exit_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+        MirOperand::Variable(accumulator)
+    }
+
+    /// Lowers a case analysis to a switch on the subject's tag. Each arm reads its
+    /// bindings out of the payload slots and assigns its answer to one merged
+    /// variable, as the branches of a decision do. Without `otherwise`, every tag
+    /// has an arm, so the default of the switch is unreachable.
+    fn build_case(&mut self, subject: &crate::hir::HirExpression, variety: &str, arms: &[crate::hir::HirCaseArm], otherwise: Option<&crate::hir::HirExpression>, current_block: &mut BasicBlock, blocks: &mut Vec<BasicBlock>) -> MirOperand {
+        let subject = self.build_expression(subject, current_block, blocks);
+        let variants = self.varieties.get(variety).cloned().unwrap_or_default();
+        let tag = self.new_ssa_var(OnuType::I64);
+        current_block.instructions.push(MirInstruction::Index { dest: tag, subject: subject.clone(), index: 0 });
+        let dest = self.new_ssa_var(OnuType::Nothing);
+
+        let arm_ids: Vec<usize> = arms.iter().map(|_| self.new_block_id()).collect();
+        let (otherwise_id, merge_id) = (self.new_block_id(), self.new_block_id());
+        let tags = arms.iter().map(|arm| variants.iter().position(|v| v.name == arm.variant).unwrap_or_default());
+        current_block.terminator = MirTerminator::Switch {
+            discriminant: MirOperand::Variable(tag),
+            arms: tags.zip(arm_ids.iter().copied()).collect(),
+            otherwise: otherwise_id,
+        };
+
+        let mut answers = Vec::new(); // (index of the arm's last block, its answer)
+        for (arm, id) in arms.iter().zip(arm_ids) {
+            blocks.push(std::mem::replace(current_block, BasicBlock { id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+            let arm_tag = variants.iter().position(|v| v.name == arm.variant).unwrap_or_default();
+            let first = crate::registry::first_payload_slot(&variants, arm_tag);
+            let shadowed: Vec<(String, Option<usize>)> = arm.bindings.iter().map(|name| (name.clone(), self.var_map.get(name).copied())).collect();
+            for (i, (name, typ)) in arm.bindings.iter().zip(&arm.payload).enumerate() {
+                let value = self.new_ssa_var(typ.clone());
+                current_block.instructions.push(MirInstruction::Index { dest: value, subject: subject.clone(), index: first + i });
+                self.var_map.insert(name.clone(), value);
+            }
+            let answer = self.build_expression(&arm.body, current_block, blocks);
+            for (name, previous) in shadowed {
+                match previous {
+                    Some(previous) => self.var_map.insert(name, previous),
+                    None => self.var_map.remove(&name),
+                };
+            }
+            current_block.terminator = MirTerminator::Branch(merge_id);
+            answers.push((blocks.len(), answer));
+        }
+
+        blocks.push(std::mem::replace(current_block, BasicBlock { id: otherwise_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+        if let Some(otherwise) = otherwise {
+            let answer = self.build_expression(otherwise, current_block, blocks);
+            current_block.terminator = MirTerminator::Branch(merge_id);
+            answers.push((blocks.len(), answer));
+        }
+        blocks.push(std::mem::replace(current_block, BasicBlock { id: merge_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable }));
+
+        // Answers that disagree can only meet in a statement position, so they merge as nothing.
+        let types: Vec<OnuType> = answers.iter().map(|(_, answer)| self.operand_type(answer)).collect();
+        let agreed = types.first().filter(|first| types.iter().all(|t| t == *first)).cloned();
+        if let Some(typ) = &agreed {
+            self.ssa_types.insert(dest, typ.clone());
+        }
+        for (index, answer) in answers {
+            let src = if agreed.is_some() { answer } else { MirOperand::Constant(MirLiteral::Nothing) };
+            blocks[index].instructions.push(MirInstruction::Assign { dest, src });
+        }
+        MirOperand::Variable(dest)
+    }
+
     pub fn build_program(hir: &[crate::hir::HirDiscourse]) -> MirProgram {
         let mut builder = Self::new();
         let mut functions = Vec::new();
@@ -104,7 +378,19 @@ impl MirBuilder {
         MirProgram { functions }
     }
 
-    pub fn build_program_with_registry(&mut self, hir: &[crate::hir::HirDiscourse], _registry: &crate::registry::Registry) -> MirProgram {
+    pub fn build_program_with_registry(&mut self, hir: &[crate::hir::HirDiscourse], registry: &crate::registry::Registry) -> MirProgram {
+        for discourse in hir {
+            match discourse {
+                crate::hir::HirDiscourse::Structure { name, .. } => {
+                    let fields = registry.get_structure(name).map_or_else(Vec::new, |fields| fields.iter().map(|f| f.name.clone()).collect());
+                    self.structures.insert(name.clone(), fields);
+                }
+                crate::hir::HirDiscourse::Variety { name, .. } => {
+                    self.varieties.insert(name.clone(), registry.get_variety(name).cloned().unwrap_or_default());
+                }
+                _ => {}
+            }
+        }
         let mut functions = Vec::new();
         for discourse in hir {
             if let crate::hir::HirDiscourse::Behavior { header, body } = discourse {
@@ -116,11 +402,12 @@ impl MirBuilder {
 
     fn build_function(&mut self, header: &crate::hir::HirBehaviorHeader, body: &crate::hir::HirExpression) -> MirFunction {
         self.var_map.clear();
+        self.ssa_types.clear();
         self.next_ssa_var = 0;
         self.next_block_id = 0;
         let mut args = Vec::new();
         for arg in &header.args {
-            let ssa_var = self.new_ssa_var();
+            let ssa_var = self.new_ssa_var(arg.typ.clone());
             self.var_map.insert(arg.name.clone(), ssa_var);
             args.push(MirArgument {
                 name: arg.name.clone(),
@@ -145,6 +432,9 @@ impl MirBuilder {
             args,
             return_type: header.return_type.clone(),
             blocks,
+            ssa_types: std::mem::take(&mut self.ssa_types),
+            span: header.span,
+            internal: header.visibility == crate::parser::Visibility::Internal,
         }
     }
 
@@ -153,6 +443,8 @@ impl MirBuilder {
             crate::hir::HirExpression::Literal(lit) => MirOperand::Constant(match lit {
                 crate::hir::HirLiteral::I64(n) => MirLiteral::I64(*n),
                 crate::hir::HirLiteral::F64(n) => MirLiteral::F64(*n),
+                crate::hir::HirLiteral::Integer(n, typ) => MirLiteral::Integer(*n, typ.clone()),
+                crate::hir::HirLiteral::Float(n, typ) => MirLiteral::Float(*n, typ.clone()),
                 crate::hir::HirLiteral::Boolean(b) => MirLiteral::Boolean(*b),
                 crate::hir::HirLiteral::Text(s) => MirLiteral::Text(s.clone()),
                 crate::hir::HirLiteral::Nothing => MirLiteral::Nothing,
@@ -163,13 +455,58 @@ impl MirBuilder {
                 });
                 MirOperand::Variable(ssa_var)
             }
-            crate::hir::HirExpression::Call { name, args } => {
+            crate::hir::HirExpression::Call { name, args, typ, .. } if name == "array" => {
+                let mut elements = Vec::new();
+                for arg in args {
+                    elements.push(self.build_expression(arg, current_block, blocks));
+                }
+                let dest = self.new_ssa_var(typ.clone().unwrap_or(OnuType::Array(Box::new(OnuType::Nothing))));
+                current_block.instructions.push(MirInstruction::Array { dest, elements });
+                MirOperand::Variable(dest)
+            }
+            crate::hir::HirExpression::Call { name, args, .. } if crate::hir::matrix_dimensions(name).is_some() => {
+                let (rows, cols) = crate::hir::matrix_dimensions(name).unwrap();
+                let mut entries = Vec::new();
+                for arg in args {
+                    entries.push(self.build_expression(arg, current_block, blocks));
+                }
+                let dest = self.new_ssa_var(OnuType::Matrix);
+                current_block.instructions.push(MirInstruction::Matrix { dest, rows, cols, entries });
+                MirOperand::Variable(dest)
+            }
+            crate::hir::HirExpression::Call { name, args, typ, .. } if expr.traversed_behavior().is_some() => {
+                self.build_traversal(name, args, typ.clone().unwrap_or(OnuType::Nothing), current_block, blocks)
+            }
+            crate::hir::HirExpression::Call { name, args, .. } if Self::is_logic(name, args) => {
+                // The logical verbs short-circuit: each is a decision between blocks.
+                let truth = |b: bool| Box::new(crate::hir::HirExpression::Literal(crate::hir::HirLiteral::Boolean(b)));
+                let (then_branch, else_branch) = match (name.as_str(), args.as_slice()) {
+                    ("unites-with", [_, rhs]) => (Box::new(rhs.clone()), truth(false)),
+                    ("joins-with", [_, rhs]) => (truth(true), Box::new(rhs.clone())),
+                    _ => (truth(false), truth(true)),
+                };
+                let decision = crate::hir::HirExpression::If { condition: Box::new(args[0].clone()), then_branch, else_branch };
+                self.build_expression(&decision, current_block, blocks)
+            }
+            crate::hir::HirExpression::Call { name, args, typ, span } => {
                 let mut mir_args = Vec::new();
                 for arg in args {
                     mir_args.push(self.build_expression(arg, current_block, blocks));
                 }
+
+                // A field accessor reads the field out of the structure in place.
+                if let [subject] = mir_args.as_slice()
+                    && let OnuType::Record(structure) = self.operand_type(subject)
+                    && let Some(index) = self.structures.get(&structure).and_then(|fields| {
+                        fields.iter().position(|field| crate::registry::accessor_name(field) == *name)
+                    }) {
+                    let dest = self.new_ssa_var(typ.clone().unwrap_or(OnuType::Nothing));
+                    current_block.instructions.push(MirInstruction::Index { dest, subject: subject.clone(), index });
+                    return MirOperand::Variable(dest);
+                }
                 
-                let bin_op = if mir_args.len() == 2 {
+                // Matrix arithmetic stays a call; only numbers become instructions.
+                let bin_op = if mir_args.len() == 2 && self.operand_type(&mir_args[0]) != OnuType::Matrix {
                     match name.as_str() {
                         "added-to" => Some(MirBinOp::Add),
                         "decreased-by" => Some(MirBinOp::Sub),
@@ -184,29 +521,39 @@ impl MirBuilder {
                     None
                 };
 
-                let dest = self.new_ssa_var();
-                if let Some(op) = bin_op {
+                let dest_type = match (&bin_op, typ) {
+                    (Some(MirBinOp::Eq | MirBinOp::Gt | MirBinOp::Lt), _) => OnuType::Boolean,
+                    (_, Some(t)) => t.clone(),
+                    (Some(_), None) => self.operand_type(&mir_args[0]),
+                    (None, None) => OnuType::Nothing,
+                };
+                let dest = self.new_ssa_var(dest_type);
+                if let (None, Some(_), [src]) = (&bin_op, OnuType::from_conversion(name), mir_args.as_slice()) {
+                    current_block.instructions.push(MirInstruction::Convert { dest, src: src.clone() });
+                } else if let Some(op) = bin_op {
                     current_block.instructions.push(MirInstruction::BinaryOperation {
                         dest,
                         op,
                         lhs: mir_args[0].clone(),
                         rhs: mir_args[1].clone(),
+                        span: *span,
                     });
                 } else {
-                    current_block.instructions.push(MirInstruction::Call { dest, name: name.clone(), args: mir_args });
+                    current_block.instructions.push(MirInstruction::Call { dest, name: name.clone(), args: mir_args, span: *span });
                 }
                 MirOperand::Variable(dest)
             }
-            crate::hir::HirExpression::Derivation { name, value, body, .. } => {
+            crate::hir::HirExpression::Derivation { name, typ, value, body, .. } => {
                 let val_op = self.build_expression(value, current_block, blocks);
-                let dest = self.new_ssa_var();
+                let dest_type = typ.clone().unwrap_or_else(|| self.operand_type(&val_op));
+                let dest = self.new_ssa_var(dest_type);
                 current_block.instructions.push(MirInstruction::Assign { dest, src: val_op });
                 self.var_map.insert(name.clone(), dest);
                 self.build_expression(body, current_block, blocks)
             }
             crate::hir::HirExpression::If { condition, then_branch, else_branch } => {
                 let cond_op = self.build_expression(condition, current_block, blocks);
-                let dest = self.new_ssa_var();
+                let dest = self.new_ssa_var(OnuType::Nothing);
                 
                 let then_id = self.new_block_id();
                 let else_id = self.new_block_id();
@@ -215,20 +562,31 @@ impl MirBuilder {
                 current_block.terminator = MirTerminator::CondBranch { condition: cond_op, then_block: then_id, else_block: else_id };
                 
                 // Finalize the current block by pushing it
-                let mut old_current = std::mem::replace(current_block, BasicBlock { id: then_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable });
+                let old_current = std::mem::replace(current_block, BasicBlock { id: then_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable });
                 blocks.push(old_current);
                 
                 // Then Branch
                 let then_res = self.build_expression(then_branch, current_block, blocks);
                 current_block.terminator = MirTerminator::Branch(merge_id);
-                let mut then_finalized = std::mem::replace(current_block, BasicBlock { id: else_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable });
-                then_finalized.instructions.push(MirInstruction::Assign { dest, src: then_res });
+                let then_finalized = std::mem::replace(current_block, BasicBlock { id: else_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable });
+                let then_index = blocks.len();
                 blocks.push(then_finalized);
                 
                 // Else Branch
                 let else_res = self.build_expression(else_branch, current_block, blocks);
                 current_block.terminator = MirTerminator::Branch(merge_id);
                 let mut else_finalized = std::mem::replace(current_block, BasicBlock { id: merge_id, instructions: Vec::new(), terminator: MirTerminator::Unreachable });
+
+                // Branches that disagree can only meet in a statement position (the type
+                // checker saw to that), so the merged value is simply nothing.
+                let (then_type, else_type) = (self.operand_type(&then_res), self.operand_type(&else_res));
+                let (then_res, else_res) = if then_type == else_type {
+                    self.ssa_types.insert(dest, then_type);
+                    (then_res, else_res)
+                } else {
+                    (MirOperand::Constant(MirLiteral::Nothing), MirOperand::Constant(MirLiteral::Nothing))
+                };
+                blocks[then_index].instructions.push(MirInstruction::Assign { dest, src: then_res });
                 else_finalized.instructions.push(MirInstruction::Assign { dest, src: else_res });
                 blocks.push(else_finalized);
                 
@@ -250,13 +608,42 @@ impl MirBuilder {
                 for e in elements {
                     mir_elements.push(self.build_expression(e, current_block, blocks));
                 }
-                let dest = self.new_ssa_var();
+                let types = mir_elements.iter().map(|e| self.operand_type(e)).collect();
+                let dest = self.new_ssa_var(OnuType::Tuple(types));
                 current_block.instructions.push(MirInstruction::Tuple { dest, elements: mir_elements });
                 MirOperand::Variable(dest)
             }
+            crate::hir::HirExpression::Construct { structure, fields } => {
+                let mut elements = Vec::new();
+                for (_, e) in fields {
+                    elements.push(self.build_expression(e, current_block, blocks));
+                }
+                let dest = self.new_ssa_var(OnuType::Record(structure.clone()));
+                current_block.instructions.push(MirInstruction::Tuple { dest, elements });
+                MirOperand::Variable(dest)
+            }
+            crate::hir::HirExpression::Variant { variety, variant, fields } => {
+                let variants = self.varieties.get(variety).cloned().unwrap_or_default();
+                let tag = variants.iter().position(|v| v.name == *variant).unwrap_or_default();
+                let first = crate::registry::first_payload_slot(&variants, tag);
+                let mut payload = Vec::new();
+                for (i, (_, e)) in fields.iter().enumerate() {
+                    payload.push((first + i, self.build_expression(e, current_block, blocks)));
+                }
+                let dest = self.new_ssa_var(OnuType::Variety(variety.clone()));
+                current_block.instructions.push(MirInstruction::Variant { dest, tag, payload });
+                MirOperand::Variable(dest)
+            }
+            crate::hir::HirExpression::Case { subject, variety, arms, otherwise } => {
+                self.build_case(subject, variety, arms, otherwise.as_deref(), current_block, blocks)
+            }
             crate::hir::HirExpression::Index { subject, index } => {
                 let subj_op = self.build_expression(subject, current_block, blocks);
-                let dest = self.new_ssa_var();
+                let element_type = match self.operand_type(&subj_op) {
+                    OnuType::Tuple(types) => types.get(*index).cloned().unwrap_or(OnuType::Nothing),
+                    _ => OnuType::Nothing,
+                };
+                let dest = self.new_ssa_var(element_type);
                 current_block.instructions.push(MirInstruction::Index { dest, subject: subj_op, index: *index });
                 MirOperand::Variable(dest)
             }
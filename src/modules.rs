@@ -0,0 +1,461 @@
+//! Ọ̀nụ Module Loader: The Discourse Resolution Layer
+//!
+//! This module finds the discourse a program draws on. A module header may name
+//! other modules with `draws-from:`; each is resolved to a `.onu` file on the
+//! session's search path, read, and lexed, and its own `draws-from:` is followed
+//! in turn. The loader returns every module once, each after the modules it
+//! draws from, and refuses a module that is missing, misnamed or that draws on
+//! itself, however indirectly.
+//!
+//! Each module is checked against its own registry, so two modules may use the
+//! same behavior name as long as no module sees both. When the checked modules
+//! are linked into one program, such a behavior of a drawn-upon module is
+//! qualified by its module's name (`Tape.read-tape`), as is its entry behavior.
+
+use crate::error::{OnuError, Span};
+use crate::hir::{HirDiscourse, HirExpression, TRAVERSALS};
+use crate::lexer::{Lexer, Token, TokenWithSpan};
+use crate::parser::{Discourse, Parser, Visibility};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// ModuleSource is one lexed discourse unit and what its header declares.
+#[derive(Debug, Clone)]
+pub struct ModuleSource {
+    /// The name in `the module called`; a program's root may declare none.
+    pub name: Option<String>,
+    pub draws_from: Vec<String>,
+    /// The file the module was read from; the root may come from elsewhere.
+    pub path: Option<PathBuf>,
+    pub tokens: Vec<TokenWithSpan>,
+    pub span: Span, // where the module header begins
+}
+
+impl ModuleSource {
+    /// Reads the module header, if any, out of a discourse unit's tokens. A header
+    /// that fails to parse is left for the parser proper to report.
+    pub fn new(tokens: Vec<TokenWithSpan>, path: Option<PathBuf>) -> Self {
+        let header = tokens
+            .iter()
+            .position(|t| t.token == Token::TheModuleCalled)
+            .and_then(|start| Parser::new(&tokens[start..]).parse_discourse().ok());
+        match header {
+            Some(Discourse::Module { name, draws_from, span, .. }) => Self { name: Some(name), draws_from, path, tokens, span },
+            _ => Self { name: None, draws_from: Vec::new(), path, tokens, span: Span::default() },
+        }
+    }
+
+    /// The module's name, or an empty one for an anonymous root.
+    pub fn key(&self) -> &str {
+        self.name.as_deref().unwrap_or_default()
+    }
+}
+
+/// The files a module's name may be written to: `TapeMachine.onu` or `tape_machine.onu`.
+pub fn file_names(module: &str) -> [String; 2] {
+    let mut snake = String::new();
+    let mut previous: Option<char> = None;
+    for c in module.chars() {
+        if c.is_uppercase() && previous.is_some_and(|p| p.is_lowercase() || p.is_ascii_digit()) {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+        previous = Some(c);
+    }
+    [format!("{}.onu", module), format!("{}.onu", snake)]
+}
+
+pub struct ModuleLoader<'a> {
+    search_path: &'a [PathBuf],
+}
+
+impl<'a> ModuleLoader<'a> {
+    pub fn new(search_path: &'a [PathBuf]) -> Self {
+        Self { search_path }
+    }
+
+    /// The first file on the search path written for the module, if any.
+    pub fn resolve(&self, module: &str) -> Option<PathBuf> {
+        self.search_path
+            .iter()
+            .flat_map(|dir| file_names(module).map(|name| dir.join(name)))
+            .find(|path| path.is_file())
+    }
+
+    /// Loads every module the root draws on, directly or not. Each module appears
+    /// once, after all the modules it draws from; the root comes last.
+    pub fn load(&self, root: ModuleSource) -> Result<Vec<ModuleSource>, OnuError> {
+        let mut trail: Vec<String> = root.name.iter().cloned().collect();
+        let mut loaded = Vec::new();
+        self.visit(root, &mut trail, &mut loaded)?;
+        Ok(loaded)
+    }
+
+    fn visit(&self, module: ModuleSource, trail: &mut Vec<String>, loaded: &mut Vec<ModuleSource>) -> Result<(), OnuError> {
+        for drawn in &module.draws_from {
+            if trail.contains(drawn) {
+                let cycle: Vec<&str> = trail.iter().skip_while(|name| *name != drawn).map(String::as_str).collect();
+                return Err(Self::violation(module.span, format!(
+                    "The module '{}' draws on itself: {} -> {}.",
+                    drawn,
+                    cycle.join(" -> "),
+                    drawn
+                )));
+            }
+            if loaded.iter().any(|m| m.name.as_ref() == Some(drawn)) {
+                continue;
+            }
+            let dependency = self.read(drawn, &module)?;
+            trail.push(drawn.clone());
+            self.visit(dependency, trail, loaded)?;
+            trail.pop();
+        }
+        loaded.push(module);
+        Ok(())
+    }
+
+    fn read(&self, name: &str, importer: &ModuleSource) -> Result<ModuleSource, OnuError> {
+        let Some(path) = self.resolve(name) else {
+            let searched: Vec<String> = self.search_path.iter().map(|dir| dir.display().to_string()).collect();
+            let [exact, snake] = file_names(name);
+            return Err(Self::violation(importer.span, format!(
+                "The module '{}' draws from '{}', but neither {} nor {} is on the search path ({}).",
+                importer.key(),
+                name,
+                exact,
+                snake,
+                if searched.is_empty() { "which is empty".to_string() } else { searched.join(", ") }
+            )));
+        };
+        let source = std::fs::read_to_string(&path)
+            .map_err(|e| Self::violation(importer.span, format!("The module '{}' could not be read from {}: {}.", name, path.display(), e)))?;
+        let module = ModuleSource::new(Lexer::new(&source).tokenize(), Some(path.clone()));
+        if module.name.as_deref() != Some(name) {
+            return Err(Self::violation(importer.span, format!(
+                "{} was found for the module '{}', but declares {}.",
+                path.display(),
+                name,
+                module.name.as_ref().map_or("no module".to_string(), |other| format!("the module '{}'", other))
+            )));
+        }
+        Ok(module)
+    }
+
+    fn violation(span: Span, message: String) -> OnuError {
+        OnuError::ImportViolation { message, span }
+    }
+}
+
+/// The behaviors a program starts from. Each module's entry stays its own: it is
+/// never drawn, and only the root's is the program's.
+pub const ENTRY_BEHAVIORS: [&str; 2] = ["run", "main"];
+
+/// Refuses a module that could not tell which behavior a name means: one it
+/// defines itself and also draws, or one two of the modules it draws from expose.
+/// A behavior another module keeps internal is never drawn, so its name is free.
+pub fn check_drawn_names(module: &ModuleSource, own: &[(String, Visibility)], behaviors: &HashMap<String, Vec<(String, Visibility)>>) -> Result<(), OnuError> {
+    let mut seen: HashMap<&str, &str> = own.iter().map(|(name, _)| (name.as_str(), module.key())).collect();
+    for drawn in &module.draws_from {
+        let exposed = behaviors.get(drawn).into_iter().flatten().filter(|(_, visibility)| *visibility == Visibility::Exposed);
+        for (name, _) in exposed.filter(|(name, _)| !ENTRY_BEHAVIORS.contains(&name.as_str())) {
+            if let Some(other) = seen.insert(name, drawn) {
+                let message = if other == module.key() {
+                    format!("The module '{}' defines the behavior '{}', which it also draws from '{}'.", other, name, drawn)
+                } else {
+                    format!("The module '{}' draws the behavior '{}' from both '{}' and '{}'.", module.key(), name, other, drawn)
+                };
+                return Err(OnuError::ImportViolation { message, span: module.span });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Renames the behaviors named in `renamed`, wherever a module defines or calls
+/// them, including where a traversal names one as its argument.
+pub fn qualify(hir: &mut [HirDiscourse], renamed: &HashMap<String, String>) {
+    for discourse in hir {
+        if let HirDiscourse::Behavior { header, body } = discourse {
+            if let Some(qualified) = renamed.get(&header.name) {
+                header.name = qualified.clone();
+            }
+            qualify_expression(body, renamed);
+        }
+    }
+}
+
+fn qualify_expression(expr: &mut HirExpression, renamed: &HashMap<String, String>) {
+    match expr {
+        HirExpression::Call { name, args, .. } => {
+            if TRAVERSALS.contains(&name.as_str())
+                && let Some(HirExpression::Variable(behavior)) = args.get_mut(1)
+                && let Some(qualified) = renamed.get(behavior.as_str())
+            {
+                *behavior = qualified.clone();
+            }
+            if let Some(qualified) = renamed.get(name.as_str()) {
+                *name = qualified.clone();
+            }
+            args.iter_mut().for_each(|arg| qualify_expression(arg, renamed));
+        }
+        HirExpression::Derivation { value, body, .. } => {
+            qualify_expression(value, renamed);
+            qualify_expression(body, renamed);
+        }
+        HirExpression::If { condition, then_branch, else_branch } => {
+            qualify_expression(condition, renamed);
+            qualify_expression(then_branch, renamed);
+            qualify_expression(else_branch, renamed);
+        }
+        HirExpression::Case { subject, arms, otherwise, .. } => {
+            qualify_expression(subject, renamed);
+            arms.iter_mut().for_each(|arm| qualify_expression(&mut arm.body, renamed));
+            otherwise.iter_mut().for_each(|e| qualify_expression(e, renamed));
+        }
+        HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => exprs.iter_mut().for_each(|e| qualify_expression(e, renamed)),
+        HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+            fields.iter_mut().for_each(|(_, e)| qualify_expression(e, renamed))
+        }
+        HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => qualify_expression(subject, renamed),
+        HirExpression::Emit(e) => qualify_expression(e, renamed),
+        HirExpression::Literal(_) | HirExpression::Variable(_) => {}
+    }
+}
+
+/// The directory a source file lives in, where the modules it draws on are first sought.
+pub fn directory_of(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CompilerSession;
+
+    /// Writes the given module files into a fresh directory for one test.
+    fn library(test: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("onu_modules_{}", test));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        for (name, source) in files {
+            std::fs::write(dir.join(name), source).unwrap();
+        }
+        dir
+    }
+
+    fn build(dir: PathBuf, source: &str) -> Result<CompilerSession, OnuError> {
+        let mut session = CompilerSession::new().unwrap();
+        session.search_path = vec![dir];
+        session.build_mir(source).map(|_| session)
+    }
+
+    fn violation(dir: PathBuf, source: &str) -> String {
+        match build(dir, source) {
+            Err(OnuError::ImportViolation { message, .. }) => message,
+            Err(other) => panic!("Expected an import violation, got {}", other),
+            Ok(_) => panic!("Expected an import violation"),
+        }
+    }
+
+    const COUNTER: &str = "
+the module called Counter
+    with concern: counting
+    draws-from: Steps
+
+the behavior called next
+    with intent: count on
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n utilizes step
+";
+
+    const STEPS: &str = "
+the module called Steps
+    with concern: counting steps
+
+the behavior called step
+    with intent: take one counting step
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 1
+
+the behavior called double-step
+    with intent: take two counting steps
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 2
+";
+
+    #[test]
+    fn test_module_names_map_to_file_names() {
+        assert_eq!(file_names("TapeMachine"), ["TapeMachine.onu".to_string(), "tape_machine.onu".to_string()]);
+        assert_eq!(file_names("Tape"), ["Tape.onu".to_string(), "tape.onu".to_string()]);
+    }
+
+    #[test]
+    fn test_drawn_modules_load_after_their_own_dependencies() {
+        let dir = library("order", &[("Counter.onu", COUNTER), ("steps.onu", STEPS)]);
+        let session = build(dir, "
+the module called Main
+    with concern: counting
+    draws-from: Counter
+
+the behavior called main
+    with intent: count
+    takes: nothing
+    delivers: an integer
+    as:
+        1 utilizes next
+").unwrap();
+        let order: Vec<&str> = session.hir.iter().filter_map(|d| match d {
+            HirDiscourse::Module { name, .. } => Some(name.as_str()),
+            _ => None,
+        }).collect();
+        assert_eq!(order, vec!["Steps", "Counter", "Main"]);
+        // Main sees what it draws from Counter, but not what Counter draws from Steps.
+        assert!(session.modules["Main"].is_registered("next") && !session.modules["Main"].is_registered("step"));
+    }
+
+    #[test]
+    fn test_cycles_are_refused() {
+        let dir = library("cycle", &[
+            ("A.onu", "the module called A\n    with concern: letters\n    draws-from: B\n"),
+            ("B.onu", "the module called B\n    with concern: letters\n    draws-from: A\n"),
+        ]);
+        let message = violation(dir.clone(), "the module called Main\n    with concern: letters\n    draws-from: A\n");
+        assert!(message.contains("The module 'A' draws on itself: A -> B -> A."), "{}", message);
+
+        let message = violation(dir, "the module called Main\n    with concern: letters\n    draws-from: Main\n");
+        assert!(message.contains("The module 'Main' draws on itself: Main -> Main."), "{}", message);
+    }
+
+    #[test]
+    fn test_missing_and_misnamed_modules_are_refused() {
+        let dir = library("missing", &[("Counter.onu", STEPS)]);
+        let message = violation(dir.clone(), "the module called Main\n    with concern: counting\n    draws-from: Clock\n");
+        assert!(message.contains("draws from 'Clock', but neither Clock.onu nor clock.onu is on the search path"), "{}", message);
+
+        let message = violation(dir, "the module called Main\n    with concern: counting\n    draws-from: Counter\n");
+        assert!(message.contains("was found for the module 'Counter', but declares the module 'Steps'"), "{}", message);
+    }
+
+    #[test]
+    fn test_a_name_must_mean_one_behavior() {
+        let dir = library("ambiguous", &[("steps.onu", STEPS)]);
+        let message = violation(dir, "
+the module called Main
+    with concern: counting
+    draws-from: Steps
+
+the behavior called step
+    with intent: take a counting step
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 3
+");
+        assert!(message.contains("The module 'Main' defines the behavior 'step', which it also draws from 'Steps'."), "{}", message);
+    }
+
+    #[test]
+    fn test_modules_keep_their_own_registries() {
+        // Main repeats a body of Steps, which Main does not see, and reuses its name.
+        let dir = library("separate", &[("Counter.onu", COUNTER), ("steps.onu", STEPS)]);
+        let session = build(dir, "
+the module called Main
+    with concern: counting
+    draws-from: Counter
+
+the behavior called step
+    with intent: count steps
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 2
+").unwrap();
+        let names: Vec<&str> = session.mir.as_ref().unwrap().functions.iter().map(|f| f.name.as_str()).collect();
+        assert!(names.contains(&"step") && names.contains(&"Steps.step") && names.contains(&"next"), "{:?}", names);
+    }
+
+    const PACE: &str = "
+the module called Pace
+    with concern: counting steps
+
+the behavior called stride
+    exposes
+    with intent: take a counting stride
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n utilizes step
+
+the behavior called step
+    keeps internal
+    with intent: take one counting step
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 1
+";
+
+    #[test]
+    fn test_behaviors_kept_internal_cannot_be_called_from_other_modules() {
+        let dir = library("internal", &[("pace.onu", PACE)]);
+        let err = build(dir, "
+the module called Main
+    with concern: counting
+    draws-from: Pace
+
+the behavior called main
+    with intent: count
+    takes: nothing
+    delivers: an integer
+    as:
+        1 utilizes step
+").err().expect("Expected a visibility violation");
+        let OnuError::VisibilityViolation { message, .. } = err else { panic!("Expected a visibility violation, got {}", err) };
+        assert!(message.contains("'main' calls 'step', which the module 'Pace' keeps internal"), "{}", message);
+    }
+
+    #[test]
+    fn test_names_kept_internal_stay_free_for_importers() {
+        let dir = library("free", &[("pace.onu", PACE)]);
+        let session = build(dir, "
+the module called Main
+    with concern: counting
+    draws-from: Pace
+
+the behavior called step
+    with intent: take a long counting step
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to 5
+
+the behavior called main
+    with intent: count
+    takes: nothing
+    delivers: an integer
+    as:
+        (1 utilizes stride) utilizes step
+").unwrap();
+        let functions = &session.mir.as_ref().unwrap().functions;
+        let internal: Vec<&str> = functions.iter().filter(|f| f.internal).map(|f| f.name.as_str()).collect();
+        assert_eq!(internal, vec!["Pace.step"]);
+        assert!(functions.iter().any(|f| f.name == "step" && !f.internal));
+    }
+}
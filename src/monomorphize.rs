@@ -1,187 +1,345 @@
-use crate::hir::{HirDiscourse, HirExpression};
+//! Ọ̀nụ Monomorphizer: The Static Dispatch Layer
+//!
+//! This module turns shape-polymorphic behaviors into concrete ones before MIR
+//! building. A behavior is generic when any of its arguments is typed by a shape;
+//! such a behavior is a template, never compiled itself.
+//!
+//! Instantiation:
+//! Starting from every concrete behavior (the entry point among them), each call
+//! to a generic behavior binds the callee's roles to the concrete argument types
+//! the type checker annotated. Every distinct binding yields one specialized clone
+//! whose argument, return and annotation types have the roles substituted. The
+//! clone's own body is then walked the same way, so recursive and transitive
+//! calls through generic code resolve too.
+//!
+//! Naming:
+//! A specialization is named after the behavior and the concrete types of its
+//! role-typed arguments, in order: `get-size` receiving a float becomes
+//! `get-size_float`. The same instantiation is only ever built once.
+
+use crate::hir::{HirBehaviorHeader, HirDiscourse, HirExpression};
 use crate::types::OnuType;
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 
 pub struct Monomorphizer {
-    type_map: HashMap<String, Vec<OnuType>>, // Function name -> Concrete types used
+    generics: HashMap<String, (HirBehaviorHeader, HirExpression)>,
+    instances: HashMap<(String, Vec<OnuType>), String>, // (behavior, role argument types) -> specialized name
+    pending: VecDeque<(String, HashMap<String, OnuType>, String)>, // (behavior, role bindings, specialized name)
 }
 
 impl Monomorphizer {
     pub fn new() -> Self {
         Self {
-            type_map: HashMap::new(),
+            generics: HashMap::new(),
+            instances: HashMap::new(),
+            pending: VecDeque::new(),
         }
     }
 
-    pub fn run(hir: &mut Vec<HirDiscourse>) {
+    /// Replaces every generic behavior in the program with its reachable specializations.
+    pub fn run(hir: &mut Vec<HirDiscourse>) -> Result<(), String> {
         let mut monomorphizer = Self::new();
-        monomorphizer.identify_specializations(hir);
-        monomorphizer.apply_specializations(hir);
-    }
+        hir.retain(|discourse| match discourse {
+            HirDiscourse::Behavior { header, body } if header.args.iter().any(|arg| Self::is_generic(&arg.typ)) => {
+                monomorphizer.generics.insert(header.name.clone(), (header.clone(), body.clone()));
+                false
+            }
+            _ => true,
+        });
+
+        for discourse in hir.iter_mut() {
+            if let HirDiscourse::Behavior { header, body } = discourse {
+                monomorphizer.rewrite_body(header, body)?;
+            }
+        }
 
-    fn identify_specializations(&mut self, hir: &[HirDiscourse]) {
-        for discourse in hir {
-            if let HirDiscourse::Behavior { header: _, body } = discourse {
-                self.visit_expression(body);
+        while let Some((name, bindings, specialized_name)) = monomorphizer.pending.pop_front() {
+            let (header, body) = monomorphizer.generics[&name].clone();
+            let mut specialized_header = header;
+            specialized_header.name = specialized_name;
+            for arg in &mut specialized_header.args {
+                arg.typ = Self::substitute(&arg.typ, &bindings);
             }
+            specialized_header.return_type = Self::substitute(&specialized_header.return_type, &bindings);
+
+            let mut specialized_body = body;
+            Self::substitute_annotations(&mut specialized_body, &bindings);
+            monomorphizer.rewrite_body(&specialized_header, &mut specialized_body)?;
+            hir.push(HirDiscourse::Behavior { header: specialized_header, body: specialized_body });
         }
+        Ok(())
     }
 
-    fn visit_expression(&mut self, expr: &HirExpression) {
+    fn rewrite_body(&mut self, header: &HirBehaviorHeader, body: &mut HirExpression) -> Result<(), String> {
+        let mut scope = header.args.iter().map(|arg| (arg.name.clone(), arg.typ.clone())).collect();
+        self.rewrite_expression(body, &mut scope)
+    }
+
+    /// Points every call to a generic behavior at the specialization for its argument types.
+    fn rewrite_expression(&mut self, expr: &mut HirExpression, scope: &mut HashMap<String, OnuType>) -> Result<(), String> {
         match expr {
-            HirExpression::Call { name, args } => {
-                if name == "receiving" || name == "utilizing" {
-                     if let Some(HirExpression::Variable(vname)) = args.get(0) {
-                          if vname == "get-size" {
-                               if let Some(HirExpression::ActsAs { subject: _, shape: _ }) = args.get(1) {
-                                    self.type_map.entry(vname.clone()).or_insert_with(Vec::new).push(OnuType::F64);
-                               }
-                          }
-                     }
+            HirExpression::Call { name, args, typ, .. } => {
+                for arg in args.iter_mut() {
+                    self.rewrite_expression(arg, scope)?;
                 }
-                
-                if name == "get-size" {
-                     for arg in args {
-                          if let HirExpression::ActsAs { subject: _, shape: _ } = arg {
-                               self.type_map.entry(name.clone()).or_insert_with(Vec::new).push(OnuType::F64);
-                          }
-                     }
+                if self.generics.contains_key(name.as_str()) {
+                    let actual: Vec<OnuType> = args.iter().map(|arg| arg.annotated_type(scope)).collect();
+                    *name = self.instantiate(name, &actual)?;
                 }
-
-                for arg in args {
-                    if let HirExpression::ActsAs { subject: _, shape: _ } = arg {
-                         if name == "get-size" {
-                             self.type_map.entry(name.clone()).or_insert_with(Vec::new).push(OnuType::F64);
-                         }
+                // A traversal applies its named behavior to each element (and a fold's accumulator).
+                if crate::hir::TRAVERSALS.contains(&name.as_str()) {
+                    let element = match args[0].annotated_type(scope) {
+                        OnuType::Array(element) => *element,
+                        other => other,
+                    };
+                    let actual = match (name.as_str(), typ) {
+                        ("folds-with", Some(accumulator)) => vec![accumulator.clone(), element],
+                        _ => vec![element],
+                    };
+                    if let Some(HirExpression::Variable(behavior)) = args.get_mut(1)
+                        && self.generics.contains_key(behavior.as_str()) {
+                        *behavior = self.instantiate(behavior, &actual)?;
                     }
-                    self.visit_expression(arg);
                 }
+                Ok(())
             }
-            HirExpression::Derivation { name: _, typ: _, value, body } => {
-                self.visit_expression(value);
-                self.visit_expression(body);
+            HirExpression::Derivation { name, typ, value, body, .. } => {
+                self.rewrite_expression(value, scope)?;
+                let resolved = typ.clone().unwrap_or_else(|| value.annotated_type(scope));
+                let shadowed = scope.insert(name.clone(), resolved);
+                let result = self.rewrite_expression(body, scope);
+                match shadowed {
+                    Some(previous) => scope.insert(name.clone(), previous),
+                    None => scope.remove(name.as_str()),
+                };
+                result
             }
             HirExpression::If { condition, then_branch, else_branch } => {
-                self.visit_expression(condition);
-                self.visit_expression(then_branch);
-                self.visit_expression(else_branch);
+                self.rewrite_expression(condition, scope)?;
+                self.rewrite_expression(then_branch, scope)?;
+                self.rewrite_expression(else_branch, scope)
             }
-            HirExpression::ActsAs { subject, .. } => {
-                self.visit_expression(subject);
+            HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => self.rewrite_expression(subject, scope),
+            HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => exprs.iter_mut().try_for_each(|e| self.rewrite_expression(e, scope)),
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                fields.iter_mut().try_for_each(|(_, e)| self.rewrite_expression(e, scope))
             }
-            HirExpression::Block(exprs) => {
-                for e in exprs {
-                    self.visit_expression(e);
+            HirExpression::Case { subject, arms, otherwise, .. } => {
+                self.rewrite_expression(subject, scope)?;
+                for arm in arms.iter_mut() {
+                    let mut inner = arm.scope(scope);
+                    self.rewrite_expression(&mut arm.body, &mut inner)?;
                 }
+                otherwise.iter_mut().try_for_each(|e| self.rewrite_expression(e, scope))
             }
-            HirExpression::Emit(e) => {
-                self.visit_expression(e);
-            }
-            _ => {}
+            HirExpression::Emit(e) => self.rewrite_expression(e, scope),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => Ok(()),
         }
     }
 
-    fn apply_specializations(&mut self, hir: &mut Vec<HirDiscourse>) {
-        let mut new_discourse = Vec::new();
-        for discourse in hir.iter() {
-            if let HirDiscourse::Behavior { header, body } = discourse {
-                if let Some(concrete_types) = self.type_map.get(&header.name) {
-                    for typ in concrete_types {
-                        let type_suffix = match typ {
-                            OnuType::F64 => "float",
-                            OnuType::I64 => "integer",
-                            _ => "unknown",
-                        };
-                        let mut specialized_header = header.clone();
-                        specialized_header.name = format!("{}_{}", header.name, type_suffix);
-                        
-                        // Replace generic/shape arguments with concrete types
-                        for arg in &mut specialized_header.args {
-                            if matches!(arg.typ, OnuType::Shape(_)) {
-                                arg.typ = typ.clone();
-                            }
-                        }
-                        
-                        let mut specialized_body = body.clone();
-                        self.rewrite_expression(&mut specialized_body, &header.name, &specialized_header.name);
-
-                        new_discourse.push(HirDiscourse::Behavior {
-                            header: specialized_header,
-                            body: specialized_body,
-                        });
+    /// Returns the specialized name for a call, scheduling the specialization if it is new.
+    fn instantiate(&mut self, name: &str, actual: &[OnuType]) -> Result<String, String> {
+        let (header, _) = &self.generics[name];
+        let mut bindings = HashMap::new();
+        for (arg, actual_type) in header.args.iter().zip(actual) {
+            Self::bind(&arg.typ, actual_type, &mut bindings).map_err(|role| {
+                format!("The role '{}' of '{}' cannot be bound to the argument type {}.", role, name, actual_type)
+            })?;
+        }
+
+        let role_types: Vec<OnuType> = header
+            .args
+            .iter()
+            .filter(|arg| Self::is_generic(&arg.typ))
+            .map(|arg| Self::substitute(&arg.typ, &bindings))
+            .collect();
+        let key = (name.to_string(), role_types);
+        if let Some(specialized) = self.instances.get(&key) {
+            return Ok(specialized.clone());
+        }
+        let suffix: Vec<String> = key.1.iter().map(Self::mangle).collect();
+        let specialized = format!("{}_{}", name, suffix.join("_"));
+        self.instances.insert(key, specialized.clone());
+        self.pending.push_back((name.to_string(), bindings, specialized.clone()));
+        Ok(specialized)
+    }
+
+    /// Binds each role in `expected` to the matching part of `actual`, naming the role on failure.
+    fn bind(expected: &OnuType, actual: &OnuType, bindings: &mut HashMap<String, OnuType>) -> Result<(), String> {
+        match (expected, actual) {
+            (OnuType::Shape(role), _) => {
+                if Self::is_generic(actual) {
+                    return Err(role.clone());
+                }
+                match bindings.get(role) {
+                    Some(bound) if bound != actual => Err(role.clone()),
+                    _ => {
+                        bindings.insert(role.clone(), actual.clone());
+                        Ok(())
                     }
                 }
             }
-        }
-        
-        // Also rewrite the call sites in original functions (like main)
-        for discourse in hir.iter_mut() {
-            if let HirDiscourse::Behavior { header: _, body } = discourse {
-                 for (original_name, types) in &self.type_map {
-                      for typ in types {
-                           let type_suffix = match typ {
-                               OnuType::F64 => "float",
-                               OnuType::I64 => "integer",
-                               _ => "unknown",
-                           };
-                           let specialized_name = format!("{}_{}", original_name, type_suffix);
-                           self.rewrite_call_sites(body, original_name, &specialized_name);
-                      }
-                 }
+            (OnuType::Tuple(expected), OnuType::Tuple(actual)) if expected.len() == actual.len() => {
+                expected.iter().zip(actual).try_for_each(|(e, a)| Self::bind(e, a, bindings))
             }
+            (OnuType::Array(expected), OnuType::Array(actual)) => Self::bind(expected, actual, bindings),
+            _ => Ok(()),
         }
-
-        hir.extend(new_discourse);
     }
 
-    fn rewrite_expression(&self, _expr: &mut HirExpression, _old_name: &str, _new_name: &str) {
-        // Recursively rewrite body if needed (e.g. recursive calls)
-        // For now, we mainly care about rewriting the call sites in 'main' or callers
+    fn substitute(typ: &OnuType, bindings: &HashMap<String, OnuType>) -> OnuType {
+        match typ {
+            OnuType::Shape(role) => bindings.get(role).cloned().unwrap_or_else(|| typ.clone()),
+            OnuType::Tuple(types) => OnuType::Tuple(types.iter().map(|t| Self::substitute(t, bindings)).collect()),
+            OnuType::Array(inner) => OnuType::Array(Box::new(Self::substitute(inner, bindings))),
+            _ => typ.clone(),
+        }
     }
 
-    fn rewrite_call_sites(&self, expr: &mut HirExpression, old_name: &str, new_name: &str) {
+    /// Substitutes the roles in the type checker's annotations of a cloned body.
+    fn substitute_annotations(expr: &mut HirExpression, bindings: &HashMap<String, OnuType>) {
         match expr {
-            HirExpression::Call { name, args } => {
-                if name == "receiving" || name == "utilizes" {
-                     if let Some(HirExpression::Variable(vn)) = args.get_mut(0) {
-                          if vn == old_name {
-                               // Found: get-size utilizing (x acts-as Measurable)
-                               // Rewrite to: get-size_float utilizing x
-                               *vn = new_name.to_string();
-                               if let HirExpression::ActsAs { subject, .. } = args.get(1).unwrap() {
-                                    args[1] = (**subject).clone();
-                               }
-                          }
-                     }
-                }
-                if name == old_name {
-                    *name = new_name.to_string();
-                }
-                for arg in args {
-                    self.rewrite_call_sites(arg, old_name, new_name);
+            HirExpression::Call { args, typ, .. } => {
+                if let Some(t) = typ {
+                    *t = Self::substitute(t, bindings);
                 }
+                args.iter_mut().for_each(|arg| Self::substitute_annotations(arg, bindings));
             }
-            HirExpression::Derivation { value, body, .. } => {
-                self.rewrite_call_sites(value, old_name, new_name);
-                self.rewrite_call_sites(body, old_name, new_name);
+            HirExpression::Derivation { typ, value, body, .. } => {
+                if let Some(t) = typ {
+                    *t = Self::substitute(t, bindings);
+                }
+                Self::substitute_annotations(value, bindings);
+                Self::substitute_annotations(body, bindings);
             }
             HirExpression::If { condition, then_branch, else_branch } => {
-                self.rewrite_call_sites(condition, old_name, new_name);
-                self.rewrite_call_sites(then_branch, old_name, new_name);
-                self.rewrite_call_sites(else_branch, old_name, new_name);
+                Self::substitute_annotations(condition, bindings);
+                Self::substitute_annotations(then_branch, bindings);
+                Self::substitute_annotations(else_branch, bindings);
             }
-            HirExpression::ActsAs { subject, .. } => {
-                self.rewrite_call_sites(subject, old_name, new_name);
+            HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => Self::substitute_annotations(subject, bindings),
+            HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => exprs.iter_mut().for_each(|e| Self::substitute_annotations(e, bindings)),
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                fields.iter_mut().for_each(|(_, e)| Self::substitute_annotations(e, bindings))
             }
-            HirExpression::Block(exprs) => {
-                for e in exprs {
-                    self.rewrite_call_sites(e, old_name, new_name);
+            HirExpression::Case { subject, arms, otherwise, .. } => {
+                Self::substitute_annotations(subject, bindings);
+                for arm in arms.iter_mut() {
+                    arm.payload.iter_mut().for_each(|t| *t = Self::substitute(t, bindings));
+                    Self::substitute_annotations(&mut arm.body, bindings);
                 }
+                otherwise.iter_mut().for_each(|e| Self::substitute_annotations(e, bindings));
             }
-            HirExpression::Emit(e) => {
-                self.rewrite_call_sites(e, old_name, new_name);
+            HirExpression::Emit(e) => Self::substitute_annotations(e, bindings),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => {}
+        }
+    }
+
+    fn is_generic(typ: &OnuType) -> bool {
+        match typ {
+            OnuType::Shape(_) => true,
+            OnuType::Tuple(types) => types.iter().any(Self::is_generic),
+            OnuType::Array(inner) => Self::is_generic(inner),
+            _ => false,
+        }
+    }
+
+    /// The stable name fragment of a concrete type.
+    fn mangle(typ: &OnuType) -> String {
+        match typ {
+            OnuType::I64 => "integer".to_string(),
+            OnuType::F64 => "float".to_string(),
+            OnuType::Strings => "string".to_string(),
+            OnuType::Boolean => "boolean".to_string(),
+            OnuType::Matrix => "matrix".to_string(),
+            OnuType::Nothing => "nothing".to_string(),
+            OnuType::Tuple(types) => {
+                let parts: Vec<String> = types.iter().map(Self::mangle).collect();
+                format!("tuple-of-{}-{}", types.len(), parts.join("-"))
             }
-            _ => {}
+            OnuType::Array(inner) => format!("array-of-{}", Self::mangle(inner)),
+            other => other.to_string(),
         }
     }
 }
+
+impl Default for Monomorphizer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CompilerSession;
+    use crate::hir::HirDiscourse;
+
+    const SHAPE: &str = "
+the shape Measurable promises:
+    a behavior called magnitude
+        receiving: nothing
+        returning: a float
+
+the behavior called magnitude
+    receiving:
+        a float called input
+    returning:
+        a float
+    as:
+        input
+
+the behavior called get-size
+    receiving:
+        a Measurable via the role Measurable called item
+    returning:
+        a float
+    as:
+        item magnitude
+";
+
+    fn behaviors(source: &str) -> Vec<String> {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(&format!("{}{}", SHAPE, source)).unwrap();
+        let mut names: Vec<String> = session
+            .hir
+            .iter()
+            .filter_map(|d| match d {
+                HirDiscourse::Behavior { header, .. } => Some(header.name.clone()),
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_repeated_instantiations_are_built_once() {
+        let names = behaviors("
+the behavior called main
+    as:
+        let x is a float 10.5
+        let y is a float 2.5
+        (x get-size) added-to (y get-size)
+");
+        assert_eq!(names, vec!["get-size_float", "magnitude", "main"]);
+    }
+
+    #[test]
+    fn test_transitive_instantiations_are_reached() {
+        let names = behaviors("
+the behavior called doubled-size
+    receiving:
+        a Measurable via the role Measurable called item
+    returning:
+        a float
+    as:
+        (item get-size) scales-by 2.0
+
+the behavior called main
+    as:
+        let x is a float 10.5
+        x doubled-size
+");
+        assert_eq!(names, vec!["doubled-size_float", "get-size_float", "magnitude", "main"]);
+    }
+}
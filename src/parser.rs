@@ -1,14 +1,14 @@
-/// Ọ̀nụ Parser: The Structural Discourse Layer
-///
-/// This module implements a recursive descent parser for the Ọ̀nụ language.
-/// Its responsibility is to transform a flat stream of tokens into a 
-/// hierarchical Abstract Syntax Tree (AST) composed of 'Discourse' units
-/// and 'Expression' nodes.
-///
-/// Clean Architecture:
-/// This parser is an Interface Adapter. It translates the external language
-/// representation (tokens) into the internal representation (AST) that the
-/// Use Case layer (Interpreter) can understand.
+//! Ọ̀nụ Parser: The Structural Discourse Layer
+//!
+//! This module implements a recursive descent parser for the Ọ̀nụ language.
+//! Its responsibility is to transform a flat stream of tokens into a 
+//! hierarchical Abstract Syntax Tree (AST) composed of 'Discourse' units
+//! and 'Expression' nodes.
+//!
+//! Clean Architecture:
+//! This parser is an Interface Adapter. It translates the external language
+//! representation (tokens) into the internal representation (AST) that the
+//! Use Case layer (Interpreter) can understand.
 
 use crate::lexer::{Token, TokenWithSpan};
 use crate::error::{OnuError, Span};
@@ -18,15 +18,49 @@ use crate::types::OnuType;
 /// Discourse represents the top-level semantic units of an Ọ̀nụ program.
 /// Each unit represents a 'proposition' in the academic discourse.
 #[derive(Debug, PartialEq, Eq, Clone)]
+#[allow(clippy::large_enum_variant)]
 pub enum Discourse {
-    /// A module defines a namespace with a single concern (SRP enforcement).
-    Module { name: String, concern: String },
+    /// A module defines a namespace with a single concern (SRP enforcement),
+    /// and names the modules whose behaviors it draws on.
+    Module { name: String, concern: String, draws_from: Vec<String>, span: Span },
     /// A shape defines a contract (interface) that other things promise to fulfill.
     Shape { name: String, behaviors: Vec<BehaviorHeader> },
+    /// A structure is a named record whose fields each declare who may read them.
+    Structure { name: String, fields: Vec<Field>, span: Span },
+    /// A variety is a named tagged union: a value is exactly one of its variants.
+    Variety { name: String, variants: Vec<Variant>, span: Span },
     /// A behavior is a pure function that fulfills an intent.
     Behavior { header: BehaviorHeader, body: Expression },
 }
 
+/// Visibility governs who may read a field of a structure, or call a behavior.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Visibility {
+    /// `exposes:` the field is readable wherever the structure is; an exposed
+    /// behavior may be called by any module that draws on its own.
+    Exposed,
+    /// `keeps:` the field is readable within the module that declares the structure.
+    Kept,
+    /// `keeps internal:` the field is readable only by behaviors whose subject is the
+    /// structure; an internal behavior may be called only within its own module.
+    Internal,
+}
+
+/// Field represents a named member of a structure.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Field {
+    pub name: String,
+    pub type_info: TypeInfo,
+    pub visibility: Visibility,
+}
+
+/// Variant represents one alternative of a variety and the payload it carries.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Variant {
+    pub name: String,
+    pub payload: Vec<Argument>,
+}
+
 /// TypeInfo contains the grammatical metadata for a type declaration.
 #[derive(Debug, PartialEq, Eq, Clone, Hash)]
 pub struct TypeInfo {
@@ -64,17 +98,31 @@ pub enum Expression {
     Matrix { rows: usize, cols: usize, data: Vec<Expression> },
     Emit(Box<Expression>),
     Broadcasts(Box<Expression>), // Active-tense alias for Emit
+    /// `span` is where the derivation is written; like a call's, it is not compared.
     Derivation { 
         name: String, 
         type_info: Option<TypeInfo>,
         value: Box<Expression>, 
-        body: Box<Expression> 
+        body: Box<Expression>,
+        span: Span,
     },
     ActsAs {
         subject: Box<Expression>,
         shape: String,
     },
-    BehaviorCall { name: String, args: Vec<Expression> },
+    /// `span` is where the verb is written; it takes no part in equality or hashing.
+    BehaviorCall { name: String, args: Vec<Expression>, span: Span },
+    /// `a Point with x 1.5 y 2.0`: a structure built from its fields, as written.
+    Construct { structure: String, fields: Vec<(String, Expression)> },
+    /// `a Shift with offset 1`: one variant of a variety, built from its payload.
+    Variant { variety: String, variant: String, fields: Vec<(String, Expression)> },
+    /// `when op is a Shift carrying n then ... otherwise ...`: a case analysis of a variety.
+    Case {
+        subject: Box<Expression>,
+        variety: String,
+        arms: Vec<CaseArm>,
+        otherwise: Option<Box<Expression>>,
+    },
     If {
         condition: Box<Expression>,
         then_branch: Box<Expression>,
@@ -83,6 +131,14 @@ pub enum Expression {
     Block(Vec<Expression>),
 }
 
+/// CaseArm answers one variant of a case analysis, binding its payload by position.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CaseArm {
+    pub variant: String,
+    pub bindings: Vec<String>,
+    pub body: Expression,
+}
+
 impl PartialEq for Expression {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
@@ -115,9 +171,18 @@ impl PartialEq for Expression {
             (Expression::ActsAs { subject: s1, shape: sh1 }, Expression::ActsAs { subject: s2, shape: sh2 }) => {
                 s1 == s2 && sh1 == sh2
             }
-            (Expression::BehaviorCall { name: n1, args: a1 }, Expression::BehaviorCall { name: n2, args: a2 }) => {
+            (Expression::BehaviorCall { name: n1, args: a1, .. }, Expression::BehaviorCall { name: n2, args: a2, .. }) => {
                 n1 == n2 && a1 == a2
             }
+            (Expression::Construct { structure: s1, fields: f1 }, Expression::Construct { structure: s2, fields: f2 }) => {
+                s1 == s2 && f1 == f2
+            }
+            (Expression::Variant { variety: t1, variant: v1, fields: f1 }, Expression::Variant { variety: t2, variant: v2, fields: f2 }) => {
+                t1 == t2 && v1 == v2 && f1 == f2
+            }
+            (Expression::Case { subject: s1, variety: t1, arms: a1, otherwise: o1 }, Expression::Case { subject: s2, variety: t2, arms: a2, otherwise: o2 }) => {
+                s1 == s2 && t1 == t2 && a1 == a2 && o1 == o2
+            }
             (Expression::If { condition: c1, then_branch: t1, else_branch: e1 }, Expression::If { condition: c2, then_branch: t2, else_branch: e2 }) => {
                 c1 == c2 && t1 == t2 && e1 == e2
             }
@@ -148,7 +213,7 @@ impl std::hash::Hash for Expression {
             Expression::Boolean(b) => b.hash(state),
             Expression::Text(s) => s.hash(state),
             Expression::Identifier(s) => s.hash(state),
-            Expression::Nothing => {}.hash(state),
+            Expression::Nothing => {}
             Expression::Tuple(v) => v.hash(state),
             Expression::Array(v) => v.hash(state),
             Expression::Matrix { rows, cols, data } => {
@@ -167,10 +232,25 @@ impl std::hash::Hash for Expression {
                 subject.hash(state);
                 shape.hash(state);
             }
-            Expression::BehaviorCall { name, args } => {
+            Expression::BehaviorCall { name, args, .. } => {
                 name.hash(state);
                 args.hash(state);
             }
+            Expression::Construct { structure, fields } => {
+                structure.hash(state);
+                fields.hash(state);
+            }
+            Expression::Variant { variety, variant, fields } => {
+                variety.hash(state);
+                variant.hash(state);
+                fields.hash(state);
+            }
+            Expression::Case { subject, variety, arms, otherwise } => {
+                subject.hash(state);
+                variety.hash(state);
+                arms.hash(state);
+                otherwise.hash(state);
+            }
             Expression::If { condition, then_branch, else_branch } => {
                 condition.hash(state);
                 then_branch.hash(state);
@@ -192,6 +272,14 @@ pub struct BehaviorHeader {
     pub delivers: ReturnType,
     pub diminishing: Option<String>, // name of the proof/variable that is smaller
     pub skip_termination_check: bool,
+    pub allow_repetition: bool, // `with intentional repetition`: exempt from DRY enforcement
+    pub visibility: Visibility, // `exposes` (the default) or `keeps internal`
+    pub span: Span, // where the header begins, for diagnostics about the whole behavior
+}
+
+/// Whether a token opens a new discourse unit, ending the one before it.
+fn is_discourse_marker(token: &Token) -> bool {
+    matches!(token, Token::TheModuleCalled | Token::TheShape | Token::TheStructureCalled | Token::TheVarietyCalled | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled)
 }
 
 /// The Parser maintains a position in the token stream and builds the AST.
@@ -202,17 +290,22 @@ pub struct Parser<'a, 'b> {
     is_pure_context: bool,
     current_depth: usize,
     max_depth: usize,
+    /// Set while `parse_program` runs: a malformed body line is recorded and skipped.
+    recovering: bool,
+    diagnostics: Vec<OnuError>,
+    /// Behaviors whose bodies `parse_program` had to resume past a malformed line.
+    resumed: Vec<String>,
 }
 
 impl<'a, 'b> Parser<'a, 'b> {
     /// Creates a new Parser from a slice of tokens.
     pub fn new(tokens: &'a [TokenWithSpan]) -> Self {
-        Self { tokens, pos: 0, registry: None, is_pure_context: false, current_depth: 0, max_depth: 16 }
+        Self { tokens, pos: 0, registry: None, is_pure_context: false, current_depth: 0, max_depth: 16, recovering: false, diagnostics: Vec::new(), resumed: Vec::new() }
     }
 
     /// Creates a new Parser with a Registry for semantic enforcement.
     pub fn with_registry(tokens: &'a [TokenWithSpan], registry: &'b Registry) -> Self {
-        Self { tokens, pos: 0, registry: Some(registry), is_pure_context: false, current_depth: 0, max_depth: 16 }
+        Self { tokens, pos: 0, registry: Some(registry), is_pure_context: false, current_depth: 0, max_depth: 16, recovering: false, diagnostics: Vec::new(), resumed: Vec::new() }
     }
 
     fn enter_expression(&mut self) -> Result<(), OnuError> {
@@ -242,6 +335,61 @@ impl<'a, 'b> Parser<'a, 'b> {
         })
     }
 
+    /// Parses every discourse unit in the tokens. A malformed unit is recorded and
+    /// parsing resumes at the next discourse marker; a malformed line of a behavior
+    /// body resumes at the body's next `derivation:`. One mistake thus hides none of
+    /// the others. Returns the units that parsed, a behavior's body only partially
+    /// where it had to be resumed, and every diagnostic in the order it was found.
+    pub fn parse_program(&mut self) -> (Vec<Discourse>, Vec<OnuError>) {
+        self.recovering = true;
+        let mut ast = Vec::new();
+        while !self.is_eof() {
+            let start = self.pos;
+            self.current_depth = 0;
+            let found = self.diagnostics.len();
+            match self.parse_discourse() {
+                Ok(discourse) => {
+                    if let Discourse::Behavior { header, .. } = &discourse
+                        && self.diagnostics.len() > found
+                    {
+                        self.resumed.push(header.name.clone());
+                    }
+                    ast.push(discourse);
+                }
+                Err(e) => {
+                    self.diagnostics.push(e);
+                    self.synchronize(start);
+                }
+            }
+        }
+        self.recovering = false;
+        (ast, std::mem::take(&mut self.diagnostics))
+    }
+
+    /// Names the behaviors of the last `parse_program` whose bodies parsed only in part.
+    /// Their bodies are incomplete, so later passes have nothing sound to check.
+    pub fn resumed(&self) -> &[String] {
+        &self.resumed
+    }
+
+    /// Moves past a malformed unit that began at `start`, to the next discourse marker.
+    pub fn synchronize(&mut self, start: usize) {
+        self.pos = self.pos.max(start + 1);
+        while self.peek_token().is_some_and(|t| !is_discourse_marker(&t)) {
+            self.pos += 1;
+        }
+    }
+
+    /// Moves past a malformed body line that began at `start`, to the next
+    /// `derivation:` of the body, or to its end.
+    fn resume_at_derivation(&mut self, start: usize) {
+        self.pos = self.pos.max(start + 1);
+        self.current_depth = 0;
+        while self.peek_token().is_some_and(|t| t != Token::Derivation && !is_discourse_marker(&t)) {
+            self.pos += 1;
+        }
+    }
+
     /// Parses a single discourse unit.
     pub fn parse_discourse(&mut self) -> Result<Discourse, OnuError> {
         let token = self.peek_token().ok_or_else(|| OnuError::ParseError {
@@ -252,6 +400,8 @@ impl<'a, 'b> Parser<'a, 'b> {
         match token {
             Token::TheModuleCalled => self.parse_module(),
             Token::TheShape => self.parse_shape(),
+            Token::TheStructureCalled => self.parse_structure(),
+            Token::TheVarietyCalled => self.parse_variety(),
             Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled => self.parse_behavior(),
             _ => Err(OnuError::ParseError {
                 message: format!("Unexpected token: {:?}", token),
@@ -270,11 +420,13 @@ impl<'a, 'b> Parser<'a, 'b> {
         match token {
             Token::TheModuleCalled => self.parse_module(),
             Token::TheShape => self.parse_shape(),
+            Token::TheStructureCalled => self.parse_structure(),
+            Token::TheVarietyCalled => self.parse_variety(),
             Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled => {
                 let header = self.parse_behavior_header()?;
                 // Skip tokens until the next discourse marker or EOF
                 while let Some(t) = self.peek_token() {
-                    if matches!(t, Token::TheModuleCalled | Token::TheShape | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled) {
+                    if is_discourse_marker(&t) {
                         break;
                     }
                     self.pos += 1;
@@ -289,6 +441,7 @@ impl<'a, 'b> Parser<'a, 'b> {
     }
 
     fn parse_module(&mut self) -> Result<Discourse, OnuError> {
+        let span = self.current_span();
         self.consume(Token::TheModuleCalled)?;
         let name = self.consume_identifier(false)?;
         self.consume(Token::WithConcern)?;
@@ -296,7 +449,7 @@ impl<'a, 'b> Parser<'a, 'b> {
         
         let mut concern = String::new();
         while let Some(token) = self.peek_token() {
-            if matches!(token, Token::TheModuleCalled | Token::TheShape | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled) {
+            if is_discourse_marker(&token) || token == Token::DrawsFrom {
                 break;
             }
             if !concern.is_empty() {
@@ -304,8 +457,39 @@ impl<'a, 'b> Parser<'a, 'b> {
             }
             concern.push_str(&self.consume_identifier(false)?);
         }
-        
-        Ok(Discourse::Module { name, concern })
+
+        // `draws-from: Tape and Registers` lists the modules whose behaviors this one uses.
+        let mut draws_from: Vec<String> = Vec::new();
+        if let Some(Token::DrawsFrom) = self.peek_token() {
+            self.pos += 1;
+            self.consume(Token::Colon)?;
+            while let Some(token) = self.peek_token() {
+                if is_discourse_marker(&token) {
+                    break;
+                }
+                if self.peek_word("and") {
+                    self.pos += 1;
+                    continue;
+                }
+                let drawn_span = self.current_span();
+                let drawn = self.consume_identifier(false)?;
+                if draws_from.contains(&drawn) {
+                    return Err(OnuError::ParseError {
+                        message: format!("The module '{}' draws from '{}' twice.", name, drawn),
+                        span: drawn_span,
+                    });
+                }
+                draws_from.push(drawn);
+            }
+            if draws_from.is_empty() {
+                return Err(OnuError::ParseError {
+                    message: format!("The module '{}' names no module after 'draws-from:'.", name),
+                    span,
+                });
+            }
+        }
+
+        Ok(Discourse::Module { name, concern, draws_from, span })
     }
 
     fn parse_shape(&mut self) -> Result<Discourse, OnuError> {
@@ -329,11 +513,103 @@ impl<'a, 'b> Parser<'a, 'b> {
         Ok(Discourse::Shape { name, behaviors })
     }
 
+    fn parse_structure(&mut self) -> Result<Discourse, OnuError> {
+        let span = self.current_span();
+        self.consume(Token::TheStructureCalled)?;
+        let name = self.consume_identifier(false)?;
+        let mut fields: Vec<Field> = Vec::new();
+        while let Some(token) = self.peek_token() {
+            let visibility = match token {
+                Token::Exposes => Visibility::Exposed,
+                Token::Keeps => Visibility::Kept,
+                Token::KeepsInternal => Visibility::Internal,
+                _ => break,
+            };
+            self.pos += 1;
+            self.consume(Token::Colon)?;
+            while let Some(token) = self.peek_token() {
+                if is_discourse_marker(&token) || matches!(token, Token::Exposes | Token::Keeps | Token::KeepsInternal) {
+                    break;
+                }
+                let field_span = self.current_span();
+                let type_info = self.parse_type_info()?;
+                self.skip_called();
+                let field = self.consume_identifier(true)?;
+                if fields.iter().any(|f| f.name == field) {
+                    return Err(OnuError::ParseError {
+                        message: format!("The structure '{}' keeps the field '{}' twice.", name, field),
+                        span: field_span,
+                    });
+                }
+                fields.push(Field { name: field, type_info, visibility });
+            }
+        }
+        if fields.is_empty() {
+            return Err(OnuError::ParseError {
+                message: format!("The structure '{}' keeps no fields; declare them under 'keeps:', 'keeps internal:' or 'exposes:'.", name),
+                span,
+            });
+        }
+        Ok(Discourse::Structure { name, fields, span })
+    }
+
+    /// Parses `the variety called Instruction is one of:` and its variants, each
+    /// optionally `carrying` a payload: `a Shift carrying an integer called offset`.
+    fn parse_variety(&mut self) -> Result<Discourse, OnuError> {
+        let span = self.current_span();
+        self.consume(Token::TheVarietyCalled)?;
+        let name = self.consume_identifier(false)?;
+        self.consume(Token::Is)?;
+        self.consume_word("one")?;
+        self.consume(Token::Of)?;
+        self.consume(Token::Colon)?;
+        let mut variants: Vec<Variant> = Vec::new();
+        while let Some(Token::A | Token::An) = self.peek_token() {
+            let variant_span = self.current_span();
+            self.pos += 1;
+            let variant = self.consume_identifier(false)?;
+            if variants.iter().any(|v| v.name == variant) {
+                return Err(OnuError::ParseError {
+                    message: format!("The variety '{}' names the variant '{}' twice.", name, variant),
+                    span: variant_span,
+                });
+            }
+            let mut payload: Vec<Argument> = Vec::new();
+            if self.peek_word("carrying") {
+                self.pos += 1;
+                loop {
+                    let type_info = self.parse_type_info()?;
+                    self.skip_called();
+                    let field = self.consume_identifier(true)?;
+                    if payload.iter().any(|a| a.name == field) {
+                        return Err(OnuError::ParseError {
+                            message: format!("The variant '{}' carries the value '{}' twice.", variant, field),
+                            span: variant_span,
+                        });
+                    }
+                    payload.push(Argument { name: field, type_info });
+                    if !self.peek_word("and") {
+                        break;
+                    }
+                    self.pos += 1;
+                }
+            }
+            variants.push(Variant { name: variant, payload });
+        }
+        if variants.is_empty() {
+            return Err(OnuError::ParseError {
+                message: format!("The variety '{}' names no variants; list them after 'is one of:'.", name),
+                span,
+            });
+        }
+        Ok(Discourse::Variety { name, variants, span })
+    }
+
     /// Peeks ahead to see if the current behavior header is followed by an 'as' clause.
     fn header_has_as_clause(&self) -> bool {
         let mut offset = 1; // Start after the discourse marker
         while let Some(t) = self.peek_ahead(offset) {
-            if matches!(t, Token::TheModuleCalled | Token::TheShape | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled) {
+            if is_discourse_marker(&t) {
                 return false; // Found next discourse unit before 'as'
             }
             if matches!(t, Token::As) {
@@ -355,14 +631,25 @@ impl<'a, 'b> Parser<'a, 'b> {
         }
         
         let mut expressions = Vec::new();
+        let mut resumed = false;
         while let Some(token) = self.peek_token() {
-            if matches!(token, Token::TheModuleCalled | Token::TheShape | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled) {
+            if is_discourse_marker(&token) {
                 break;
             }
-            if matches!(token, Token::Derivation | Token::Let | Token::If) {
-                expressions.push(self.parse_primary()?);
+            let start = self.pos;
+            let parsed = if matches!(token, Token::Derivation | Token::Let | Token::If) {
+                self.parse_primary()
             } else {
-                expressions.push(self.parse_expression()?);
+                self.parse_expression()
+            };
+            match parsed {
+                Ok(expression) => expressions.push(expression),
+                Err(e) if self.recovering => {
+                    self.diagnostics.push(e);
+                    self.resume_at_derivation(start);
+                    resumed = true;
+                }
+                Err(e) => return Err(e),
             }
         }
         
@@ -372,7 +659,8 @@ impl<'a, 'b> Parser<'a, 'b> {
             Expression::Block(expressions)
         };
 
-        if header.delivers.0 == OnuType::Nothing {
+        // A resumed body is only what survived of it, so what it yields says nothing.
+        if header.delivers.0 == OnuType::Nothing && !resumed {
             let is_yielding = match body {
                 Expression::I8(_) | Expression::I16(_) | Expression::I32(_) | Expression::I64(_) | Expression::I128(_) |
                 Expression::U8(_) | Expression::U16(_) | Expression::U32(_) | Expression::U64(_) | Expression::U128(_) |
@@ -408,6 +696,7 @@ impl<'a, 'b> Parser<'a, 'b> {
         while let Some(token) = self.peek_token() {
             match token {
                 Token::Utilizes | Token::Receiving => {
+                    let span = self.current_span();
                     self.pos += 1;
                     let behavior_name = self.consume_identifier(false)?;
                     
@@ -420,7 +709,7 @@ impl<'a, 'b> Parser<'a, 'b> {
                         args.push(self.parse_primary()?);
                     }
                     
-                    left = Expression::BehaviorCall { name: behavior_name, args };
+                    left = Expression::BehaviorCall { name: behavior_name, args, span };
                     continue;
                 }
                 Token::Identifier(_) | 
@@ -446,22 +735,26 @@ impl<'a, 'b> Parser<'a, 'b> {
 
                     let mut is_behavior = false;
                     let mut arity = 0;
-                    if let Some(registry) = self.registry {
-                        if registry.is_registered(&name) {
-                            is_behavior = true;
-                            arity = registry.get_arity(&name).unwrap_or(0);
-                        }
+                    if let Some(registry) = self.registry
+                        && registry.is_registered(&name) {
+                        is_behavior = true;
+                        arity = registry.get_arity(&name).unwrap_or(0);
                     }
 
                     if is_behavior {
+                        let span = self.current_span();
                         self.pos += 1;
                         let mut args = Vec::new();
                         args.push(left);
                         
-                        for _ in 0..(arity.saturating_sub(1)) {
+                        // A traversal names the behavior it applies rather than invoking it.
+                        if crate::hir::TRAVERSALS.contains(&name.as_str()) {
+                            args.push(Expression::Identifier(self.consume_identifier(false)?));
+                        }
+                        while args.len() < arity {
                             args.push(self.parse_primary()?);
                         }
-                        left = Expression::BehaviorCall { name, args };
+                        left = Expression::BehaviorCall { name, args, span };
                         continue;
                     } else {
                         break;
@@ -496,9 +789,12 @@ impl<'a, 'b> Parser<'a, 'b> {
             }
             Some(Token::IntegerLiteral(n)) => {
                 self.pos += 1;
-                // Default parsing of generic integer literal to I64 for now.
-                // Cast safety: i128 to i64 might overflow, but for literals in most samples it's safe.
-                Ok(Expression::I64(n as i64))
+                // A literal too large for an i64 keeps the narrowest width that holds it.
+                Ok(match (i64::try_from(n), u64::try_from(n)) {
+                    (Ok(n), _) => Expression::I64(n),
+                    (_, Ok(n)) => Expression::U64(n),
+                    _ => Expression::I128(n),
+                })
             }
             Some(Token::BooleanLiteral(b)) => {
                 self.pos += 1;
@@ -589,22 +885,28 @@ impl<'a, 'b> Parser<'a, 'b> {
                     let name = if token == Token::Emit { "emit" } else { "broadcasts" };
                     return Err(OnuError::ParseError {
                         message: format!("Side-effect '{}' is not allowed in a pure behavior. Use 'the effect behavior called...'.", name),
-                        span: span,
+                        span,
                     });
                 }
                 self.pos += 1;
                 let value = self.parse_expression()?;
                 let name = if token == Token::Emit { "emit" } else { "broadcasts" };
-                Ok(Expression::BehaviorCall { name: name.to_string(), args: vec![value] })
+                Ok(Expression::BehaviorCall { name: name.to_string(), args: vec![value], span })
             }
             Some(Token::Derivation) => {
+                let span = self.current_span();
                 self.consume(Token::Derivation)?;
                 self.consume(Token::Colon)?;
-                let name = self.consume_identifier(true)?;
+                let names = if let Some(Token::LParen) = self.peek_token() {
+                    self.parse_destructured_names()?
+                } else {
+                    vec![self.consume_identifier(true)?]
+                };
                 self.consume(Token::DerivesFrom)?;
 
-                // Optional type info
+                // Optional type info; `a Point with ...` is a value, not a type.
                 let type_info = match self.peek_token() {
+                    Some(Token::A) | Some(Token::An) | Some(Token::The) if self.construction_ahead() => None,
                     Some(Token::A) | Some(Token::An) | Some(Token::The) | Some(Token::Nothing) => Some(self.parse_type_info()?),
                     _ => None,
                 };
@@ -631,10 +933,11 @@ impl<'a, 'b> Parser<'a, 'b> {
                     Box::new(Expression::Block(body_exprs))
                 };
                 
-                Ok(Expression::Derivation { name, type_info, value, body })
+                Ok(Self::destructure(names, type_info, value, body, span))
             }
             Some(Token::Let) => {
                 // Map 'let' to Derivation AST
+                let span = self.current_span();
                 self.consume(Token::Let)?;
                 let name = self.consume_identifier(true)?;
                 self.consume(Token::Is)?;
@@ -653,8 +956,9 @@ impl<'a, 'b> Parser<'a, 'b> {
                            else if body_exprs.len() == 1 { Box::new(body_exprs.pop().unwrap()) } 
                            else { Box::new(Expression::Block(body_exprs)) };
                 
-                Ok(Expression::Derivation { name, type_info, value, body })
+                Ok(Expression::Derivation { name, type_info, value, body, span })
             }
+            Some(Token::When) => self.parse_case(),
             Some(Token::If) => {
                 self.consume(Token::If)?;
                 let condition = Box::new(self.parse_expression()?);
@@ -671,24 +975,24 @@ impl<'a, 'b> Parser<'a, 'b> {
             Some(Token::Identifier(s)) => {
                 // SVO Enforcement: Prefix usage of registered behaviors is forbidden,
                 // UNLESS they take zero arguments (act as constants/propositions).
-                if let Some(registry) = self.registry {
-                    if registry.is_registered(&s) {
-                        let arity = registry.get_arity(&s).unwrap_or(0);
-                        if arity > 0 {
-                            return Err(OnuError::ParseError {
-                                message: format!("The behavior '{}' refuses to be used as a prefix. Please utilize Subject-Verb-Object (SVO) grammar.", s),
-                                span,
-                            });
-                        } else {
-                            // Arity 0: Treat as an immediate call
-                            self.pos += 1;
-                            return Ok(Expression::BehaviorCall { name: s, args: vec![] });
-                        }
+                if let Some(registry) = self.registry
+                    && registry.is_registered(&s) {
+                    let arity = registry.get_arity(&s).unwrap_or(0);
+                    if arity > 0 {
+                        return Err(OnuError::ParseError {
+                            message: format!("The behavior '{}' refuses to be used as a prefix. Please utilize Subject-Verb-Object (SVO) grammar.", s),
+                            span,
+                        });
+                    } else {
+                        // Arity 0: Treat as an immediate call
+                        self.pos += 1;
+                        return Ok(Expression::BehaviorCall { name: s, args: vec![], span });
                     }
                 }
                 self.pos += 1;
                 Ok(Expression::Identifier(s))
             }
+            Some(Token::A | Token::An | Token::The) if self.construction_ahead() => self.parse_construction(),
             Some(Token::A) => {
                 self.pos += 1;
                 Ok(Expression::Identifier("a".to_string()))
@@ -716,14 +1020,149 @@ impl<'a, 'b> Parser<'a, 'b> {
         }
     }
 
+    /// Whether an article begins a construction: `a Point with ...`, or a variant,
+    /// which needs no `with` when it carries nothing: `an Enter`.
+    fn construction_ahead(&self) -> bool {
+        match (self.peek_ahead(1), self.peek_ahead(2)) {
+            (Some(Token::Identifier(name)), Some(Token::With)) if self.registry.is_some_and(|r| r.is_structure(&name)) => true,
+            (Some(Token::Identifier(name)), _) => self.registry.is_some_and(|r| r.variety_of(&name).is_some()),
+            _ => false,
+        }
+    }
+
+    /// Parses `a Point with x 1.5 y 2.0`, reading one name and value per declared field,
+    /// or a variant with one per value of its payload.
+    fn parse_construction(&mut self) -> Result<Expression, OnuError> {
+        self.pos += 1; // the article
+        let name = self.consume_identifier(false)?;
+        let registry = self.registry.expect("a construction is only recognised through the registry");
+        let (variety, count) = match registry.variety_of(&name) {
+            Some((variety, tag)) => (Some(variety.to_string()), registry.get_variety(variety).map_or(0, |variants| variants[tag].payload.len())),
+            None => (None, registry.get_structure(&name).map_or(0, |fields| fields.len())),
+        };
+        if count > 0 {
+            self.consume(Token::With)?;
+        }
+        let mut fields = Vec::new();
+        for _ in 0..count {
+            let field = self.consume_identifier(false)?;
+            fields.push((field, self.parse_primary()?));
+        }
+        Ok(match variety {
+            Some(variety) => Expression::Variant { variety, variant: name, fields },
+            None => Expression::Construct { structure: name, fields },
+        })
+    }
+
+    /// The variant an arm of a case analysis answers, if the tokens ahead open one: `is a Shift`.
+    fn arm_ahead(&self) -> Option<(String, String)> {
+        match (self.peek_token(), self.peek_ahead(1), self.peek_ahead(2)) {
+            (Some(Token::Is), Some(Token::A | Token::An), Some(Token::Identifier(variant))) => {
+                let (variety, _) = self.registry?.variety_of(&variant)?;
+                Some((variety.to_string(), variant))
+            }
+            _ => None,
+        }
+    }
+
+    /// Parses `when op is a Shift carrying n then ... is an Enter then ... otherwise ...`.
+    /// The arms run on while they answer variants of one variety, so an arm of another
+    /// variety closes the analysis and is left to an enclosing one.
+    fn parse_case(&mut self) -> Result<Expression, OnuError> {
+        let span = self.current_span();
+        self.consume(Token::When)?;
+        let subject = Box::new(self.parse_expression()?);
+        let mut variety: Option<String> = None;
+        let mut arms = Vec::new();
+        while let Some((arm_variety, variant)) = self.arm_ahead() {
+            if variety.as_ref().is_some_and(|v| *v != arm_variety) {
+                break;
+            }
+            variety = Some(arm_variety);
+            self.pos += 3; // `is`, the article and the variant
+            let mut bindings = Vec::new();
+            if self.peek_word("carrying") {
+                self.pos += 1;
+                while !matches!(self.peek_token(), Some(Token::Then) | None) {
+                    bindings.push(self.consume_identifier(true)?);
+                }
+            }
+            self.consume(Token::Then)?;
+            let body = self.parse_expression()?;
+            arms.push(CaseArm { variant, bindings, body });
+        }
+        let Some(variety) = variety else {
+            return Err(OnuError::ParseError {
+                message: "A 'when' answers at least one variant: 'when x is a <Variant> then ...'.".to_string(),
+                span,
+            });
+        };
+        let otherwise = if let Some(Token::Otherwise) = self.peek_token() {
+            self.pos += 1;
+            Some(Box::new(self.parse_expression()?))
+        } else {
+            None
+        };
+        Ok(Expression::Case { subject, variety, arms, otherwise })
+    }
+
+    /// Parses the names a destructuring derivation binds: `(q : r)`.
+    fn parse_destructured_names(&mut self) -> Result<Vec<String>, OnuError> {
+        let span = self.current_span();
+        self.consume(Token::LParen)?;
+        let mut names = vec![self.consume_identifier(true)?];
+        while let Some(Token::Colon) = self.peek_token() {
+            self.pos += 1;
+            names.push(self.consume_identifier(true)?);
+        }
+        self.consume(Token::RParen)?;
+        if names.len() < 2 || names.len() > crate::hir::PROJECTIONS.len() {
+            return Err(OnuError::ParseError {
+                message: format!(
+                    "A destructuring derivation binds between 2 and {} names, but {} were given.",
+                    crate::hir::PROJECTIONS.len(),
+                    names.len()
+                ),
+                span,
+            });
+        }
+        Ok(names)
+    }
+
+    /// Builds the derivation of one name, or desugars `derivation: (q : r) derives-from t`
+    /// into a derivation of the whole tuple under a name no source can spell, whose body
+    /// derives `q` from `first-of` it and `r` from `second-of` it.
+    fn destructure(mut names: Vec<String>, type_info: Option<TypeInfo>, value: Box<Expression>, body: Box<Expression>, span: Span) -> Expression {
+        if names.len() == 1 {
+            return Expression::Derivation { name: names.pop().unwrap(), type_info, value, body, span };
+        }
+        let whole = format!("({})", names.join(" : "));
+        let body = names.iter().enumerate().rev().fold(body, |body, (i, name)| {
+            Box::new(Expression::Derivation {
+                name: name.clone(),
+                type_info: None,
+                value: Box::new(Expression::BehaviorCall {
+                    name: crate::hir::PROJECTIONS[i].to_string(),
+                    args: vec![Expression::Identifier(whole.clone())],
+                    span,
+                }),
+                body,
+                span,
+            })
+        });
+        Expression::Derivation { name: whole, type_info, value, body, span }
+    }
+
     fn is_terminator(&self, token: &Token) -> bool {
         matches!(token, Token::RParen | Token::RBracket | Token::Returning | Token::Delivers | Token::As | Token::Then | Token::Else | 
-                       Token::TheModuleCalled | Token::TheShape | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled |
-                       Token::WithIntent | Token::Receiving | Token::Takes | Token::WithDiminishing | Token::NoGuaranteedTermination |
+                       Token::Is | Token::Otherwise |
+                       Token::TheModuleCalled | Token::TheShape | Token::TheStructureCalled | Token::TheVarietyCalled | Token::TheBehaviorCalled | Token::TheEffectBehaviorCalled |
+                       Token::WithIntent | Token::Receiving | Token::Takes | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::IntentionalRepetition |
                        Token::Promises | Token::WithConcern)
     }
 
     pub fn parse_behavior_header(&mut self) -> Result<BehaviorHeader, OnuError> {
+        let span = self.current_span();
         let is_effect = if let Some(Token::TheEffectBehaviorCalled) = self.peek_token() {
             self.consume(Token::TheEffectBehaviorCalled)?;
             true
@@ -733,13 +1172,25 @@ impl<'a, 'b> Parser<'a, 'b> {
         };
 
         let name = self.consume_identifier(false)?;
+
+        let visibility = match self.peek_token() {
+            Some(Token::KeepsInternal) => {
+                self.consume(Token::KeepsInternal)?;
+                Visibility::Internal
+            }
+            Some(Token::Exposes) => {
+                self.consume(Token::Exposes)?;
+                Visibility::Exposed
+            }
+            _ => Visibility::Exposed,
+        };
         
         let mut intent = String::new();
         if let Some(Token::WithIntent) = self.peek_token() {
             self.consume(Token::WithIntent)?;
             self.consume(Token::Colon)?;
             while let Some(token) = self.peek_token() {
-                if matches!(token, Token::Receiving | Token::Takes | Token::Returning | Token::Delivers | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::As) {
+                if matches!(token, Token::Receiving | Token::Takes | Token::Returning | Token::Delivers | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::IntentionalRepetition | Token::As) {
                     break;
                 }
                 if !intent.is_empty() {
@@ -760,28 +1211,25 @@ impl<'a, 'b> Parser<'a, 'b> {
             self.consume(Token::Colon)?;
         }
         
-        if !matches!(self.peek_token(), Some(Token::Returning | Token::Delivers | Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination)) {
+        if !matches!(self.peek_token(), Some(Token::Returning | Token::Delivers | Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::IntentionalRepetition)) {
             // Handle explicit 'receiving: nothing' or 'takes: nothing'
             if let Some(Token::Nothing) = self.peek_token() {
                 self.consume(Token::Nothing)?;
             } else {
                 while let Some(token) = self.peek_token() {
-                    if matches!(token, Token::Returning | Token::Delivers | Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination) {
+                    if matches!(token, Token::Returning | Token::Delivers | Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::IntentionalRepetition) {
                         break;
                     }
                     
-                    let type_info = self.parse_type_info()?;
-                    
-                    if let Some(Token::Called) = self.peek_token() {
-                        self.consume(Token::Called)?;
-                    } else if let Some(Token::Identifier(ref s)) = self.peek_token() {
-                        if s == "called" {
-                            self.pos += 1;
-                        }
-                    }
-                    
+                    let mut type_info = self.parse_type_info()?;
+                    self.skip_called();
                     let var_name = self.consume_identifier(true)?;
 
+                    // The role may also follow the name: `an integer called n via the role Measurable`.
+                    if type_info.via_role.is_none() && let Some(Token::Via) = self.peek_token() {
+                        type_info.via_role = Some(self.parse_role_clause()?);
+                    }
+
                     takes.push(Argument {
                         name: var_name,
                         type_info,
@@ -800,7 +1248,7 @@ impl<'a, 'b> Parser<'a, 'b> {
             self.consume(Token::Colon)?;
         }
         
-        let type_info = if matches!(self.peek_token(), Some(Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination)) {
+        let type_info = if matches!(self.peek_token(), Some(Token::As | Token::WithDiminishing | Token::NoGuaranteedTermination | Token::IntentionalRepetition)) {
             TypeInfo {
                 onu_type: OnuType::Nothing,
                 display_name: "nothing".to_string(),
@@ -824,6 +1272,13 @@ impl<'a, 'b> Parser<'a, 'b> {
             skip_termination_check = true;
         }
 
+        let allow_repetition = if let Some(Token::IntentionalRepetition) = self.peek_token() {
+            self.consume(Token::IntentionalRepetition)?;
+            true
+        } else {
+            false
+        };
+
         Ok(BehaviorHeader {
             name,
             is_effect,
@@ -832,6 +1287,9 @@ impl<'a, 'b> Parser<'a, 'b> {
             delivers: returning,
             diminishing,
             skip_termination_check,
+            allow_repetition,
+            visibility,
+            span,
         })
     }
 
@@ -843,6 +1301,29 @@ impl<'a, 'b> Parser<'a, 'b> {
         self.tokens.get(self.pos + offset).map(|t| t.token.clone())
     }
 
+    /// Whether the next token is the plain word `word`, such as `carrying` or `and`.
+    fn peek_word(&self, word: &str) -> bool {
+        matches!(self.peek_token(), Some(Token::Identifier(ref s)) if s == word)
+    }
+
+    fn consume_word(&mut self, word: &str) -> Result<(), OnuError> {
+        if self.peek_word(word) {
+            self.pos += 1;
+            return Ok(());
+        }
+        Err(OnuError::ParseError {
+            message: format!("Expected '{}', found {:?}", word, self.peek_token()),
+            span: self.current_span(),
+        })
+    }
+
+    /// Skips the optional `called` between a declared type and its name.
+    fn skip_called(&mut self) {
+        if matches!(self.peek_token(), Some(Token::Called)) || self.peek_word("called") {
+            self.pos += 1;
+        }
+    }
+
     fn parse_type_info(&mut self) -> Result<TypeInfo, OnuError> {
         // Check for explicit 'nothing' first
         if self.peek_token() == Some(Token::Nothing) {
@@ -888,21 +1369,23 @@ impl<'a, 'b> Parser<'a, 'b> {
                 OnuType::Tuple(types)
             }
             "array" => {
-                self.consume(Token::Of)?;
                 let inner = self.parse_type_info()?;
                 OnuType::Array(Box::new(inner.onu_type))
             }
+            _ if self.registry.is_some_and(|r| r.is_structure(&type_name)) => OnuType::Record(type_name.clone()),
+            _ if self.registry.is_some_and(|r| r.is_variety(&type_name)) => OnuType::Variety(type_name.clone()),
             _ => OnuType::from_name(&type_name).unwrap_or(OnuType::Shape(type_name.clone())),
         };
 
         let mut via_role = None;
         if let Some(Token::Via) = self.peek_token() {
-            self.consume(Token::Via)?;
-            self.consume(Token::The)?;
-            self.consume(Token::Role)?;
-            let role_name = self.consume_identifier(false)?;
-            via_role = Some(role_name.clone());
-            onu_type = OnuType::Shape(role_name);
+            let role_name = self.parse_role_clause()?;
+            // A concrete type keeps its identity and merely promises the role;
+            // an abstract one is the role itself.
+            if matches!(onu_type, OnuType::Shape(_)) {
+                onu_type = OnuType::Shape(role_name.clone());
+            }
+            via_role = Some(role_name);
         }
 
         Ok(TypeInfo {
@@ -913,6 +1396,14 @@ impl<'a, 'b> Parser<'a, 'b> {
         })
     }
 
+    /// Parses `via the role <Shape>`, returning the shape's name.
+    fn parse_role_clause(&mut self) -> Result<String, OnuError> {
+        self.consume(Token::Via)?;
+        self.consume(Token::The)?;
+        self.consume(Token::Role)?;
+        self.consume_identifier(false)
+    }
+
     fn consume(&mut self, expected: Token) -> Result<(), OnuError> {
         let span = self.current_span();
         match self.tokens.get(self.pos) {
@@ -937,15 +1428,13 @@ impl<'a, 'b> Parser<'a, 'b> {
             Some(t) => {
                 let res = match t.token {
                     Token::Identifier(ref name) => {
-                        if restricted {
-                            if let Some(registry) = self.registry {
-                                if registry.is_registered(name) {
-                                    return Err(OnuError::ParseError {
-                                        message: format!("Ambiguous identifier '{}': Name is already used by a registered behavior.", name),
-                                        span,
-                                    });
-                                }
-                            }
+                        if restricted
+                            && let Some(registry) = self.registry
+                            && registry.is_registered(name) {
+                            return Err(OnuError::ParseError {
+                                message: format!("Ambiguous identifier '{}': Name is already used by a registered behavior.", name),
+                                span,
+                            });
                         }
                         name.clone()
                     },
@@ -958,6 +1447,8 @@ impl<'a, 'b> Parser<'a, 'b> {
                     Token::The => "the".to_string(),
                     Token::With => "with".to_string(),
                     Token::If => "if".to_string(),
+                    Token::When => "when".to_string(),
+                    Token::Otherwise => "otherwise".to_string(),
                     Token::Is => "is".to_string(),
                     Token::Called => "called".to_string(),
                     Token::As => "as".to_string(),
@@ -1007,6 +1498,7 @@ impl<'a, 'b> Parser<'a, 'b> {
 mod tests {
     use super::*;
     use crate::lexer::Token;
+    use crate::registry::VarietyVariant;
 
     fn t(token: Token) -> TokenWithSpan {
         TokenWithSpan { token, span: Span::default() }
@@ -1027,11 +1519,71 @@ mod tests {
             result,
             Discourse::Module {
                 name: "MeasurementDomain".to_string(),
-                concern: "recursion".to_string()
+                concern: "recursion".to_string(),
+                draws_from: vec![],
+                span: Span::default(),
             }
         );
     }
 
+    #[test]
+    fn test_parse_program_resumes_at_the_next_discourse() {
+        let word = |w: &str| t(Token::Identifier(w.to_string()));
+        let tokens = vec![
+            word("stray"),
+            t(Token::TheModuleCalled),
+            word("Recovered"),
+            t(Token::WithConcern),
+            t(Token::Colon),
+            word("recovery"),
+            t(Token::TheShape),
+        ];
+        let (ast, diagnostics) = Parser::new(&tokens).parse_program();
+        assert_eq!(ast.len(), 1);
+        assert!(matches!(&ast[0], Discourse::Module { name, .. } if name == "Recovered"));
+        assert_eq!(diagnostics.len(), 2, "{:?}", diagnostics);
+    }
+
+    #[test]
+    fn test_parse_module_draws_from() {
+        let word = |w: &str| t(Token::Identifier(w.to_string()));
+        let tokens = vec![
+            t(Token::TheModuleCalled),
+            word("Brainfuck"),
+            t(Token::WithConcern),
+            t(Token::Colon),
+            word("tapes"),
+            t(Token::DrawsFrom),
+            t(Token::Colon),
+            word("Tape"),
+            word("and"),
+            word("Registers"),
+        ];
+        let Discourse::Module { concern, draws_from, .. } = Parser::new(&tokens).parse_discourse().unwrap() else {
+            panic!("Expected a module");
+        };
+        assert_eq!(concern, "tapes");
+        assert_eq!(draws_from, vec!["Tape".to_string(), "Registers".to_string()]);
+
+        let mut tokens = tokens;
+        tokens.push(word("Tape"));
+        let err = Parser::new(&tokens).parse_discourse().unwrap_err();
+        assert!(err.to_string().contains("The module 'Brainfuck' draws from 'Tape' twice."), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_behavior_visibility() {
+        let header = |marker: Option<Token>| {
+            let mut tokens = vec![t(Token::TheBehaviorCalled), t(Token::Identifier("helper".to_string()))];
+            tokens.extend(marker.map(t));
+            tokens.extend([t(Token::Takes), t(Token::Colon), t(Token::Nothing), t(Token::Delivers), t(Token::Colon), t(Token::Nothing)]);
+            Parser::new(&tokens).parse_behavior_header().unwrap().visibility
+        };
+        assert_eq!(header(None), Visibility::Exposed);
+        assert_eq!(header(Some(Token::Exposes)), Visibility::Exposed);
+        assert_eq!(header(Some(Token::KeepsInternal)), Visibility::Internal);
+    }
+
     #[test]
     fn test_parser_accesses_registry() {
         let tokens = vec![
@@ -1060,6 +1612,7 @@ mod tests {
             Expression::BehaviorCall {
                 name: "multiplied-by".to_string(),
                 args: vec![Expression::I64(5), Expression::I64(2)],
+                span: Span::default(),
             }
         );
     }
@@ -1080,6 +1633,7 @@ mod tests {
             Expression::BehaviorCall {
                 name: "sine".to_string(),
                 args: vec![Expression::Identifier("angle".to_string())],
+                span: Span::default(),
             }
         );
     }
@@ -1100,6 +1654,147 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("refuses to be used as a prefix"));
     }
 
+    #[test]
+    fn test_parse_destructuring_derivation() {
+        let tokens = vec![
+            t(Token::Derivation),
+            t(Token::Colon),
+            t(Token::LParen),
+            t(Token::Identifier("q".to_string())),
+            t(Token::Colon),
+            t(Token::Identifier("r".to_string())),
+            t(Token::RParen),
+            t(Token::DerivesFrom),
+            t(Token::Identifier("pair".to_string())),
+            t(Token::Identifier("r".to_string())),
+        ];
+        let mut parser = Parser::new(&tokens);
+        let result = parser.parse_expression().unwrap();
+
+        let project = |verb: &str| Box::new(Expression::BehaviorCall {
+            name: verb.to_string(),
+            args: vec![Expression::Identifier("(q : r)".to_string())],
+            span: Span::default(),
+        });
+        assert_eq!(
+            result,
+            Expression::Derivation {
+                name: "(q : r)".to_string(),
+                type_info: None,
+                value: Box::new(Expression::Identifier("pair".to_string())),
+                body: Box::new(Expression::Derivation {
+                    name: "q".to_string(),
+                    type_info: None,
+                    value: project("first-of"),
+                    body: Box::new(Expression::Derivation {
+                        name: "r".to_string(),
+                        type_info: None,
+                        value: project("second-of"),
+                        body: Box::new(Expression::Identifier("r".to_string())),
+                        span: Span::default(),
+                    }),
+                    span: Span::default(),
+                }),
+                span: Span::default(),
+            }
+        );
+
+        let tokens = vec![
+            t(Token::Derivation),
+            t(Token::Colon),
+            t(Token::LParen),
+            t(Token::Identifier("q".to_string())),
+            t(Token::RParen),
+            t(Token::DerivesFrom),
+            t(Token::Identifier("pair".to_string())),
+        ];
+        let err = Parser::new(&tokens).parse_expression().unwrap_err();
+        assert!(err.to_string().contains("binds between 2 and 5 names, but 1 were given"), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_structure() {
+        let tokens = vec![
+            t(Token::TheStructureCalled),
+            t(Token::Identifier("Point".to_string())),
+            t(Token::Exposes),
+            t(Token::Colon),
+            t(Token::A),
+            t(Token::Float),
+            t(Token::Identifier("called".to_string())),
+            t(Token::Identifier("x".to_string())),
+            t(Token::KeepsInternal),
+            t(Token::Colon),
+            t(Token::An),
+            t(Token::Integer),
+            t(Token::Identifier("called".to_string())),
+            t(Token::Identifier("id".to_string())),
+        ];
+        let Discourse::Structure { name, fields, .. } = Parser::new(&tokens).parse_discourse().unwrap() else {
+            panic!("Expected a structure");
+        };
+        assert_eq!(name, "Point");
+        let fields: Vec<(&str, &OnuType, Visibility)> = fields.iter().map(|f| (f.name.as_str(), &f.type_info.onu_type, f.visibility)).collect();
+        assert_eq!(fields, vec![("x", &OnuType::F64, Visibility::Exposed), ("id", &OnuType::I64, Visibility::Internal)]);
+    }
+
+    #[test]
+    fn test_parse_variety_and_case() {
+        let word = |w: &str| t(Token::Identifier(w.to_string()));
+        let tokens = vec![
+            t(Token::TheVarietyCalled),
+            word("Signal"),
+            t(Token::Is),
+            word("one"),
+            t(Token::Of),
+            t(Token::Colon),
+            t(Token::A),
+            word("Stop"),
+            t(Token::A),
+            word("Go"),
+            word("carrying"),
+            t(Token::An),
+            t(Token::Integer),
+            word("called"),
+            word("speed"),
+        ];
+        let Discourse::Variety { name, variants, .. } = Parser::new(&tokens).parse_discourse().unwrap() else {
+            panic!("Expected a variety");
+        };
+        assert_eq!(name, "Signal");
+        let variants: Vec<(&str, usize)> = variants.iter().map(|v| (v.name.as_str(), v.payload.len())).collect();
+        assert_eq!(variants, vec![("Stop", 0), ("Go", 1)]);
+
+        let mut registry = Registry::new();
+        registry.add_variety("Signal", vec![
+            VarietyVariant { name: "Stop".to_string(), payload: vec![] },
+            VarietyVariant { name: "Go".to_string(), payload: vec![("speed".to_string(), OnuType::I64)] },
+        ]);
+        let tokens = vec![
+            t(Token::When),
+            word("s"),
+            t(Token::Is),
+            t(Token::A),
+            word("Go"),
+            word("carrying"),
+            word("v"),
+            t(Token::Then),
+            word("v"),
+            t(Token::Otherwise),
+            word("s"),
+        ];
+        let result = Parser::with_registry(&tokens, &registry).parse_expression().unwrap();
+        assert_eq!(
+            result,
+            Expression::Case {
+                subject: Box::new(Expression::Identifier("s".to_string())),
+                variety: "Signal".to_string(),
+                arms: vec![CaseArm { variant: "Go".to_string(), bindings: vec!["v".to_string()], body: Expression::Identifier("v".to_string()) }],
+                otherwise: Some(Box::new(Expression::Identifier("s".to_string()))),
+            }
+        );
+    }
+
     #[test]
     fn test_parse_nested_infix() {
         let tokens = vec![
@@ -1123,9 +1818,11 @@ mod tests {
                     Expression::BehaviorCall {
                         name: "added-to".to_string(),
                         args: vec![Expression::I64(5), Expression::I64(2)],
+                        span: Span::default(),
                     },
                     Expression::I64(3)
                 ],
+                span: Span::default(),
             }
         );
     }
@@ -1254,6 +1951,7 @@ mod tests {
             Expression::BehaviorCall {
                 name: "ackermann".to_string(),
                 args: vec![Expression::Identifier("m".to_string()), Expression::Identifier("n".to_string())],
+                span: Span::default(),
             }
         );
     }
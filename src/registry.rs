@@ -1,14 +1,17 @@
-/// Ọ̀nụ Semantic Registry: The DRY Enforcement Layer
-///
-/// This module provides a mechanism to ensure that every behavior's implementation 
-/// is unique within a program. It implements the Single-Source Registry mandate.
-///
-/// Enforcement Mechanism:
-/// The parser hashes the semantic body (the AST) of every behavior declaration.
-/// If two declarations produce the same hash, the compiler refuses to parse 
-/// the second, preventing duplicate logic across the codebase.
-
-use crate::error::OnuError;
+//! Ọ̀nụ Semantic Registry: The DRY Enforcement Layer
+//!
+//! This module provides a mechanism to ensure that every behavior's implementation 
+//! is unique within a program. It implements the Single-Source Registry mandate.
+//!
+//! Enforcement Mechanism:
+//! The compiler hashes the semantic body (the AST) of every behavior declaration,
+//! after renaming its arguments and derivations to canonical names. If two
+//! declarations produce the same hash, the compiler refuses the second,
+//! preventing duplicate logic across the codebase even when only the names differ.
+//! A behavior declared `with intentional repetition` is exempt.
+
+use crate::error::{OnuError, Span};
+use crate::parser::{CaseArm, Expression, Visibility};
 use crate::types::OnuType;
 use std::collections::hash_map::DefaultHasher;
 use std::collections::{HashMap, HashSet};
@@ -24,6 +27,33 @@ pub struct BehaviorSignature {
     pub return_type: OnuType,
 }
 
+/// StructureField records one field of a declared structure.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct StructureField {
+    pub name: String,
+    pub typ: OnuType,
+    pub visibility: Visibility,
+}
+
+/// VarietyVariant records one variant of a declared variety and the payload it carries.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct VarietyVariant {
+    pub name: String,
+    pub payload: Vec<(String, OnuType)>,
+}
+
+/// The slot, within a value of its variety, where the payload of the variant numbered
+/// `tag` begins. Slot 0 holds the tag; each variant's payload follows the payloads of
+/// the variants declared before it, so no slot is ever read as two types.
+pub fn first_payload_slot(variants: &[VarietyVariant], tag: usize) -> usize {
+    1 + variants.iter().take(tag).map(|variant| variant.payload.len()).sum::<usize>()
+}
+
+/// The verb that reads a field of a structure: `p x-of` reads the field `x`.
+pub fn accessor_name(field: &str) -> String {
+    format!("{}-of", field)
+}
+
 /// Computes a structural hash for any hashable item (usually an Expression AST node).
 pub fn compute_hash<T: Hash>(item: &T) -> SemanticHash {
     let mut hasher = DefaultHasher::new();
@@ -41,23 +71,120 @@ pub fn compute_behavior_hash(body: &crate::parser::Expression, signature: &Behav
     hasher.finish()
 }
 
+/// Rewrites a behavior body so that every name it binds (its arguments, in order,
+/// then each derivation as it is introduced) becomes a canonical placeholder.
+/// Two bodies that differ only in the names they bind normalize identically.
+pub fn alpha_normalize(body: &Expression, args: &[String]) -> Expression {
+    let mut scope: Vec<(String, String)> = args
+        .iter()
+        .enumerate()
+        .map(|(i, name)| (name.clone(), format!("${}", i)))
+        .collect();
+    let mut next = args.len();
+    normalize_expression(body, &mut scope, &mut next)
+}
+
+fn normalize_expression(expr: &Expression, scope: &mut Vec<(String, String)>, next: &mut usize) -> Expression {
+    let mut recurse = |e: &Expression, scope: &mut Vec<(String, String)>| normalize_expression(e, scope, next);
+    match expr {
+        Expression::Identifier(name) => Expression::Identifier(
+            scope.iter().rev().find(|(bound, _)| bound == name).map_or_else(|| name.clone(), |(_, canonical)| canonical.clone()),
+        ),
+        Expression::Derivation { name, type_info, value, body, span } => {
+            let value = Box::new(recurse(value, scope));
+            let canonical = format!("${}", next);
+            *next += 1;
+            scope.push((name.clone(), canonical.clone()));
+            let body = Box::new(normalize_expression(body, scope, next));
+            scope.pop();
+            Expression::Derivation { name: canonical, type_info: type_info.clone(), value, body, span: *span }
+        }
+        Expression::Tuple(v) => Expression::Tuple(v.iter().map(|e| recurse(e, scope)).collect()),
+        Expression::Array(v) => Expression::Array(v.iter().map(|e| recurse(e, scope)).collect()),
+        Expression::Matrix { rows, cols, data } => Expression::Matrix {
+            rows: *rows,
+            cols: *cols,
+            data: data.iter().map(|e| recurse(e, scope)).collect(),
+        },
+        Expression::Emit(e) => Expression::Emit(Box::new(recurse(e, scope))),
+        Expression::Broadcasts(e) => Expression::Broadcasts(Box::new(recurse(e, scope))),
+        Expression::ActsAs { subject, shape } => Expression::ActsAs { subject: Box::new(recurse(subject, scope)), shape: shape.clone() },
+        Expression::BehaviorCall { name, args, span } => Expression::BehaviorCall {
+            name: name.clone(),
+            args: args.iter().map(|e| recurse(e, scope)).collect(),
+            span: *span,
+        },
+        Expression::Construct { structure, fields } => Expression::Construct {
+            structure: structure.clone(),
+            fields: fields.iter().map(|(name, e)| (name.clone(), recurse(e, scope))).collect(),
+        },
+        Expression::Variant { variety, variant, fields } => Expression::Variant {
+            variety: variety.clone(),
+            variant: variant.clone(),
+            fields: fields.iter().map(|(name, e)| (name.clone(), recurse(e, scope))).collect(),
+        },
+        Expression::Case { subject, variety, arms, otherwise } => {
+            let subject = Box::new(recurse(subject, scope));
+            let arms = arms
+                .iter()
+                .map(|arm| {
+                    // The payload's names bind within the arm, like derivations.
+                    let bindings: Vec<String> = arm.bindings.iter().map(|name| {
+                        let canonical = format!("${}", next);
+                        *next += 1;
+                        scope.push((name.clone(), canonical.clone()));
+                        canonical
+                    }).collect();
+                    let body = normalize_expression(&arm.body, scope, next);
+                    scope.truncate(scope.len() - bindings.len());
+                    CaseArm { variant: arm.variant.clone(), bindings, body }
+                })
+                .collect();
+            let otherwise = otherwise.as_ref().map(|e| Box::new(normalize_expression(e, scope, next)));
+            Expression::Case { subject, variety: variety.clone(), arms, otherwise }
+        }
+        Expression::If { condition, then_branch, else_branch } => Expression::If {
+            condition: Box::new(recurse(condition, scope)),
+            then_branch: Box::new(recurse(then_branch, scope)),
+            else_branch: Box::new(recurse(else_branch, scope)),
+        },
+        Expression::Block(v) => Expression::Block(v.iter().map(|e| recurse(e, scope)).collect()),
+        literal => literal.clone(),
+    }
+}
+
 /// The Registry maintains a map of semantic hashes to behavior names.
 #[derive(Debug, Clone)]
 pub struct Registry {
-    /// A map from semantic hash to the first name associated with that implementation.
-    entries: HashMap<SemanticHash, String>, // Hash -> Name
+    /// A map from semantic hash to the first name (and its span) associated with that implementation.
+    entries: HashMap<SemanticHash, (String, Span)>, // Hash -> (Name, Span)
     /// A set of all registered behavior names (built-ins and user-defined).
     names: HashSet<String>,
-    /// A set of behavior names that have been implemented (built-ins or user-defined).
-    implemented_names: HashSet<String>,
+    /// A map from behavior name to the subject types it is implemented for
+    /// (built-ins or user-defined). The subject is the first input, or nothing.
+    implementations: HashMap<String, HashSet<OnuType>>,
     /// A map from behavior name to its arity (number of parameters).
     arities: HashMap<String, usize>,
     /// A map from behavior name to its full type signature.
     signatures: HashMap<String, BehaviorSignature>,
+    /// A map from behavior name to further signatures it accepts, chosen by argument type.
+    overloads: HashMap<String, Vec<BehaviorSignature>>,
     /// A map from shape name to its list of required behavior signatures.
     shapes: HashMap<String, Vec<(String, BehaviorSignature)>>,
+    /// A map from structure name to its fields, in declaration order.
+    structures: HashMap<String, Vec<StructureField>>,
+    /// A map from variety name to its variants, in declaration order (their tags).
+    varieties: HashMap<String, Vec<VarietyVariant>>,
     /// A set of registered suite names to track dynamic loading.
     suites: HashSet<String>,
+    /// A map from behavior name to the drawn-upon module that keeps it internal.
+    kept_internal: HashMap<String, String>,
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Registry {
@@ -66,20 +193,30 @@ impl Registry {
         Self {
             entries: HashMap::new(),
             names: HashSet::new(),
-            implemented_names: HashSet::new(),
+            implementations: HashMap::new(),
             arities: HashMap::new(),
             signatures: HashMap::new(),
+            overloads: HashMap::new(),
             shapes: HashMap::new(),
+            structures: HashMap::new(),
+            varieties: HashMap::new(),
             suites: HashSet::new(),
+            kept_internal: HashMap::new(),
         }
     }
 
-    /// Registers a suite of behaviors and shapes.
+    /// Registers a suite of behaviors and shapes. A name listed more than once is
+    /// overloaded: its first signature is the primary one, the rest are overloads.
     pub fn add_suite(&mut self, name: &str, signatures: Vec<(&str, BehaviorSignature)>, shapes: Vec<(&str, Vec<(String, BehaviorSignature)>)>) {
         if self.suites.insert(name.to_string()) {
+            let mut seen = HashSet::new();
             for (bh_name, sig) in signatures {
-                self.add_signature(bh_name, sig);
-                self.mark_implemented(bh_name);
+                if seen.insert(bh_name) {
+                    self.add_signature(bh_name, sig);
+                    self.mark_implemented(bh_name);
+                } else {
+                    self.add_overload(bh_name, sig);
+                }
             }
             for (sh_name, behaviors) in shapes {
                 self.add_shape(sh_name, behaviors);
@@ -97,18 +234,108 @@ impl Registry {
         self.shapes.get(name)
     }
 
+    /// Declares a structure's name ahead of its fields, so that types naming it resolve.
+    pub fn declare_structure(&mut self, name: &str) {
+        self.structures.entry(name.to_string()).or_default();
+    }
+
+    /// Registers a structure and the accessor verb of each of its fields, implemented
+    /// for the structure. An accessor sharing its name with another behavior over a
+    /// subject (such as another structure's field) becomes one of its overloads; one
+    /// sharing it with a shape's promise, which omits the subject, fulfills it.
+    pub fn add_structure(&mut self, name: &str, fields: Vec<StructureField>) {
+        let subject = OnuType::Record(name.to_string());
+        for field in &fields {
+            let accessor = accessor_name(&field.name);
+            let signature = BehaviorSignature { input_types: vec![subject.clone()], return_type: field.typ.clone() };
+            if self.signatures.get(&accessor).is_some_and(|existing| existing.input_types.len() == 1) {
+                self.add_overload(&accessor, signature);
+            } else {
+                self.add_signature(&accessor, signature);
+                self.mark_implemented(&accessor);
+            }
+        }
+        self.structures.insert(name.to_string(), fields);
+    }
+
+    /// Checks if a name is declared as a structure.
+    pub fn is_structure(&self, name: &str) -> bool {
+        self.structures.contains_key(name)
+    }
+
+    /// Returns the fields of a structure, in declaration order.
+    pub fn get_structure(&self, name: &str) -> Option<&Vec<StructureField>> {
+        self.structures.get(name)
+    }
+
+    /// The position, within its structure, of the field an accessor verb reads.
+    pub fn field_position(&self, structure: &str, accessor: &str) -> Option<usize> {
+        self.structures.get(structure)?.iter().position(|field| accessor_name(&field.name) == accessor)
+    }
+
+    /// Declares a variety's name ahead of its variants, so that types naming it resolve.
+    pub fn declare_variety(&mut self, name: &str) {
+        self.varieties.entry(name.to_string()).or_default();
+    }
+
+    /// Registers a variety and its variants; each variant's tag is its position.
+    pub fn add_variety(&mut self, name: &str, variants: Vec<VarietyVariant>) {
+        self.varieties.insert(name.to_string(), variants);
+    }
+
+    /// Checks if a name is declared as a variety.
+    pub fn is_variety(&self, name: &str) -> bool {
+        self.varieties.contains_key(name)
+    }
+
+    /// Returns the variants of a variety, in declaration order.
+    pub fn get_variety(&self, name: &str) -> Option<&Vec<VarietyVariant>> {
+        self.varieties.get(name)
+    }
+
+    /// The variety a variant belongs to, and the variant's tag within it.
+    pub fn variety_of(&self, variant: &str) -> Option<(&str, usize)> {
+        self.varieties.iter().find_map(|(variety, variants)| {
+            variants.iter().position(|v| v.name == variant).map(|tag| (variety.as_str(), tag))
+        })
+    }
+
+    /// A registry that only names this one's structures and varieties, enough for
+    /// the structural prepass to parse headers that use them as types.
+    pub fn type_names(&self) -> Registry {
+        let mut names = Registry::new();
+        self.structures.keys().for_each(|name| names.declare_structure(name));
+        self.varieties.keys().for_each(|name| names.declare_variety(name));
+        names
+    }
+
+    /// Takes on the structures and varieties of another module's registry, so that
+    /// a linked program can lay out every type any of its modules declares.
+    pub fn adopt_types(&mut self, other: &Registry) {
+        for (name, fields) in &other.structures {
+            self.structures.entry(name.clone()).or_insert_with(|| fields.clone());
+        }
+        for (name, variants) in &other.varieties {
+            self.varieties.entry(name.clone()).or_insert_with(|| variants.clone());
+        }
+    }
+
     /// Registers a new behavior implementation by its name and semantic hash.
-    /// If the hash already exists, it returns a BehaviorConflict error (DRY enforcement).
-    pub fn register(&mut self, name: String, hash: SemanticHash) -> Result<(), OnuError> {
-        if let Some(existing_name) = self.entries.get(&hash) {
+    /// If the hash already belongs to another behavior, it returns a BehaviorConflict
+    /// error carrying both spans (DRY enforcement).
+    pub fn register(&mut self, name: String, hash: SemanticHash, span: Span) -> Result<(), OnuError> {
+        if let Some((existing_name, existing_span)) = self.entries.get(&hash)
+            && *existing_name != name {
             return Err(OnuError::BehaviorConflict {
                 name,
                 other_name: existing_name.clone(),
+                span,
+                other_span: *existing_span,
             });
         }
         self.names.insert(name.clone());
-        self.implemented_names.insert(name.clone());
-        self.entries.insert(hash, name);
+        self.implementations.entry(name.clone()).or_default();
+        self.entries.insert(hash, (name, span));
         Ok(())
     }
 
@@ -117,14 +344,30 @@ impl Registry {
         self.names.contains(name)
     }
 
-    /// Checks if a behavior has been implemented.
+    /// Checks if a behavior has been implemented for any subject type.
     pub fn is_implemented(&self, name: &str) -> bool {
-        self.implemented_names.contains(name)
+        self.implementations.contains_key(name)
     }
 
-    /// Marks a behavior name as implemented (e.g. for built-ins).
+    /// Checks if a behavior has been implemented for the given subject type.
+    pub fn implements(&self, name: &str, subject: &OnuType) -> bool {
+        self.implementations.get(name).is_some_and(|subjects| subjects.contains(subject))
+    }
+
+    /// Marks a behavior as implemented for the subject of its registered signature
+    /// (e.g. for built-ins).
     pub fn mark_implemented(&mut self, name: &str) {
-        self.implemented_names.insert(name.to_string());
+        let subject = self
+            .signatures
+            .get(name)
+            .and_then(|sig| sig.input_types.first().cloned())
+            .unwrap_or(OnuType::Nothing);
+        self.add_implementation(name, subject);
+    }
+
+    /// Records that a behavior is implemented for the given subject type.
+    pub fn add_implementation(&mut self, name: &str, subject: OnuType) {
+        self.implementations.entry(name.to_string()).or_default().insert(subject);
     }
 
     /// Returns the arity of a registered behavior.
@@ -150,57 +393,79 @@ impl Registry {
         self.signatures.get(name)
     }
 
-    /// Verifies if a type satisfies a specific shape (interface).
-    /// Currently, this is a structural check: does the registry contain all 
-    /// behaviors promised by the shape for this type?
-    ///
-    /// Logic: When the parser encounters `acts-as`, the Registry must perform 
-    /// a deep comparison of the Subject's AST against the Shape's Promises.
-    pub fn satisfies(&self, _type_name: &str, shape_name: &str) -> bool {
-        if let Some(required_behaviors) = self.shapes.get(shape_name) {
-            for (bh_name, required_sig) in required_behaviors {
-                // Must be implemented to satisfy a shape
-                if !self.implemented_names.contains(bh_name) {
-                    return false;
-                }
-                
-                if let Some(existing_sig) = self.signatures.get(bh_name) {
-                    if existing_sig != required_sig {
-                        return false;
-                    }
-                }
-            }
-            true
-        } else {
-            false
+    /// Adds a further signature to an already registered behavior, implementing
+    /// it for the overload's subject type.
+    pub fn add_overload(&mut self, name: &str, signature: BehaviorSignature) {
+        let subject = signature.input_types.first().cloned().unwrap_or(OnuType::Nothing);
+        self.add_implementation(name, subject);
+        self.overloads.entry(name.to_string()).or_default().push(signature);
+    }
+
+    /// Chooses the signature of a behavior for the given argument types: the one
+    /// they match exactly, else the one whose subject they share, else the primary.
+    pub fn resolve_signature(&self, name: &str, arg_types: &[OnuType]) -> Option<&BehaviorSignature> {
+        let primary = self.signatures.get(name)?;
+        let candidates: Vec<&BehaviorSignature> = std::iter::once(primary).chain(self.overloads.get(name).into_iter().flatten()).collect();
+        candidates
+            .iter()
+            .find(|sig| sig.input_types == arg_types)
+            .or_else(|| candidates.iter().find(|sig| sig.input_types.first().is_some_and(|subject| Some(subject) == arg_types.first())))
+            .copied()
+            .or(Some(primary))
+    }
+
+    /// Verifies if a concrete type satisfies a specific shape (interface): every
+    /// behavior the shape promises must be implemented for that subject type.
+    pub fn satisfies(&self, subject: &OnuType, shape_name: &str) -> bool {
+        self.verify_acts_as(subject, shape_name, Span::default()).is_ok()
+    }
+
+    /// Records that a behavior registered from another module is kept internal there.
+    pub fn keep_internal(&mut self, name: &str, module: &str) {
+        self.kept_internal.insert(name.to_string(), module.to_string());
+    }
+
+    /// Forgets that another module keeps `name` internal, once the registering
+    /// module declares a behavior of that name itself.
+    pub fn reclaim(&mut self, name: &str) {
+        self.kept_internal.remove(name);
+    }
+
+    /// Refuses a call from `caller` to a behavior another module keeps internal.
+    pub fn verify_reachable(&self, caller: &str, name: &str, span: Span) -> Result<(), OnuError> {
+        match self.kept_internal.get(name) {
+            Some(module) => Err(OnuError::VisibilityViolation {
+                message: format!(
+                    "'{}' calls '{}', which the module '{}' keeps internal; only the behaviors of {} may call it.",
+                    caller, name, module, module
+                ),
+                span,
+            }),
+            None => Ok(()),
         }
     }
 
     /// High-level satisfaction check that returns a Result with a descriptive error.
-    pub fn verify_acts_as(&self, subject_name: &str, shape_name: &str) -> Result<(), OnuError> {
-        if let Some(required_behaviors) = self.shapes.get(shape_name) {
-            for (bh_name, required_sig) in required_behaviors {
-                let matched = if let Some(existing_sig) = self.signatures.get(bh_name) {
-                    existing_sig == required_sig
-                } else {
-                    self.names.contains(bh_name)
-                };
-
-                if !matched {
-                    return Err(OnuError::ParseError {
-                        message: format!("VIOLATION: [{}] refuses to act-as [{}] because it lacks the [{}] action", 
-                            subject_name, shape_name, bh_name),
-                        span: Default::default(),
-                    });
-                }
+    /// Abstract subjects (roles) are resolved later by monomorphization and pass here.
+    pub fn verify_acts_as(&self, subject: &OnuType, shape_name: &str, span: Span) -> Result<(), OnuError> {
+        let Some(required_behaviors) = self.shapes.get(shape_name) else {
+            return Err(OnuError::ShapeViolation {
+                message: format!("The shape [{}] is not defined in the registry.", shape_name),
+                span,
+            });
+        };
+        if matches!(subject, OnuType::Shape(_)) {
+            return Ok(());
+        }
+        for (bh_name, _) in required_behaviors {
+            if !self.implements(bh_name, subject) {
+                return Err(OnuError::ShapeViolation {
+                    message: format!("[{}] refuses to act-as [{}] because it lacks the [{}] action.", subject, shape_name, bh_name),
+                    span,
+                });
             }
-            Ok(())
-        } else {
-            Err(OnuError::ParseError {
-                message: format!("VIOLATION: Shape [{}] is not defined in the registry", shape_name),
-                span: Default::default(),
-            })
         }
+        Ok(())
     }
 }
 
@@ -227,15 +492,19 @@ mod tests {
         let mut registry = Registry::new();
         let hash = compute_hash(&10u64);
 
-        registry.register("foo".to_string(), hash).unwrap();
-        let result = registry.register("bar".to_string(), hash);
+        let (first, second) = (Span { line: 2, column: 1 }, Span { line: 9, column: 1 });
+
+        registry.register("foo".to_string(), hash, first).unwrap();
+        let result = registry.register("bar".to_string(), hash, second);
 
         assert!(result.is_err());
         assert_eq!(
             result.unwrap_err(),
             OnuError::BehaviorConflict {
                 name: "bar".to_string(),
-                other_name: "foo".to_string()
+                other_name: "foo".to_string(),
+                span: second,
+                other_span: first,
             }
         );
     }
@@ -249,13 +518,12 @@ mod tests {
         assert!(!registry.is_registered("sub"));
         
         let hash = compute_hash(&10u64);
-        registry.register("foo".to_string(), hash).unwrap();
+        registry.register("foo".to_string(), hash, Span::default()).unwrap();
         assert!(registry.is_registered("foo"));
     }
 
     #[test]
     fn test_behavior_collision_detection() {
-        use crate::parser::Expression;
         use crate::types::OnuType;
 
         let mut registry = Registry::new();
@@ -266,10 +534,10 @@ mod tests {
         };
 
         let hash = compute_behavior_hash(&body, &sig);
-        registry.register("foo".to_string(), hash).unwrap();
+        registry.register("foo".to_string(), hash, Span::default()).unwrap();
 
         // Same body, same signature -> conflict
-        let result = registry.register("bar".to_string(), hash);
+        let result = registry.register("bar".to_string(), hash, Span::default());
         assert!(result.is_err());
 
         // Same body, different signature -> no conflict
@@ -278,6 +546,118 @@ mod tests {
             return_type: OnuType::F64,
         };
         let hash2 = compute_behavior_hash(&body, &sig2);
-        registry.register("baz".to_string(), hash2).unwrap();
+        registry.register("baz".to_string(), hash2, Span::default()).unwrap();
+    }
+
+    #[test]
+    fn test_implementations_are_tracked_per_subject_type() {
+        use crate::types::OnuType;
+
+        let mut registry = Registry::new();
+        registry.add_shape("Measurable", vec![("measure".to_string(), BehaviorSignature { input_types: vec![], return_type: OnuType::I64 })]);
+        registry.add_implementation("measure", OnuType::I64);
+
+        assert!(registry.implements("measure", &OnuType::I64));
+        assert!(registry.satisfies(&OnuType::I64, "Measurable"));
+        assert!(!registry.satisfies(&OnuType::F64, "Measurable"));
+        assert!(registry.satisfies(&OnuType::Shape("Measurable".to_string()), "Measurable"));
+        assert!(!registry.satisfies(&OnuType::I64, "Unknown"));
+    }
+
+    #[test]
+    fn test_overloads_resolve_by_argument_type() {
+        use crate::types::OnuType;
+
+        let sig = |t: OnuType| BehaviorSignature { input_types: vec![t.clone(), t.clone()], return_type: t };
+        let mut registry = Registry::new();
+        registry.add_suite("Powers", vec![("raised-to", sig(OnuType::F64)), ("raised-to", sig(OnuType::F32))], Vec::new());
+
+        assert_eq!(registry.get_arity("raised-to"), Some(2));
+        assert!(registry.implements("raised-to", &OnuType::F32));
+        assert_eq!(registry.resolve_signature("raised-to", &[OnuType::F32, OnuType::F32]), Some(&sig(OnuType::F32)));
+        // A literal of the wrong width still lands on the subject's overload.
+        assert_eq!(registry.resolve_signature("raised-to", &[OnuType::F32, OnuType::F64]), Some(&sig(OnuType::F32)));
+        assert_eq!(registry.resolve_signature("raised-to", &[OnuType::I64, OnuType::I64]), Some(&sig(OnuType::F64)));
+        assert_eq!(registry.resolve_signature("lowered-to", &[]), None);
+    }
+
+    #[test]
+    fn test_structures_register_an_accessor_per_field() {
+        use crate::types::OnuType;
+
+        let field = |name: &str, typ: OnuType| StructureField { name: name.to_string(), typ, visibility: Visibility::Kept };
+        let mut registry = Registry::new();
+        registry.add_structure("Point", vec![field("x", OnuType::F64), field("y", OnuType::F64)]);
+        registry.add_structure("Cell", vec![field("x", OnuType::I64)]);
+
+        let point = OnuType::Record("Point".to_string());
+        let cell = OnuType::Record("Cell".to_string());
+        assert!(registry.is_structure("Point"));
+        assert_eq!(registry.field_position("Point", "y-of"), Some(1));
+        assert_eq!(registry.field_position("Cell", "y-of"), None);
+        assert!(registry.implements("x-of", &point) && registry.implements("x-of", &cell));
+        assert_eq!(registry.resolve_signature("x-of", &[cell]).map(|sig| &sig.return_type), Some(&OnuType::I64));
+        assert_eq!(registry.resolve_signature("x-of", &[point]).map(|sig| &sig.return_type), Some(&OnuType::F64));
+    }
+
+    #[test]
+    fn test_varieties_lay_out_payloads_after_the_tag() {
+        use crate::types::OnuType;
+
+        let variant = |name: &str, payload: &[(&str, OnuType)]| VarietyVariant {
+            name: name.to_string(),
+            payload: payload.iter().map(|(n, t)| (n.to_string(), t.clone())).collect(),
+        };
+        let variants = vec![
+            variant("Stop", &[]),
+            variant("Go", &[("speed", OnuType::I64)]),
+            variant("Turn", &[("degrees", OnuType::I64), ("left", OnuType::Boolean)]),
+        ];
+        let mut registry = Registry::new();
+        registry.add_variety("Signal", variants.clone());
+
+        assert!(registry.is_variety("Signal") && !registry.is_structure("Signal"));
+        assert_eq!(registry.variety_of("Turn"), Some(("Signal", 2)));
+        assert_eq!(registry.variety_of("Signal"), None);
+        let slots: Vec<usize> = (0..variants.len()).map(|tag| first_payload_slot(&variants, tag)).collect();
+        assert_eq!(slots, vec![1, 1, 2]);
+    }
+
+    #[test]
+    fn test_alpha_renamed_bodies_hash_identically() {
+        use crate::types::OnuType;
+
+        let body = |arg: &str, local: &str| Expression::Derivation {
+            name: local.to_string(),
+            type_info: None,
+            value: Box::new(Expression::BehaviorCall {
+                name: "added-to".to_string(),
+                args: vec![Expression::Identifier(arg.to_string()), Expression::I64(1)],
+                span: Span::default(),
+            }),
+            body: Box::new(Expression::Identifier(local.to_string())),
+            span: Span::default(),
+        };
+        let sig = BehaviorSignature { input_types: vec![OnuType::I64], return_type: OnuType::I64 };
+
+        let first = compute_behavior_hash(&alpha_normalize(&body("n", "next"), &["n".to_string()]), &sig);
+        let renamed = compute_behavior_hash(&alpha_normalize(&body("count", "after"), &["count".to_string()]), &sig);
+        assert_eq!(first, renamed);
+
+        // A free name is not a binding, so it is never renamed.
+        let free = compute_behavior_hash(&alpha_normalize(&body("m", "next"), &["n".to_string()]), &sig);
+        assert_ne!(first, free);
+    }
+
+    #[test]
+    fn test_behaviors_kept_internal_are_unreachable_until_reclaimed() {
+        let mut registry = Registry::new();
+        registry.keep_internal("helper", "Tape");
+        let err = registry.verify_reachable("run", "helper", Span::default()).unwrap_err();
+        assert!(err.to_string().contains("'run' calls 'helper', which the module 'Tape' keeps internal"), "{}", err);
+        assert!(registry.verify_reachable("run", "read-tape", Span::default()).is_ok());
+
+        registry.reclaim("helper");
+        assert!(registry.verify_reachable("run", "helper", Span::default()).is_ok());
     }
 }
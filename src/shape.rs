@@ -0,0 +1,183 @@
+//! Ọ̀nụ Shape Checker: The Role Conformance Layer
+//!
+//! This module verifies that every subject placed in a role keeps the role's
+//! promises. It runs over the type-annotated HIR and checks two sites:
+//! each argument declared `via the role`, and each `acts-as` expression.
+//! For a concrete subject type, every behavior the shape promises must be
+//! implemented for that type in the `Registry`. Abstract subjects are left to
+//! monomorphization, which resolves them to concrete types.
+
+use crate::error::{OnuError, Span};
+use crate::hir::{HirDiscourse, HirExpression};
+use crate::registry::Registry;
+use crate::types::OnuType;
+use std::collections::HashMap;
+
+pub struct ShapeChecker<'a> {
+    registry: &'a Registry,
+    scope: HashMap<String, OnuType>, // variable name -> type
+    span: Span,
+}
+
+impl<'a> ShapeChecker<'a> {
+    pub fn new(registry: &'a Registry) -> Self {
+        Self { registry, scope: HashMap::new(), span: Span::default() }
+    }
+
+    /// Checks every role declaration and `acts-as` in the program.
+    pub fn check_program(&mut self, hir: &[HirDiscourse]) -> Result<(), OnuError> {
+        for discourse in hir {
+            let HirDiscourse::Behavior { header, body } = discourse else { continue };
+            self.span = header.span;
+            self.scope = header.args.iter().map(|arg| (arg.name.clone(), arg.typ.clone())).collect();
+            for arg in &header.args {
+                if let Some(role) = &arg.role {
+                    self.registry.verify_acts_as(&arg.typ, role, header.span)?;
+                }
+            }
+            self.check_expression(body)?;
+        }
+        Ok(())
+    }
+
+    fn check_expression(&mut self, expr: &HirExpression) -> Result<(), OnuError> {
+        match expr {
+            HirExpression::ActsAs { subject, shape } => {
+                self.check_expression(subject)?;
+                let subject_type = subject.annotated_type(&self.scope);
+                self.registry.verify_acts_as(&subject_type, shape, self.span)
+            }
+            HirExpression::Derivation { name, typ, value, body, .. } => {
+                self.check_expression(value)?;
+                let resolved = typ.clone().unwrap_or_else(|| value.annotated_type(&self.scope));
+                let shadowed = self.scope.insert(name.clone(), resolved);
+                let result = self.check_expression(body);
+                match shadowed {
+                    Some(previous) => self.scope.insert(name.clone(), previous),
+                    None => self.scope.remove(name.as_str()),
+                };
+                result
+            }
+            HirExpression::Call { args, .. } => args.iter().try_for_each(|arg| self.check_expression(arg)),
+            HirExpression::If { condition, then_branch, else_branch } => {
+                self.check_expression(condition)?;
+                self.check_expression(then_branch)?;
+                self.check_expression(else_branch)
+            }
+            HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => exprs.iter().try_for_each(|e| self.check_expression(e)),
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                fields.iter().try_for_each(|(_, e)| self.check_expression(e))
+            }
+            HirExpression::Case { subject, arms, otherwise, .. } => {
+                self.check_expression(subject)?;
+                for arm in arms {
+                    let outer = std::mem::take(&mut self.scope);
+                    self.scope = arm.scope(&outer);
+                    let result = self.check_expression(&arm.body);
+                    self.scope = outer;
+                    result?;
+                }
+                otherwise.iter().try_for_each(|e| self.check_expression(e))
+            }
+            HirExpression::Index { subject, .. } => self.check_expression(subject),
+            HirExpression::Emit(e) => self.check_expression(e),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CompilerSession;
+    use crate::error::OnuError;
+
+    const SHAPE: &str = "
+the shape Measurable promises:
+    a behavior called magnitude
+        receiving: nothing
+        returning: a float
+
+the behavior called magnitude
+    receiving:
+        a float called input
+    returning:
+        a float
+    as:
+        input
+";
+
+    fn check(body: &str) -> Result<(), OnuError> {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(&format!("{}{}", SHAPE, body)).map(|_| ())
+    }
+
+    #[test]
+    fn test_acts_as_accepts_an_implementing_type() {
+        check("
+the behavior called main
+    as:
+        let x is a float 10.5
+        x acts-as a Measurable
+").unwrap();
+    }
+
+    #[test]
+    fn test_acts_as_refuses_a_type_without_the_promised_behavior() {
+        let err = check("
+the behavior called main
+    as:
+        let n is an integer 10
+        n acts-as a Measurable
+").unwrap_err();
+        let OnuError::ShapeViolation { message, .. } = err else { panic!("Expected a shape violation, got {}", err) };
+        assert!(message.contains("lacks the [magnitude] action"), "{}", message);
+    }
+
+    #[test]
+    fn test_structures_act_as_shapes_through_their_fields() {
+        let source = "
+the shape Measurable promises:
+    a behavior called magnitude-of
+        receiving: nothing
+        returning: a float
+
+the structure called Vector
+    keeps:
+        a float called magnitude
+
+the structure called Label
+    keeps:
+        a string called text
+";
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(&format!("{}{}", source, "
+the behavior called main
+    as:
+        derivation: v derives-from a Vector with magnitude 2.5
+        v acts-as a Measurable
+")).unwrap();
+
+        let mut session = CompilerSession::new().unwrap();
+        let err = session.build_mir(&format!("{}{}", source, "
+the behavior called main
+    as:
+        derivation: l derives-from a Label with text \"hi\"
+        l acts-as a Measurable
+")).unwrap_err();
+        let OnuError::ShapeViolation { message, .. } = err else { panic!("Expected a shape violation, got {}", err) };
+        assert!(message.contains("[Label] refuses to act-as [Measurable]"), "{}", message);
+    }
+
+    #[test]
+    fn test_role_must_be_defined() {
+        let err = check("
+the behavior called size-of
+    takes:
+        a float called x via the role Sizable
+    delivers: a float
+    as:
+        x scales-by 2.0
+").unwrap_err();
+        assert!(matches!(err, OnuError::ShapeViolation { .. }), "{}", err);
+    }
+}
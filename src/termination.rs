@@ -0,0 +1,439 @@
+//! Ọ̀nụ Termination Checker: The Well-Foundedness Layer
+//!
+//! This module proves that recursive behaviors finish. It builds the call graph
+//! of the program's behaviors, finds every cycle in it (self- and mutual
+//! recursion alike), and requires each behavior on a cycle to declare a
+//! `with diminishing:` argument.
+//!
+//! Proof Obligation:
+//! Every call that closes a cycle must sit behind a decision (the base case) and
+//! pass the callee's diminishing argument a value strictly smaller than the
+//! caller's own: the argument `decreased-by` a positive literal, or the `tail-of`
+//! or `init-of` a text, possibly through intermediate derivations.
+//!
+//! Behaviors declared `with no guaranteed termination` are exempt from the proof,
+//! but never silently: they are listed in the `TerminationReport`.
+
+use crate::error::OnuError;
+use crate::hir::{HirBehaviorHeader, HirDiscourse, HirExpression, HirLiteral};
+use std::collections::{HashMap, HashSet};
+
+/// The outcome of a successful check: which behaviors opted out of the proof.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TerminationReport {
+    pub unchecked: Vec<String>,
+}
+
+/// How a value relates to the diminishing argument of the behavior being checked.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Measure {
+    Equal,
+    Smaller,
+    Unknown,
+}
+
+pub struct TerminationChecker<'a> {
+    behaviors: HashMap<&'a str, (&'a HirBehaviorHeader, &'a HirExpression)>,
+}
+
+impl<'a> TerminationChecker<'a> {
+    pub fn new(hir: &'a [HirDiscourse]) -> Self {
+        let behaviors = hir
+            .iter()
+            .filter_map(|discourse| match discourse {
+                HirDiscourse::Behavior { header, body } => Some((header.name.as_str(), (header, body))),
+                _ => None,
+            })
+            .collect();
+        Self { behaviors }
+    }
+
+    /// Checks every recursive cycle in the program, stopping at the first unproven one.
+    pub fn check_program(&self) -> Result<TerminationReport, OnuError> {
+        let mut report = TerminationReport::default();
+        for component in self.recursive_components() {
+            for name in &component {
+                let (header, body) = self.behaviors[name.as_str()];
+                if !header.skip_termination_check {
+                    self.check_behavior(header, body, &component)?;
+                }
+            }
+        }
+        for (name, (header, _)) in &self.behaviors {
+            if header.skip_termination_check {
+                report.unchecked.push(name.to_string());
+            }
+        }
+        report.unchecked.sort();
+        Ok(report)
+    }
+
+    fn check_behavior(&self, header: &HirBehaviorHeader, body: &HirExpression, component: &HashSet<String>) -> Result<(), OnuError> {
+        let Some(diminishing) = &header.diminishing else {
+            return Err(Self::violation(header, format!(
+                "'{}' is recursive but declares no `with diminishing:` argument.",
+                header.name
+            )));
+        };
+        if !header.args.iter().any(|arg| &arg.name == diminishing) {
+            return Err(Self::violation(header, format!(
+                "'{}' declares '{}' as diminishing, but receives no argument by that name.",
+                header.name, diminishing
+            )));
+        }
+        let mut measures = HashMap::new();
+        measures.insert(diminishing.clone(), Measure::Equal);
+        self.check_expression(header, body, &measures, false, component)
+    }
+
+    fn check_expression(
+        &self,
+        header: &HirBehaviorHeader,
+        expr: &HirExpression,
+        measures: &HashMap<String, Measure>,
+        guarded: bool,
+        component: &HashSet<String>,
+    ) -> Result<(), OnuError> {
+        match expr {
+            HirExpression::Call { name, args, .. } => {
+                for arg in args {
+                    self.check_expression(header, arg, measures, guarded, component)?;
+                }
+                if component.contains(name) {
+                    self.check_recursive_call(header, name, args, measures, guarded)?;
+                }
+                // A traversal's behavior receives elements, never the diminishing argument itself.
+                match expr.traversed_behavior() {
+                    Some(behavior) if component.contains(behavior) && !self.behaviors[behavior].0.skip_termination_check => {
+                        Err(Self::violation(header, format!(
+                            "'{}' traverses an array with '{}', which recursion reaches from it; no smaller value can be proven.",
+                            header.name, behavior
+                        )))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            HirExpression::Derivation { name, value, body, .. } => {
+                self.check_expression(header, value, measures, guarded, component)?;
+                let mut inner = measures.clone();
+                inner.insert(name.clone(), Self::measure(value, measures));
+                self.check_expression(header, body, &inner, guarded, component)
+            }
+            HirExpression::If { condition, then_branch, else_branch } => {
+                self.check_expression(header, condition, measures, guarded, component)?;
+                self.check_expression(header, then_branch, measures, true, component)?;
+                self.check_expression(header, else_branch, measures, true, component)
+            }
+            HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => {
+                self.check_expression(header, subject, measures, guarded, component)
+            }
+            HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => {
+                for e in exprs {
+                    self.check_expression(header, e, measures, guarded, component)?;
+                }
+                Ok(())
+            }
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                for (_, e) in fields {
+                    self.check_expression(header, e, measures, guarded, component)?;
+                }
+                Ok(())
+            }
+            HirExpression::Case { subject, arms, otherwise, .. } => {
+                self.check_expression(header, subject, measures, guarded, component)?;
+                for arm in arms {
+                    // A payload is no smaller than anything the caller knows of.
+                    let mut inner = measures.clone();
+                    for name in &arm.bindings {
+                        inner.insert(name.clone(), Measure::Unknown);
+                    }
+                    self.check_expression(header, &arm.body, &inner, true, component)?;
+                }
+                match otherwise {
+                    Some(otherwise) => self.check_expression(header, otherwise, measures, true, component),
+                    None => Ok(()),
+                }
+            }
+            HirExpression::Emit(e) => self.check_expression(header, e, measures, guarded, component),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => Ok(()),
+        }
+    }
+
+    fn check_recursive_call(
+        &self,
+        header: &HirBehaviorHeader,
+        callee: &str,
+        args: &[HirExpression],
+        measures: &HashMap<String, Measure>,
+        guarded: bool,
+    ) -> Result<(), OnuError> {
+        let (callee_header, _) = self.behaviors[callee];
+        // A callee that opted out is reported in the summary; its cycle is not proven here.
+        if callee_header.skip_termination_check {
+            return Ok(());
+        }
+        if !guarded {
+            return Err(Self::violation(header, format!(
+                "The recursive call to '{}' in '{}' is not guarded by a base case.",
+                callee, header.name
+            )));
+        }
+        let position = callee_header
+            .diminishing
+            .as_ref()
+            .and_then(|d| callee_header.args.iter().position(|arg| &arg.name == d));
+        let Some(position) = position else {
+            return Err(Self::violation(header, format!(
+                "'{}' is reached recursively from '{}' but declares no `with diminishing:` argument.",
+                callee, header.name
+            )));
+        };
+        match args.get(position).map(|arg| Self::measure(arg, measures)) {
+            Some(Measure::Smaller) => Ok(()),
+            _ => Err(Self::violation(header, format!(
+                "'{}' calls '{}' without passing a strictly smaller value for its diminishing argument.",
+                header.name, callee
+            ))),
+        }
+    }
+
+    /// Relates a value to the caller's diminishing argument.
+    fn measure(expr: &HirExpression, measures: &HashMap<String, Measure>) -> Measure {
+        match expr {
+            HirExpression::Variable(name) => measures.get(name).copied().unwrap_or(Measure::Unknown),
+            HirExpression::ActsAs { subject, .. } => Self::measure(subject, measures),
+            HirExpression::Call { name, args, .. } => {
+                let shrinks = match (name.as_str(), args.as_slice()) {
+                    ("decreased-by", [subject, HirExpression::Literal(HirLiteral::I64(step))]) if *step > 0 => Some(subject),
+                    ("decreased-by", [subject, HirExpression::Literal(HirLiteral::Integer(step, _))]) if *step > 0 => Some(subject),
+                    ("tail-of" | "init-of", [subject]) => Some(subject),
+                    _ => None,
+                };
+                match shrinks.map(|subject| Self::measure(subject, measures)) {
+                    Some(Measure::Equal | Measure::Smaller) => Measure::Smaller,
+                    _ => Measure::Unknown,
+                }
+            }
+            _ => Measure::Unknown,
+        }
+    }
+
+    /// Returns the strongly connected components of the call graph that contain a cycle.
+    fn recursive_components(&self) -> Vec<HashSet<String>> {
+        let mut names: Vec<&str> = self.behaviors.keys().copied().collect();
+        names.sort();
+        let edges: HashMap<&str, HashSet<&str>> = names
+            .iter()
+            .map(|name| {
+                let mut callees = HashSet::new();
+                self.collect_callees(self.behaviors[name].1, &mut callees);
+                (*name, callees)
+            })
+            .collect();
+
+        let mut tarjan = Tarjan { edges: &edges, index: HashMap::new(), low: HashMap::new(), stack: Vec::new(), on_stack: HashSet::new(), components: Vec::new() };
+        for name in &names {
+            if !tarjan.index.contains_key(name) {
+                tarjan.visit(name);
+            }
+        }
+        tarjan
+            .components
+            .into_iter()
+            .filter(|component| component.len() > 1 || component.iter().any(|name| edges[name].contains(name)))
+            .map(|component| component.into_iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    fn collect_callees(&self, expr: &'a HirExpression, callees: &mut HashSet<&'a str>) {
+        match expr {
+            HirExpression::Call { name, args, .. } => {
+                if let Some((key, _)) = self.behaviors.get_key_value(name.as_str()) {
+                    callees.insert(key);
+                }
+                if let Some((key, _)) = expr.traversed_behavior().and_then(|behavior| self.behaviors.get_key_value(behavior)) {
+                    callees.insert(key);
+                }
+                for arg in args {
+                    self.collect_callees(arg, callees);
+                }
+            }
+            HirExpression::Derivation { value, body, .. } => {
+                self.collect_callees(value, callees);
+                self.collect_callees(body, callees);
+            }
+            HirExpression::If { condition, then_branch, else_branch } => {
+                self.collect_callees(condition, callees);
+                self.collect_callees(then_branch, callees);
+                self.collect_callees(else_branch, callees);
+            }
+            HirExpression::ActsAs { subject, .. } | HirExpression::Index { subject, .. } => self.collect_callees(subject, callees),
+            HirExpression::Tuple(exprs) | HirExpression::Block(exprs) => {
+                for e in exprs {
+                    self.collect_callees(e, callees);
+                }
+            }
+            HirExpression::Construct { fields, .. } | HirExpression::Variant { fields, .. } => {
+                for (_, e) in fields {
+                    self.collect_callees(e, callees);
+                }
+            }
+            HirExpression::Case { subject, arms, otherwise, .. } => {
+                self.collect_callees(subject, callees);
+                for arm in arms {
+                    self.collect_callees(&arm.body, callees);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.collect_callees(otherwise, callees);
+                }
+            }
+            HirExpression::Emit(e) => self.collect_callees(e, callees),
+            HirExpression::Literal(_) | HirExpression::Variable(_) => {}
+        }
+    }
+
+    fn violation(header: &HirBehaviorHeader, message: String) -> OnuError {
+        OnuError::TerminationViolation { message, span: header.span }
+    }
+}
+
+/// Tarjan's strongly connected components over the behavior call graph.
+struct Tarjan<'g, 'a> {
+    edges: &'g HashMap<&'a str, HashSet<&'a str>>,
+    index: HashMap<&'a str, usize>,
+    low: HashMap<&'a str, usize>,
+    stack: Vec<&'a str>,
+    on_stack: HashSet<&'a str>,
+    components: Vec<Vec<&'a str>>,
+}
+
+impl<'a> Tarjan<'_, 'a> {
+    fn visit(&mut self, name: &'a str) {
+        let index = self.index.len();
+        self.index.insert(name, index);
+        self.low.insert(name, index);
+        self.stack.push(name);
+        self.on_stack.insert(name);
+
+        for &callee in &self.edges[name] {
+            if !self.index.contains_key(callee) {
+                self.visit(callee);
+                let low = self.low[name].min(self.low[callee]);
+                self.low.insert(name, low);
+            } else if self.on_stack.contains(callee) {
+                let low = self.low[name].min(self.index[callee]);
+                self.low.insert(name, low);
+            }
+        }
+
+        if self.low[name] == self.index[name] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack.remove(member);
+                component.push(member);
+                if member == name {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CompilerSession;
+    use crate::error::OnuError;
+
+    fn check(source: &str) -> Result<CompilerSession, OnuError> {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(source)?;
+        Ok(session)
+    }
+
+    #[test]
+    fn test_mutual_recursion_through_derivations_is_proven() {
+        let session = check("
+the behavior called is-even
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then 1
+            else
+                derivation: previous derives-from an integer n decreased-by 1
+                previous utilizes is-odd
+
+the behavior called is-odd
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then 0
+            else (n decreased-by 1) utilizes is-even
+").unwrap();
+        assert!(session.termination.unchecked.is_empty());
+    }
+
+    #[test]
+    fn test_unguarded_recursion_is_refused() {
+        let result = check("
+the behavior called countdown
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        (n decreased-by 1) utilizes countdown
+");
+        let Err(OnuError::TerminationViolation { message, .. }) = result else { panic!("Expected a termination violation") };
+        assert!(message.contains("not guarded"), "{}", message);
+    }
+
+    #[test]
+    fn test_unproven_behaviors_are_reported() {
+        let session = check("
+the behavior called wander
+    takes:
+        an integer called n
+    delivers: an integer
+    with no guaranteed termination
+    as:
+        (n added-to 1) utilizes wander
+").unwrap();
+        assert_eq!(session.termination.unchecked, vec!["wander".to_string()]);
+    }
+
+    #[test]
+    fn test_recursion_through_a_traversal_is_refused() {
+        let result = check("
+the behavior called total
+    takes:
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then 0
+            else [n] folds-with accumulate 0
+
+the behavior called accumulate
+    takes:
+        an integer called sum
+        an integer called n
+    delivers: an integer
+    with diminishing: n
+    as:
+        if n matches 0
+            then sum
+            else sum added-to ((n decreased-by 1) utilizes total)
+");
+        let Err(OnuError::TerminationViolation { message, .. }) = result else { panic!("Expected a termination violation") };
+        assert!(message.contains("traverses an array with 'accumulate'"), "{}", message);
+    }
+}
+
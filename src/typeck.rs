@@ -0,0 +1,1084 @@
+//! Ọ̀nụ Type Checker: The Static Verification Layer
+//!
+//! This module walks the HIR of every behavior and infers the `OnuType` of each
+//! expression against the signatures held by the `Registry`. It verifies call
+//! arity and argument types, checks that each body delivers what its header
+//! promises, and records the inferred types back into the HIR so that MIR
+//! construction and code generation never need to guess.
+//!
+//! Widths never mix implicitly: both operands of an arithmetic verb must share
+//! one numeric type, and a value changes width only through a conversion
+//! behavior (`as-u8`, `widens-to-i64`, ...). A literal of no declared width
+//! settles on the width its context requires, provided it fits; an integer
+//! literal may settle on a float width if the float holds it exactly.
+//!
+//! Overloaded behaviors, such as the float builtins, are resolved against the
+//! `Registry` by the types of their arguments.
+//!
+//! Arrays hold elements of one type. The array verbs and the traversals
+//! (`transforms-each-by`, `filters-by`, `folds-with`) are typed here by the
+//! array's element type; a traversal's behavior is checked against the element
+//! (and, for a fold, the accumulator) as if it were called on them.
+//!
+//! Matrices hold floats. Their dimensions are tracked through literals,
+//! derivations and the matrix verbs wherever they are known, so adding or
+//! multiplying mismatched shapes is refused here; the runtime checks the rest.
+//!
+//! Structures are built with every declared field, in any order, and reordered
+//! here as declared. A field kept internal may be read only by behaviors whose
+//! subject is its structure.
+//!
+//! A variant is built with every value of its payload, like a structure. A case
+//! analysis must analyse a value of the variety its arms answer; each arm binds
+//! the whole payload or none of it, and the arms settle on one type as the
+//! branches of a decision do. Whether every variant is answered is left to the
+//! exhaustiveness checker.
+//!
+//! Shapes act as wildcards here: a value of any concrete type may stand where a
+//! role is expected, and the monomorphizer later resolves the role statically.
+
+use crate::error::{OnuError, Span};
+use crate::hir::{HirBehaviorHeader, HirDiscourse, HirExpression, HirLiteral, PROJECTIONS, TRAVERSALS};
+use crate::parser::Visibility;
+use crate::registry::{accessor_name, BehaviorSignature, Registry};
+use crate::types::OnuType;
+use std::collections::HashMap;
+
+/// The verbs lowered to arithmetic instructions; both operands share one numeric type.
+const ARITHMETIC: [&str; 4] = ["added-to", "decreased-by", "scales-by", "partitions-by"];
+
+/// The verbs lowered to comparisons; they deliver a truth value.
+const COMPARISONS: [&str; 3] = ["matches", "exceeds", "falls-short-of"];
+
+/// The logical verbs; they take and deliver truth values.
+const LOGIC: [&str; 3] = ["unites-with", "joins-with", "opposes"];
+
+/// The array verbs; each works on an array of any element type.
+const ARRAYS: [&str; 4] = ["element-at", "length-of", "appended-with", "slice-of"];
+
+pub struct TypeChecker<'a> {
+    registry: &'a Registry,
+    scope: HashMap<String, OnuType>, // variable name -> type
+    dimensions: HashMap<String, (usize, usize)>, // matrix variable name -> (rows, cols), where known
+    behavior: String,
+    subject: Option<OnuType>, // the type of the current behavior's first input
+    span: Span, // where the innermost call or derivation being checked is written
+}
+
+impl<'a> TypeChecker<'a> {
+    pub fn new(registry: &'a Registry) -> Self {
+        Self {
+            registry,
+            scope: HashMap::new(),
+            dimensions: HashMap::new(),
+            behavior: String::new(),
+            subject: None,
+            span: Span::default(),
+        }
+    }
+
+    /// Checks and annotates every behavior in the program, stopping at the first mismatch.
+    pub fn check_program(&mut self, hir: &mut [HirDiscourse]) -> Result<(), OnuError> {
+        for discourse in hir.iter_mut() {
+            if let HirDiscourse::Behavior { header, body } = discourse {
+                self.check_behavior(header, body)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_behavior(&mut self, header: &HirBehaviorHeader, body: &mut HirExpression) -> Result<(), OnuError> {
+        self.behavior = header.name.clone();
+        self.subject = header.args.first().map(|arg| arg.typ.clone());
+        self.span = header.span;
+        self.scope = header.args.iter().map(|arg| (arg.name.clone(), arg.typ.clone())).collect();
+        self.dimensions.clear();
+
+        let delivered = self.infer(body)?;
+        if header.return_type != OnuType::Nothing && !self.settle(body, &delivered, &header.return_type)? {
+            return Err(self.error(format!(
+                "The behavior '{}' promises to deliver {} but its body delivers {}.",
+                header.name, header.return_type, delivered
+            )));
+        }
+        Ok(())
+    }
+
+    /// Infers the type of an expression, annotating calls and derivations along the way.
+    /// A mismatch is reported at the innermost call or derivation that contains it.
+    fn infer(&mut self, expr: &mut HirExpression) -> Result<OnuType, OnuError> {
+        let outer = self.span;
+        if let HirExpression::Call { span, .. } | HirExpression::Derivation { span, .. } = expr
+            && span.line != 0
+        {
+            self.span = *span;
+        }
+        let inferred = self.infer_expression(expr);
+        self.span = outer;
+        inferred
+    }
+
+    fn infer_expression(&mut self, expr: &mut HirExpression) -> Result<OnuType, OnuError> {
+        match expr {
+            HirExpression::Literal(lit) => Ok(match lit {
+                HirLiteral::I64(_) => OnuType::I64,
+                HirLiteral::F64(_) => OnuType::F64,
+                HirLiteral::Integer(_, typ) | HirLiteral::Float(_, typ) => typ.clone(),
+                HirLiteral::Boolean(_) => OnuType::Boolean,
+                HirLiteral::Text(_) => OnuType::Strings,
+                HirLiteral::Nothing => OnuType::Nothing,
+            }),
+            HirExpression::Variable(name) => self.scope.get(name).cloned().ok_or_else(|| {
+                self.error(format!("The name '{}' is used but never derived or received.", name))
+            }),
+            HirExpression::Call { name, args, typ, .. } => {
+                self.registry.verify_reachable(&self.behavior, name, self.span)?;
+                let mut arg_types = Vec::new();
+                for (position, arg) in args.iter_mut().enumerate() {
+                    // A traversal names its behavior; the name is not a value in scope.
+                    if position == 1 && TRAVERSALS.contains(&name.as_str()) && let HirExpression::Variable(behavior) = arg {
+                        self.registry.verify_reachable(&self.behavior, behavior, self.span)?;
+                        arg_types.push(OnuType::Nothing);
+                        continue;
+                    }
+                    arg_types.push(self.infer(arg)?);
+                }
+                let delivered = self.infer_call(name, args, &arg_types)?;
+                *typ = Some(delivered.clone());
+                Ok(delivered)
+            }
+            HirExpression::Derivation { name, typ, value, body, .. } => {
+                let inferred = self.infer(value)?;
+                let resolved = match typ.as_ref() {
+                    Some(OnuType::Shape(_)) | None => inferred,
+                    Some(declared) if self.settle(value, &inferred, declared)? => declared.clone(),
+                    Some(declared) => {
+                        return Err(self.error(format!(
+                            "'{}' is declared as {} but derives from {}.",
+                            name, declared, inferred
+                        )));
+                    }
+                };
+                *typ = Some(resolved.clone());
+                let shadowed = self.scope.insert(name.clone(), resolved);
+                let shadowed_dimensions = match self.dimensions_of(value) {
+                    Some(dimensions) => self.dimensions.insert(name.clone(), dimensions),
+                    None => self.dimensions.remove(name.as_str()),
+                };
+                let delivered = self.infer(body);
+                match shadowed {
+                    Some(previous) => self.scope.insert(name.clone(), previous),
+                    None => self.scope.remove(name.as_str()),
+                };
+                match shadowed_dimensions {
+                    Some(previous) => self.dimensions.insert(name.clone(), previous),
+                    None => self.dimensions.remove(name.as_str()),
+                };
+                delivered
+            }
+            HirExpression::If { condition, then_branch, else_branch } => {
+                let condition_type = self.infer(condition)?;
+                if !matches!(condition_type, OnuType::Boolean | OnuType::Shape(_)) {
+                    return Err(self.error(format!("A condition must be a truth value, but {} was given.", condition_type)));
+                }
+                let mut then_type = self.infer(then_branch)?;
+                let mut else_type = self.infer(else_branch)?;
+                // A branch delivering a bare literal takes the width of the other branch.
+                if then_type != else_type {
+                    if self.settle(then_branch, &then_type, &else_type)? {
+                        then_type = else_type.clone();
+                    } else if self.settle(else_branch, &else_type, &then_type)? {
+                        else_type = then_type.clone();
+                    }
+                }
+                if then_type == else_type {
+                    Ok(then_type)
+                } else if then_type == OnuType::Nothing || else_type == OnuType::Nothing {
+                    Ok(OnuType::Nothing)
+                } else if Self::compatible(&then_type, &else_type) {
+                    Ok(if matches!(then_type, OnuType::Shape(_)) { else_type } else { then_type })
+                } else {
+                    Err(self.error(format!(
+                        "The branches of a decision deliver different types: {} and {}.",
+                        then_type, else_type
+                    )))
+                }
+            }
+            HirExpression::ActsAs { subject, .. } => self.infer(subject),
+            HirExpression::Tuple(elements) => {
+                let mut types = Vec::new();
+                for element in elements.iter_mut() {
+                    types.push(self.infer(element)?);
+                }
+                Ok(OnuType::Tuple(types))
+            }
+            HirExpression::Construct { structure, fields } => {
+                let registry = self.registry;
+                let declared = registry.get_structure(structure).ok_or_else(|| {
+                    self.error(format!("The structure '{}' is built but never declared.", structure))
+                })?;
+                let declared: Vec<(String, OnuType)> = declared.iter().map(|field| (field.name.clone(), field.typ.clone())).collect();
+                self.settle_fields(structure, "field", &declared, fields)?;
+                Ok(OnuType::Record(structure.clone()))
+            }
+            HirExpression::Variant { variety, variant, fields } => {
+                let registry = self.registry;
+                let declared = registry
+                    .get_variety(variety)
+                    .and_then(|variants| variants.iter().find(|v| v.name == *variant))
+                    .ok_or_else(|| self.error(format!("The variant '{}' is built but never declared.", variant)))?;
+                self.settle_fields(variant, "value", &declared.payload, fields)?;
+                Ok(OnuType::Variety(variety.clone()))
+            }
+            HirExpression::Case { subject, variety, arms, otherwise } => {
+                let subject_type = self.infer(subject)?;
+                if !matches!(&subject_type, OnuType::Variety(v) if v == variety) && !matches!(subject_type, OnuType::Shape(_)) {
+                    return Err(self.error(format!(
+                        "A case analysis answers the variants of {}, but its subject is {}.",
+                        variety, subject_type
+                    )));
+                }
+                let registry = self.registry;
+                let variants = registry.get_variety(variety).ok_or_else(|| {
+                    self.error(format!("The variety '{}' is analysed but never declared.", variety))
+                })?;
+                let mut delivered = Vec::new();
+                for arm in arms.iter_mut() {
+                    let Some(declared) = variants.iter().find(|v| v.name == arm.variant) else {
+                        return Err(self.error(format!("'{}' is not a variant of {}.", arm.variant, variety)));
+                    };
+                    // An arm binds every value of the payload, or none of them.
+                    if !arm.bindings.is_empty() && arm.bindings.len() != declared.payload.len() {
+                        return Err(self.error(format!(
+                            "The variant '{}' carries {} value(s), but its arm binds {}.",
+                            arm.variant, declared.payload.len(), arm.bindings.len()
+                        )));
+                    }
+                    arm.payload = declared.payload.iter().take(arm.bindings.len()).map(|(_, typ)| typ.clone()).collect();
+                    let (outer_scope, outer_dimensions) = (self.scope.clone(), self.dimensions.clone());
+                    self.scope = arm.scope(&outer_scope);
+                    self.dimensions.retain(|name, _| !arm.bindings.contains(name));
+                    let answer = self.infer(&mut arm.body);
+                    self.scope = outer_scope;
+                    self.dimensions = outer_dimensions;
+                    delivered.push(answer?);
+                }
+                if let Some(otherwise) = otherwise.as_mut() {
+                    delivered.push(self.infer(otherwise)?);
+                }
+
+                // Every answer settles on the type of the first, as the branches of a decision do.
+                let mut answers: Vec<&mut HirExpression> = arms.iter_mut().map(|arm| &mut arm.body).chain(otherwise.as_deref_mut()).collect();
+                let expected = delivered[0].clone();
+                for (answer, actual) in answers.iter_mut().zip(delivered.iter_mut()).skip(1) {
+                    if *actual != expected && self.settle(answer, actual, &expected)? {
+                        *actual = expected.clone();
+                    }
+                }
+                match delivered.iter().find(|actual| !Self::compatible(actual, &expected)) {
+                    None => Ok(expected),
+                    Some(_) if delivered.contains(&OnuType::Nothing) => Ok(OnuType::Nothing),
+                    Some(other) => Err(self.error(format!(
+                        "The arms of a case analysis deliver different types: {} and {}.",
+                        expected, other
+                    ))),
+                }
+            }
+            HirExpression::Index { subject, index } => match self.infer(subject)? {
+                OnuType::Tuple(types) => types.get(*index).cloned().ok_or_else(|| {
+                    self.error(format!("A tuple of {} elements has no element for '{}' to project.", types.len(), PROJECTIONS[*index]))
+                }),
+                other => Err(self.error(format!("'{}' projects an element of a tuple, but its subject is {}.", PROJECTIONS[*index], other))),
+            },
+            HirExpression::Block(exprs) => {
+                let mut last = OnuType::Nothing;
+                for e in exprs.iter_mut() {
+                    last = self.infer(e)?;
+                }
+                Ok(last)
+            }
+            HirExpression::Emit(e) => match self.infer(e)? {
+                OnuType::Record(structure) => Err(self.error(format!("A {} has no text of its own; broadcast its fields instead.", structure))),
+                OnuType::Variety(variety) => Err(self.error(format!("A {} has no text of its own; analyse it with 'when' instead.", variety))),
+                _ => Ok(OnuType::Nothing),
+            },
+        }
+    }
+
+    /// Settles each given field on its declared type and reorders the fields as declared.
+    /// `owner` names the structure or variant and `kind` what it calls its members.
+    fn settle_fields(&mut self, owner: &str, kind: &str, declared: &[(String, OnuType)], fields: &mut Vec<(String, HirExpression)>) -> Result<(), OnuError> {
+        let mut given = std::mem::take(fields);
+        for (field, typ) in declared {
+            let Some(position) = given.iter().position(|(name, _)| name == field) else {
+                return Err(self.error(format!("A {} is built without its {} '{}'.", owner, kind, field)));
+            };
+            let (name, mut value) = given.remove(position);
+            let actual = self.infer(&mut value)?;
+            if !self.settle(&mut value, &actual, typ)? {
+                return Err(self.error(format!(
+                    "The {} '{}' of a {} is {}, but {} was given.",
+                    kind, field, owner, typ, actual
+                )));
+            }
+            fields.push((name, value));
+        }
+        if let Some((name, _)) = given.first() {
+            return Err(self.error(format!("A {} has no {} '{}'.", owner, kind, name)));
+        }
+        Ok(())
+    }
+
+    fn infer_call(&self, name: &str, args: &mut [HirExpression], arg_types: &[OnuType]) -> Result<OnuType, OnuError> {
+        if name == "array" {
+            // Every element settles on the type of the first.
+            let element = arg_types.first().cloned().unwrap_or(OnuType::Nothing);
+            for (arg, t) in args.iter_mut().zip(arg_types).skip(1) {
+                if !self.settle(arg, t, &element)? {
+                    return Err(self.error(format!("An array cannot hold both {} and {}.", element, t)));
+                }
+            }
+            return Ok(OnuType::Array(Box::new(element)));
+        }
+        if ARRAYS.contains(&name) {
+            let element = self.element_type(name, arg_types)?;
+            let expected = match name {
+                "element-at" => vec![OnuType::I64],
+                "appended-with" => vec![element.clone()],
+                "slice-of" => vec![OnuType::I64, OnuType::I64],
+                _ => vec![],
+            };
+            for (position, ((arg, actual), expected)) in args.iter_mut().zip(arg_types).skip(1).zip(&expected).enumerate() {
+                if !self.settle(arg, actual, expected)? {
+                    return Err(self.error(format!(
+                        "Argument {} of '{}' must be {}, but {} was given.",
+                        position + 2,
+                        name,
+                        expected,
+                        actual
+                    )));
+                }
+            }
+            return Ok(match name {
+                "element-at" => element,
+                "length-of" => OnuType::I64,
+                _ => arg_types[0].clone(),
+            });
+        }
+        if TRAVERSALS.contains(&name) {
+            return self.infer_traversal(name, args, arg_types);
+        }
+        if crate::hir::matrix_dimensions(name).is_some() {
+            // Every entry settles on a float.
+            for (arg, t) in args.iter_mut().zip(arg_types) {
+                if !self.settle(arg, t, &OnuType::F64)? {
+                    return Err(self.error(format!("A matrix holds floats, but an entry is {}.", t)));
+                }
+            }
+            return Ok(OnuType::Matrix);
+        }
+        if let Some(target) = OnuType::from_conversion(name) {
+            let [source] = arg_types else {
+                return Err(self.error(format!("'{}' converts exactly one number, but was given {} argument(s).", name, arg_types.len())));
+            };
+            if !Self::is_numeric(source) {
+                return Err(self.error(format!("'{}' converts numbers, but was given {}.", name, source)));
+            }
+            if name.starts_with("widens-to-") && *source != target && !source.widens_to(&target) {
+                return Err(self.error(format!(
+                    "'{}' cannot widen {} to {} without loss; convert explicitly with 'as-{}'.",
+                    name, source, target, target
+                )));
+            }
+            return Ok(target);
+        }
+        if LOGIC.contains(&name) {
+            if let Some(t) = arg_types.iter().find(|t| !matches!(t, OnuType::Boolean | OnuType::Shape(_))) {
+                return Err(self.error(format!("'{}' requires truth values, but was given {}.", name, t)));
+            }
+            return Ok(OnuType::Boolean);
+        }
+        // Matrix arithmetic is resolved against its overloads below, like any behavior.
+        if (ARITHMETIC.contains(&name) || COMPARISONS.contains(&name)) && arg_types.first() != Some(&OnuType::Matrix) {
+            let [lhs, rhs] = arg_types else {
+                return Err(self.error(format!(
+                    "'{}' requires two numbers, but was given {} argument(s).",
+                    name,
+                    arg_types.len()
+                )));
+            };
+            // A bare literal takes the width of the other operand.
+            let concrete = !matches!(lhs, OnuType::Shape(_)) && !matches!(rhs, OnuType::Shape(_));
+            let (lhs, rhs) = if lhs == rhs || !concrete {
+                (lhs, rhs)
+            } else if self.settle(&mut args[1], rhs, lhs)? {
+                (lhs, lhs)
+            } else if self.settle(&mut args[0], lhs, rhs)? {
+                (rhs, rhs)
+            } else {
+                (lhs, rhs)
+            };
+            // Only `matches` may also compare texts and truth values.
+            let comparable = name == "matches" || (Self::is_numeric(lhs) && Self::is_numeric(rhs));
+            if !Self::compatible(lhs, rhs) || !comparable {
+                return Err(self.error(format!("'{}' requires two numbers of the same type, but was given {} and {}.", name, lhs, rhs)));
+            }
+            if COMPARISONS.contains(&name) {
+                return Ok(OnuType::Boolean);
+            }
+            return Ok(if matches!(lhs, OnuType::Shape(_)) { rhs.clone() } else { lhs.clone() });
+        }
+
+        let signature = self.registry.resolve_signature(name, arg_types).ok_or_else(|| {
+            self.error(format!("The behavior '{}' is invoked but never defined.", name))
+        })?;
+        if signature.input_types.len() != arg_types.len() {
+            return Err(self.error(format!(
+                "'{}' receives {} argument(s), but was given {}.",
+                name,
+                signature.input_types.len(),
+                arg_types.len()
+            )));
+        }
+        for (position, ((arg, actual), expected)) in args.iter_mut().zip(arg_types).zip(&signature.input_types).enumerate() {
+            if !self.settle(arg, actual, expected)? {
+                return Err(self.error(format!(
+                    "Argument {} of '{}' must be {}, but {} was given.",
+                    position + 1,
+                    name,
+                    expected,
+                    actual
+                )));
+            }
+        }
+        if arg_types.first() == Some(&OnuType::Matrix) {
+            self.check_dimensions(name, args)?;
+        }
+        if let [OnuType::Record(structure)] = arg_types {
+            self.check_visibility(structure, name)?;
+        }
+        Ok(Self::delivered(signature, arg_types))
+    }
+
+    /// Refuses reading an internal field outside the behaviors whose subject is its structure.
+    fn check_visibility(&self, structure: &str, accessor: &str) -> Result<(), OnuError> {
+        let Some(field) = self.registry.get_structure(structure).and_then(|fields| {
+            fields.iter().find(|field| accessor_name(&field.name) == accessor)
+        }) else {
+            return Ok(());
+        };
+        let owner = OnuType::Record(structure.to_string());
+        if field.visibility == Visibility::Internal && self.subject.as_ref() != Some(&owner) {
+            return Err(OnuError::VisibilityViolation {
+                message: format!(
+                    "'{}' reads the field '{}', which the {} keeps internal; only behaviors whose subject is a {} may read it.",
+                    self.behavior, field.name, structure, structure
+                ),
+                span: self.span,
+            });
+        }
+        Ok(())
+    }
+
+    /// The dimensions of a matrix expression, where they can be known statically.
+    fn dimensions_of(&self, expr: &HirExpression) -> Option<(usize, usize)> {
+        match expr {
+            HirExpression::Variable(name) => self.dimensions.get(name).copied(),
+            HirExpression::ActsAs { subject, .. } => self.dimensions_of(subject),
+            HirExpression::Call { name, args, .. } => match (name.as_str(), args.as_slice()) {
+                ("added-to" | "scales-by", [subject, _]) => self.dimensions_of(subject),
+                ("multiplied-with", [subject, object]) => Some((self.dimensions_of(subject)?.0, self.dimensions_of(object)?.1)),
+                ("transposed", [subject]) => self.dimensions_of(subject).map(|(rows, cols)| (cols, rows)),
+                _ => crate::hir::matrix_dimensions(name),
+            },
+            _ => None,
+        }
+    }
+
+    /// Refuses matrix verbs whose operands' known shapes cannot combine.
+    fn check_dimensions(&self, name: &str, args: &[HirExpression]) -> Result<(), OnuError> {
+        let known = |expr: &HirExpression| self.dimensions_of(expr);
+        match (name, args) {
+            ("added-to", [a, b]) => match (known(a), known(b)) {
+                (Some(x), Some(y)) if x != y => Err(self.error(format!(
+                    "'added-to' cannot combine a {}x{} matrix with a {}x{} matrix.",
+                    x.0, x.1, y.0, y.1
+                ))),
+                _ => Ok(()),
+            },
+            ("multiplied-with", [a, b]) => match (known(a), known(b)) {
+                (Some(x), Some(y)) if x.1 != y.0 => Err(self.error(format!(
+                    "'multiplied-with' cannot multiply a {}x{} matrix by a {}x{} matrix; the object needs {} rows.",
+                    x.0, x.1, y.0, y.1, x.1
+                ))),
+                _ => Ok(()),
+            },
+            ("determinant", [m]) => match known(m) {
+                Some((rows, cols)) if rows != cols => Err(self.error(format!(
+                    "'determinant' needs a square matrix, but was given a {}x{} matrix.",
+                    rows, cols
+                ))),
+                _ => Ok(()),
+            },
+            ("entry-at", [m, HirExpression::Literal(row), HirExpression::Literal(col)]) => {
+                let position = |lit: &HirLiteral| match lit {
+                    HirLiteral::I64(n) => Some(*n as i128),
+                    HirLiteral::Integer(n, _) => Some(*n),
+                    _ => None,
+                };
+                match (known(m), position(row), position(col)) {
+                    (Some((rows, cols)), Some(row), Some(col)) if !(0..rows as i128).contains(&row) || !(0..cols as i128).contains(&col) => {
+                        Err(self.error(format!("'entry-at' position ({}, {}) lies outside a {}x{} matrix.", row, col, rows, cols)))
+                    }
+                    _ => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Types `xs transforms-each-by f`, `xs filters-by f` and `xs folds-with f initial`
+    /// by checking `f` as if it were called on an element (after the accumulator, for a fold).
+    fn infer_traversal(&self, name: &str, args: &mut [HirExpression], arg_types: &[OnuType]) -> Result<OnuType, OnuError> {
+        let element = self.element_type(name, arg_types)?;
+        let Some(HirExpression::Variable(behavior)) = args.get(1) else {
+            return Err(self.error(format!("'{}' takes the name of a behavior as its second argument.", name)));
+        };
+        let behavior = behavior.clone();
+        let mut inputs = vec![element.clone()];
+        if let Some(initial) = arg_types.get(2) {
+            inputs.insert(0, initial.clone());
+        }
+        let signature = self.registry.resolve_signature(&behavior, &inputs).ok_or_else(|| {
+            self.error(format!("The behavior '{}' is invoked but never defined.", behavior))
+        })?;
+        if signature.input_types.len() != inputs.len() {
+            return Err(self.error(format!(
+                "'{}' applies '{}' to {} argument(s), but it receives {}.",
+                name,
+                behavior,
+                inputs.len(),
+                signature.input_types.len()
+            )));
+        }
+        // A fold's initial value settles on the accumulator the behavior receives.
+        if name == "folds-with" && self.settle(&mut args[2], &inputs[0], &signature.input_types[0])? && !matches!(signature.input_types[0], OnuType::Shape(_)) {
+            inputs[0] = signature.input_types[0].clone();
+        }
+        if let Some((expected, actual)) = signature.input_types.iter().zip(&inputs).find(|(expected, actual)| !Self::compatible(expected, actual)) {
+            return Err(self.error(format!("'{}' cannot apply '{}' to {}; it receives {}.", name, behavior, actual, expected)));
+        }
+        let delivered = Self::delivered(signature, &inputs);
+        match name {
+            "transforms-each-by" => Ok(OnuType::Array(Box::new(delivered))),
+            "filters-by" if Self::compatible(&delivered, &OnuType::Boolean) => Ok(OnuType::Array(Box::new(element))),
+            "folds-with" if Self::compatible(&delivered, &inputs[0]) => Ok(inputs.swap_remove(0)),
+            "filters-by" => Err(self.error(format!("'{}' needs a behavior delivering a truth value, but '{}' delivers {}.", name, behavior, delivered))),
+            _ => Err(self.error(format!(
+                "'{}' needs a behavior delivering its accumulator {}, but '{}' delivers {}.",
+                name, inputs[0], behavior, delivered
+            ))),
+        }
+    }
+
+    /// The element type of the array an array verb or traversal works on.
+    fn element_type(&self, name: &str, arg_types: &[OnuType]) -> Result<OnuType, OnuError> {
+        match arg_types.first() {
+            Some(OnuType::Array(element)) => Ok(element.as_ref().clone()),
+            Some(OnuType::Shape(role)) => Ok(OnuType::Shape(role.clone())),
+            Some(other) => Err(self.error(format!("'{}' works on arrays, but was given {}.", name, other))),
+            None => Err(self.error(format!("'{}' works on an array, but was given none.", name))),
+        }
+    }
+
+    /// What a call delivers. A role in return position delivers whatever concrete
+    /// type filled the role.
+    fn delivered(signature: &BehaviorSignature, arg_types: &[OnuType]) -> OnuType {
+        match &signature.return_type {
+            OnuType::Shape(role) => signature
+                .input_types
+                .iter()
+                .zip(arg_types)
+                .find(|(expected, actual)| matches!(expected, OnuType::Shape(r) if r == role) && !matches!(actual, OnuType::Shape(_)))
+                .map(|(_, actual)| actual.clone())
+                .unwrap_or_else(|| signature.return_type.clone()),
+            other => other.clone(),
+        }
+    }
+
+    /// Whether a value of `actual` type may stand where `expected` is required.
+    /// A literal of no declared width in a delivering position is rewritten to the
+    /// expected width of its own family, and refused if its value does not fit.
+    fn settle(&self, expr: &mut HirExpression, actual: &OnuType, expected: &OnuType) -> Result<bool, OnuError> {
+        match expr {
+            HirExpression::Literal(HirLiteral::I64(n)) if expected.is_integer() => {
+                let value = *n as i128;
+                if !expected.holds(value) {
+                    return Err(self.error(format!("The literal {} does not fit in {}.", value, expected)));
+                }
+                if *expected != OnuType::I64 {
+                    *expr = HirExpression::Literal(HirLiteral::Integer(value, expected.clone()));
+                }
+                Ok(true)
+            }
+            HirExpression::Literal(HirLiteral::I64(n)) if expected.is_float() => {
+                if !expected.holds(*n as i128) {
+                    return Err(self.error(format!("The literal {} does not fit in {}.", n, expected)));
+                }
+                *expr = HirExpression::Literal(HirLiteral::Float(*n as f64, expected.clone()));
+                Ok(true)
+            }
+            HirExpression::Literal(HirLiteral::F64(x)) if *expected == OnuType::F32 => {
+                *expr = HirExpression::Literal(HirLiteral::Float(*x, OnuType::F32));
+                Ok(true)
+            }
+            HirExpression::Block(exprs) if !Self::compatible(actual, expected) => match exprs.last_mut() {
+                Some(last) => self.settle(last, actual, expected),
+                None => Ok(false),
+            },
+            HirExpression::Derivation { body, .. } if !Self::compatible(actual, expected) => self.settle(body, actual, expected),
+            // An array literal settles element by element; an empty one takes any element type.
+            HirExpression::Call { name, args, typ, .. } if name == "array" && !Self::compatible(actual, expected) => {
+                let OnuType::Array(element) = expected else { return Ok(false) };
+                for arg in args.iter_mut() {
+                    let arg_type = arg.annotated_type(&self.scope);
+                    if !self.settle(arg, &arg_type, element)? {
+                        return Ok(false);
+                    }
+                }
+                *typ = Some(expected.clone());
+                Ok(true)
+            }
+            _ => Ok(Self::compatible(actual, expected)),
+        }
+    }
+
+    fn compatible(a: &OnuType, b: &OnuType) -> bool {
+        match (a, b) {
+            (OnuType::Shape(_), _) | (_, OnuType::Shape(_)) => true,
+            (OnuType::Tuple(xs), OnuType::Tuple(ys)) => xs.len() == ys.len() && xs.iter().zip(ys).all(|(x, y)| Self::compatible(x, y)),
+            (OnuType::Array(x), OnuType::Array(y)) => Self::compatible(x, y),
+            _ => a == b,
+        }
+    }
+
+    fn is_numeric(t: &OnuType) -> bool {
+        t.bit_width().is_some() || matches!(t, OnuType::Shape(_))
+    }
+
+    fn error(&self, message: String) -> OnuError {
+        OnuError::TypeError {
+            message: format!("In '{}': {}", self.behavior, message),
+            span: self.span,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CompilerSession;
+    use crate::error::OnuError;
+    use crate::hir::{HirDiscourse, HirExpression, HirLiteral};
+    use crate::types::OnuType;
+
+    fn check(source: &str) -> Result<CompilerSession, OnuError> {
+        let mut session = CompilerSession::new().unwrap();
+        session.build_mir(source)?;
+        Ok(session)
+    }
+
+    fn type_error(source: &str) -> String {
+        match check(source) {
+            Err(OnuError::TypeError { message, .. }) => message,
+            Err(other) => panic!("Expected a type error, got {}", other),
+            Ok(_) => panic!("Expected a type error, but the discourse was accepted"),
+        }
+    }
+
+    #[test]
+    fn test_calls_are_annotated_with_their_types() {
+        let session = check("
+the behavior called greet
+    receiving:
+        an integer called n
+    returning:
+        a string
+    as:
+        n as-text
+").unwrap();
+        let HirDiscourse::Behavior { body, .. } = &session.hir[0] else { panic!("Expected a behavior") };
+        assert!(matches!(body, HirExpression::Call { typ: Some(OnuType::Strings), .. }));
+        let mir = session.mir.unwrap();
+        assert_eq!(mir.functions[0].ssa_types.get(&1), Some(&OnuType::Strings));
+    }
+
+    #[test]
+    fn test_argument_types_are_checked() {
+        let message = type_error("
+the behavior called shout
+    receiving:
+        an integer called n
+    returning:
+        a string
+    as:
+        n joined-with \"!\"
+");
+        assert!(message.contains("Argument 1 of 'joined-with'"), "{}", message);
+    }
+
+    #[test]
+    fn test_mismatches_are_located_in_the_body() {
+        let source = "
+the behavior called shout
+    receiving:
+        an integer called n
+    returning:
+        a string
+    as:
+        derivation: loud derives-from n joined-with \"!\"
+        loud
+";
+        match check(source) {
+            Err(OnuError::TypeError { span, .. }) => assert_eq!(span.line, 8),
+            other => panic!("Expected a type error, got {:?}", other.err()),
+        }
+    }
+
+    #[test]
+    fn test_delivered_type_must_match_the_header() {
+        let message = type_error("
+the behavior called count
+    receiving:
+        a string called s
+    returning:
+        an integer
+    as:
+        s tail-of
+");
+        assert!(message.contains("promises to deliver i64"), "{}", message);
+    }
+
+    #[test]
+    fn test_arithmetic_requires_matching_numbers() {
+        let message = type_error("
+the behavior called mix
+    receiving:
+        an integer called n
+        a float called x
+    returning:
+        an integer
+    as:
+        n added-to x
+");
+        assert!(message.contains("requires two numbers"), "{}", message);
+    }
+
+    #[test]
+    fn test_decisions_require_truth_values() {
+        let message = type_error("
+the behavior called pick
+    receiving:
+        an integer called n
+    returning:
+        an integer
+    as:
+        if n then 1 else 0
+");
+        assert!(message.contains("condition"), "{}", message);
+
+        let message = type_error("
+the behavior called both
+    receiving:
+        an integer called n
+    returning:
+        a boolean
+    as:
+        n unites-with (n matches 0)
+");
+        assert!(message.contains("requires truth values"), "{}", message);
+    }
+
+    #[test]
+    fn test_integer_literals_settle_on_floats_they_fit_exactly() {
+        check("
+the behavior called triple
+    receiving:
+        a float called x
+    returning:
+        a float
+    as:
+        x scales-by 3
+").unwrap();
+        let message = type_error("
+the behavior called nudge
+    receiving:
+        an f32 called x
+    returning:
+        an f32
+    as:
+        x added-to 16777217
+");
+        assert!(message.contains("does not fit in f32"), "{}", message);
+    }
+
+    #[test]
+    fn test_indexing_text_becomes_char_at() {
+        let session = check("
+the behavior called first
+    receiving:
+        a string called s
+    returning:
+        an integer
+    as:
+        s char-at 0
+").unwrap();
+        let HirDiscourse::Behavior { body, .. } = &session.hir[0] else { panic!("Expected a behavior") };
+        assert!(matches!(body, HirExpression::Call { name, .. } if name == "char-at"));
+    }
+
+    #[test]
+    fn test_projections_read_tuples_by_position() {
+        check("
+the behavior called swapped
+    takes:
+        a tuple of (integer : string) called pair
+    delivers: a tuple of (string : integer)
+    as:
+        derivation: (n : s) derives-from pair
+        (s : pair first-of)
+").unwrap();
+
+        let message = type_error("
+the behavior called third
+    takes:
+        a tuple of (integer : integer) called pair
+    delivers: an integer
+    as:
+        pair third-of
+");
+        assert!(message.contains("A tuple of 2 elements has no element for 'third-of'"), "{}", message);
+
+        let message = type_error("
+the behavior called first
+    takes:
+        a string called s
+    delivers: an integer
+    as:
+        s first-of
+");
+        assert!(message.contains("'first-of' projects an element of a tuple, but its subject is"), "{}", message);
+    }
+
+    #[test]
+    fn test_case_arms_are_checked_against_their_variety() {
+        let signal = "
+the variety called Signal is one of:
+    a Stop
+    a Go carrying an integer called speed
+";
+        let message = type_error(&format!("{}{}", signal, "
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Stop then 0
+            is a Go carrying speed then speed as-text
+"));
+        assert!(message.contains("The arms of a case analysis deliver different types: i64 and"), "{}", message);
+
+        let message = type_error(&format!("{}{}", signal, "
+the behavior called pace
+    takes:
+        an integer called s
+    delivers: an integer
+    as:
+        when s
+            is a Stop then 0
+            otherwise 1
+"));
+        assert!(message.contains("A case analysis answers the variants of Signal, but its subject is i64."), "{}", message);
+
+        let message = type_error(&format!("{}{}", signal, "
+the behavior called fastest
+    takes: nothing
+    delivers: a Signal
+    as:
+        a Go with speed 1.5
+"));
+        assert!(message.contains("The value 'speed' of a Go is i64, but f64 was given."), "{}", message);
+    }
+
+    const POINT: &str = "
+the structure called Point
+    exposes:
+        a float called x
+    keeps internal:
+        an integer called id
+
+the behavior called identity-of
+    takes:
+        a Point called p
+    delivers: an integer
+    as:
+        p id-of
+";
+
+    #[test]
+    fn test_structures_are_built_with_every_field() {
+        let message = type_error(&format!("{}{}", POINT, "
+the behavior called origin
+    takes: nothing
+    delivers: a Point
+    as:
+        a Point with x 0.0 x 1.0
+"));
+        assert!(message.contains("A Point is built without its field 'id'."), "{}", message);
+
+        let message = type_error(&format!("{}{}", POINT, "
+the behavior called origin
+    takes: nothing
+    delivers: a Point
+    as:
+        a Point with id 0.5 x 0.0
+"));
+        assert!(message.contains("The field 'id' of a Point is i64, but f64 was given."), "{}", message);
+    }
+
+    #[test]
+    fn test_internal_fields_are_read_only_by_their_structure() {
+        let err = check(&format!("{}{}", POINT, "
+the behavior called peek
+    takes:
+        an integer called n
+        a Point called p
+    delivers: an integer
+    as:
+        p id-of
+")).err().unwrap();
+        let OnuError::VisibilityViolation { message, .. } = err else { panic!("Expected a visibility violation, got {}", err) };
+        assert!(message.contains("'peek' reads the field 'id', which the Point keeps internal"), "{}", message);
+    }
+
+    #[test]
+    fn test_widths_do_not_mix_implicitly() {
+        let message = type_error("
+the behavior called mix
+    receiving:
+        a u8 called small
+        an i64 called large
+    returning:
+        an i64
+    as:
+        large added-to small
+");
+        assert!(message.contains("given i64 and u8"), "{}", message);
+    }
+
+    #[test]
+    fn test_widening_must_be_lossless() {
+        check("
+the behavior called widen
+    receiving:
+        a u8 called small
+    returning:
+        an i16
+    as:
+        small widens-to-i16
+").unwrap();
+        let message = type_error("
+the behavior called narrow
+    receiving:
+        an i16 called n
+    returning:
+        a u8
+    as:
+        n widens-to-u8
+");
+        assert!(message.contains("convert explicitly with 'as-u8'"), "{}", message);
+    }
+
+    #[test]
+    fn test_literals_settle_on_the_context_width() {
+        let session = check("
+the behavior called bump
+    receiving:
+        a u8 called n
+    returning:
+        a u8
+    as:
+        n added-to 1
+").unwrap();
+        let HirDiscourse::Behavior { body, .. } = &session.hir[0] else { panic!("Expected a behavior") };
+        let HirExpression::Call { args, .. } = body else { panic!("Expected a call") };
+        assert_eq!(args[1], HirExpression::Literal(HirLiteral::Integer(1, OnuType::U8)));
+
+        let message = type_error("
+the behavior called overflow
+    receiving:
+        a u8 called n
+    returning:
+        a u8
+    as:
+        n added-to 300
+");
+        assert!(message.contains("300 does not fit in u8"), "{}", message);
+    }
+
+    #[test]
+    fn test_traversals_are_typed_through_the_named_behavior() {
+        check("
+the behavior called halved
+    receiving:
+        an integer called n
+    returning:
+        a float
+    as:
+        (n utilizes as-f64) partitions-by 2.0
+
+the behavior called halves
+    receiving:
+        an array of integer called xs
+    returning:
+        an array of float
+    as:
+        xs transforms-each-by halved
+").unwrap();
+        let message = type_error("
+the behavior called evens
+    receiving:
+        an array of integer called xs
+    returning:
+        an array of integer
+    as:
+        xs filters-by as-text
+");
+        assert!(message.contains("needs a behavior delivering a truth value"), "{}", message);
+    }
+
+    #[test]
+    fn test_matrix_dimensions_are_tracked_through_derivations() {
+        let message = type_error("
+the behavior called skewed
+    receiving: nothing
+    returning:
+        a float
+    as:
+        derivation: m derives-from a matrix [1 2 3 : 4 5 6]
+        derivation: square derives-from a matrix m multiplied-with (m transposed)
+        (m multiplied-with square) determinant
+");
+        assert!(message.contains("cannot multiply a 2x3 matrix by a 2x2 matrix"), "{}", message);
+        let message = type_error("
+the behavior called corner
+    receiving: nothing
+    returning:
+        a float
+    as:
+        [1 2 : 3 4] entry-at 0 2
+");
+        assert!(message.contains("position (0, 2) lies outside a 2x2 matrix"), "{}", message);
+    }
+}
+
@@ -1,8 +1,8 @@
-/// Ọ̀nụ Core Types: The Domain Logic Layer
-///
-/// This module defines the formal type system of Ọ̀nụ.
-/// Following Clean Architecture, types are first-class domain entities
-/// that govern the rules of static analysis and runtime evaluation.
+//! Ọ̀nụ Core Types: The Domain Logic Layer
+//!
+//! This module defines the formal type system of Ọ̀nụ.
+//! Following Clean Architecture, types are first-class domain entities
+//! that govern the rules of static analysis and runtime evaluation.
 
 use std::fmt;
 
@@ -26,6 +26,8 @@ pub enum OnuType {
     // --- Structural ---
     Tuple(Vec<OnuType>), // Fixed-size collection of potentially different types
     Array(Box<OnuType>), // Variable-size collection of the same type
+    Record(String),      // A structure declared with `the structure called`, by name
+    Variety(String),     // A tagged union declared with `the variety called`, by name
     
     // --- Abstract ---
     Shape(String), // Reference to a Shape (Interface)
@@ -59,12 +61,102 @@ impl fmt::Display for OnuType {
                 write!(f, ")")
             },
             OnuType::Array(inner) => write!(f, "array of {}", inner),
+            OnuType::Record(name) | OnuType::Variety(name) => write!(f, "{}", name),
             OnuType::Shape(name) => write!(f, "role {}", name),
         }
     }
 }
 
 impl OnuType {
+    /// Every integer and float width, narrowest first within each family.
+    pub const NUMERIC: [OnuType; 12] = [
+        OnuType::I8, OnuType::I16, OnuType::I32, OnuType::I64, OnuType::I128,
+        OnuType::U8, OnuType::U16, OnuType::U32, OnuType::U64, OnuType::U128,
+        OnuType::F32, OnuType::F64,
+    ];
+
+    /// The target of a conversion behavior: `as-u8` converts to u8 (wrapping or
+    /// truncating as needed), `widens-to-i64` converts only without loss.
+    pub fn from_conversion(name: &str) -> Option<Self> {
+        let target = name.strip_prefix("as-").or_else(|| name.strip_prefix("widens-to-"))?;
+        Self::NUMERIC.into_iter().find(|t| t.to_string() == target)
+    }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(
+            self,
+            OnuType::I8 | OnuType::I16 | OnuType::I32 | OnuType::I64 | OnuType::I128
+                | OnuType::U8 | OnuType::U16 | OnuType::U32 | OnuType::U64 | OnuType::U128
+        )
+    }
+
+    pub fn is_unsigned(&self) -> bool {
+        matches!(self, OnuType::U8 | OnuType::U16 | OnuType::U32 | OnuType::U64 | OnuType::U128)
+    }
+
+    pub fn is_float(&self) -> bool {
+        matches!(self, OnuType::F32 | OnuType::F64)
+    }
+
+    /// The width in bits of a numeric type, or `None` for any other type.
+    pub fn bit_width(&self) -> Option<u32> {
+        match self {
+            OnuType::I8 | OnuType::U8 => Some(8),
+            OnuType::I16 | OnuType::U16 => Some(16),
+            OnuType::I32 | OnuType::U32 | OnuType::F32 => Some(32),
+            OnuType::I64 | OnuType::U64 | OnuType::F64 => Some(64),
+            OnuType::I128 | OnuType::U128 => Some(128),
+            _ => None,
+        }
+    }
+
+    /// Whether every value of this numeric type is exactly representable in `target`.
+    pub fn widens_to(&self, target: &OnuType) -> bool {
+        let (Some(from), Some(to)) = (self.bit_width(), target.bit_width()) else { return false };
+        if self.is_float() {
+            return target.is_float() && from <= to;
+        }
+        if target.is_float() {
+            // The significand holds 24 bits in an f32 and 53 in an f64.
+            let significand = if to == 32 { 24 } else { 53 };
+            let magnitude = if self.is_unsigned() { from } else { from - 1 };
+            return magnitude <= significand;
+        }
+        match (self.is_unsigned(), target.is_unsigned()) {
+            (false, true) => false,
+            (true, false) => from < to,
+            _ => from <= to,
+        }
+    }
+
+    /// The smallest and largest values of an integer type.
+    pub fn integer_range(&self) -> Option<(i128, i128)> {
+        match self {
+            OnuType::I8 => Some((i8::MIN as i128, i8::MAX as i128)),
+            OnuType::I16 => Some((i16::MIN as i128, i16::MAX as i128)),
+            OnuType::I32 => Some((i32::MIN as i128, i32::MAX as i128)),
+            OnuType::I64 => Some((i64::MIN as i128, i64::MAX as i128)),
+            OnuType::I128 => Some((i128::MIN, i128::MAX)),
+            OnuType::U8 => Some((0, u8::MAX as i128)),
+            OnuType::U16 => Some((0, u16::MAX as i128)),
+            OnuType::U32 => Some((0, u32::MAX as i128)),
+            OnuType::U64 => Some((0, u64::MAX as i128)),
+            // A u128 is carried in an i128, so its range stops at i128::MAX.
+            OnuType::U128 => Some((0, i128::MAX)),
+            _ => None,
+        }
+    }
+
+    /// Whether an integer literal lies within the range of this integer type, or
+    /// within the integers this float type represents exactly.
+    pub fn holds(&self, value: i128) -> bool {
+        match self {
+            OnuType::F32 => value.unsigned_abs() <= 1 << 24,
+            OnuType::F64 => value.unsigned_abs() <= 1 << 53,
+            _ => self.integer_range().is_some_and(|(min, max)| (min..=max).contains(&value)),
+        }
+    }
+
     /// Maps a discourse type name string to an OnuType.
     pub fn from_name(name: &str) -> Option<Self> {
         match name {
@@ -151,3 +151,228 @@ fn test_pedagogical_diagnostics() {
     assert!(err_str.contains("Conclusion:"));
     assert!(err_str.contains("violates the grammatical covenant"));
 }
+
+#[test]
+fn test_llvm_codegen_lowers_emit_by_type() {
+    let mut session = CompilerSession::new().unwrap();
+    let source = "
+the effect behavior called run
+    as:
+        broadcasts 42
+";
+    let binary = session.compile(source).unwrap();
+    let mentions = |symbol: &[u8]| binary.windows(symbol.len()).any(|w| w == symbol);
+    assert!(mentions(b"broadcasts-integer"), "Integer broadcasts should call the integer printer");
+    assert!(!mentions(b"puts"), "Broadcasts should no longer be rewritten to puts");
+}
+
+#[test]
+fn test_checked_arithmetic_guards_overflow_and_zero_divisors() {
+    let source = "
+the behavior called share
+    takes:
+        an integer called total
+        an integer called parts
+    delivers: an integer
+    as:
+        (total scales-by 2) partitions-by parts
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("llvm.smul.with.overflow.i64"), "{}", ir);
+    assert!(ir.contains("overflow-trap") && ir.contains("zero-divisor-trap"), "{}", ir);
+
+    let mut wrapping = CompilerSession::new().unwrap();
+    wrapping.arithmetic = onu::mir::ArithmeticMode::Wrapping;
+    let ir = wrapping.get_llvm_ir(source).unwrap();
+    assert!(!ir.contains("with.overflow"), "{}", ir);
+    assert!(ir.contains("zero-divisor-trap"), "A zero divisor is refused in every mode");
+}
+
+#[test]
+fn test_traps_name_the_line_of_the_failing_arithmetic() {
+    let source = "
+the behavior called share
+    takes:
+        an integer called total
+        an integer called parts
+    delivers: an integer
+    as:
+        derivation: doubled derives-from an integer total scales-by 2
+        doubled partitions-by parts
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    let trap_call = |trap: &str| ir.lines().find(|line| line.contains("call") && line.contains(trap)).unwrap_or_default().to_string();
+    assert!(trap_call("overflow-trap").ends_with("i64 8)"), "{}", ir);
+    assert!(trap_call("zero-divisor-trap").ends_with("i64 9)"), "{}", ir);
+}
+
+#[test]
+fn test_float_builtins_lower_to_intrinsics_with_optional_fast_math() {
+    let source = "
+the behavior called hypotenuse
+    takes:
+        an f32 called a
+        an f32 called b
+    delivers: an f32
+    as:
+        ((a scales-by a) added-to (b raised-to 2)) square-root-of
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("llvm.sqrt.f32") && ir.contains("llvm.pow.f32"), "{}", ir);
+    assert!(!ir.contains("unsafe-fp-math"), "{}", ir);
+
+    let mut fast = CompilerSession::new().unwrap();
+    fast.fast_math = true;
+    let ir = fast.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("\"unsafe-fp-math\"=\"true\""), "{}", ir);
+}
+
+#[test]
+fn test_matrix_products_become_loops_only_for_polly() {
+    let source = "
+the behavior called product
+    takes:
+        a matrix called a
+        a matrix called b
+    delivers: a matrix
+    as:
+        a multiplied-with b
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("@matrix-product("), "{}", ir);
+
+    let mut polly = CompilerSession::new().unwrap();
+    polly.polly_loops = true;
+    let ir = polly.get_llvm_ir(source).unwrap();
+    assert!(!ir.contains("@matrix-product("), "{}", ir);
+    assert!(ir.contains("@matrix-product-target(") && ir.contains("fmul double"), "{}", ir);
+}
+
+#[test]
+fn test_tuples_are_returned_through_a_c_result_pointer() {
+    let source = "
+the behavior called swapped
+    takes:
+        an integer called a
+        a float called b
+    delivers: a tuple of (float : integer)
+    as:
+        (b : a)
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("define void @swapped({ double, i64 }* sret({ double, i64 }) %0, i64 %1, double %2)"), "{}", ir);
+}
+
+#[test]
+fn test_structures_lower_to_named_llvm_structs() {
+    let source = "
+the structure called Point
+    keeps:
+        a float called x
+        an integer called tag
+
+the behavior called retagged
+    takes:
+        a Point called p
+        an integer called tag
+    delivers: a Point
+    as:
+        a Point with x (p x-of) tag tag
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("%Point = type { double, i64 }"), "{}", ir);
+    assert!(ir.contains("define void @retagged(%Point* sret(%Point) %0, %Point %1, i64 %2)"), "{}", ir);
+}
+
+#[test]
+fn test_varieties_lower_to_tagged_structs_and_switches() {
+    let source = "
+the variety called Signal is one of:
+    a Stop
+    a Go carrying an integer called speed
+    a Turn carrying a float called degrees
+
+the behavior called started
+    takes:
+        a Signal called s
+    delivers: a Signal
+    as:
+        when s
+            is a Stop then a Go with speed 1
+            is a Go carrying speed then a Go with speed (speed added-to 1)
+            is a Turn carrying degrees then a Turn with degrees (degrees scales-by 2.0)
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("%Signal = type { i64, i64, double }"), "{}", ir);
+    assert!(ir.contains("define void @started(%Signal* sret(%Signal) %0, %Signal %1)"), "{}", ir);
+    assert!(ir.contains("switch i64"), "{}", ir);
+}
+
+#[test]
+fn test_behaviors_kept_internal_get_internal_linkage() {
+    let source = "
+the behavior called doubled
+    keeps internal
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n added-to n
+
+the behavior called tripled
+    keeps internal
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n scales-by 3
+
+the behavior called quadrupled
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        (n utilizes doubled) utilizes doubled
+";
+    let session = CompilerSession::new().unwrap();
+    let ir = session.get_llvm_ir(source).unwrap();
+    assert!(ir.contains("define internal i64 @doubled(i64 %0)"), "{}", ir);
+    assert!(ir.contains("define i64 @quadrupled(i64 %0)"), "{}", ir);
+    // Nothing calls 'tripled', so it is dropped.
+    assert!(!ir.contains("@tripled"), "{}", ir);
+}
+
+#[test]
+fn test_target_machine_emits_object_and_assembly() {
+    use onu::codegen::{EmitKind, TargetOptions};
+    let source = "
+the behavior called double
+    receiving:
+        an integer called n
+    returning:
+        an integer
+    as:
+        n added-to n
+";
+    let dir = std::env::temp_dir();
+    let object = dir.join("onu_emit_test.o");
+    let assembly = dir.join("onu_emit_test.s");
+
+    CompilerSession::new().unwrap().emit_artifact(source, EmitKind::Object, &TargetOptions::default(), &object).unwrap();
+    CompilerSession::new().unwrap().emit_artifact(source, EmitKind::Assembly, &TargetOptions::default(), &assembly).unwrap();
+
+    let object_bytes = std::fs::read(&object).unwrap();
+    assert!(!object_bytes.is_empty(), "Object file should not be empty");
+    let listing = std::fs::read_to_string(&assembly).unwrap();
+    assert!(listing.contains("double"), "Assembly should define the behavior symbol");
+
+    let _ = std::fs::remove_file(object);
+    let _ = std::fs::remove_file(assembly);
+}
@@ -20,3 +20,78 @@ the behavior called broken
     assert!(err.contains("PEER REVIEW MEMO"));
     assert!(err.contains("Observation:"));
 }
+
+#[test]
+fn test_every_parse_error_is_reported_in_one_batch() {
+    let script = r#"
+the behavior called first
+    with intent: take a wrong turn
+    takes: nothing
+    delivers: an integer
+    as:
+        derivation: x derives-from )
+        derivation: y derives-from 2
+        y added-to )
+
+the behavior called second
+    with intent: promise what cannot be
+    takes: nothing
+    delivers: )
+    as: 2
+
+the behavior called third
+    with intent: stand unharmed
+    takes: nothing
+    delivers: an integer
+    as: 3
+"#;
+    let mut compiler = onu::CompilerSession::new().unwrap();
+    let err = compiler.build_mir(script).unwrap_err();
+    let onu::error::OnuError::ReviewBatch { memos } = &err else {
+        panic!("Expected a batch of memos, found {}", err);
+    };
+    let lines: Vec<usize> = memos.iter().map(|memo| match memo {
+        onu::error::OnuError::ParseError { span, .. } => span.line,
+        other => panic!("Expected a parse error, found {}", other),
+    }).collect();
+    assert_eq!(lines, vec![7, 9, 14], "{}", err);
+    assert!(err.to_string().contains("The review found 3 violations"));
+
+    // The units that parsed stay available to later passes.
+    let names: Vec<&str> = compiler.ast.iter().filter_map(|d| match d {
+        onu::parser::Discourse::Behavior { header, .. } => Some(header.name.as_str()),
+        _ => None,
+    }).collect();
+    assert_eq!(names, vec!["first", "third"]);
+}
+
+#[test]
+fn test_parse_and_type_errors_share_one_batch() {
+    let script = r#"
+the behavior called unparsed
+    with intent: promise what cannot be
+    takes: nothing
+    delivers: )
+    as: 2
+
+the behavior called mistyped
+    with intent: deliver the wrong kind
+    takes: nothing
+    delivers: an integer
+    as:
+        "text"
+
+the behavior called sound
+    with intent: stand unharmed
+    takes: nothing
+    delivers: an integer
+    as: 3
+"#;
+    let mut compiler = onu::CompilerSession::new().unwrap();
+    let err = compiler.build_mir(script).unwrap_err();
+    let onu::error::OnuError::ReviewBatch { memos } = &err else {
+        panic!("Expected a batch of memos, found {}", err);
+    };
+    assert!(matches!(memos.as_slice(), [onu::error::OnuError::ParseError { .. }, onu::error::OnuError::TypeError { .. }]), "{}", err);
+    assert!(err.to_string().contains("'mistyped' promises to deliver"), "{}", err);
+}
@@ -56,9 +56,11 @@ the behavior called bar
         .output()
         .expect("Failed to execute cargo run");
 
-    let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("PEER REVIEW MEMO"));
-    assert!(stdout.contains("Duplicate semantic implementation detected"));
+    // Diagnostics go to stderr, and a refused program fails with its memo's status.
+    assert_eq!(output.status.code(), Some(20));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("PEER REVIEW MEMO"));
+    assert!(stderr.contains("Duplicate semantic implementation detected"));
     
     fs::remove_file(file_path).unwrap();
 }
@@ -86,7 +88,9 @@ the effect behavior called run
 
     assert!(output.status.success());
     let stdout = String::from_utf8_lossy(&output.stdout);
-    assert!(stdout.contains("Hello, World!"));
+    // The check's own messages stay off stdout when the program runs.
+    assert_eq!(stdout, "Hello, World!\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Behavior 'run' parsed and registered"));
     
     fs::remove_file(file_path).unwrap();
 }
@@ -236,3 +240,139 @@ the effect behavior called main
     
     fs::remove_file(file_path).unwrap();
 }
+
+#[test]
+fn test_cli_builds_and_runs_a_package() {
+    let package = std::env::temp_dir().join("onu_package_int");
+    let _ = fs::remove_dir_all(&package);
+    fs::create_dir_all(package.join("src")).unwrap();
+    fs::write(package.join("Onu.toml"), r#"
+[package]
+name = "greeter"
+entry = "Greeter"
+sources = ["src"]
+
+[build]
+profile = "release"
+"#).unwrap();
+    fs::write(package.join("src").join("greeter.onu"), r#"
+the module called Greeter
+    with concern: greeting
+    draws-from: Salutation
+
+the effect behavior called run
+    with intent: greeting
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts ("World" utilizes saluted)
+"#).unwrap();
+    fs::write(package.join("src").join("salutation.onu"), r#"
+the module called Salutation
+    with concern: greeting
+
+the behavior called exclaimed
+    keeps internal
+    with intent: greeting
+    takes:
+        a string called words
+    delivers: a string
+    as:
+        words joined-with "!"
+
+the behavior called saluted
+    with intent: greeting
+    takes:
+        a string called name
+    delivers: a string
+    as:
+        ("Hello, " joined-with name) utilizes exclaimed
+"#).unwrap();
+    let manifest = format!("--manifest-path={}", package.join("Onu.toml").display());
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "run", &manifest])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stdout));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Hello, World!"), "{}", stdout);
+    assert!(package.join("target").join("release").join("greeter").is_file());
+    // Each module is compiled into an object of its own.
+    let deps = package.join("target").join("release").join("deps");
+    let mut objects: Vec<String> = fs::read_dir(&deps).unwrap().map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned()).collect();
+    objects.sort();
+    assert_eq!(objects, vec!["Greeter.o".to_string(), "Salutation.o".to_string()]);
+
+    let output = Command::new("cargo")
+        .args(["run", "--", "build", &manifest, "--profile=debug"])
+        .output()
+        .expect("Failed to execute cargo run");
+
+    assert!(output.status.success());
+    assert!(package.join("target").join("debug").join("greeter").is_file());
+
+    fs::remove_dir_all(&package).unwrap();
+}
+
+#[test]
+fn test_cli_subcommands_emit_test_and_format() {
+    let dir = std::env::temp_dir().join("onu_subcommands_int");
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("checks.onu");
+    fs::write(&file, r#"
+the behavior called doubled
+  takes:
+    an integer called n
+  delivers: an integer
+  with no guaranteed termination
+  as:
+    n added-to n
+
+the behavior called test-doubling
+  takes: nothing
+  delivers: a boolean
+  as:
+    (2 utilizes doubled) matches 4
+
+the behavior called test-tripling
+  takes: nothing
+  delivers: a boolean
+  as:
+    (2 utilizes doubled) matches 6
+"#).unwrap();
+    let file = file.to_str().unwrap();
+    let onu = |args: &[&str]| {
+        Command::new("cargo")
+            .args(["run", "--quiet", "--"])
+            .args(args)
+            .output()
+            .expect("Failed to execute cargo run")
+    };
+
+    let output = onu(&["emit", "llvm-ir", file, "-O2"]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stdout).contains("define i64 @doubled(i64 %0)"));
+    // The IR comes from the checked session, so its summaries are reported too.
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Termination summary: 1 behavior(s)"));
+
+    let output = onu(&["test", file]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("test test-doubling ... ok"), "{}", stdout);
+    assert!(stdout.contains("test test-tripling ... FAILED"), "{}", stdout);
+    assert!(stdout.contains("1 passed; 1 failed"), "{}", stdout);
+
+    assert_eq!(onu(&["fmt", "--check", file]).status.code(), Some(1));
+    assert!(onu(&["fmt", file]).status.success());
+    assert!(onu(&["fmt", file, "--check"]).status.success());
+    assert!(fs::read_to_string(file).unwrap().contains("\n    takes:\n        an integer called n\n"));
+
+    let output = onu(&["check", file, "--no-such-flag"]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown option '--no-such-flag'"));
+
+    fs::remove_dir_all(&dir).unwrap();
+}
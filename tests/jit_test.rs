@@ -0,0 +1,496 @@
+use onu::CompilerSession;
+use onu::env::MockEnvironment;
+use onu::mir::ArithmeticMode;
+
+fn run_jit(source: &str) -> Vec<String> {
+    run_jit_with(source, ArithmeticMode::default())
+}
+
+fn run_jit_with(source: &str, arithmetic: ArithmeticMode) -> Vec<String> {
+    let mut session = CompilerSession::new().unwrap();
+    session.arithmetic = arithmetic;
+    let mut env = MockEnvironment::new();
+    let result = session.run_jit(source, &mut env);
+    assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+    env.emitted
+}
+
+#[test]
+fn test_jit_hello_world() {
+    let emitted = run_jit(r#"
+the effect behavior called run
+    with intent: greet
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts "Hello, World!"
+"#);
+    assert_eq!(emitted, vec!["Hello, World!"]);
+}
+
+#[test]
+fn test_jit_factorial() {
+    let emitted = run_jit(r#"
+the behavior called factorial
+    with intent: calculate the product of a sequence
+    takes:
+        an integer called the-count
+    delivers: an integer
+    with diminishing: the-count
+    as:
+        if the-count matches 0
+            then 1
+            else
+                derivation: the-previous-count derives-from an integer the-count decreased-by 1
+                derivation: the-accumulated-value derives-from an integer the-previous-count utilizes factorial
+                the-count scales-by the-accumulated-value
+
+the effect behavior called main
+    with intent: demonstrate factorial
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: result derives-from an integer 5 utilizes factorial
+        broadcasts result
+"#);
+    assert_eq!(emitted, vec!["120"]);
+}
+
+#[test]
+fn test_jit_text_builtins() {
+    let emitted = run_jit(r#"
+the effect behavior called run
+    with intent: join two fragments
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: greeting derives-from a string "Hello, " joined-with "Onu"
+        broadcasts greeting
+        broadcasts (1 : 2.5)
+"#);
+    assert_eq!(emitted, vec!["Hello, Onu", "(1, 2.5)"]);
+}
+
+#[test]
+fn test_jit_links_every_registered_builtin() {
+    // bf.onu relies on set-char, which the old C runtime never implemented.
+    let source = std::fs::read_to_string("bf.onu").unwrap();
+    let emitted = run_jit(&source);
+    assert_eq!(emitted, vec!["Initializing Tape Machine...", "E"]);
+}
+
+const DOUBLING: &str = r#"
+the behavior called double
+    takes:
+        an i8 called n
+    delivers: an i8
+    as:
+        n scales-by 2
+
+the effect behavior called run
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts (100 utilizes double)
+        broadcasts (7 partitions-by 2)
+"#;
+
+#[test]
+fn test_jit_wrapping_arithmetic_wraps() {
+    assert_eq!(run_jit_with(DOUBLING, ArithmeticMode::Wrapping), vec!["-56", "3"]);
+}
+
+#[test]
+fn test_jit_saturating_arithmetic_clamps() {
+    assert_eq!(run_jit_with(DOUBLING, ArithmeticMode::Saturating), vec!["127", "3"]);
+}
+
+
+#[test]
+fn test_jit_logic_short_circuits() {
+    let emitted = run_jit(r#"
+the effect behavior called run
+    with intent: decide lazily
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: never derives-from a boolean (1 matches 2) unites-with ((10 partitions-by 0) matches 1)
+        derivation: always derives-from a boolean (1 matches 1) joins-with ((10 partitions-by 0) matches 1)
+        if (never opposes) unites-with always
+            then broadcasts "lazy"
+            else broadcasts "eager"
+"#);
+    assert_eq!(emitted, vec!["lazy"]);
+}
+
+const FLOATS: &str = r#"
+the effect behavior called run
+    with intent: exercise floats
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: side derives-from a float 2.0 scales-by 3
+        derivation: area derives-from a float side raised-to 2.0
+        derivation: small derives-from an f32 (area square-root-of) utilizes as-f32
+        derivation: d1 derives-from nothing broadcasts (area formatted-to 2)
+        derivation: d2 derives-from nothing broadcasts ((small added-to 0.5) utilizes as-text)
+        derivation: d3 derives-from nothing broadcasts ((7.5 floor-of) as-text)
+        derivation: d4 derives-from nothing broadcasts ((small rounded) formatted-to 1)
+        broadcasts ((area exceeds 30) utilizes as-text)
+"#;
+
+#[test]
+fn test_jit_float_builtins() {
+    assert_eq!(run_jit(FLOATS), vec!["36.00", "6.5", "7", "6.0", "true"]);
+}
+
+#[test]
+fn test_jit_float_conversions_saturate_at_the_target_width() {
+    let emitted = run_jit(r#"
+the effect behavior called run
+    with intent: convert floats
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: big derives-from a float 300.7
+        derivation: below derives-from a float 0.0 decreased-by 5.5
+        derivation: d1 derives-from nothing broadcasts (big utilizes as-i8)
+        derivation: d2 derives-from nothing broadcasts (below utilizes as-u8)
+        derivation: d3 derives-from nothing broadcasts (below utilizes as-i16)
+        broadcasts ((big scales-by big) utilizes as-u16)
+"#);
+    assert_eq!(emitted, vec!["127", "0", "-5", "65535"]);
+}
+
+#[test]
+fn test_jit_array_traversals() {
+    let emitted = run_jit(r#"
+the behavior called is-large
+    takes:
+        an integer called n
+    delivers: a boolean
+    as:
+        n exceeds 2
+
+the behavior called summed
+    takes:
+        an integer called total
+        an integer called n
+    delivers: an integer
+    as:
+        total added-to n
+
+the effect behavior called run
+    with intent: traverse an array
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: xs derives-from an array of integer [1 2 3 4]
+        derivation: d1 derives-from nothing broadcasts (xs transforms-each-by as-text)
+        derivation: d2 derives-from nothing broadcasts ((xs filters-by is-large) appended-with 5)
+        derivation: d3 derives-from nothing broadcasts (xs slice-of 1 3)
+        derivation: d4 derives-from nothing broadcasts [[1.5] [2.5 3.5]]
+        broadcasts ((xs folds-with summed 0) added-to (xs length-of))
+"#);
+    assert_eq!(emitted, vec!["[1, 2, 3, 4]", "[3, 4, 5]", "[2, 3]", "[[1.5], [2.5, 3.5]]", "14"]);
+}
+
+const MATRICES: &str = r#"
+the effect behavior called run
+    with intent: exercise matrices
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: a derives-from a matrix [1 2 3 : 4 5 6]
+        derivation: d1 derives-from nothing broadcasts (a multiplied-with (a transposed))
+        derivation: d2 derives-from nothing broadcasts ((a scales-by 2) added-to a)
+        derivation: d3 derives-from nothing broadcasts (a entry-at 1 0)
+        broadcasts ([2 1 : 1 3] utilizes determinant)
+"#;
+
+#[test]
+fn test_jit_matrices() {
+    let expected = vec!["[14 32 : 32 77]", "[3 6 9 : 12 15 18]", "4", "5"];
+    assert_eq!(run_jit(MATRICES), expected);
+
+    let mut session = CompilerSession::new().unwrap();
+    session.polly_loops = true;
+    let mut env = MockEnvironment::new();
+    assert!(session.run_jit(MATRICES, &mut env).is_ok());
+    assert_eq!(env.emitted, expected);
+}
+
+#[test]
+fn test_jit_tuples() {
+    let emitted = run_jit(r#"
+the behavior called divided-by
+    takes:
+        an integer called n
+        an integer called d
+    delivers: a tuple of (integer : integer)
+    as:
+        derivation: q derives-from n partitions-by d
+        (q : n decreased-by (q scales-by d))
+
+the effect behavior called run
+    with intent: return and destructure tuples
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: (q : r) derives-from 17 divided-by 5
+        derivation: pair derives-from (1.5 : "two")
+        derivation: d1 derives-from nothing broadcasts (q as-text)
+        derivation: d2 derives-from nothing broadcasts (r as-text)
+        derivation: d3 derives-from nothing broadcasts (pair second-of)
+        derivation: d4 derives-from nothing broadcasts ((pair first-of) as-text)
+        broadcasts ("abc" char-at 1) as-text
+"#);
+    assert_eq!(emitted, vec!["3", "2", "two", "1.5", "98"]);
+}
+
+#[test]
+fn test_jit_structures() {
+    let emitted = run_jit(r#"
+the shape Positioned promises:
+    a behavior called x-of
+        takes: nothing
+        delivers: a float
+
+the structure called Point
+    exposes:
+        a float called x
+        a float called y
+    keeps internal:
+        an integer called id
+
+the behavior called shifted
+    takes:
+        a Point called p
+        a float called dx
+    delivers: a Point
+    as:
+        a Point with x (p x-of added-to dx) y (p y-of) id (p id-of)
+
+the behavior called horizontal
+    takes:
+        a Positioned called thing
+    delivers: a float
+    as:
+        thing x-of
+
+the effect behavior called run
+    with intent: build and read structures
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: p derives-from a Point with y 2.0 x 1.5 id 7
+        derivation: q derives-from p shifted 1.0
+        derivation: d1 derives-from nothing broadcasts (q x-of as-text)
+        derivation: d2 derives-from nothing broadcasts ((q utilizes horizontal) as-text)
+        broadcasts (q y-of as-text)
+"#);
+    assert_eq!(emitted, vec!["2.5", "2.5", "2"]);
+}
+
+#[test]
+fn test_jit_varieties() {
+    let emitted = run_jit(r#"
+the variety called Signal is one of:
+    a Stop
+    a Go carrying an integer called speed
+    a Turn carrying an integer called degrees and a boolean called left
+
+the behavior called pace
+    takes:
+        a Signal called s
+    delivers: an integer
+    as:
+        when s
+            is a Go carrying speed then speed
+            is a Turn carrying degrees left then if left then degrees else 0 decreased-by degrees
+            otherwise 0
+
+the behavior called slowed
+    takes:
+        a Signal called s
+    delivers: a Signal
+    as:
+        when s
+            is a Go carrying speed then if speed exceeds 1 then a Go with speed (speed decreased-by 1) else a Stop
+            otherwise s
+
+the effect behavior called run
+    with intent: build and analyse varieties
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: d1 derives-from nothing broadcasts ((a Stop utilizes pace) as-text)
+        derivation: d2 derives-from nothing broadcasts ((a Go with speed 7 slowed utilizes pace) as-text)
+        broadcasts ((a Turn with left false degrees 90 utilizes pace) as-text)
+"#);
+    assert_eq!(emitted, vec!["0", "6", "-90"]);
+}
+
+#[test]
+fn test_jit_draws_on_other_modules() {
+    // Greeting has its own entry, and draws a helper from Marks whose name the
+    // program reuses unseen; both are qualified by their module when linked.
+    let dir = std::env::temp_dir().join("onu_jit_modules");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("marks.onu"), r#"
+the module called Marks
+    with concern: greeting marks
+
+the behavior called decorated
+    with intent: mark a greeting
+    takes:
+        a string called name
+    delivers: a string
+    as:
+        name joined-with "!"
+"#).unwrap();
+    std::fs::write(dir.join("greeting.onu"), r#"
+the module called Greeting
+    with concern: greeting text
+    draws-from: Marks
+
+the behavior called salutation
+    with intent: build a greeting text
+    takes:
+        a string called name
+    delivers: a string
+    as:
+        "Hello, " joined-with (name utilizes decorated)
+
+the effect behavior called run
+    with intent: greet the world
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts ("World" utilizes salutation)
+"#).unwrap();
+
+    let mut session = CompilerSession::new().unwrap();
+    session.search_path = vec![dir];
+    let mut env = MockEnvironment::new();
+    let result = session.run_jit(r#"
+the module called Welcome
+    with concern: greeting text
+    draws-from: Greeting
+
+the behavior called decorated
+    with intent: mark a greeting text
+    takes:
+        a string called name
+    delivers: a string
+    as:
+        name joined-with "?"
+
+the effect behavior called run
+    with intent: greet a guest
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: d1 derives-from nothing broadcasts ("Ada" utilizes salutation)
+        broadcasts ("Ada" utilizes decorated)
+"#, &mut env);
+    assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+    assert_eq!(env.emitted, vec!["Hello, Ada!", "Ada?"]);
+}
+
+#[test]
+fn test_jit_trap_is_returned_as_an_error() {
+    let source = r#"
+the behavior called overflowed
+    with intent: exceed the width
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        n scales-by 9223372036854775807
+
+the effect behavior called run
+    with intent: trap
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: before derives-from nothing broadcasts "before"
+        broadcasts (2 utilizes overflowed)
+"#;
+    let mut session = CompilerSession::new().unwrap();
+    let mut env = MockEnvironment::new();
+    let err = session.run_jit(source, &mut env).unwrap_err();
+    // The test process is still here to inspect the trap.
+    assert!(matches!(err, onu::error::OnuError::RuntimeError { span: onu::error::Span { line: 8, .. }, .. }), "{:?}", err);
+    assert!(err.to_string().contains("'scales-by' overflowed its width in the behavior 'overflowed' (line 8)."), "{}", err);
+    assert_eq!(env.emitted, vec!["before"]);
+
+    // The session can run again after a trap.
+    let emitted = run_jit(r#"
+the effect behavior called run
+    with intent: recover
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts "after"
+"#);
+    assert_eq!(emitted, vec!["after"]);
+}
+
+/// The line a trap names when the program runs through the JIT and through the interpreter.
+fn trap_lines(source: &str) -> (usize, usize) {
+    let mut session = CompilerSession::new().unwrap();
+    let jit = session.run_jit(source, &mut MockEnvironment::new()).unwrap_err();
+    let program = session.build_mir(source).unwrap();
+    let mut env = MockEnvironment::new();
+    let interpreted = onu::interpreter::MirInterpreter::new(&program, &mut env).run_entry().unwrap_err();
+    match (jit, interpreted) {
+        (onu::error::OnuError::RuntimeError { span: a, .. }, onu::error::OnuError::RuntimeError { span: b, .. }) => (a.line, b.line),
+        other => panic!("Expected two runtime errors, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_jit_element_at_traps_where_it_is_written() {
+    let lines = trap_lines(r#"
+the behavior called last
+    with intent: read past the end
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        derivation: xs derives-from an array of integer [1 2 3]
+        xs element-at n
+
+the effect behavior called run
+    with intent: trap
+    takes: nothing
+    delivers: nothing
+    as:
+        broadcasts (3 utilizes last)
+"#);
+    assert_eq!(lines, (9, 9));
+}
+
+#[test]
+fn test_jit_matrix_traps_where_it_is_written() {
+    let lines = trap_lines(r#"
+the behavior called corner
+    with intent: read past the last row
+    takes:
+        a matrix called m
+    delivers: a float
+    as:
+        derivation: row derives-from an integer 2
+        m entry-at row 0
+
+the effect behavior called run
+    with intent: trap
+    takes: nothing
+    delivers: nothing
+    as:
+        derivation: m derives-from a matrix [1 2 : 3 4]
+        broadcasts (m utilizes corner)
+"#);
+    assert_eq!(lines, (9, 9));
+}
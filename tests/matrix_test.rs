@@ -45,3 +45,21 @@ the behavior called bad-matrix
     assert!(result.is_err());
     assert!(result.unwrap_err().contains("Matrix Error"));
 }
+
+#[test]
+fn test_matrix_shapes_are_checked_statically_when_known() {
+    let mut session = Session::new(Box::new(StdoutEnvironment));
+    let script = r#"
+the behavior called bad-sum
+    with intent: fail
+    takes: nothing
+    delivers: a matrix
+    as:
+        derivation: m derives-from a matrix [ 1.0 2.0 : 3.0 4.0 ]
+        m added-to ((m transposed) multiplied-with [ 1.0 : 2.0 ])
+"#;
+    let result = session.run_script(script);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("cannot combine a 2x2 matrix with a 2x1 matrix"));
+}
+
@@ -69,3 +69,55 @@ the behavior called run
     let err = result.unwrap_err();
     assert!(err.contains("Runtime Error") || err.contains("requires two numbers") || err.contains("argument"));
 }
+
+#[test]
+fn test_session_run_script_renamed_duplicate_error() {
+    let mut session = Session::new(Box::new(StdoutEnvironment));
+    let script = r#"
+the behavior called increment
+    with intent: test
+    takes:
+        an integer called n
+    delivers: an integer
+    as:
+        derivation: next derives-from an integer n added-to 1
+        next
+
+the behavior called bump
+    with intent: test
+    takes:
+        an integer called count
+    delivers: an integer
+    as:
+        derivation: after derives-from an integer count added-to 1
+        after
+"#;
+    let result = session.run_script(script);
+    assert!(result.is_err());
+    let err = result.unwrap_err();
+    assert!(err.contains("identical"), "{}", err);
+    assert!(err.contains("first written at 2:1"), "{}", err);
+}
+
+#[test]
+fn test_session_run_script_intentional_repetition() {
+    let mut session = Session::new(Box::new(StdoutEnvironment));
+    let script = r#"
+the behavior called foo
+    with intent: test
+    takes: nothing
+    delivers: an integer
+    as:
+        10
+
+the behavior called bar
+    with intent: test
+    takes: nothing
+    delivers: an integer
+    with intentional repetition
+    as:
+        10
+"#;
+    let result = session.run_script(script);
+    assert!(result.is_ok(), "Expected Ok, got {:?}", result);
+}